@@ -0,0 +1,29 @@
+//! Print every 4x4 square whose first row is a given word, as each is
+//! found, then how many there were.
+//!
+//! ```sh
+//! cargo run --example every_square -- wordlist.txt lamp
+//! ```
+
+use magic_square::prelude::*;
+
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), Some(word)) = (args.next(), args.next()) else {
+        return Err("usage: every_square <wordlist> <word>".to_string());
+    };
+    let dict = Dictionary::from_file(&path)?;
+    let mut square = MagicSquare::from_template(&word, 4, &dict);
+    square.set_live_view(LiveView::Off);
+
+    let mut found = 0;
+    for grid in square.solutions() {
+        for row in grid.iter() {
+            println!("{}", row.iter().collect::<String>());
+        }
+        println!();
+        found += 1;
+    }
+    println!("{} squares", found);
+    Ok(())
+}
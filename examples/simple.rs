@@ -0,0 +1,15 @@
+//! Fill a blank 4x4 square from a wordlist.
+//!
+//! ```sh
+//! cargo run --example simple -- wordlist.txt
+//! ```
+
+use magic_square::prelude::*;
+
+fn main() -> Result<(), String> {
+    let path = std::env::args().nth(1).ok_or("usage: simple <wordlist>")?;
+
+    let square = generate(4, 4).with_dict(&path).solve()?;
+    square.print();
+    Ok(())
+}
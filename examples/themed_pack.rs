@@ -0,0 +1,43 @@
+//! Generate a pack of squares, one per theme word, sharing one dictionary.
+//! Each theme word becomes the first row of its square, and every square is
+//! written to `<word>.txt` in the output directory.
+//!
+//! ```sh
+//! cargo run --example themed_pack -- wordlist.txt out/ cat dog owl
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use magic_square::prelude::*;
+
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), Some(out_dir)) = (args.next(), args.next()) else {
+        return Err("usage: themed_pack <wordlist> <out-dir> <word>...".to_string());
+    };
+    let dict = Dictionary::from_file(&path)?;
+    fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
+
+    for word in args {
+        let size = word.chars().count();
+        let result = generate(size, size)
+            .with_dictionary(dict.clone())
+            .template(&word)
+            .solve();
+        match result {
+            Ok(square) => {
+                let text: String = square
+                    .grid()
+                    .iter()
+                    .map(|row| row.iter().collect::<String>() + "\n")
+                    .collect();
+                let file = Path::new(&out_dir).join(format!("{}.txt", word));
+                fs::write(&file, text).map_err(|e| e.to_string())?;
+                println!("{}: wrote {}", word, file.display());
+            }
+            Err(e) => println!("{}: {}", word, e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+
+use crate::check::check;
+use crate::MagicSquare;
+
+/// A blank cell of a puzzle, and every letter that appears there in some
+/// solution.
+pub struct CellLetters {
+    pub row: usize,
+    pub col: usize,
+    /// The letters, in alphabetical order.
+    pub letters: Vec<char>,
+}
+
+impl CellLetters {
+    /// Whether every solution puts the same letter in this cell.
+    pub fn is_forced(&self) -> bool {
+        self.letters.len() == 1
+    }
+}
+
+/// Find, for every blank cell, which letters some solution puts there, to
+/// judge whether a puzzle is fair: a puzzle whose cells are all forced has
+/// exactly one solution.
+///
+/// Each letter is tried in each blank cell, and kept if the rest of the
+/// square can then be filled. Every solution found along the way marks all
+/// of its letters as possible at once, so most cells need only a few
+/// searches. This is still one search per letter that fits no solution
+/// found so far, so it is much slower than a single `fill`.
+///
+/// # Arguments
+///
+/// * `square` - A square with its fixed letters set. It is left filled with
+///   the first solution found.
+///
+/// # Returns
+///
+/// * `Ok(Vec<CellLetters>)` with one entry per blank cell, in reading order.
+/// * `Err(String)` if the square cannot be filled at all.
+pub fn alternates(square: &mut MagicSquare) -> Result<Vec<CellLetters>, String> {
+    let original = square.fixed_grid();
+    let blanks: Vec<(usize, usize)> = original
+        .iter()
+        .enumerate()
+        .flat_map(|(row, letters)| {
+            letters
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| **c == '_')
+                .map(move |(col, _)| (row, col))
+        })
+        .collect();
+    if blanks.is_empty() {
+        return Ok(vec![]);
+    }
+
+    square.fill()?;
+    let first = square.grid().to_vec();
+    let mut possible: Vec<Vec<BTreeSet<char>>> = original
+        .iter()
+        .map(|letters| vec![BTreeSet::new(); letters.len()])
+        .collect();
+    let record = |possible: &mut Vec<Vec<BTreeSet<char>>>, grid: &[Vec<char>]| {
+        for &(row, col) in blanks.iter() {
+            possible[row][col].insert(grid[row][col]);
+        }
+    };
+    record(&mut possible, &first);
+
+    let alphabet = square.dict.alphabet().to_vec();
+    for &(row, col) in blanks.iter() {
+        for &c in alphabet.iter() {
+            if possible[row][col].contains(&c) {
+                continue;
+            }
+            let mut grid = original.clone();
+            grid[row][col] = c;
+            square.reset_to(&grid);
+            if !check(square).is_plausible() {
+                continue;
+            }
+            let blank = grid.iter().flatten().any(|c| *c == '_');
+            if !blank || square.fill().is_ok() {
+                debug!("({}, {}) can also be {}", row, col, c);
+                record(&mut possible, square.grid());
+            }
+        }
+    }
+
+    // Leave the square as the first solution, with its blanks editable
+    square.reset_to(&original);
+    square.square = first;
+
+    Ok(blanks
+        .into_iter()
+        .map(|(row, col)| CellLetters {
+            row,
+            col,
+            letters: possible[row][col].iter().copied().collect(),
+        })
+        .collect())
+}
@@ -0,0 +1,172 @@
+//! Filling a square by simulated annealing.
+//!
+//! Instead of building a square up a cell at a time, this starts from a
+//! full grid whose rows are random words that fit them, and repairs it: a
+//! move puts another random word in one row, and the search keeps moves
+//! that leave fewer columns that are not words, and, while the grid is
+//! still hot, some that leave more. As it cools it settles into a square,
+//! or reheats and tries again. It never proves there is no square, but on
+//! big grids it often finds one where backtracking stalls, and a word
+//! frequency list steers it towards common words for free.
+
+use crate::rng::Rng;
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// The temperature the search starts at, and reheats to.
+const HOT: f64 = 1.0;
+/// The temperature below which the search reheats.
+const COLD: f64 = 0.01;
+/// How much the temperature falls after each move.
+const COOLING: f64 = 0.999;
+/// How many moves a search with no attempt limit makes before giving up.
+const MAX_MOVES: usize = 1_000_000;
+/// How much a word's rarity counts against a grid, next to a column that
+/// is not a word, when the dictionary has word frequencies.
+const RARITY_WEIGHT: f64 = 0.05;
+
+impl MagicSquare {
+    /// Fill the square by simulated annealing over whole row words.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if every column became a word.
+    /// * `Err(String)` if some row has no word, or the search gave up, in
+    ///   which case the square is left as it was and
+    ///   [`best_fill`](MagicSquare::best_fill) has the grid with the fewest
+    ///   bad columns, with those columns blanked.
+    pub(crate) fn fill_anneal(&mut self) -> Result<(), String> {
+        let start = self.square.clone();
+        let mut rng = Rng::new(self.search_seed().unwrap_or(0));
+
+        // The words each row may take, in grid order; rows with nothing
+        // left to fill keep their letters
+        let mut choices: Vec<(usize, Vec<Vec<char>>)> = vec![];
+        for row in 0..self.square.len() {
+            if !self.has_blank(row) {
+                continue;
+            }
+            let template: String = self.word(Slot::Row(row));
+            let words: Vec<Vec<char>> = self
+                .dict
+                .iter_with_template(&template)
+                .filter(|word| !self.mirror_rows || self.dict.contains_reversed(word))
+                .map(|word| Slot::Row(row).orient(self.direction, word.chars().collect()))
+                .collect();
+            if words.is_empty() {
+                return Err(format!("No word fits row {}", row));
+            }
+            choices.push((row, words));
+        }
+        for (row, words) in &choices {
+            self.square[*row] = words[rng.below(words.len())].clone();
+        }
+
+        let (mut bad, mut energy) = self.energy();
+        let mut fewest = usize::MAX;
+        let mut temperature = HOT;
+        let mut moves = 0;
+        while !bad.is_empty() {
+            if bad.len() < fewest {
+                fewest = bad.len();
+                self.note_partial(&bad);
+            }
+            if choices.is_empty() || moves == MAX_MOVES {
+                self.gave_up = true;
+            }
+            if self.should_stop() {
+                self.square = start;
+                return Err(format!("Gave up with {} columns not words", fewest));
+            }
+            self._attempt += 1;
+            moves += 1;
+
+            let (row, words) = &choices[rng.below(choices.len())];
+            let word = &words[rng.below(words.len())];
+            let before = std::mem::replace(&mut self.square[*row], word.clone());
+            let (next_bad, next_energy) = self.energy();
+            let delta = next_energy - energy;
+            if delta <= 0.0 || rng.unit() < (-delta / temperature).exp() {
+                (bad, energy) = (next_bad, next_energy);
+                self.draw_grid();
+            } else {
+                self.square[*row] = before;
+            }
+            temperature *= COOLING;
+            if temperature < COLD {
+                temperature = HOT;
+            }
+        }
+        self.note_depth();
+        Ok(())
+    }
+
+    /// The columns of the grid that are not words, and the grid's energy:
+    /// how many there are, plus how rare its words are if the dictionary
+    /// has word frequencies.
+    fn energy(&self) -> (Vec<usize>, f64) {
+        let cols = self.square.first().map_or(0, Vec::len);
+        let bad: Vec<usize> = (0..cols)
+            .filter(|&col| {
+                self.note_query();
+                !self.dict.contains(&self.word(Slot::Col(col)))
+            })
+            .collect();
+        let mut energy = bad.len() as f64;
+        if self.dict.has_frequencies() {
+            for slot in Slot::all(&self.square) {
+                let word = self.word(slot);
+                if self.dict.contains(&word) {
+                    let frequency = self.dict.frequency(&word) as f64;
+                    energy += RARITY_WEIGHT / (1.0 + frequency.ln_1p());
+                }
+            }
+        }
+        (bad, energy)
+    }
+
+    /// Remember the grid with the columns in `bad` blanked, where the solver
+    /// may write, as the most complete grid if it is.
+    fn note_partial(&mut self, bad: &[usize]) {
+        let full = self.square.clone();
+        for row in 0..self.square.len() {
+            for &col in bad {
+                if self.editable_mask[row][col] {
+                    self.square[row][col] = '_';
+                }
+            }
+        }
+        self.note_depth();
+        self.square = full;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{Direction, MagicSquare, Strategy};
+
+    #[test]
+    fn fills_blank_and_started_squares() {
+        for seed in 0..4 {
+            let seeded = |square: &mut MagicSquare| square.set_seed(Some(seed));
+            assert_fills("___", 3, Strategy::Anneal, seeded);
+            assert_fills("_____/_____/_____", 3, Strategy::Anneal, seeded);
+            assert_fills("_f_/_u_/_n_", 3, Strategy::Anneal, seeded);
+        }
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_directions() {
+        assert_fills("___", 3, Strategy::Anneal, |square| {
+            square.set_seed(Some(1));
+            square.set_mirror_rows(true);
+        });
+        for direction in [Direction::BottomUp, Direction::Boustrophedon] {
+            assert_fills("___", 3, Strategy::Anneal, |square| {
+                square.set_seed(Some(1));
+                square.set_direction(direction);
+            });
+        }
+    }
+}
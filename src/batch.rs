@@ -0,0 +1,207 @@
+use std::fs;
+
+use magic_square::dictionary::Dictionary;
+use magic_square::normalize::fold_diacritics;
+use magic_square::{LiveView, MagicSquare};
+
+use crate::cli::Args;
+
+/// A single puzzle to generate in a batch run.
+struct Job {
+    template: String,
+    rows: usize,
+    output: String,
+    /// The seed to shuffle with, in place of the command line's.
+    seed: Option<u64>,
+}
+
+/// The fields of a `[[job]]` table as they are read, before defaults apply.
+#[derive(Default)]
+struct JobFields {
+    template: Option<String>,
+    rows: Option<usize>,
+    output: Option<String>,
+    seed: Option<u64>,
+}
+
+impl JobFields {
+    fn into_job(self, index: usize) -> Result<Job, String> {
+        Ok(Job {
+            template: self.template.unwrap_or_else(|| "_____".to_string()),
+            rows: self.rows.unwrap_or(4),
+            output: self
+                .output
+                .ok_or_else(|| format!("job {} has no output", index + 1))?,
+            seed: self.seed,
+        })
+    }
+}
+
+/// A parsed batch spec: an optional shared dictionary and a list of jobs.
+///
+/// The spec is a small subset of TOML:
+///
+/// ```toml
+/// dictionary = "wordlist.txt"
+///
+/// [[job]]
+/// template = "hello"
+/// rows = 4
+/// output = "hello.txt"
+/// seed = 7
+/// ```
+struct BatchSpec {
+    dictionary: Option<String>,
+    jobs: Vec<Job>,
+}
+
+/// A value on the right-hand side of a `key = value` line.
+enum Value {
+    Str(String),
+    Int(usize),
+}
+
+/// Parse the right-hand side of a `key = value` line, dropping any trailing
+/// comment.
+fn parse_value(raw: &str) -> Result<Value, String> {
+    if let Some(rest) = raw.strip_prefix('"') {
+        let end = rest
+            .find('"')
+            .ok_or_else(|| format!("unterminated string {}", raw))?;
+        let trailing = rest[end + 1..].trim();
+        if !trailing.is_empty() && !trailing.starts_with('#') {
+            return Err(format!("unexpected text after string: {}", trailing));
+        }
+        return Ok(Value::Str(rest[..end].to_string()));
+    }
+
+    let number = raw.split('#').next().unwrap_or("").trim();
+    number
+        .parse::<usize>()
+        .map(Value::Int)
+        .map_err(|_| format!("expected a string or integer, got {}", raw))
+}
+
+/// Parse a batch spec from the contents of a spec file.
+///
+/// # Arguments
+///
+/// * `contents` - The text of the spec file.
+///
+/// # Returns
+///
+/// * `Ok(BatchSpec)` if the spec is well-formed.
+/// * `Err(String)` describing the first offending line otherwise.
+fn parse_spec(contents: &str) -> Result<BatchSpec, String> {
+    let mut spec = BatchSpec {
+        dictionary: None,
+        jobs: vec![],
+    };
+    // Fields of the job currently being read, if we're inside a [[job]]
+    let mut current: Option<JobFields> = None;
+
+    for (lineno, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {}", lineno + 1, msg);
+
+        if line == "[[job]]" {
+            if let Some(fields) = current.take() {
+                spec.jobs
+                    .push(fields.into_job(spec.jobs.len()).map_err(err)?);
+            }
+            current = Some(JobFields::default());
+            continue;
+        }
+
+        let (key, raw) = line
+            .split_once('=')
+            .ok_or_else(|| err(format!("expected `key = value`, got {}", line)))?;
+        let value = parse_value(raw.trim()).map_err(err)?;
+
+        match (&mut current, key.trim(), value) {
+            (None, "dictionary", Value::Str(path)) => spec.dictionary = Some(path),
+            (Some(fields), "template", Value::Str(t)) => fields.template = Some(t.to_lowercase()),
+            (Some(fields), "rows", Value::Int(n)) => fields.rows = Some(n),
+            (Some(fields), "output", Value::Str(path)) => fields.output = Some(path),
+            (Some(fields), "seed", Value::Int(n)) => fields.seed = Some(n as u64),
+            (_, key, _) => return Err(err(format!("unexpected key {}", key))),
+        }
+    }
+    if let Some(fields) = current.take() {
+        spec.jobs.push(fields.into_job(spec.jobs.len())?);
+    }
+
+    Ok(spec)
+}
+
+/// Run every job in a batch spec file, loading the dictionary only once and
+/// writing each filled square (one row per line) to the job's output file.
+///
+/// # Arguments
+///
+/// * `path` - The path to the spec file.
+/// * `args` - The command line, for the dictionary to use when the spec
+///   names none (`--dict`), the proper-noun, blocklist, overlay and
+///   `--add-word` flags, `--progress`, and the solver flags every job is
+///   searched with.
+///
+/// # Returns
+///
+/// * `Ok(())` if the spec was read and every job was attempted.
+/// * `Err(String)` if the spec or dictionary could not be read.
+pub(crate) fn run(path: &str, args: &Args) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let spec = parse_spec(&contents)?;
+
+    let dict = match spec.dictionary.as_ref().or(args.dict.as_ref()) {
+        Some(path) => Dictionary::load(path)?,
+        None => Dictionary::from_os_dict().or_else(|_| crate::fallback_dictionary())?,
+    };
+    let dict = crate::filter_dictionary(dict, args)?;
+    let dict = if args.compact { dict.compact() } else { dict };
+
+    let seed = args.seed();
+    let mut failures = 0;
+    for (i, job) in spec.jobs.iter().enumerate() {
+        let template = if args.fold_accents {
+            fold_diacritics(&job.template)
+        } else {
+            job.template.clone()
+        };
+        let mut square = MagicSquare::from_template(&template, job.rows, &dict);
+        if let Err(problem) = crate::apply_solver_flags(&mut square, args, job.seed.or(seed)) {
+            println!("Job {} ({}): {}", i + 1, job.template, problem);
+            failures += 1;
+            continue;
+        }
+        // Each job's result goes to stdout, so the grid is never redrawn
+        square.set_live_view(if args.progress {
+            LiveView::Progress
+        } else {
+            LiveView::Off
+        });
+        if square.fill().is_err() {
+            println!("Job {} ({}): could not fill square.", i + 1, job.template);
+            failures += 1;
+            continue;
+        }
+
+        let mut out = String::new();
+        for row in 0..square.grid().len() {
+            out.extend(square.get_row(row));
+            out.push('\n');
+        }
+        fs::write(&job.output, out).map_err(|e| format!("{}: {}", job.output, e))?;
+        println!("Job {} ({}): wrote {}", i + 1, job.template, job.output);
+    }
+
+    println!(
+        "{} of {} jobs succeeded.",
+        spec.jobs.len() - failures,
+        spec.jobs.len()
+    );
+    Ok(())
+}
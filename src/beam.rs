@@ -0,0 +1,102 @@
+//! Filling a square with a beam search.
+//!
+//! Rather than following one partial grid down until it fails, this keeps
+//! the most promising few partial grids at each depth and extends all of
+//! them by one cell. A grid's promise is the product of how many words fit
+//! each of its rows and columns, so grids that leave their slots the most
+//! room survive. Grids that fall out of the beam are never revisited, so
+//! the search may miss a square that exists, but it cannot get stuck deep
+//! in a hopeless corner of a big grid.
+
+use std::cmp::Ordering;
+
+use crate::check;
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+impl MagicSquare {
+    /// Fill the square a cell at a time in reading order, keeping the
+    /// `beam_width` most promising grids after each cell.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if some grid in the beam was filled.
+    /// * `Err(String)` if every grid in the beam reached a cell no letter
+    ///   fits, leaving the square as it was. That does not show there is no
+    ///   square.
+    pub(crate) fn fill_beam(&mut self) -> Result<(), String> {
+        let start = self.square.clone();
+        let mut beam = vec![start.clone()];
+        // Every grid in the beam has filled the same cells, so the first
+        // one says which is next
+        loop {
+            let Some((row, col)) = self.empty_cells().next() else {
+                return Ok(());
+            };
+            let mut children = vec![];
+            for grid in &beam {
+                self.square.clone_from(grid);
+                for c in self.letter_order(row, col) {
+                    // Out of attempts: give up with what the beam has
+                    if self.should_stop() {
+                        self.square = start;
+                        return Err(format!("Gave up at ({}, {})", row, col));
+                    }
+                    self._attempt += 1;
+                    if !self.is_valid_letter(row, col, c) {
+                        continue;
+                    }
+                    self.square[row][col] = c;
+                    children.push((self.promise(), self.square.clone()));
+                    self.square[row][col] = '_';
+                }
+            }
+            if children.is_empty() {
+                let depth = self.note_backtrack();
+                trace!("beam emptied at ({}, {}), depth {}", row, col, depth);
+                self.square = start;
+                return Err(format!("No grid in the beam could fill ({}, {})", row, col));
+            }
+            // Stable, so equally promising grids keep the letter order
+            children.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+            children.truncate(self.beam_width);
+            beam = children.into_iter().map(|(_, grid)| grid).collect();
+            self.square.clone_from(&beam[0]);
+            self.draw_grid();
+            self.note_depth();
+        }
+    }
+
+    /// How promising a partial grid is: the log of the product of how many
+    /// words fit each of its rows and columns as they stand.
+    fn promise(&self) -> f64 {
+        Slot::all(&self.square)
+            .into_iter()
+            .map(|slot| {
+                let template: String = slot.read(&self.square, self.direction).into_iter().collect();
+                (check::candidates(self, &template) as f64).ln()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{Direction, Strategy};
+
+    #[test]
+    fn fills_blank_and_started_squares() {
+        assert_fills("___", 3, Strategy::Beam, |_| {});
+        assert_fills("____", 4, Strategy::Beam, |_| {});
+        assert_fills("help", 4, Strategy::Beam, |_| {});
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_directions() {
+        assert_fills("___", 3, Strategy::Beam, |square| square.set_mirror_rows(true));
+        for direction in [Direction::BottomUp, Direction::Boustrophedon] {
+            assert_fills("___", 3, Strategy::Beam, |square| square.set_direction(direction));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use crate::slot::{runs, Slot, BLOCK};
+use crate::MagicSquare;
+
+/// A row or column of the fixed-letter grid, and how many words fit it.
+pub struct SlotCheck {
+    pub slot: Slot,
+    /// The slot's fixed letters and blanks, in reading order.
+    pub template: String,
+    pub candidates: usize,
+}
+
+/// The result of a quick feasibility pre-check. See [`check`].
+pub struct Feasibility {
+    /// Every row and column, with its candidate count.
+    pub slots: Vec<SlotCheck>,
+    /// Blank cells where no letter fits both the row's candidates and the
+    /// column's candidates, as `(row, col)`.
+    pub dead_cells: Vec<(usize, usize)>,
+}
+
+impl Feasibility {
+    /// Whether the puzzle survived every check. A plausible puzzle can still
+    /// turn out to be unsolvable, but an implausible one certainly is.
+    pub fn is_plausible(&self) -> bool {
+        self.slots.iter().all(|s| s.candidates > 0) && self.dead_cells.is_empty()
+    }
+}
+
+/// How many words fit a slot's template, in reading order. A slot with
+/// blocks counts the words fitting its most constrained run that must be a
+/// word, or one if it has none. In a toroidal square, every rotation of a
+/// word that fits counts.
+pub(crate) fn candidates(square: &MagicSquare, template: &str) -> usize {
+    square.note_query();
+    if square.toroidal {
+        return square.dict.count_rotated_with_template(template);
+    }
+    if !template.contains(BLOCK) {
+        return square.dict.count_with_template(template);
+    }
+    let cells: Vec<char> = template.chars().collect();
+    runs(&cells, |&c| c == BLOCK)
+        .into_iter()
+        .filter(|run| square.rules().is_word(run, cells.len()))
+        .map(|run| {
+            let template: String = cells[run].iter().collect();
+            square.dict.count_with_template(&template)
+        })
+        .min()
+        .unwrap_or(1)
+}
+
+/// For each grid position of `slot`, the letters that some candidate word
+/// puts there. A run between blocks too short to be a word may take any
+/// letter.
+fn letters_by_cell(square: &MagicSquare, slot: Slot, template: &str) -> Vec<HashSet<char>> {
+    let cells: Vec<char> = template.chars().collect();
+    let mut letters = vec![HashSet::new(); cells.len()];
+    for run in runs(&cells, |&c| c == BLOCK) {
+        if !square.rules().is_word(&run, cells.len()) {
+            for i in run {
+                letters[i] = match cells[i] {
+                    '_' => square.dict.alphabet().iter().copied().collect(),
+                    c => HashSet::from([c]),
+                };
+            }
+            continue;
+        }
+        let template: String = cells[run.clone()].iter().collect();
+        let words: Vec<String> = if square.toroidal {
+            square.dict.search_rotated_with_template(&template)
+        } else {
+            square.dict.search_with_template(&template)
+        };
+        for word in words {
+            for (i, c) in word.chars().enumerate() {
+                letters[run.start + i].insert(c);
+            }
+        }
+    }
+    if slot.is_reversed(square.direction) {
+        letters.reverse();
+    }
+    letters
+}
+
+/// Check, without searching, whether a square's fixed letters could
+/// plausibly be completed.
+///
+/// Two things are checked: that every row and column template matches at
+/// least one word, and that every blank cell has at least one letter that
+/// both its row's and its column's candidates agree on.
+///
+/// # Arguments
+///
+/// * `square` - A square with its fixed letters set.
+///
+/// # Returns
+///
+/// * A `Feasibility` listing the candidate count of each slot and any blank
+///   cells that no letter can fill.
+pub fn check(square: &MagicSquare) -> Feasibility {
+    let grid = square.fixed_grid();
+    let slots: Vec<SlotCheck> = Slot::all(&grid)
+        .into_iter()
+        .map(|slot| {
+            let template: String = slot.read(&grid, square.direction).into_iter().collect();
+            SlotCheck {
+                candidates: candidates(square, &template),
+                slot,
+                template,
+            }
+        })
+        .collect();
+
+    // Intersections only matter when both slots have candidates at all
+    let mut dead_cells = vec![];
+    if slots.iter().all(|s| s.candidates > 0) {
+        let rows: Vec<Vec<HashSet<char>>> = slots
+            .iter()
+            .filter(|s| matches!(s.slot, Slot::Row(_)))
+            .map(|s| letters_by_cell(square, s.slot, &s.template))
+            .collect();
+        let cols: Vec<Vec<HashSet<char>>> = slots
+            .iter()
+            .filter(|s| matches!(s.slot, Slot::Col(_)))
+            .map(|s| letters_by_cell(square, s.slot, &s.template))
+            .collect();
+        for (row, letters) in grid.iter().enumerate() {
+            for (col, &c) in letters.iter().enumerate() {
+                if c == '_' && rows[row][col].is_disjoint(&cols[col][row]) {
+                    dead_cells.push((row, col));
+                }
+            }
+        }
+    }
+
+    Feasibility { slots, dead_cells }
+}
+
+/// For each cell of `slot`, in grid order, the letters some candidate of
+/// the slot puts there, given the square's fixed letters.
+fn slot_letters(square: &MagicSquare, grid: &[Vec<char>], slot: Slot) -> Vec<HashSet<char>> {
+    let template: String = slot.read(grid, square.direction).into_iter().collect();
+    letters_by_cell(square, slot, &template)
+}
+
+/// The letters that could go in each blank cell: those that some word
+/// fitting the cell's row and some word fitting its column agree on.
+///
+/// # Arguments
+///
+/// * `square` - A square with its fixed letters set.
+///
+/// # Returns
+///
+/// * Every blank cell as `((row, col), letters)`, in reading order, with
+///   its letters in alphabetical order. A cell with no letters is dead.
+pub fn hints(square: &MagicSquare) -> Vec<((usize, usize), Vec<char>)> {
+    let grid = square.fixed_grid();
+    let cols = grid.first().map_or(0, |r| r.len());
+    let rows: Vec<Vec<HashSet<char>>> = (0..grid.len())
+        .map(|row| slot_letters(square, &grid, Slot::Row(row)))
+        .collect();
+    let columns: Vec<Vec<HashSet<char>>> = (0..cols)
+        .map(|col| slot_letters(square, &grid, Slot::Col(col)))
+        .collect();
+
+    let mut hints = vec![];
+    for (row, letters) in grid.iter().enumerate() {
+        for (col, &c) in letters.iter().enumerate() {
+            if c != '_' {
+                continue;
+            }
+            let mut fits: Vec<char> = rows[row][col]
+                .intersection(&columns[col][row])
+                .copied()
+                .collect();
+            fits.sort();
+            hints.push(((row, col), fits));
+        }
+    }
+    hints
+}
+
+/// The words that fit a slot given the square's fixed letters, keeping
+/// only those that leave every crossing slot with some candidate.
+///
+/// # Arguments
+///
+/// * `square` - A square with its fixed letters set.
+/// * `slot` - The row or column to complete.
+///
+/// # Returns
+///
+/// * The fitting words in reading order, alphabetically.
+pub fn slot_candidates(square: &MagicSquare, slot: Slot) -> Vec<String> {
+    let grid = square.fixed_grid();
+    let cells = slot.cells(&grid);
+    // For each blank cell of the slot (in grid order), what the crossing
+    // slot allows there
+    let crossing: Vec<Option<HashSet<char>>> = cells
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            (c == '_').then(|| match slot {
+                Slot::Row(row) => slot_letters(square, &grid, Slot::Col(i)).swap_remove(row),
+                Slot::Col(col) => slot_letters(square, &grid, Slot::Row(i)).swap_remove(col),
+            })
+        })
+        .collect();
+
+    let template: String = slot.read(&grid, square.direction).into_iter().collect();
+    square
+        .dict
+        .iter_with_template(&template)
+        .filter(|word| {
+            let letters = slot.orient(square.direction, word.chars().collect());
+            letters
+                .iter()
+                .zip(crossing.iter())
+                .all(|(c, allowed)| allowed.as_ref().is_none_or(|set| set.contains(c)))
+        })
+        .map(str::to_string)
+        .collect()
+}
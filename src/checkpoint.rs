@@ -0,0 +1,372 @@
+//! Saving where a long search has got to, so it can carry on later.
+//!
+//! A letter-by-letter search is its stack of choice points: the cells it
+//! has placed letters in, the letters it tries in each, in order, and how
+//! many of them it has tried. Everything else follows from placing those
+//! letters again: the grid, the cells they forced, the candidate letters
+//! and the words fitting each slot. So a checkpoint saves the stack, the
+//! counters, the most complete grid reached and the state of the shuffle,
+//! along with the candidate letters of the last cell as a check, and
+//! resuming places the letters again and tries the next one. Nogoods are
+//! not saved: their keys are hashes that can differ between builds, and
+//! the search only goes slower without them.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::codec::{Reader, Writer};
+use crate::propagate::Domains;
+use crate::rng::Rng;
+use crate::square::{ChoicePoint, Strategy, CLOCK_CHECK_FREQ};
+use crate::MagicSquare;
+
+const CHECKPOINT_MAGIC: &[u8] = b"magic-square checkpoint 1\n";
+
+/// How often a search saves its place, unless told otherwise.
+pub(crate) const DEFAULT_CHECKPOINT_EVERY: Duration = Duration::from_secs(60);
+
+/// A cell on the search's stack, as a checkpoint saves it.
+pub(crate) struct SavedPoint {
+    pub(crate) row: usize,
+    pub(crate) col: usize,
+    /// The letters to try, in order.
+    pub(crate) letters: Vec<char>,
+    /// The index in `letters` of the next letter to try.
+    pub(crate) next: usize,
+}
+
+/// Where a letter-by-letter search had got to.
+pub(crate) struct Checkpoint {
+    /// The fingerprint of the word list searched.
+    dictionary: u64,
+    /// The grid before the search placed anything.
+    grid: Vec<Vec<char>>,
+    attempts: usize,
+    backtracks: Vec<usize>,
+    /// The most complete grid the search had reached, and how many cells
+    /// it had filled.
+    deepest: Vec<Vec<char>>,
+    deepest_depth: usize,
+    /// The state of the shuffle, if shuffling.
+    rng: Option<u64>,
+    points: Vec<SavedPoint>,
+    /// The letters each cell could take on reaching the last cell, in
+    /// reading order, if propagating.
+    domains: Option<Vec<Vec<char>>>,
+}
+
+/// The letters every cell of a grid of `grid`'s shape may take, in reading
+/// order.
+fn cell_letters(domains: &Domains, grid: &[Vec<char>]) -> Vec<Vec<char>> {
+    grid.iter()
+        .enumerate()
+        .flat_map(|(row, cells)| (0..cells.len()).map(move |col| domains.cell_letters(row, col)))
+        .collect()
+}
+
+fn write_grid(out: &mut Writer, grid: &[Vec<char>]) {
+    out.usize(grid.len());
+    for row in grid {
+        out.str(&row.iter().collect::<String>());
+    }
+}
+
+fn read_grid(input: &mut Reader) -> Result<Vec<Vec<char>>, String> {
+    (0..input.count()?)
+        .map(|_| Ok(input.string()?.chars().collect()))
+        .collect()
+}
+
+impl Checkpoint {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Writer::default();
+        out.bytes.extend_from_slice(CHECKPOINT_MAGIC);
+        out.u64(self.dictionary);
+        write_grid(&mut out, &self.grid);
+        out.usize(self.attempts);
+        out.usize(self.backtracks.len());
+        for &count in &self.backtracks {
+            out.usize(count);
+        }
+        write_grid(&mut out, &self.deepest);
+        out.usize(self.deepest_depth);
+        match self.rng {
+            Some(state) => {
+                out.usize(1);
+                out.u64(state);
+            }
+            None => out.usize(0),
+        }
+        out.usize(self.points.len());
+        for point in &self.points {
+            out.usize(point.row);
+            out.usize(point.col);
+            out.str(&point.letters.iter().collect::<String>());
+            out.usize(point.next);
+        }
+        match &self.domains {
+            Some(cells) => {
+                out.usize(1);
+                out.usize(cells.len());
+                for letters in cells {
+                    out.str(&letters.iter().collect::<String>());
+                }
+            }
+            None => out.usize(0),
+        }
+        out.bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Checkpoint, String> {
+        let mut input = Reader::named(bytes, "checkpoint");
+        input.expect(CHECKPOINT_MAGIC)?;
+        let dictionary = input.u64()?;
+        let grid = read_grid(&mut input)?;
+        let attempts = input.usize()?;
+        let backtracks = (0..input.count()?)
+            .map(|_| input.usize())
+            .collect::<Result<Vec<usize>, String>>()?;
+        let deepest = read_grid(&mut input)?;
+        let deepest_depth = input.usize()?;
+        let rng = match input.usize()? {
+            0 => None,
+            _ => Some(input.u64()?),
+        };
+        let points = (0..input.count()?)
+            .map(|_| {
+                Ok(SavedPoint {
+                    row: input.usize()?,
+                    col: input.usize()?,
+                    letters: input.string()?.chars().collect(),
+                    next: input.usize()?,
+                })
+            })
+            .collect::<Result<Vec<SavedPoint>, String>>()?;
+        let domains = match input.usize()? {
+            0 => None,
+            _ => Some(
+                (0..input.count()?)
+                    .map(|_| Ok(input.string()?.chars().collect()))
+                    .collect::<Result<Vec<Vec<char>>, String>>()?,
+            ),
+        };
+        if !input.is_empty() {
+            return Err("checkpoint has trailing bytes".to_string());
+        }
+        Ok(Checkpoint {
+            dictionary,
+            grid,
+            attempts,
+            backtracks,
+            deepest,
+            deepest_depth,
+            rng,
+            points,
+            domains,
+        })
+    }
+
+    /// Save the checkpoint to `path`. Written to a temporary file first,
+    /// so a crash never leaves a partial checkpoint behind.
+    fn write(&self, path: &Path) -> Result<(), String> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        std::fs::write(&partial, self.encode())
+            .and_then(|()| std::fs::rename(&partial, path))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl MagicSquare {
+    /// Make the next `fill` carry on from the checkpoint saved at `path`
+    /// by a search of the same puzzle with the same settings (see
+    /// [`set_checkpoint`](MagicSquare::set_checkpoint)), rather than start
+    /// afresh. Its attempts and backtracks count on from the checkpoint's.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the checkpoint was read.
+    /// * `Err(String)` if it could not be, or was saved with another word
+    ///   list or for another grid.
+    pub fn resume_from(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let checkpoint =
+            Checkpoint::decode(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if checkpoint.dictionary != self.dict.fingerprint() {
+            return Err(format!("{}: saved with another word list", path.display()));
+        }
+        if checkpoint.grid != self.fixed_grid() {
+            return Err(format!("{}: saved for another grid", path.display()));
+        }
+        self.resume = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Why the search cannot save or resume its place as it is set up, if
+    /// it cannot: only the letter-by-letter search has a place to save,
+    /// and each search a restarted one runs starts over.
+    pub(crate) fn checkpoint_error(&self) -> Option<String> {
+        if self.checkpoint.is_none() && self.resume.is_none() {
+            return None;
+        }
+        if self.strategy != Strategy::Letters || self.restart_unit.is_some() {
+            return Some(
+                "Checkpoints apply to the letter-by-letter search, without restarts".to_string(),
+            );
+        }
+        None
+    }
+
+    /// Save the search's place, if it is saving it and it is time to.
+    pub(crate) fn checkpoint_if_due(&mut self, stack: &[ChoicePoint]) {
+        let Some(at) = self.next_checkpoint else {
+            return;
+        };
+        if self._attempt.is_multiple_of(CLOCK_CHECK_FREQ) && Instant::now() >= at {
+            self.save_checkpoint(stack);
+        }
+    }
+
+    /// Save the search's place, with the last cell on `stack` about to
+    /// try its next letter, if it is saving it.
+    pub(crate) fn save_checkpoint(&mut self, stack: &[ChoicePoint]) {
+        let Some(path) = &self.checkpoint else {
+            return;
+        };
+        let Some(last) = stack.last() else {
+            return;
+        };
+        let checkpoint = Checkpoint {
+            dictionary: self.dict.fingerprint(),
+            grid: self.fixed_grid(),
+            attempts: self._attempt,
+            backtracks: self.backtracks.clone(),
+            deepest: self.deepest.clone(),
+            deepest_depth: self.deepest_depth,
+            rng: self.rng.as_ref().map(Rng::state),
+            points: stack
+                .iter()
+                .map(|point| SavedPoint {
+                    row: point.row,
+                    col: point.col,
+                    letters: point.letters.clone(),
+                    next: point.next,
+                })
+                .collect(),
+            domains: last
+                .saved
+                .as_ref()
+                .map(|domains| cell_letters(domains, &self.square)),
+        };
+        match checkpoint.write(path) {
+            Ok(()) => debug!(
+                "saved the search at depth {} to {}",
+                stack.len(),
+                path.display()
+            ),
+            Err(e) => info!("could not save a checkpoint: {}", e),
+        }
+        self.next_checkpoint = Some(Instant::now() + self.checkpoint_every);
+    }
+
+    /// Carry on a letter-by-letter search from a checkpoint, with `stack`
+    /// empty: rebuild its stack, and search from there.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled.
+    /// * `Err(String)` if it could not be, or the checkpoint does not fit
+    ///   the settings.
+    pub(crate) fn resume_letters(
+        &mut self,
+        stack: &mut Vec<ChoicePoint>,
+        checkpoint: Checkpoint,
+    ) -> Result<(), String> {
+        self.start_tallies();
+        self._attempt += checkpoint.attempts;
+        self.give_up_at = self.give_up_at.map(|at| at + checkpoint.attempts);
+        if self.backtracks.len() < checkpoint.backtracks.len() {
+            self.backtracks.resize(checkpoint.backtracks.len(), 0);
+        }
+        for (total, count) in self.backtracks.iter_mut().zip(&checkpoint.backtracks) {
+            *total += count;
+        }
+        if checkpoint.deepest_depth > self.deepest_depth {
+            self.deepest_depth = checkpoint.deepest_depth;
+            self.deepest = checkpoint.deepest;
+        }
+        let depth = checkpoint.points.len();
+        self.replay(stack, checkpoint.points)?;
+        let domains = stack
+            .last()
+            .and_then(|point| point.saved.as_ref())
+            .map(|domains| cell_letters(domains, &self.square));
+        if domains != checkpoint.domains {
+            return Err("The checkpoint was saved with other settings".to_string());
+        }
+        self.rng = checkpoint.rng.map(Rng::new);
+        info!(
+            "resuming at depth {} after {} attempts",
+            depth, checkpoint.attempts
+        );
+        self.search_letters(stack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::test_util::{assert_fills, assert_square, words};
+    use crate::{Diagonals, LiveView, MagicSquare, Strategy};
+
+    /// A checkpoint file of the test's own.
+    fn checkpoint_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("magic-square-{}-{}.ckpt", name, std::process::id()))
+    }
+
+    /// Fill a square of `template` in two goes, giving up halfway and
+    /// resuming from the checkpoint, and check that it keeps every rule
+    /// and is the square one search finds.
+    fn assert_resumes(name: &str, template: &str, rows: usize, setup: fn(&mut MagicSquare)) {
+        let whole = assert_fills(template, rows, Strategy::Letters, setup);
+        let attempts = whole.solve_stats().nodes;
+        assert!(attempts > 1, "{} took one attempt", name);
+
+        let dict = words();
+        let path = checkpoint_path(name);
+        let square = || {
+            let mut square = MagicSquare::from_template(template, rows, &dict);
+            square.set_live_view(LiveView::Off);
+            setup(&mut square);
+            square.set_checkpoint(Some(path.clone()));
+            square
+        };
+        let mut first = square();
+        first.set_attempt_limit(Some(attempts / 2));
+        assert!(first.fill().is_err(), "{} filled before giving up", name);
+        let mut second = square();
+        second.resume_from(&path).unwrap();
+        let result = second.fill();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(result, Ok(()), "{}", name);
+        assert_square(&second);
+        assert_eq!(second.grid(), whole.grid(), "{}", name);
+    }
+
+    #[test]
+    fn resumes_to_the_square_one_search_finds() {
+        assert_resumes("plain", "____", 4, |square| square.set_propagate(false));
+        assert_resumes("propagated", "_____", 5, |_| {});
+        assert_resumes("shuffled", "____", 4, |square| square.set_seed(Some(3)));
+        assert_resumes("blocked", "___#/____/____/#___", 4, |_| {});
+    }
+
+    #[test]
+    fn resumes_with_mirrored_rows_and_diagonals() {
+        assert_resumes("mirrored", "____", 4, |square| square.set_mirror_rows(true));
+        assert_resumes("diagonals", "____", 4, |square| {
+            square.set_diagonals(Diagonals::Main)
+        });
+    }
+}
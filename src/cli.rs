@@ -0,0 +1,862 @@
+use std::time::Duration;
+
+use magic_square::dictionary::{ProperNouns, SCOWL_SIZES};
+use magic_square::shrink::Behavior;
+use magic_square::{CellOrder, Diagonals, Direction, LetterOrder, Overlap, SoftRule, Strategy};
+
+/// How results are written to stdout.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// The live-rendered grid followed by its words (the default).
+    Text,
+    /// A single JSON object, with no live rendering.
+    Json,
+    /// The puzzle in a code block and the solution in spoiler tags, for
+    /// pasting into Discord or a similar chat, with no live rendering.
+    Chat,
+}
+
+impl Format {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Json => "json",
+            Format::Chat => "chat",
+        }
+    }
+
+    pub(crate) fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "chat" => Ok(Format::Chat),
+            _ => Err(format!("unknown format {} (expected text, json or chat)", name)),
+        }
+    }
+}
+
+pub(crate) fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Standard => "standard",
+        Direction::BottomUp => "bottom-up",
+        Direction::Boustrophedon => "boustrophedon",
+    }
+}
+
+/// Parse an `--accents` mode: whether to fold accented letters.
+fn parse_accents(name: &str) -> Result<bool, String> {
+    match name {
+        "fold" => Ok(true),
+        "strict" => Ok(false),
+        _ => Err(format!("unknown accents mode {} (expected fold or strict)", name)),
+    }
+}
+
+/// Parse a `--vocab` size, which must be one of the SCOWL sizes.
+fn parse_vocab(size: &str) -> Result<u32, String> {
+    size.parse::<u32>()
+        .ok()
+        .filter(|size| SCOWL_SIZES.contains(size))
+        .ok_or_else(|| {
+            let sizes: Vec<String> = SCOWL_SIZES.iter().map(u32::to_string).collect();
+            format!("--vocab needs a SCOWL size ({}), not {}", sizes.join(", "), size)
+        })
+}
+
+pub(crate) fn overlap_name(overlap: Overlap) -> &'static str {
+    match overlap {
+        Overlap::Allowed => "allow",
+        Overlap::Forbidden => "forbid",
+        Overlap::Symmetric => "symmetric",
+    }
+}
+
+fn parse_overlap(name: &str) -> Result<Overlap, String> {
+    match name {
+        "allow" => Ok(Overlap::Allowed),
+        "forbid" => Ok(Overlap::Forbidden),
+        "symmetric" => Ok(Overlap::Symmetric),
+        _ => Err(format!("unknown overlap {} (expected allow, forbid or symmetric)", name)),
+    }
+}
+
+pub(crate) fn diagonals_name(diagonals: Diagonals) -> &'static str {
+    match diagonals {
+        Diagonals::Off => "off",
+        Diagonals::Main => "main",
+        Diagonals::Both => "both",
+    }
+}
+
+fn parse_diagonals(name: &str) -> Result<Diagonals, String> {
+    match name {
+        "off" => Ok(Diagonals::Off),
+        "main" => Ok(Diagonals::Main),
+        "both" => Ok(Diagonals::Both),
+        _ => Err(format!("unknown diagonals {} (expected off, main or both)", name)),
+    }
+}
+
+pub(crate) fn cell_order_name(order: CellOrder) -> &'static str {
+    match order {
+        CellOrder::Reading => "reading",
+        CellOrder::MostConstrained => "constrained",
+    }
+}
+
+fn parse_cell_order(name: &str) -> Result<CellOrder, String> {
+    match name {
+        "reading" => Ok(CellOrder::Reading),
+        "constrained" => Ok(CellOrder::MostConstrained),
+        _ => Err(format!("unknown cell order {} (expected reading or constrained)", name)),
+    }
+}
+
+pub(crate) fn letter_order_name(order: LetterOrder) -> &'static str {
+    match order {
+        LetterOrder::Alphabet => "alphabet",
+        LetterOrder::Matches => "matches",
+    }
+}
+
+fn parse_letter_order(name: &str) -> Result<LetterOrder, String> {
+    match name {
+        "alphabet" => Ok(LetterOrder::Alphabet),
+        "matches" => Ok(LetterOrder::Matches),
+        _ => Err(format!("unknown letter order {} (expected alphabet or matches)", name)),
+    }
+}
+
+pub(crate) fn strategy_name(strategy: Strategy) -> &'static str {
+    match strategy {
+        Strategy::Letters => "letters",
+        Strategy::Rows => "rows",
+        Strategy::ExactCover => "cover",
+        Strategy::Words => "words",
+        Strategy::Beam => "beam",
+        Strategy::Anneal => "anneal",
+        Strategy::Genetic => "genetic",
+        #[cfg(feature = "sat")]
+        Strategy::Sat => "sat",
+    }
+}
+
+fn parse_strategy(name: &str) -> Result<Strategy, String> {
+    match name {
+        "letters" => Ok(Strategy::Letters),
+        "rows" => Ok(Strategy::Rows),
+        "cover" => Ok(Strategy::ExactCover),
+        "words" => Ok(Strategy::Words),
+        "beam" => Ok(Strategy::Beam),
+        "anneal" => Ok(Strategy::Anneal),
+        "genetic" => Ok(Strategy::Genetic),
+        #[cfg(feature = "sat")]
+        "sat" => Ok(Strategy::Sat),
+        #[cfg(not(feature = "sat"))]
+        "sat" => Err("--strategy sat needs the sat feature".to_string()),
+        _ => Err(format!("unknown strategy {} (expected letters, rows, cover, words, beam, anneal or genetic)", name)),
+    }
+}
+
+fn parse_direction(name: &str) -> Result<Direction, String> {
+    match name {
+        "standard" => Ok(Direction::Standard),
+        "bottom-up" => Ok(Direction::BottomUp),
+        "boustrophedon" => Ok(Direction::Boustrophedon),
+        _ => Err(format!(
+            "unknown direction {} (expected standard, bottom-up or boustrophedon)",
+            name
+        )),
+    }
+}
+
+fn parse_restarts(unit: &str) -> Result<usize, String> {
+    unit.trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&unit| unit > 0)
+        .ok_or_else(|| format!("--restarts needs a number of attempts, not {}", unit))
+}
+
+fn parse_beam_width(width: &str) -> Result<usize, String> {
+    width
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&width| width > 0)
+        .ok_or_else(|| format!("--beam-width needs a number of grids, not {}", width))
+}
+
+fn parse_population(size: &str) -> Result<usize, String> {
+    size.trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&size| size > 1)
+        .ok_or_else(|| format!("--population needs a number of grids above 1, not {}", size))
+}
+
+fn parse_mutation_rate(rate: &str) -> Result<f64, String> {
+    rate.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| format!("--mutation-rate needs a chance from 0 to 1, not {}", rate))
+}
+
+fn parse_generations(count: &str) -> Result<usize, String> {
+    count
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| format!("--generations needs a number of generations, not {}", count))
+}
+
+fn parse_min_word_len(len: &str) -> Result<usize, String> {
+    len.trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&len| len > 0)
+        .ok_or_else(|| format!("--min-word-len needs a number of letters, not {}", len))
+}
+
+fn parse_max_attempts(limit: &str) -> Result<usize, String> {
+    limit
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&limit| limit > 0)
+        .ok_or_else(|| format!("--max-attempts needs a number of attempts, not {}", limit))
+}
+
+/// Parse a `--time-limit` in seconds, which may have a fraction.
+fn parse_time_limit(secs: &str) -> Result<Duration, String> {
+    secs.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|&secs| secs > 0.0 && secs.is_finite())
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("--time-limit needs a number of seconds, not {}", secs))
+}
+
+/// Parse an `--optimize-for` budget in seconds, which may have a fraction
+/// and an `s` after it.
+fn parse_optimize_for(secs: &str) -> Result<Duration, String> {
+    secs.trim()
+        .trim_end_matches('s')
+        .parse::<f64>()
+        .ok()
+        .filter(|&secs| secs > 0.0 && secs.is_finite())
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("--optimize-for needs a number of seconds, not {}", secs))
+}
+
+/// Parse a `--soft` rule and its weight, as `distinct:2`,
+/// `min-freq=100:0.5` or `theme=water:3`.
+fn parse_soft(rule: &str) -> Result<(SoftRule, f64), String> {
+    let bad = || {
+        format!(
+            "--soft needs distinct, min-freq=COUNT or theme=WORD, a colon and a weight, not {}",
+            rule
+        )
+    };
+    let (name, weight) = rule.trim().rsplit_once(':').ok_or_else(bad)?;
+    let weight = weight
+        .parse::<f64>()
+        .ok()
+        .filter(|&weight| weight > 0.0 && weight.is_finite())
+        .ok_or_else(bad)?;
+    let rule = match name.split_once('=') {
+        None if name == "distinct" => SoftRule::Distinct,
+        Some(("min-freq", count)) => SoftRule::MinFrequency(count.parse().map_err(|_| bad())?),
+        Some(("theme", word)) if !word.is_empty() => SoftRule::Theme(word.to_lowercase()),
+        _ => return Err(bad()),
+    };
+    Ok((rule, weight))
+}
+
+fn parse_checkpoint_every(secs: &str) -> Result<Duration, String> {
+    secs.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|&secs| secs > 0.0 && secs.is_finite())
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("--checkpoint-every needs a number of seconds, not {}", secs))
+}
+
+fn parse_threads(count: &str) -> Result<usize, String> {
+    count
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| format!("--threads needs a number of threads, not {}", count))
+}
+
+fn parse_seed(seed: &str) -> Result<u64, String> {
+    seed.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("--seed needs a number, not {}", seed))
+}
+
+/// Environment variables that set flags, for deployments where flags are
+/// awkward to pass. Flags on the command line take precedence.
+pub(crate) const ENV_FLAGS: &[(&str, &str)] = &[
+    ("MAGIC_SQUARE_DICT", "--dict"),
+    ("MAGIC_SQUARE_LANG", "--lang"),
+    ("MAGIC_SQUARE_FREQ", "--freq"),
+    ("MAGIC_SQUARE_VOCAB", "--vocab"),
+    ("MAGIC_SQUARE_ACCENTS", "--accents"),
+    ("MAGIC_SQUARE_COMPACT", "--compact"),
+    ("MAGIC_SQUARE_CLEAN", "--clean"),
+    ("MAGIC_SQUARE_FORMAT", "--format"),
+    ("MAGIC_SQUARE_DIRECTION", "--direction"),
+    ("MAGIC_SQUARE_PROGRESS", "--progress"),
+    ("MAGIC_SQUARE_MIRROR_ROWS", "--mirror-rows"),
+    ("MAGIC_SQUARE_SYMMETRIC", "--symmetric"),
+    ("MAGIC_SQUARE_OVERLAP", "--overlap"),
+    ("MAGIC_SQUARE_DISTINCT", "--distinct"),
+    ("MAGIC_SQUARE_DIAGONALS", "--diagonals"),
+    ("MAGIC_SQUARE_MIN_WORD_LEN", "--min-word-len"),
+    ("MAGIC_SQUARE_TOROIDAL", "--toroidal"),
+    ("MAGIC_SQUARE_STRATEGY", "--strategy"),
+    ("MAGIC_SQUARE_BEAM_WIDTH", "--beam-width"),
+    ("MAGIC_SQUARE_POPULATION", "--population"),
+    ("MAGIC_SQUARE_MUTATION_RATE", "--mutation-rate"),
+    ("MAGIC_SQUARE_GENERATIONS", "--generations"),
+    ("MAGIC_SQUARE_CELL_ORDER", "--cell-order"),
+    ("MAGIC_SQUARE_LETTER_ORDER", "--letter-order"),
+    ("MAGIC_SQUARE_PROPAGATE", "--propagate"),
+    ("MAGIC_SQUARE_FORWARD_CHECK", "--forward-check"),
+    ("MAGIC_SQUARE_NOGOODS", "--nogoods"),
+    ("MAGIC_SQUARE_PARALLEL", "--parallel"),
+    ("MAGIC_SQUARE_THREADS", "--threads"),
+    ("MAGIC_SQUARE_SHUFFLE", "--shuffle"),
+    ("MAGIC_SQUARE_SEED", "--seed"),
+    ("MAGIC_SQUARE_DETERMINISTIC", "--deterministic"),
+    ("MAGIC_SQUARE_RESTARTS", "--restarts"),
+    ("MAGIC_SQUARE_MAX_ATTEMPTS", "--max-attempts"),
+    ("MAGIC_SQUARE_TIME_LIMIT", "--time-limit"),
+    ("MAGIC_SQUARE_OPTIMIZE_FOR", "--optimize-for"),
+    ("MAGIC_SQUARE_ALLOW_OFFENSIVE", "--allow-offensive"),
+    ("MAGIC_SQUARE_PROPER_NOUNS", "--proper-nouns"),
+    ("MAGIC_SQUARE_BLOCKLIST", "--blocklist"),
+    ("MAGIC_SQUARE_OVERLAY", "--overlay"),
+];
+
+/// Parse a boolean environment variable: `1`, `true` or `yes` (any case)
+/// are true, `0`, `false`, `no` and the empty string are false.
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(true),
+        "" | "0" | "false" | "no" => Ok(false),
+        _ => Err(format!("{} must be true or false, not {}", name, value)),
+    }
+}
+
+/// Command-line arguments, split into positional arguments and flags.
+///
+/// Positional arguments keep their order: `[dictionary] [template] [rows]`,
+/// or `batch <jobs.toml>`. A lone `-` is positional (it means stdin).
+pub(crate) struct Args {
+    pub(crate) positional: Vec<String>,
+    /// Every long flag that was passed, in order, for reporting where each
+    /// setting came from.
+    pub(crate) given: Vec<String>,
+    /// The `ENV_FLAGS` variables that were set.
+    pub(crate) from_env: Vec<&'static str>,
+    /// How many times `--verbose`/`-v` was passed (`-vv` counts twice).
+    pub(crate) verbose: u8,
+    /// The output format, from `--format`.
+    pub(crate) format: Format,
+    /// Show a progress line instead of redrawing the grid, from `--progress`.
+    pub(crate) progress: bool,
+    /// How many of the most checked templates to print after a solve, from
+    /// `--hot-templates`.
+    pub(crate) hot_templates: Option<usize>,
+    /// Print what the search did after a solve, from `--stats`.
+    pub(crate) stats: bool,
+    /// How words are read out of the grid, from `--direction`.
+    pub(crate) direction: Direction,
+    /// Require rows to be words backwards too, from `--mirror-rows`.
+    pub(crate) mirror_rows: bool,
+    /// Whether a word may fill both a row and a column, from `--overlap`,
+    /// or `--symmetric` for each row the same word as its column.
+    pub(crate) overlap: Overlap,
+    /// Make every row and column a different word, from `--distinct`.
+    pub(crate) distinct: bool,
+    /// Which diagonals must be words too, from `--diagonals`.
+    pub(crate) diagonals: Diagonals,
+    /// The fewest letters a word between blocked cells may have, from
+    /// `--min-word-len`.
+    pub(crate) min_word_len: usize,
+    /// Read rows and columns around a torus, from `--toroidal`.
+    pub(crate) toroidal: bool,
+    /// Whether the solver places letters or whole rows, from
+    /// `--strategy`.
+    pub(crate) strategy: Strategy,
+    /// How many partial grids `--strategy beam` keeps, from
+    /// `--beam-width`.
+    pub(crate) beam_width: usize,
+    /// How many grids `--strategy genetic` evolves, from `--population`.
+    pub(crate) population: usize,
+    /// The chance `--strategy genetic` changes each letter, from
+    /// `--mutation-rate`.
+    pub(crate) mutation_rate: f64,
+    /// How many generations `--strategy genetic` breeds, from
+    /// `--generations`.
+    pub(crate) generations: usize,
+    /// Which empty cell the solver fills next, from `--cell-order`.
+    pub(crate) cell_order: CellOrder,
+    /// Which letter the solver tries first at a cell, from
+    /// `--letter-order`.
+    pub(crate) letter_order: LetterOrder,
+    /// Narrow every cell's candidate letters after each placement, unless
+    /// turned off by `--no-propagate`.
+    pub(crate) propagate: bool,
+    /// Reject letters that leave a cell in their row or column with none,
+    /// from `--forward-check`.
+    pub(crate) forward_check: bool,
+    /// Remember partial squares that lead nowhere, from `--nogoods`.
+    pub(crate) nogoods: bool,
+    /// Split the search over threads, from `--parallel`.
+    pub(crate) parallel: bool,
+    /// How many threads to split it over, from `--threads`.
+    pub(crate) threads: Option<usize>,
+    /// Try letters in a different random order on every run, from
+    /// `--shuffle`.
+    pub(crate) shuffle: bool,
+    /// The seed to shuffle the letter order with, from `--seed`.
+    pub(crate) seed: Option<u64>,
+    /// Search in reading order trying letters a to z, whatever the other
+    /// flags say, from `--deterministic`.
+    pub(crate) deterministic: bool,
+    /// The attempts to allow the first search before restarting, from
+    /// `--restarts`.
+    pub(crate) restarts: Option<usize>,
+    /// The most attempts the search may make before giving up, from
+    /// `--max-attempts`.
+    pub(crate) max_attempts: Option<usize>,
+    /// How long the search may run before giving up, from `--time-limit`.
+    pub(crate) time_limit: Option<Duration>,
+    /// How long to search for the best square rather than the first, from
+    /// `--optimize-for`.
+    pub(crate) optimize_for: Option<Duration>,
+    /// Rules `--optimize-for` may break, with their weights, from `--soft`.
+    pub(crate) soft: Vec<(SoftRule, f64)>,
+    /// Where to save the search's place, from `--checkpoint`.
+    pub(crate) checkpoint: Option<String>,
+    /// How often to save the search's place, from `--checkpoint-every`.
+    pub(crate) checkpoint_every: Option<Duration>,
+    /// The checkpoint to carry on from, from `--resume`.
+    pub(crate) resume: Option<String>,
+    /// Where to save the solved puzzle (or, for `shrink`, the reproducer,
+    /// or for `display`, each screen), from `--save`.
+    pub(crate) save: Option<String>,
+    /// What `shrink` must preserve, from `--behavior`.
+    pub(crate) behavior: Option<Behavior>,
+    /// Seconds `display` shows the puzzle, then the solution, from
+    /// `--interval`.
+    pub(crate) interval: Option<u64>,
+    /// How many solutions `count` stops at, from `--stop-at`.
+    pub(crate) stop_at: Option<usize>,
+    /// Theme words to place symmetrically before filling, from `--theme`.
+    pub(crate) themes: Vec<String>,
+    /// Words to add to the dictionary for this run, from `--add-word`.
+    pub(crate) added_words: Vec<String>,
+    /// The dictionary file or URL, from `--dict`. When set, the dictionary
+    /// path is left out of the positional arguments.
+    pub(crate) dict: Option<String>,
+    /// The language to find an installed wordlist for, from `--lang`. When
+    /// set, the dictionary path is left out of the positional arguments.
+    pub(crate) lang: Option<String>,
+    /// The SCOWL size to load a SCOWL directory at, from `--vocab`.
+    pub(crate) vocab: Option<u32>,
+    /// A word-frequency list to prefer common words with, from `--freq`.
+    pub(crate) freq: Option<String>,
+    /// Fold accented letters to plain ones on load (`--accents fold`), rather
+    /// than keeping them as distinct letters (`--accents strict`).
+    pub(crate) fold_accents: bool,
+    /// Use a word graph instead of hash sets and prefix trees for lookups,
+    /// to save memory, from `--compact`.
+    pub(crate) compact: bool,
+    /// Drop words with characters other than letters on load, from
+    /// `--clean`.
+    pub(crate) clean: bool,
+    /// Keep words on the built-in offensive-word blocklist, from
+    /// `--allow-offensive`.
+    pub(crate) allow_offensive: bool,
+    /// Whether to keep, drop, or keep only capitalized words, from
+    /// `--proper-nouns`.
+    pub(crate) proper_nouns: ProperNouns,
+    /// Extra blocklists of words to remove, from `--blocklist`.
+    pub(crate) blocklists: Vec<String>,
+    /// Files of `+word` / `-word` edits to apply in order, from `--overlay`.
+    pub(crate) overlays: Vec<String>,
+    /// Answer JSON requests on stdin, one per line, from `--stdio`.
+    pub(crate) stdio: bool,
+    /// Run a language server for grid files on stdin and stdout, from
+    /// `--lsp`.
+    pub(crate) lsp: bool,
+}
+
+impl Args {
+    /// Parse the arguments that follow the program name.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The raw arguments, without the program name.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Args)` if every flag was recognized.
+    /// * `Err(String)` naming the first unknown or malformed flag.
+    pub(crate) fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut parsed = Args {
+            positional: vec![],
+            given: vec![],
+            from_env: vec![],
+            verbose: 0,
+            format: Format::Text,
+            progress: false,
+            hot_templates: None,
+            stats: false,
+            direction: Direction::Standard,
+            mirror_rows: false,
+            overlap: Overlap::Allowed,
+            distinct: false,
+            diagonals: Diagonals::Off,
+            min_word_len: 2,
+            toroidal: false,
+            strategy: Strategy::Letters,
+            beam_width: 100,
+            population: 200,
+            mutation_rate: 0.02,
+            generations: 1000,
+            cell_order: CellOrder::Reading,
+            letter_order: LetterOrder::Alphabet,
+            propagate: true,
+            forward_check: false,
+            nogoods: false,
+            parallel: false,
+            threads: None,
+            shuffle: false,
+            seed: None,
+            deterministic: false,
+            restarts: None,
+            max_attempts: None,
+            time_limit: None,
+            optimize_for: None,
+            soft: vec![],
+            checkpoint: None,
+            checkpoint_every: None,
+            resume: None,
+            save: None,
+            behavior: None,
+            interval: None,
+            stop_at: None,
+            themes: vec![],
+            added_words: vec![],
+            dict: None,
+            lang: None,
+            vocab: None,
+            freq: None,
+            fold_accents: false,
+            compact: false,
+            clean: false,
+            allow_offensive: false,
+            proper_nouns: ProperNouns::Keep,
+            blocklists: vec![],
+            overlays: vec![],
+            stdio: false,
+            lsp: false,
+        };
+
+        parsed.apply_env()?;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg.starts_with("--") {
+                parsed.given.push(arg.clone());
+            }
+            match arg.as_str() {
+                "--verbose" => parsed.verbose += 1,
+                "--progress" => parsed.progress = true,
+                "--stats" => parsed.stats = true,
+                "--mirror-rows" => parsed.mirror_rows = true,
+                "--symmetric" => parsed.overlap = Overlap::Symmetric,
+                "--distinct" => parsed.distinct = true,
+                "--toroidal" => parsed.toroidal = true,
+                "--propagate" => parsed.propagate = true,
+                "--no-propagate" => parsed.propagate = false,
+                "--forward-check" => parsed.forward_check = true,
+                "--nogoods" => parsed.nogoods = true,
+                "--parallel" => parsed.parallel = true,
+                "--shuffle" => parsed.shuffle = true,
+                "--deterministic" => parsed.deterministic = true,
+                "--compact" => parsed.compact = true,
+                "--clean" => parsed.clean = true,
+                "--stdio" => parsed.stdio = true,
+                "--lsp" => parsed.lsp = true,
+                "--allow-offensive" => parsed.allow_offensive = true,
+                "--blocklist" => {
+                    parsed
+                        .blocklists
+                        .push(args.next().ok_or("--blocklist needs a path")?);
+                }
+                "--overlay" => {
+                    parsed
+                        .overlays
+                        .push(args.next().ok_or("--overlay needs a path")?);
+                }
+                "--theme" => {
+                    parsed
+                        .themes
+                        .push(args.next().ok_or("--theme needs a word")?);
+                }
+                "--add-word" => {
+                    parsed
+                        .added_words
+                        .push(args.next().ok_or("--add-word needs a word")?);
+                }
+                "--dict" => {
+                    parsed.dict = Some(args.next().ok_or("--dict needs a path or URL")?);
+                    parsed.lang = None;
+                }
+                "--lang" => {
+                    parsed.lang = Some(args.next().ok_or("--lang needs a language code")?);
+                    parsed.dict = None;
+                }
+                "--accents" => {
+                    let name = args.next().ok_or("--accents needs fold or strict")?;
+                    parsed.fold_accents = parse_accents(&name)?;
+                }
+                "--proper-nouns" => {
+                    let name = args.next().ok_or("--proper-nouns needs keep, exclude or only")?;
+                    parsed.proper_nouns = ProperNouns::parse(&name)?;
+                }
+                "--vocab" => {
+                    let size = args.next().ok_or("--vocab needs a SCOWL size")?;
+                    parsed.vocab = Some(parse_vocab(&size)?);
+                }
+                "--freq" => {
+                    parsed.freq = Some(args.next().ok_or("--freq needs a path")?);
+                }
+                "--behavior" => {
+                    let name = args.next().ok_or("--behavior needs a value")?;
+                    parsed.behavior = Some(Behavior::parse(&name)?);
+                }
+                "--hot-templates" => {
+                    let count = args.next().ok_or("--hot-templates needs a count")?;
+                    let count = count
+                        .parse::<usize>()
+                        .map_err(|_| format!("--hot-templates needs a count, not {}", count))?;
+                    parsed.hot_templates = Some(count);
+                }
+                "--interval" => {
+                    let secs = args.next().ok_or("--interval needs a number of seconds")?;
+                    let parsed_secs = secs.parse::<u64>().ok().filter(|&secs| secs > 0);
+                    parsed.interval = Some(parsed_secs.ok_or_else(|| {
+                        format!("--interval needs a number of seconds, not {}", secs)
+                    })?);
+                }
+                "--stop-at" => {
+                    let count = args.next().ok_or("--stop-at needs a number of solutions")?;
+                    let parsed_count = count.parse::<usize>().ok().filter(|&count| count > 0);
+                    parsed.stop_at = Some(parsed_count.ok_or_else(|| {
+                        format!("--stop-at needs a number of solutions, not {}", count)
+                    })?);
+                }
+                "--seed" => {
+                    let seed = args.next().ok_or("--seed needs a number")?;
+                    parsed.seed = Some(parse_seed(&seed)?);
+                }
+                "--restarts" => {
+                    let unit = args.next().ok_or("--restarts needs a number of attempts")?;
+                    parsed.restarts = Some(parse_restarts(&unit)?);
+                }
+                "--threads" => {
+                    let count = args.next().ok_or("--threads needs a number of threads")?;
+                    parsed.threads = Some(parse_threads(&count)?);
+                }
+                "--max-attempts" => {
+                    let limit = args.next().ok_or("--max-attempts needs a number of attempts")?;
+                    parsed.max_attempts = Some(parse_max_attempts(&limit)?);
+                }
+                "--time-limit" => {
+                    let secs = args.next().ok_or("--time-limit needs a number of seconds")?;
+                    parsed.time_limit = Some(parse_time_limit(&secs)?);
+                }
+                "--optimize-for" => {
+                    let secs = args.next().ok_or("--optimize-for needs a number of seconds")?;
+                    parsed.optimize_for = Some(parse_optimize_for(&secs)?);
+                }
+                "--soft" => {
+                    let rule = args.next().ok_or("--soft needs a rule and a weight")?;
+                    parsed.soft.push(parse_soft(&rule)?);
+                }
+                "--checkpoint" => {
+                    parsed.checkpoint = Some(args.next().ok_or("--checkpoint needs a path")?);
+                }
+                "--checkpoint-every" => {
+                    let secs = args
+                        .next()
+                        .ok_or("--checkpoint-every needs a number of seconds")?;
+                    parsed.checkpoint_every = Some(parse_checkpoint_every(&secs)?);
+                }
+                "--resume" => {
+                    parsed.resume = Some(args.next().ok_or("--resume needs a path")?);
+                }
+                "--save" => {
+                    parsed.save = Some(args.next().ok_or("--save needs a path")?);
+                }
+                "--direction" => {
+                    let name = args.next().ok_or("--direction needs a value")?;
+                    parsed.direction = parse_direction(&name)?;
+                }
+                "--strategy" => {
+                    let name = args.next().ok_or("--strategy needs a value")?;
+                    parsed.strategy = parse_strategy(&name)?;
+                }
+                "--beam-width" => {
+                    let width = args.next().ok_or("--beam-width needs a number of grids")?;
+                    parsed.beam_width = parse_beam_width(&width)?;
+                }
+                "--population" => {
+                    let size = args.next().ok_or("--population needs a number of grids")?;
+                    parsed.population = parse_population(&size)?;
+                }
+                "--mutation-rate" => {
+                    let rate = args.next().ok_or("--mutation-rate needs a chance")?;
+                    parsed.mutation_rate = parse_mutation_rate(&rate)?;
+                }
+                "--generations" => {
+                    let count = args.next().ok_or("--generations needs a number")?;
+                    parsed.generations = parse_generations(&count)?;
+                }
+                "--overlap" => {
+                    let name = args.next().ok_or("--overlap needs a value")?;
+                    parsed.overlap = parse_overlap(&name)?;
+                }
+                "--diagonals" => {
+                    let name = args.next().ok_or("--diagonals needs a value")?;
+                    parsed.diagonals = parse_diagonals(&name)?;
+                }
+                "--min-word-len" => {
+                    let len = args.next().ok_or("--min-word-len needs a number of letters")?;
+                    parsed.min_word_len = parse_min_word_len(&len)?;
+                }
+                "--cell-order" => {
+                    let name = args.next().ok_or("--cell-order needs a value")?;
+                    parsed.cell_order = parse_cell_order(&name)?;
+                }
+                "--letter-order" => {
+                    let name = args.next().ok_or("--letter-order needs a value")?;
+                    parsed.letter_order = parse_letter_order(&name)?;
+                }
+                "--format" => {
+                    let name = args.next().ok_or("--format needs a value")?;
+                    parsed.format = Format::parse(&name)?;
+                }
+                short
+                    if short.len() > 1
+                        && short.starts_with('-')
+                        && short[1..].chars().all(|c| c == 'v') =>
+                {
+                    parsed.verbose += (short.len() - 1) as u8;
+                }
+                flag if flag.len() > 1 && flag.starts_with('-') => {
+                    return Err(format!("unknown flag {}", flag));
+                }
+                _ => parsed.positional.push(arg),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// The seed to shuffle the letter order with: the `--seed`, or with
+    /// `--shuffle` alone a new one for every call, which is printed to
+    /// stderr so the square can be made again. `None` keeps the usual
+    /// order, as does `--deterministic`.
+    pub(crate) fn seed(&self) -> Option<u64> {
+        if self.deterministic {
+            return None;
+        }
+        if self.seed.is_some() || !self.shuffle {
+            return self.seed;
+        }
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        // Keep it short enough to retype
+        let seed = (nanos ^ u64::from(std::process::id()) << 32) % 1_000_000_000;
+        eprintln!("Shuffling with seed {} (pass --seed {} to repeat)", seed, seed);
+        Some(seed)
+    }
+
+    /// Set flags from the `ENV_FLAGS` environment variables, before the
+    /// command line is parsed so that its flags win.
+    fn apply_env(&mut self) -> Result<(), String> {
+        for &(name, flag) in ENV_FLAGS {
+            let Ok(value) = std::env::var(name) else {
+                continue;
+            };
+            self.from_env.push(name);
+            match flag {
+                "--dict" => self.dict = Some(value),
+                "--lang" => self.lang = Some(value),
+                "--freq" => self.freq = Some(value),
+                "--vocab" => self.vocab = Some(parse_vocab(&value)?),
+                "--accents" => self.fold_accents = parse_accents(&value)?,
+                "--compact" => self.compact = parse_bool(name, &value)?,
+                "--clean" => self.clean = parse_bool(name, &value)?,
+                "--format" => self.format = Format::parse(&value)?,
+                "--direction" => self.direction = parse_direction(&value)?,
+                "--progress" => self.progress = parse_bool(name, &value)?,
+                "--mirror-rows" => self.mirror_rows = parse_bool(name, &value)?,
+                "--symmetric" => {
+                    if parse_bool(name, &value)? {
+                        self.overlap = Overlap::Symmetric;
+                    } else if self.overlap == Overlap::Symmetric {
+                        self.overlap = Overlap::Allowed;
+                    }
+                }
+                "--overlap" => self.overlap = parse_overlap(&value)?,
+                "--distinct" => self.distinct = parse_bool(name, &value)?,
+                "--diagonals" => self.diagonals = parse_diagonals(&value)?,
+                "--min-word-len" => self.min_word_len = parse_min_word_len(&value)?,
+                "--toroidal" => self.toroidal = parse_bool(name, &value)?,
+                "--strategy" => self.strategy = parse_strategy(&value)?,
+                "--beam-width" => self.beam_width = parse_beam_width(&value)?,
+                "--population" => self.population = parse_population(&value)?,
+                "--mutation-rate" => self.mutation_rate = parse_mutation_rate(&value)?,
+                "--generations" => self.generations = parse_generations(&value)?,
+                "--cell-order" => self.cell_order = parse_cell_order(&value)?,
+                "--letter-order" => self.letter_order = parse_letter_order(&value)?,
+                "--propagate" => self.propagate = parse_bool(name, &value)?,
+                "--forward-check" => self.forward_check = parse_bool(name, &value)?,
+                "--nogoods" => self.nogoods = parse_bool(name, &value)?,
+                "--parallel" => self.parallel = parse_bool(name, &value)?,
+                "--threads" => self.threads = Some(parse_threads(&value)?),
+                "--shuffle" => self.shuffle = parse_bool(name, &value)?,
+                "--seed" => self.seed = Some(parse_seed(&value)?),
+                "--deterministic" => self.deterministic = parse_bool(name, &value)?,
+                "--restarts" => self.restarts = Some(parse_restarts(&value)?),
+                "--max-attempts" => self.max_attempts = Some(parse_max_attempts(&value)?),
+                "--time-limit" => self.time_limit = Some(parse_time_limit(&value)?),
+                "--optimize-for" => self.optimize_for = Some(parse_optimize_for(&value)?),
+                "--allow-offensive" => self.allow_offensive = parse_bool(name, &value)?,
+                "--proper-nouns" => self.proper_nouns = ProperNouns::parse(&value)?,
+                "--blocklist" => self
+                    .blocklists
+                    .extend(std::env::split_paths(&value).map(|p| p.display().to_string())),
+                "--overlay" => self
+                    .overlays
+                    .extend(std::env::split_paths(&value).map(|p| p.display().to_string())),
+                _ => unreachable!("every ENV_FLAGS entry is handled"),
+            }
+        }
+        Ok(())
+    }
+}
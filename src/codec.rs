@@ -0,0 +1,121 @@
+//! A minimal binary encoding for the on-disk index cache and search
+//! checkpoints: fixed-width
+//! little-endian `u64`s for bitsets, LEB128 varints for sizes, indexes and
+//! letters (which are mostly small), and strings as a length followed by
+//! UTF-8 bytes.
+
+/// Appends values to a byte buffer.
+#[derive(Default)]
+pub(crate) struct Writer {
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn usize(&mut self, value: usize) {
+        let mut value = value as u64;
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    pub(crate) fn char(&mut self, c: char) {
+        self.usize(c as usize);
+    }
+
+    pub(crate) fn str(&mut self, s: &str) {
+        self.usize(s.len());
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+}
+
+/// Reads values back in the order a `Writer` wrote them. Every read fails
+/// with an error, rather than panicking, on a truncated or corrupt buffer.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    /// What the buffer holds, for errors.
+    what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    /// Read an index cache.
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader::named(bytes, "index cache")
+    }
+
+    /// Read a buffer holding `what`, which errors name.
+    pub(crate) fn named(bytes: &'a [u8], what: &'static str) -> Reader<'a> {
+        Reader { bytes, what }
+    }
+
+    fn error(&self, problem: &str) -> String {
+        format!("{} {}", self.what, problem)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.bytes.len() {
+            return Err(self.error("is truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, String> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, String> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|e| e.to_string());
+            }
+        }
+        Err(self.error("has a bad number"))
+    }
+
+    /// Read the number of items in a collection. Every item takes at least
+    /// one byte, so a count larger than what is left is corrupt; checking
+    /// this keeps a bad file from causing a huge allocation.
+    pub(crate) fn count(&mut self) -> Result<usize, String> {
+        let count = self.usize()?;
+        if count > self.bytes.len() {
+            return Err(self.error("is truncated"));
+        }
+        Ok(count)
+    }
+
+    pub(crate) fn char(&mut self) -> Result<char, String> {
+        u32::try_from(self.usize()?)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("has a bad letter"))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, String> {
+        let len = self.usize()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())
+    }
+
+    /// Take the given bytes if they come next.
+    pub(crate) fn expect(&mut self, expected: &[u8]) -> Result<(), String> {
+        if self.take(expected.len())? != expected {
+            return Err(self.error("is from another version"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
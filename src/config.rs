@@ -0,0 +1,500 @@
+use magic_square::dictionary::{cache_dir, Dictionary, DEFAULT_SCOWL_SIZE, DEFAULT_WORDLIST_URL};
+use magic_square::logging;
+use magic_square::overrides::Overrides;
+
+use crate::cli::{
+    cell_order_name, diagonals_name, direction_name, letter_order_name, overlap_name, strategy_name,
+    Args, ENV_FLAGS,
+};
+
+/// A resolved setting: its name, its value as TOML, and where it came from.
+struct Setting {
+    name: &'static str,
+    value: String,
+    source: String,
+}
+
+fn toml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn toml_array(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|s| toml_string(s)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Where a flag's value came from: the command line if it was passed, then
+/// its environment variable if set, otherwise its default.
+fn flag_source(args: &Args, flag: &str) -> String {
+    let env = ENV_FLAGS
+        .iter()
+        .find(|(name, f)| *f == flag && args.from_env.contains(name));
+    if args.given.iter().any(|f| f == flag) {
+        format!("command line ({})", flag)
+    } else if let Some((name, _)) = env {
+        format!("environment ({})", name)
+    } else {
+        "default".to_string()
+    }
+}
+
+/// Where the dictionary comes from, mirroring `load_dictionary`.
+fn dictionary_setting(args: &Args, positional: &[String]) -> Setting {
+    let (value, source) = if let Some(path) = &args.dict {
+        (path.clone(), flag_source(args, "--dict"))
+    } else if let Some(lang) = &args.lang {
+        (format!("lang:{}", lang), flag_source(args, "--lang"))
+    } else if let Some(path) = positional.first() {
+        (path.clone(), "command line".to_string())
+    } else if let Some(path) = Dictionary::os_dict_path() {
+        (path.to_string(), "OS dictionary".to_string())
+    } else if cfg!(feature = "embedded-dict") {
+        (
+            "built-in".to_string(),
+            "no OS dictionary; embedded-dict feature".to_string(),
+        )
+    } else {
+        (
+            DEFAULT_WORDLIST_URL.to_string(),
+            "no OS dictionary; downloaded".to_string(),
+        )
+    };
+    Setting {
+        name: "dictionary",
+        value: toml_string(&value),
+        source,
+    }
+}
+
+/// Resolve every setting a solve would use, and where each came from.
+///
+/// # Arguments
+///
+/// * `args` - The parsed command line.
+/// * `positional` - The `[dictionary] [template] [rows]` arguments.
+fn resolve(args: &Args, positional: &[String]) -> Vec<Setting> {
+    let rest = if args.dict.is_some() || args.lang.is_some() {
+        positional
+    } else {
+        positional.get(1..).unwrap_or(&[])
+    };
+    let from_cli = || "command line".to_string();
+    let default = || "default".to_string();
+
+    let mut settings = vec![dictionary_setting(args, positional)];
+    settings.push(match &args.freq {
+        Some(path) => Setting {
+            name: "frequencies",
+            value: toml_string(path),
+            source: flag_source(args, "--freq"),
+        },
+        None => Setting {
+            name: "frequencies",
+            value: toml_string(""),
+            source: "default (none)".to_string(),
+        },
+    });
+    settings.push(match args.vocab {
+        Some(size) => Setting {
+            name: "vocab",
+            value: size.to_string(),
+            source: flag_source(args, "--vocab"),
+        },
+        None => Setting {
+            name: "vocab",
+            value: DEFAULT_SCOWL_SIZE.to_string(),
+            source: "default (SCOWL directories only)".to_string(),
+        },
+    });
+    settings.push(Setting {
+        name: "accents",
+        value: toml_string(if args.fold_accents { "fold" } else { "strict" }),
+        source: flag_source(args, "--accents"),
+    });
+    settings.push(Setting {
+        name: "compact",
+        value: args.compact.to_string(),
+        source: flag_source(args, "--compact"),
+    });
+    settings.push(Setting {
+        name: "clean",
+        value: args.clean.to_string(),
+        source: flag_source(args, "--clean"),
+    });
+    settings.push(Setting {
+        name: "allow_offensive",
+        value: args.allow_offensive.to_string(),
+        source: flag_source(args, "--allow-offensive"),
+    });
+    settings.push(Setting {
+        name: "proper_nouns",
+        value: toml_string(args.proper_nouns.name()),
+        source: flag_source(args, "--proper-nouns"),
+    });
+    let mut blocklists = vec![];
+    let mut sources = vec![];
+    if let Some(path) = Dictionary::user_blocklist_path() {
+        blocklists.push(path.display().to_string());
+        sources.push("config directory");
+    }
+    let blocklist_source = flag_source(args, "--blocklist");
+    if !args.blocklists.is_empty() {
+        blocklists.extend(args.blocklists.iter().cloned());
+        sources.push(blocklist_source.as_str());
+    }
+    settings.push(Setting {
+        name: "blocklists",
+        value: toml_array(&blocklists),
+        source: if sources.is_empty() {
+            default()
+        } else {
+            sources.join(", ")
+        },
+    });
+    settings.push(Setting {
+        name: "overlays",
+        value: toml_array(&args.overlays),
+        source: flag_source(args, "--overlay"),
+    });
+    if let Ok(path) = Overrides::default_path() {
+        settings.push(Setting {
+            name: "overrides",
+            value: toml_string(&path.display().to_string()),
+            source: if path.is_file() {
+                "config directory".to_string()
+            } else {
+                "config directory (not created yet)".to_string()
+            },
+        });
+    }
+    settings.push(match rest.first() {
+        Some(template) if template == "-" => Setting {
+            name: "template",
+            value: toml_string("-"),
+            source: "command line (read from stdin)".to_string(),
+        },
+        Some(template) => Setting {
+            name: "template",
+            value: toml_string(template),
+            source: from_cli(),
+        },
+        None => Setting {
+            name: "template",
+            value: toml_string("_____"),
+            source: default(),
+        },
+    });
+    settings.push(match rest.get(1) {
+        Some(rows) => Setting {
+            name: "rows",
+            value: match rows.parse::<usize>() {
+                Ok(rows) => rows.to_string(),
+                Err(_) => toml_string(rows),
+            },
+            source: from_cli(),
+        },
+        None if rest.first().is_some_and(|t| t == "-") => Setting {
+            name: "rows",
+            value: toml_string("-"),
+            source: "the number of lines read from stdin".to_string(),
+        },
+        None => Setting {
+            name: "rows",
+            value: "4".to_string(),
+            source: default(),
+        },
+    });
+    settings.push(Setting {
+        name: "format",
+        value: toml_string(args.format.name()),
+        source: flag_source(args, "--format"),
+    });
+    settings.push(Setting {
+        name: "direction",
+        value: toml_string(direction_name(args.direction)),
+        source: flag_source(args, "--direction"),
+    });
+    settings.push(Setting {
+        name: "mirror_rows",
+        value: args.mirror_rows.to_string(),
+        source: flag_source(args, "--mirror-rows"),
+    });
+    // `--symmetric` is short for `--overlap symmetric`
+    let mut overlap_source = flag_source(args, "--overlap");
+    if overlap_source == "default" {
+        overlap_source = flag_source(args, "--symmetric");
+    }
+    settings.push(Setting {
+        name: "overlap",
+        value: toml_string(overlap_name(args.overlap)),
+        source: overlap_source,
+    });
+    settings.push(Setting {
+        name: "distinct",
+        value: args.distinct.to_string(),
+        source: flag_source(args, "--distinct"),
+    });
+    settings.push(Setting {
+        name: "diagonals",
+        value: toml_string(diagonals_name(args.diagonals)),
+        source: flag_source(args, "--diagonals"),
+    });
+    settings.push(Setting {
+        name: "min_word_len",
+        value: args.min_word_len.to_string(),
+        source: flag_source(args, "--min-word-len"),
+    });
+    settings.push(Setting {
+        name: "toroidal",
+        value: args.toroidal.to_string(),
+        source: flag_source(args, "--toroidal"),
+    });
+    settings.push(Setting {
+        name: "strategy",
+        value: toml_string(strategy_name(args.strategy)),
+        source: flag_source(args, "--strategy"),
+    });
+    settings.push(Setting {
+        name: "beam_width",
+        value: args.beam_width.to_string(),
+        source: flag_source(args, "--beam-width"),
+    });
+    settings.push(Setting {
+        name: "population",
+        value: args.population.to_string(),
+        source: flag_source(args, "--population"),
+    });
+    settings.push(Setting {
+        name: "mutation_rate",
+        value: args.mutation_rate.to_string(),
+        source: flag_source(args, "--mutation-rate"),
+    });
+    settings.push(Setting {
+        name: "generations",
+        value: args.generations.to_string(),
+        source: flag_source(args, "--generations"),
+    });
+    settings.push(Setting {
+        name: "cell_order",
+        value: toml_string(cell_order_name(args.cell_order)),
+        source: flag_source(args, "--cell-order"),
+    });
+    settings.push(Setting {
+        name: "letter_order",
+        value: toml_string(letter_order_name(args.letter_order)),
+        source: flag_source(args, "--letter-order"),
+    });
+    settings.push(Setting {
+        name: "propagate",
+        value: args.propagate.to_string(),
+        source: if args.given.iter().any(|f| f == "--no-propagate") {
+            flag_source(args, "--no-propagate")
+        } else {
+            flag_source(args, "--propagate")
+        },
+    });
+    settings.push(Setting {
+        name: "forward_check",
+        value: args.forward_check.to_string(),
+        source: flag_source(args, "--forward-check"),
+    });
+    settings.push(Setting {
+        name: "nogoods",
+        value: args.nogoods.to_string(),
+        source: flag_source(args, "--nogoods"),
+    });
+    settings.push(Setting {
+        name: "parallel",
+        value: args.parallel.to_string(),
+        source: flag_source(args, "--parallel"),
+    });
+    settings.push(match args.threads {
+        Some(count) => Setting {
+            name: "threads",
+            value: count.to_string(),
+            source: flag_source(args, "--threads"),
+        },
+        None => Setting {
+            name: "threads",
+            value: toml_string(""),
+            source: "default (one per core)".to_string(),
+        },
+    });
+    settings.push(Setting {
+        name: "shuffle",
+        value: (args.shuffle || args.seed.is_some()).to_string(),
+        source: if args.seed.is_some() && !args.shuffle {
+            format!("implied by {}", flag_source(args, "--seed"))
+        } else {
+            flag_source(args, "--shuffle")
+        },
+    });
+    settings.push(match args.seed {
+        Some(seed) => Setting {
+            name: "seed",
+            value: seed.to_string(),
+            source: flag_source(args, "--seed"),
+        },
+        None => Setting {
+            name: "seed",
+            value: toml_string(""),
+            source: if args.shuffle {
+                "default (new on every run)".to_string()
+            } else {
+                "default (none)".to_string()
+            },
+        },
+    });
+    settings.push(Setting {
+        name: "deterministic",
+        value: args.deterministic.to_string(),
+        source: flag_source(args, "--deterministic"),
+    });
+    settings.push(match args.restarts {
+        Some(unit) => Setting {
+            name: "restarts",
+            value: unit.to_string(),
+            source: flag_source(args, "--restarts"),
+        },
+        None => Setting {
+            name: "restarts",
+            value: toml_string(""),
+            source: "default (none)".to_string(),
+        },
+    });
+    settings.push(match args.max_attempts {
+        Some(limit) => Setting {
+            name: "max_attempts",
+            value: limit.to_string(),
+            source: flag_source(args, "--max-attempts"),
+        },
+        None => Setting {
+            name: "max_attempts",
+            value: toml_string(""),
+            source: "default (none)".to_string(),
+        },
+    });
+    settings.push(match args.time_limit {
+        Some(limit) => Setting {
+            name: "time_limit",
+            value: limit.as_secs_f64().to_string(),
+            source: flag_source(args, "--time-limit"),
+        },
+        None => Setting {
+            name: "time_limit",
+            value: toml_string(""),
+            source: "default (none)".to_string(),
+        },
+    });
+    settings.push(match args.optimize_for {
+        Some(budget) => Setting {
+            name: "optimize_for",
+            value: budget.as_secs_f64().to_string(),
+            source: flag_source(args, "--optimize-for"),
+        },
+        None => Setting {
+            name: "optimize_for",
+            value: toml_string(""),
+            source: "default (none)".to_string(),
+        },
+    });
+    let soft: Vec<String> = args
+        .soft
+        .iter()
+        .map(|(rule, weight)| format!("{}:{}", rule, weight))
+        .collect();
+    settings.push(Setting {
+        name: "soft",
+        value: toml_array(&soft),
+        source: flag_source(args, "--soft"),
+    });
+    let checkpoint = args.checkpoint.as_ref().or(args.resume.as_ref());
+    settings.push(Setting {
+        name: "checkpoint",
+        value: toml_string(checkpoint.map_or("", String::as_str)),
+        source: match (&args.checkpoint, &args.resume) {
+            (Some(_), _) => flag_source(args, "--checkpoint"),
+            (None, Some(_)) => format!("implied by {}", flag_source(args, "--resume")),
+            (None, None) => "default (none)".to_string(),
+        },
+    });
+    settings.push(Setting {
+        name: "checkpoint_every",
+        value: args
+            .checkpoint_every
+            .map_or(60.0, |every| every.as_secs_f64())
+            .to_string(),
+        source: flag_source(args, "--checkpoint-every"),
+    });
+    settings.push(Setting {
+        name: "resume",
+        value: toml_string(args.resume.as_deref().unwrap_or("")),
+        source: match &args.resume {
+            Some(_) => flag_source(args, "--resume"),
+            None => "default (none)".to_string(),
+        },
+    });
+    settings.push(Setting {
+        name: "progress",
+        value: args.progress.to_string(),
+        source: flag_source(args, "--progress"),
+    });
+    settings.push(Setting {
+        name: "themes",
+        value: toml_array(&args.themes),
+        source: flag_source(args, "--theme"),
+    });
+    settings.push(Setting {
+        name: "add_words",
+        value: toml_array(&args.added_words),
+        source: flag_source(args, "--add-word"),
+    });
+    settings.push(Setting {
+        name: "save",
+        value: toml_string(args.save.as_deref().unwrap_or("")),
+        source: match &args.save {
+            Some(_) => flag_source(args, "--save"),
+            None => "default (none)".to_string(),
+        },
+    });
+    settings.push(Setting {
+        name: "log_level",
+        value: toml_string(logging::level_name()),
+        source: if args.verbose > 0 {
+            "command line (--verbose)".to_string()
+        } else if std::env::var_os("RUST_LOG").is_some() {
+            "environment (RUST_LOG)".to_string()
+        } else {
+            default()
+        },
+    });
+    settings.push(Setting {
+        name: "cache_dir",
+        value: toml_string(
+            &cache_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+        ),
+        source: if std::env::var_os("XDG_CACHE_HOME").is_some_and(|d| !d.is_empty()) {
+            "environment (XDG_CACHE_HOME)".to_string()
+        } else {
+            default()
+        },
+    });
+    settings
+}
+
+/// Print the resolved configuration as TOML, with a comment after each
+/// value naming where it came from. There is no configuration file, so
+/// every value comes from a default, the environment (including the
+/// `MAGIC_SQUARE_*` variables) or the command line.
+///
+/// # Arguments
+///
+/// * `args` - The parsed command line.
+/// * `positional` - The `[dictionary] [template] [rows]` arguments.
+pub(crate) fn show(args: &Args, positional: &[String]) {
+    for setting in resolve(args, positional) {
+        println!("{} = {}  # {}", setting.name, setting.value, setting.source);
+    }
+}
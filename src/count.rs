@@ -0,0 +1,117 @@
+//! Counting every way to complete a puzzle.
+
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// How many solutions a puzzle has. See [`count`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SolutionCount {
+    /// How many solutions were found.
+    pub solutions: usize,
+    /// Whether that is every solution. `false` if counting stopped at the
+    /// threshold, so there are at least that many.
+    pub exact: bool,
+}
+
+/// Count the ways to complete a puzzle, to check that it has a manageable
+/// number of answers.
+///
+/// The search goes a row at a time, as with
+/// [`Strategy::Rows`](crate::Strategy::Rows), but does not stop at the
+/// first square: once every row above the last blank one is filled, each
+/// word that fits that row completes a square, so they are counted rather
+/// than placed. Squares that are each other's transpose count as two. A
+/// toroidal square's lines may be any rotation of a word, which whole
+/// rows do not follow, so its squares are found one at a time with
+/// [`solutions`](MagicSquare::solutions) and counted.
+///
+/// # Arguments
+///
+/// * `square` - A square with its fixed letters set. It is left as it was.
+/// * `stop_at` - Stop counting once this many solutions are found, if set,
+///   for when all that matters is whether there are too many.
+///
+/// # Returns
+///
+/// * The number of solutions, and whether it is exact.
+///
+/// ```
+/// use magic_square::count::count;
+/// use magic_square::dictionary::Dictionary;
+/// use magic_square::{LiveView, MagicSquare};
+///
+/// let dict = Dictionary::from_words(["abc"]);
+/// let mut square = MagicSquare::from_template("___", 3, &dict);
+/// square.set_live_view(LiveView::Off);
+/// assert_eq!(count(&mut square, None).solutions, 0);
+///
+/// // Around a torus, `bca` and `cab` read as `abc` too
+/// square.set_toroidal(true);
+/// assert_eq!(count(&mut square, None).solutions, 3);
+/// ```
+pub fn count(square: &mut MagicSquare, stop_at: Option<usize>) -> SolutionCount {
+    let fixed = square.fixed_grid();
+    let mut counted = SolutionCount {
+        solutions: 0,
+        exact: true,
+    };
+    if square.setup_error().is_none() {
+        let last = (0..fixed.len()).rev().find(|&row| square.has_blank(row));
+        match last {
+            Some(_) if square.toroidal => count_solutions(square, stop_at, &mut counted),
+            Some(last) => count_rows(square, 0, last, stop_at, &mut counted),
+            // Every letter is fixed, and every slot is a word
+            None => counted.solutions = 1,
+        }
+    }
+    square.reset_to(&fixed);
+    counted
+}
+
+/// Count the squares the letter-by-letter search finds, adding them to
+/// `counted`, and stop early if it reaches `stop_at`.
+fn count_solutions(square: &mut MagicSquare, stop_at: Option<usize>, counted: &mut SolutionCount) {
+    for _ in square.solutions() {
+        counted.solutions += 1;
+        if stop_at.is_some_and(|stop_at| counted.solutions >= stop_at) {
+            counted.exact = false;
+            break;
+        }
+    }
+}
+
+/// Count the squares below the rows above `row`, which are filled, adding
+/// them to `counted`, and stop early if it reaches `stop_at`.
+fn count_rows(
+    square: &mut MagicSquare,
+    row: usize,
+    last: usize,
+    stop_at: Option<usize>,
+    counted: &mut SolutionCount,
+) {
+    if !square.has_blank(row) {
+        return count_rows(square, row + 1, last, stop_at, counted);
+    }
+    let words = square.row_words(row);
+    if row == last {
+        // Each word leaves every column a word, so completes a square
+        counted.solutions += words.len();
+        square._attempt += words.len();
+        if let Some(stop_at) = stop_at.filter(|&stop_at| counted.solutions >= stop_at) {
+            counted.solutions = stop_at;
+            counted.exact = false;
+        }
+        return;
+    }
+    let before = square.square[row].clone();
+    for word in words {
+        square._attempt += 1;
+        let letters = Slot::Row(row).orient(square.direction, word.chars().collect());
+        square.set_row(row, letters);
+        count_rows(square, row + 1, last, stop_at, counted);
+        if !counted.exact {
+            break;
+        }
+    }
+    square.set_row(row, before);
+}
@@ -0,0 +1,432 @@
+//! Filling a square as an exact cover problem, with dancing links.
+//!
+//! Every row and column that still has a blank is an item that must be
+//! covered exactly once, and every word that fits one is an option that
+//! covers it. The blank cells are secondary items, coloured with the letter
+//! an option puts there: options may share a cell only if they agree on its
+//! letter. This is Knuth's Algorithm C (The Art of Computer Programming,
+//! section 7.2.2.1), which always branches on the slot with the fewest
+//! words left, and removes every word a choice rules out in the slots
+//! crossing it, so a dead end shows up as soon as some slot has no words.
+
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// The items and options of an exact cover problem, as doubly linked
+/// lists that can be taken apart and put back together in reverse order.
+///
+/// Nodes `1..=items` head the items, and the rest hold the options, one
+/// node per item, with a spacer node between each option and the next.
+/// Node 0 heads the list of primary items still to cover.
+struct Links {
+    /// Left and right neighbours in the list of primary items.
+    llink: Vec<usize>,
+    rlink: Vec<usize>,
+    /// How many options are left for each item.
+    len: Vec<usize>,
+    /// The item of each option node, the item itself for a head, or minus
+    /// the number of options before it for a spacer.
+    top: Vec<isize>,
+    /// Up and down neighbours among the nodes of an item. A spacer links
+    /// up to the first node of the option before it, and down to the last
+    /// node of the option after it.
+    ulink: Vec<usize>,
+    dlink: Vec<usize>,
+    /// The colour of each node of a secondary item, 0 for a primary item,
+    /// or -1 once the colour is known to fit.
+    color: Vec<i32>,
+    /// The option each node is part of.
+    option: Vec<usize>,
+}
+
+impl Links {
+    /// Links with `primary` primary items and `secondary` secondary items,
+    /// and no options yet.
+    fn new(primary: usize, secondary: usize) -> Links {
+        let items = primary + secondary;
+        let mut links = Links {
+            llink: (0..=primary)
+                .map(|i| if i == 0 { primary } else { i - 1 })
+                .collect(),
+            rlink: (0..=primary)
+                .map(|i| if i == primary { 0 } else { i + 1 })
+                .collect(),
+            len: vec![0; items + 1],
+            top: (0..=items as isize).collect(),
+            ulink: (0..=items).collect(),
+            dlink: (0..=items).collect(),
+            color: vec![0; items + 1],
+            option: vec![usize::MAX; items + 1],
+        };
+        links.push_spacer(0, 0);
+        links
+    }
+
+    /// Add a spacer after `options` options, the last starting at node
+    /// `first`.
+    fn push_spacer(&mut self, options: usize, first: usize) {
+        self.top.push(-(options as isize));
+        self.ulink.push(first);
+        self.dlink.push(0);
+        self.color.push(0);
+        self.option.push(usize::MAX);
+    }
+
+    /// Add an option covering `nodes`: each an item and its colour, 0 for
+    /// a primary item. Options are numbered from 0 in the order added.
+    fn push_option(&mut self, nodes: &[(usize, i32)]) {
+        let spacer = self.top.len() - 1;
+        let options = -self.top[spacer] as usize;
+        for &(item, color) in nodes {
+            let x = self.top.len();
+            let last = self.ulink[item];
+            self.top.push(item as isize);
+            self.ulink.push(last);
+            self.dlink.push(item);
+            self.color.push(color);
+            self.option.push(options);
+            self.dlink[last] = x;
+            self.ulink[item] = x;
+            self.len[item] += 1;
+        }
+        self.dlink[spacer] = self.top.len() - 1;
+        self.push_spacer(options + 1, spacer + 1);
+    }
+
+    /// The primary item left with the fewest options, the first of them
+    /// if several tie, or `None` if every primary item is covered.
+    fn choose(&self) -> Option<usize> {
+        let mut best = None;
+        let mut i = self.rlink[0];
+        while i != 0 {
+            if best.is_none_or(|b| self.len[i] < self.len[b]) {
+                best = Some(i);
+            }
+            i = self.rlink[i];
+        }
+        best
+    }
+
+    fn cover(&mut self, i: usize) {
+        let mut p = self.dlink[i];
+        while p != i {
+            self.hide(p);
+            p = self.dlink[p];
+        }
+        let (l, r) = (self.llink[i], self.rlink[i]);
+        self.rlink[l] = r;
+        self.llink[r] = l;
+    }
+
+    fn uncover(&mut self, i: usize) {
+        let (l, r) = (self.llink[i], self.rlink[i]);
+        self.rlink[l] = i;
+        self.llink[r] = i;
+        let mut p = self.ulink[i];
+        while p != i {
+            self.unhide(p);
+            p = self.ulink[p];
+        }
+    }
+
+    /// Take every other node of `p`'s option out of its item's list.
+    fn hide(&mut self, p: usize) {
+        let mut q = p + 1;
+        while q != p {
+            let x = self.top[q];
+            if x <= 0 {
+                q = self.ulink[q];
+            } else if self.color[q] < 0 {
+                q += 1;
+            } else {
+                let (u, d) = (self.ulink[q], self.dlink[q]);
+                self.dlink[u] = d;
+                self.ulink[d] = u;
+                self.len[x as usize] -= 1;
+                q += 1;
+            }
+        }
+    }
+
+    fn unhide(&mut self, p: usize) {
+        let mut q = p - 1;
+        while q != p {
+            let x = self.top[q];
+            if x <= 0 {
+                q = self.dlink[q];
+            } else if self.color[q] < 0 {
+                q -= 1;
+            } else {
+                let (u, d) = (self.ulink[q], self.dlink[q]);
+                self.dlink[u] = q;
+                self.ulink[d] = q;
+                self.len[x as usize] += 1;
+                q -= 1;
+            }
+        }
+    }
+
+    /// Remove every option that gives `p`'s secondary item another colour,
+    /// and mark the rest as known to fit. `p` keeps its colour, for
+    /// `unpurify`.
+    fn purify(&mut self, p: usize) {
+        let (c, i) = (self.color[p], self.top[p] as usize);
+        let mut q = self.dlink[i];
+        while q != i {
+            if self.color[q] == c {
+                if q != p {
+                    self.color[q] = -1;
+                }
+            } else {
+                self.hide(q);
+            }
+            q = self.dlink[q];
+        }
+    }
+
+    fn unpurify(&mut self, p: usize) {
+        let (c, i) = (self.color[p], self.top[p] as usize);
+        let mut q = self.ulink[i];
+        while q != i {
+            if self.color[q] < 0 {
+                self.color[q] = c;
+            } else if q != p {
+                self.unhide(q);
+            }
+            q = self.ulink[q];
+        }
+    }
+
+    /// Choose the option of node `x`, whose own item is already covered:
+    /// cover its other items, and narrow its cells to its letters.
+    fn commit_option(&mut self, x: usize) {
+        let mut p = x + 1;
+        while p != x {
+            let j = self.top[p];
+            if j <= 0 {
+                p = self.ulink[p];
+            } else {
+                match self.color[p] {
+                    0 => self.cover(j as usize),
+                    c if c > 0 => self.purify(p),
+                    _ => {}
+                }
+                p += 1;
+            }
+        }
+    }
+
+    /// Undo `commit_option(x)`.
+    fn uncommit_option(&mut self, x: usize) {
+        let mut p = x - 1;
+        while p != x {
+            let j = self.top[p];
+            if j <= 0 {
+                p = self.dlink[p];
+            } else {
+                match self.color[p] {
+                    0 => self.uncover(j as usize),
+                    c if c > 0 => self.unpurify(p),
+                    _ => {}
+                }
+                p -= 1;
+            }
+        }
+    }
+}
+
+/// A slot the search has chosen, and the word it is trying there.
+struct Choice {
+    item: usize,
+    /// The node of the option being tried, or `item` once every option has
+    /// been tried.
+    node: usize,
+    /// How many options have been tried, and how many there were.
+    tried: usize,
+    choices: usize,
+    /// The slot's letters before the option was written into it.
+    saved: Vec<char>,
+}
+
+impl MagicSquare {
+    /// Fill every row and column that has a blank with a word, so that
+    /// crossing words agree, as an exact cover problem.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled.
+    /// * `Err(String)` if no choice of words covers every slot, leaving the
+    ///   grid as it was.
+    pub(crate) fn fill_exact_cover(&mut self) -> Result<(), String> {
+        let slots: Vec<Slot> = Slot::all(&self.square)
+            .into_iter()
+            .filter(|&slot| {
+                self.slot_cells(slot)
+                    .iter()
+                    .any(|&(r, c)| self.is_blank(r, c))
+            })
+            .collect();
+        // Each blank cell is a secondary item, after the slots
+        let mut cell_items = vec![vec![0; self.square[0].len()]; self.square.len()];
+        let mut secondary = 0;
+        for (row, col) in self.empty_cells().collect::<Vec<_>>() {
+            secondary += 1;
+            cell_items[row][col] = slots.len() + secondary;
+        }
+
+        let mut links = Links::new(slots.len(), secondary);
+        let mut options: Vec<(Slot, Vec<char>)> = vec![];
+        for (i, &slot) in slots.iter().enumerate() {
+            let cells = self.slot_cells(slot);
+            for word in self.slot_words(slot) {
+                let mut nodes = vec![(i + 1, 0)];
+                for (&(row, col), c) in cells.iter().zip(word.iter()) {
+                    if self.is_blank(row, col) {
+                        nodes.push((cell_items[row][col], self.color_of(*c)));
+                    }
+                }
+                links.push_option(&nodes);
+                options.push((slot, word));
+            }
+        }
+        debug!(
+            "{} slots, {} cells, {} words to cover them with",
+            slots.len(),
+            secondary,
+            options.len()
+        );
+
+        let mut stack: Vec<Choice> = vec![];
+        let mut descend = true;
+        loop {
+            if descend {
+                let Some(item) = links.choose() else {
+                    return Ok(());
+                };
+                let choices = links.len[item];
+                links.cover(item);
+                stack.push(Choice {
+                    item,
+                    node: links.dlink[item],
+                    tried: 0,
+                    choices,
+                    saved: vec![],
+                });
+            }
+            let Some(choice) = stack.last_mut() else {
+                unreachable!("the search stops when the first slot runs out of words");
+            };
+            if choice.node == choice.item {
+                // Every word in this slot leads nowhere, so backtrack
+                let item = choice.item;
+                links.uncover(item);
+                stack.pop();
+                let depth = self.note_backtrack();
+                trace!("backtracking from {} at depth {}", slots[item - 1], depth);
+                let Some(parent) = stack.last_mut() else {
+                    return Err(format!("No words fill {}", slots[item - 1]));
+                };
+                links.uncommit_option(parent.node);
+                let (slot, _) = &options[links.option[parent.node]];
+                self.write_slot(*slot, &parent.saved);
+                self.path.pop();
+                parent.node = links.dlink[parent.node];
+                descend = false;
+                continue;
+            }
+
+            // Out of attempts: unwind without trying anything else
+            if self.should_stop() {
+                return Err(format!("Gave up at {}", slots[choice.item - 1]));
+            }
+            self._attempt += 1;
+            links.commit_option(choice.node);
+            let (slot, word) = &options[links.option[choice.node]];
+            choice.saved = slot.read(&self.square, self.direction);
+            self.write_slot(*slot, word);
+            self.draw_grid();
+            self.path.push((choice.tried, choice.choices));
+            choice.tried += 1;
+            self.draw_progress();
+            self.note_depth();
+            descend = true;
+        }
+    }
+
+    /// The positions of a slot's cells, in the order its word is read.
+    pub(crate) fn slot_cells(&self, slot: Slot) -> Vec<(usize, usize)> {
+        let mut cells: Vec<(usize, usize)> = match slot {
+            Slot::Row(row) => (0..self.square[row].len()).map(|col| (row, col)).collect(),
+            Slot::Col(col) => (0..self.square.len()).map(|row| (row, col)).collect(),
+        };
+        if slot.is_reversed(self.direction) {
+            cells.reverse();
+        }
+        cells
+    }
+
+    /// Whether the solver may write a cell and has not yet.
+    pub(crate) fn is_blank(&self, row: usize, col: usize) -> bool {
+        self.editable_mask[row][col] && self.square[row][col] == '_'
+    }
+
+    /// The words that fit a slot as it stands, in the order it is read, in
+    /// the order to try them: alphabetical order, or shuffled if a seed is
+    /// set, and most common first if the dictionary has word frequencies.
+    pub(crate) fn slot_words(&mut self, slot: Slot) -> Vec<Vec<char>> {
+        let template: String = slot
+            .read(&self.square, self.direction)
+            .into_iter()
+            .collect();
+        let mirrored = self.mirror_rows && matches!(slot, Slot::Row(_));
+        let mut words: Vec<String> = self
+            .dict
+            .iter_with_template(&template)
+            .filter(|word| !mirrored || self.dict.contains(&word.chars().rev().collect::<String>()))
+            .map(str::to_string)
+            .collect();
+        self.order_words(&mut words);
+        words
+            .into_iter()
+            .map(|word| word.chars().collect())
+            .collect()
+    }
+
+    /// The colour of a letter in a cell: its place in the alphabet, from 1.
+    fn color_of(&self, c: char) -> i32 {
+        let alphabet = self.dict.alphabet();
+        alphabet
+            .iter()
+            .position(|&a| a == c)
+            .unwrap_or(alphabet.len()) as i32
+            + 1
+    }
+
+    /// Write `letters`, in the order the slot is read, into a slot.
+    fn write_slot(&mut self, slot: Slot, letters: &[char]) {
+        for (&(row, col), &c) in self.slot_cells(slot).iter().zip(letters) {
+            self.square[row][col] = c;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{Direction, Strategy};
+
+    #[test]
+    fn fills_blank_and_started_squares() {
+        assert_fills("___", 3, Strategy::ExactCover, |_| {});
+        assert_fills("____", 4, Strategy::ExactCover, |_| {});
+        assert_fills("_f_/_u_/_n_", 3, Strategy::ExactCover, |_| {});
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_directions() {
+        assert_fills("____", 4, Strategy::ExactCover, |square| square.set_mirror_rows(true));
+        for direction in [Direction::BottomUp, Direction::Boustrophedon] {
+            assert_fills("____", 4, Strategy::ExactCover, |square| square.set_direction(direction));
+        }
+    }
+}
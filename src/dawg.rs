@@ -0,0 +1,210 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A node of a `Dawg`: its outgoing edges are `edges[first..first + count]`,
+/// sorted by letter.
+#[derive(Clone)]
+struct Node {
+    first: u32,
+    count: u32,
+    /// Whether a word ends here.
+    is_final: bool,
+    /// Bit `n` is set if some word ends exactly `n` letters below this node
+    /// (for `n` below 64), so prefix queries with a length need no search.
+    lengths: u64,
+}
+
+/// A directed acyclic word graph: a trie whose identical subtrees have been
+/// merged, so words sharing a suffix share nodes. It answers membership and
+/// prefix queries like `Trie` while using a fraction of the memory of a
+/// trie or hash set over a large wordlist.
+#[derive(Clone)]
+pub(crate) struct Dawg {
+    nodes: Vec<Node>,
+    edges: Vec<(char, u32)>,
+}
+
+/// A node while the graph is being built, before it is flattened.
+#[derive(Default)]
+struct Draft {
+    is_final: bool,
+    edges: Vec<(char, usize)>,
+}
+
+impl Dawg {
+    /// Build the graph from words in sorted order, merging equivalent nodes
+    /// as each word is added (Daciuk et al.'s incremental construction).
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - The words, sorted and without duplicates.
+    pub(crate) fn new<'a, I: IntoIterator<Item = &'a str>>(words: I) -> Dawg {
+        let mut drafts: Vec<Draft> = vec![Draft::default()];
+        // Nodes already merged, by a hash of their finality and edges
+        let mut register: HashMap<u64, Vec<usize>> = HashMap::new();
+        // The path of the previous word not yet merged: (parent, letter, child)
+        let mut unchecked: Vec<(usize, char, usize)> = vec![];
+        let mut previous: Vec<char> = vec![];
+
+        for word in words {
+            let letters: Vec<char> = word.chars().collect();
+            let common = letters
+                .iter()
+                .zip(previous.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            Dawg::merge(&mut drafts, &mut register, &mut unchecked, common);
+
+            let mut node = unchecked.last().map_or(0, |&(_, _, child)| child);
+            for &c in &letters[common..] {
+                drafts.push(Draft::default());
+                let child = drafts.len() - 1;
+                drafts[node].edges.push((c, child));
+                unchecked.push((node, c, child));
+                node = child;
+            }
+            drafts[node].is_final = true;
+            previous = letters;
+        }
+        Dawg::merge(&mut drafts, &mut register, &mut unchecked, 0);
+
+        Dawg::flatten(&drafts)
+    }
+
+    /// Merge the unchecked nodes deeper than `depth` into equivalent
+    /// registered nodes, or register them.
+    fn merge(
+        drafts: &mut [Draft],
+        register: &mut HashMap<u64, Vec<usize>>,
+        unchecked: &mut Vec<(usize, char, usize)>,
+        depth: usize,
+    ) {
+        while unchecked.len() > depth {
+            let (parent, c, child) = unchecked.pop().unwrap();
+            let mut hasher = DefaultHasher::new();
+            (drafts[child].is_final, &drafts[child].edges).hash(&mut hasher);
+            let candidates = register.entry(hasher.finish()).or_default();
+            let same = candidates.iter().copied().find(|&id| {
+                drafts[id].is_final == drafts[child].is_final
+                    && drafts[id].edges == drafts[child].edges
+            });
+            match same {
+                Some(existing) => {
+                    let edge = drafts[parent].edges.last_mut().unwrap();
+                    debug_assert_eq!(edge.0, c);
+                    edge.1 = existing;
+                    // The duplicate is unreachable now, so free its edges
+                    drafts[child].edges = vec![];
+                }
+                None => candidates.push(child),
+            }
+        }
+    }
+
+    /// Lay out the nodes reachable from the root in flat arrays, dropping
+    /// the drafts that were merged away.
+    fn flatten(drafts: &[Draft]) -> Dawg {
+        // The new ID of each reachable draft, in breadth-first order
+        let mut ids: Vec<u32> = vec![u32::MAX; drafts.len()];
+        let mut order: Vec<usize> = vec![0];
+        ids[0] = 0;
+        let mut next = 0;
+        while next < order.len() {
+            for &(_, child) in drafts[order[next]].edges.iter() {
+                if ids[child] == u32::MAX {
+                    ids[child] = order.len() as u32;
+                    order.push(child);
+                }
+            }
+            next += 1;
+        }
+
+        let mut nodes = Vec::with_capacity(order.len());
+        let mut edges = vec![];
+        for &draft in order.iter() {
+            let draft = &drafts[draft];
+            nodes.push(Node {
+                first: edges.len() as u32,
+                count: draft.edges.len() as u32,
+                is_final: draft.is_final,
+                lengths: 0,
+            });
+            edges.extend(draft.edges.iter().map(|&(c, child)| (c, ids[child])));
+        }
+
+        // Fill in lengths children first, with a depth-first walk: shared
+        // nodes can sit anywhere in the breadth-first order
+        let mut done = vec![false; nodes.len()];
+        let mut stack: Vec<(usize, bool)> = vec![(0, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if done[id] {
+                continue;
+            }
+            let range = nodes[id].first as usize..(nodes[id].first + nodes[id].count) as usize;
+            if expanded {
+                let mut lengths = nodes[id].is_final as u64;
+                for &(_, child) in edges[range].iter() {
+                    lengths |= nodes[child as usize].lengths << 1;
+                }
+                nodes[id].lengths = lengths;
+                done[id] = true;
+            } else {
+                stack.push((id, true));
+                stack.extend(
+                    edges[range]
+                        .iter()
+                        .map(|&(_, child)| (child as usize, false)),
+                );
+            }
+        }
+
+        Dawg { nodes, edges }
+    }
+
+    fn child(&self, node: u32, c: char) -> Option<u32> {
+        let node = &self.nodes[node as usize];
+        let edges = &self.edges[node.first as usize..(node.first + node.count) as usize];
+        edges
+            .binary_search_by_key(&c, |&(letter, _)| letter)
+            .ok()
+            .map(|i| edges[i].1)
+    }
+
+    /// The node reached by following `letters` from the root.
+    fn walk(&self, letters: &str) -> Option<u32> {
+        letters.chars().try_fold(0, |node, c| self.child(node, c))
+    }
+
+    pub(crate) fn contains(&self, word: &str) -> bool {
+        self.walk(word)
+            .is_some_and(|node| self.nodes[node as usize].is_final)
+    }
+
+    /// Whether any word of length `len` starts with `prefix`.
+    pub(crate) fn has_prefix(&self, prefix: &str, len: usize) -> bool {
+        let Some(node) = self.walk(prefix) else {
+            return false;
+        };
+        let Some(remaining) = len.checked_sub(prefix.chars().count()) else {
+            return false;
+        };
+        if remaining < 64 {
+            self.nodes[node as usize].lengths & (1 << remaining) != 0
+        } else {
+            self.ends_at_depth(node, remaining)
+        }
+    }
+
+    /// Whether some word ends exactly `depth` letters below `node`, for
+    /// lengths too long for the `lengths` bitmask.
+    fn ends_at_depth(&self, node: u32, depth: usize) -> bool {
+        let node = &self.nodes[node as usize];
+        if depth == 0 {
+            return node.is_final;
+        }
+        self.edges[node.first as usize..(node.first + node.count) as usize]
+            .iter()
+            .any(|&(_, child)| self.ends_at_depth(child, depth - 1))
+    }
+}
@@ -1,52 +1,1212 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use crate::codec::{Reader, Writer};
+use crate::dawg::Dawg;
+use crate::gzip;
+use crate::hunspell;
+use crate::index::PositionIndex;
+use crate::logging::{Level, Span};
+use crate::normalize::fold_diacritics;
+use crate::parallel;
+use crate::phonetic::metaphone;
+use crate::regex::Regex;
+use crate::trie::Trie;
+
+/// Where operating systems and distros put their wordlists, most common
+/// first. Windows has no standard wordlist, so it relies on an explicit file.
+const OS_DICT_PATHS: &[&str] = &[
+    "/usr/share/dict/words",
+    "/usr/dict/words",
+    "/usr/share/dict/web2",
+    "/usr/share/dict/american-english",
+    "/usr/share/dict/british-english",
+    "/usr/share/dict/english",
+    "/usr/local/share/dict/words",
+];
+
+/// Plain wordlists under `/usr/share/dict`, per language, as installed by
+/// the distro wordlist packages (wngerman, wfrench, ...).
+const LOCALE_WORDLISTS: &[(&str, &[&str])] = &[
+    ("en", &["words", "american-english", "british-english", "english"]),
+    ("de", &["ngerman", "ogerman", "swiss", "german"]),
+    ("fr", &["french"]),
+    ("es", &["spanish"]),
+    ("it", &["italian"]),
+    ("pt", &["portuguese", "brazilian"]),
+    ("nl", &["dutch"]),
+    ("sv", &["swedish"]),
+    ("da", &["danish"]),
+    ("nb", &["bokmaal"]),
+    ("nn", &["nynorsk"]),
+    ("fi", &["finnish"]),
+    ("pl", &["polish"]),
+    ("ca", &["catalan"]),
+];
+
+/// Where hunspell and myspell `.dic` files are installed, most common first.
+const HUNSPELL_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/opt/homebrew/share/hunspell",
+    "/Library/Spelling",
+];
+
+/// The size tiers of SCOWL wordlists, from the most common words only (10)
+/// to nearly every word (95).
+pub const SCOWL_SIZES: &[u32] = &[10, 20, 35, 40, 50, 55, 60, 70, 80, 95];
+
+/// The SCOWL size a directory is loaded at unless another is asked for.
+pub const DEFAULT_SCOWL_SIZE: u32 = 50;
+
+/// The SCOWL categories loaded: American English words, including those
+/// spelled with capitals and proper names (see `ProperNouns`), but not
+/// abbreviations, contractions or the British and Canadian variants.
+const SCOWL_CATEGORIES: &[&str] = &[
+    "english-words",
+    "english-upper",
+    "english-proper-names",
+    "american-words",
+    "american-upper",
+    "american-proper-names",
+];
+
+/// The wordlist downloaded when no OS dictionary is installed.
+pub const DEFAULT_WORDLIST_URL: &str =
+    "https://raw.githubusercontent.com/dwyl/english-words/master/words_alpha.txt";
+
+/// A compact wordlist of common three- to five-letter English words, built
+/// into the binary.
+#[cfg(feature = "embedded-dict")]
+const EMBEDDED_WORDS: &[u8] = include_bytes!("../data/words.txt");
+
+/// Slurs and crude words removed by `filter_offensive`, one per line.
+const BLOCKLIST: &str = include_str!("../data/blocklist.txt");
+
+/// The directory downloaded wordlists are cached in: `$XDG_CACHE_HOME`,
+/// falling back to `~/.cache` (or `%LOCALAPPDATA%` on Windows).
+pub fn cache_dir() -> Result<PathBuf, String> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .ok_or("could not find a cache directory; set XDG_CACHE_HOME")?;
+    Ok(base.join("magic-square"))
+}
+
+/// The directory personal settings live in: `$XDG_CONFIG_HOME`, falling
+/// back to `~/.config` (or `%APPDATA%` on Windows).
+pub fn config_dir() -> Result<PathBuf, String> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .ok_or("could not find a config directory; set XDG_CONFIG_HOME")?;
+    Ok(base.join("magic-square"))
+}
+
+/// Wordlists with fewer words than this build their indexes quickly
+/// enough that caching them is not worth a file.
+const INDEX_CACHE_MIN_WORDS: usize = 20_000;
+
+/// How many lines of a wordlist one thread lowercases at a time on load.
+const LOAD_CHUNK_LINES: usize = 16_384;
+
+/// The first bytes of an index cache file. Bump the version whenever the
+/// layout of the indexes changes, so stale caches are rebuilt.
+const INDEX_CACHE_MAGIC: &[u8] = b"magic-square index 3\n";
+
+/// A stable file name for the cached copy of `url`.
+fn cache_name(url: &str) -> String {
+    format!("{:016x}", fnv1a(url.as_bytes()))
+}
+
+/// The 64-bit FNV-1a hash of some bytes, which (unlike `std`'s hashers) is
+/// the same in every Rust release.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// A hash of a word's letters in sorted order, which every anagram of it
+/// shares. `letters` is scratch space, to save allocating per word.
+fn anagram_key(word: &str, letters: &mut Vec<char>) -> u64 {
+    letters.clear();
+    letters.extend(word.chars());
+    letters.sort_unstable();
+    let mut bytes = [0; 4];
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for c in letters.iter() {
+        for byte in c.encode_utf8(&mut bytes).bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// Letters moved `k` places to the left, the first ones wrapping round to
+/// the end.
+fn rotate(letters: &[char], k: usize) -> String {
+    let k = if letters.is_empty() { 0 } else { k % letters.len() };
+    letters[k..].iter().chain(&letters[..k]).collect()
+}
+
+/// The hash of the sorted letters of every word of the given lengths, with
+/// the word's length and position in `index.words(len)`, unsorted.
+fn anagram_entries(index: &PositionIndex, lengths: &[usize]) -> Vec<(u64, u32, u32)> {
+    let mut letters = vec![];
+    lengths
+        .iter()
+        .flat_map(|&len| {
+            index.words(len).iter().enumerate().map(move |(id, word)| (len, id, word))
+        })
+        .map(|(len, id, word)| (anagram_key(word, &mut letters), len as u32, id as u32))
+        .collect()
+}
+
+/// The hash of every word's sorted letters, with the word's length and
+/// position in `index.words(len)`, sorted so anagrams are adjacent, for
+/// `Dictionary::anagrams_of`.
+fn anagram_index(index: &PositionIndex) -> Vec<(u64, u32, u32)> {
+    let mut anagrams = anagram_entries(index, &index.lengths());
+    anagrams.sort_unstable();
+    anagrams
+}
+
+/// Every lowercase letter in the index, in order.
+fn alphabet(index: &PositionIndex) -> Vec<char> {
+    index
+        .letters()
+        .into_iter()
+        .filter(|c| c.is_alphabetic() && !c.is_uppercase())
+        .collect()
+}
+
+/// A prefix tree of the words of each of `lengths`, built in parallel.
+fn build_tries(index: &PositionIndex, lengths: Vec<usize>) -> Vec<(usize, Arc<Trie>)> {
+    let tries = parallel::map(lengths.clone(), |len| {
+        let mut trie = Trie::new();
+        // In sorted order, which `Trie::insert` is quickest with
+        for word in index.words(len) {
+            trie.insert(word);
+        }
+        Arc::new(trie)
+    });
+    lengths.into_iter().zip(tries).collect()
+}
+
+/// Which capitalized words (names, places and other proper nouns, as
+/// spelled in the source wordlist) a dictionary keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProperNouns {
+    /// Keep them alongside the other words (the default).
+    Keep,
+    /// Drop them.
+    Exclude,
+    /// Keep only them.
+    Only,
+}
+
+impl ProperNouns {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProperNouns::Keep => "keep",
+            ProperNouns::Exclude => "exclude",
+            ProperNouns::Only => "only",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<ProperNouns, String> {
+        match name {
+            "keep" => Ok(ProperNouns::Keep),
+            "exclude" => Ok(ProperNouns::Exclude),
+            "only" => Ok(ProperNouns::Only),
+            _ => Err(format!(
+                "unknown proper-noun policy {} (expected keep, exclude or only)",
+                name
+            )),
+        }
+    }
+}
+
+/// Entries of a wordlist that can never be placed in a grid, counted as it
+/// loads. Blank lines and duplicates are skipped; words with characters
+/// other than letters (such as "don't" or "e-mail") are kept unless
+/// `Dictionary::without_unusable` drops them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hygiene {
+    /// Lines that were empty or only whitespace.
+    pub blank: usize,
+    /// Words that were already listed, once lowercased ("Rose" after
+    /// "rose", or the same word twice).
+    pub duplicates: usize,
+    /// Words with a character that is not a letter.
+    pub non_alphabetic: usize,
+}
+
+impl Hygiene {
+    /// Whether the wordlist had none of these entries.
+    pub fn is_clean(&self) -> bool {
+        *self == Hygiene::default()
+    }
+}
+
+impl fmt::Display for Hygiene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if self.blank > 0 {
+            parts.push(format!("{} blank lines", self.blank));
+        }
+        if self.duplicates > 0 {
+            parts.push(format!("{} duplicates after lowercasing", self.duplicates));
+        }
+        if self.non_alphabetic > 0 {
+            parts.push(format!("{} words with non-letters", self.non_alphabetic));
+        }
+        if parts.is_empty() {
+            return write!(f, "no unusable entries");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// How a dictionary answers membership and prefix queries.
+#[derive(Clone)]
+enum Lookup {
+    /// Hash sets of the words and of every word spelled backwards, and a
+    /// prefix tree per word length: the fastest, and the default.
+    Hashed {
+        words: HashSet<String>,
+        reversed: HashSet<String>,
+        tries: HashMap<usize, Arc<Trie>>,
+    },
+    /// One minimized word graph, from `Dictionary::compact`.
+    Compact(Dawg),
+}
 
 /// A simple Dictionary implementation, with `contains` and `len` methods.
 /// Clonable, so it can be passed around.
 #[derive(Clone)]
 pub struct Dictionary {
-    words: HashSet<String>,
+    /// Membership and prefix lookups.
+    lookup: Lookup,
+    /// Words bucketed by length, with bitsets of word IDs per (length,
+    /// position, letter) for templates.
+    index: PositionIndex,
+    /// How common each word is, from `with_frequencies`. Higher is more
+    /// common; words not listed score 0.
+    frequencies: HashMap<String, u64>,
+    /// Every lowercase letter used by some word, in order.
+    alphabet: Vec<char>,
+    /// A hash of each word's letters in sorted order, with its length and
+    /// position in `index.words(len)`, sorted by hash.
+    anagrams: Vec<(u64, u32, u32)>,
+    /// The words the source wordlist only ever spelled with capitals, such
+    /// as "Paris", lowercased like every other word.
+    proper_nouns: HashSet<String>,
+    /// The unusable entries found in the source wordlist.
+    hygiene: Hygiene,
 }
 
 impl Dictionary {
-    pub(crate) fn contains(&self, word: &str) -> bool {
-        self.words.contains(word)
+    pub fn contains(&self, word: &str) -> bool {
+        match &self.lookup {
+            Lookup::Hashed { words, .. } => words.contains(word),
+            Lookup::Compact(dawg) => dawg.contains(word),
+        }
+    }
+
+    /// The number of words.
+    pub fn len(&self) -> usize {
+        self.index.lengths().into_iter().map(|len| self.index.words(len).len()).sum()
+    }
+
+    /// Whether the dictionary has no words.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every word, shortest first and then in alphabetical order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &String> {
+        self.index
+            .lengths()
+            .into_iter()
+            .flat_map(|len| self.index.words(len).iter())
+    }
+
+    /// The words as a set, for building a changed copy of the dictionary.
+    fn word_set(&self) -> HashSet<String> {
+        match &self.lookup {
+            Lookup::Hashed { words, .. } => words.clone(),
+            Lookup::Compact(_) => self.iter().cloned().collect(),
+        }
+    }
+
+    /// Build a dictionary from `words` with the same kind of lookups as this
+    /// one. Proper nouns that are still in it are carried over, as is
+    /// what was found by the source wordlist's hygiene check; frequencies
+    /// are not.
+    fn rebuild(&self, words: HashSet<String>) -> Dictionary {
+        let old = self.word_set();
+        let removed = old.difference(&words).cloned().collect();
+        let added = words.difference(&old).cloned().collect();
+        self.edit(&removed, &added)
+    }
+
+    /// This dictionary with the words in `removed` taken out and those in
+    /// `added` put in, with the same kind of lookups. Only the indexes of
+    /// the word lengths that changed are built again, so filters that drop
+    /// or add a few words are quick even on large wordlists. Proper nouns
+    /// that are still in it are carried over, as is what was found by the
+    /// source wordlist's hygiene check; frequencies are not.
+    fn edit(&self, removed: &HashSet<String>, added: &HashSet<String>) -> Dictionary {
+        let mut words = self.word_set();
+        words.retain(|w| !removed.contains(w));
+        words.extend(added.iter().cloned());
+        let mut dict = match &self.lookup {
+            Lookup::Hashed { reversed, tries, .. } => {
+                self.edit_hashed(words, removed, added, reversed, tries)
+            }
+            Lookup::Compact(_) => Dictionary::new(words).compact(),
+        };
+        dict.hygiene = self.hygiene;
+        dict.proper_nouns = self
+            .proper_nouns
+            .iter()
+            .filter(|w| !removed.contains(*w))
+            .cloned()
+            .collect();
+        dict
+    }
+
+    /// The hashed lookups and indexes for `edit`, built from this
+    /// dictionary's.
+    fn edit_hashed(
+        &self,
+        words: HashSet<String>,
+        removed: &HashSet<String>,
+        added: &HashSet<String>,
+        reversed: &HashSet<String>,
+        tries: &HashMap<usize, Arc<Trie>>,
+    ) -> Dictionary {
+        let mut changed: HashMap<usize, Vec<String>> = HashMap::new();
+        for word in removed.iter().chain(added.iter()) {
+            let len = word.chars().count();
+            changed.entry(len).or_insert_with(|| {
+                let kept = self.index.words(len).iter().filter(|w| !removed.contains(*w));
+                kept.cloned().collect()
+            });
+        }
+        for word in added.iter() {
+            if let Some(same_length) = changed.get_mut(&word.chars().count()) {
+                same_length.push(word.clone());
+            }
+        }
+        let lengths: Vec<usize> = changed.keys().copied().collect();
+        let index = self.index.with_lengths(changed);
+
+        let mut reversed = reversed.clone();
+        for word in removed.iter() {
+            reversed.remove(&word.chars().rev().collect::<String>());
+        }
+        reversed.extend(added.iter().map(|w| w.chars().rev().collect::<String>()));
+        let mut tries = tries.clone();
+        for len in lengths.iter() {
+            tries.remove(len);
+        }
+        let present: Vec<usize> = lengths
+            .iter()
+            .copied()
+            .filter(|&len| !index.words(len).is_empty())
+            .collect();
+        tries.extend(build_tries(&index, present.clone()));
+        // Word IDs only moved within the changed lengths
+        let mut anagrams: Vec<(u64, u32, u32)> = self
+            .anagrams
+            .iter()
+            .filter(|&&(_, len, _)| !lengths.contains(&(len as usize)))
+            .copied()
+            .collect();
+        anagrams.extend(anagram_entries(&index, &present));
+        anagrams.sort_unstable();
+        Dictionary {
+            lookup: Lookup::Hashed {
+                words,
+                reversed,
+                tries,
+            },
+            anagrams,
+            alphabet: alphabet(&index),
+            index,
+            frequencies: HashMap::new(),
+            proper_nouns: HashSet::new(),
+            hygiene: Hygiene::default(),
+        }
+    }
+
+    /// Replace the hash sets and prefix trees used for lookups with a
+    /// minimized word graph (a DAWG), which shares the nodes of common
+    /// suffixes as well as prefixes. On large wordlists this takes a
+    /// fraction of the memory, at the cost of somewhat slower `contains`
+    /// and `has_prefix` queries. Template queries are unaffected.
+    pub fn compact(self) -> Dictionary {
+        let Lookup::Hashed { words, .. } = &self.lookup else {
+            return self;
+        };
+        let _span = Span::enter(Level::Info, "compact dictionary");
+        let mut sorted: Vec<&String> = words.iter().collect();
+        sorted.sort();
+        let dawg = Dawg::new(sorted.into_iter().map(String::as_str));
+        Dictionary {
+            lookup: Lookup::Compact(dawg),
+            ..self
+        }
+    }
+
+    /// Whether lookups use the word graph built by `compact`.
+    pub fn is_compact(&self) -> bool {
+        matches!(self.lookup, Lookup::Compact(_))
+    }
+
+    /// Return the words of a given length, in alphabetical order. Words are
+    /// bucketed by length at load time, so this does not touch the others.
+    pub fn words_of_length(&self, len: usize) -> &[String] {
+        self.index.words(len)
+    }
+
+    /// For each position in words of length `len` (the first letter is
+    /// position 0), how many of those words have each letter there. Letters
+    /// that never appear at a position are left out; divide by the length
+    /// of `words_of_length(len)` for the share of words instead. This reads
+    /// the positional index, so it does not visit the words.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["cat", "cot", "dog", "horse"]);
+    /// let frequencies = dict.letter_frequencies(3);
+    /// assert_eq!(frequencies.len(), 3);
+    /// assert_eq!(frequencies[0][&'c'], 2);
+    /// assert_eq!(frequencies[1][&'o'], 2);
+    /// assert!(!frequencies[2].contains_key(&'e'));
+    /// ```
+    pub fn letter_frequencies(&self, len: usize) -> Vec<HashMap<char, usize>> {
+        self.index.letter_counts(len)
+    }
+
+    /// Every lowercase letter used by some word, in order: the letters the
+    /// solver tries in each cell. Accented letters are distinct letters
+    /// here, unless the dictionary was loaded with `fold_diacritics`.
+    pub fn alphabet(&self) -> &[char] {
+        &self.alphabet
+    }
+
+    /// Fold every word to unaccented letters ("café" becomes "cafe"), so
+    /// accented wordlists fill grids of plain letters. See
+    /// [`normalize::fold_diacritics`](crate::normalize::fold_diacritics).
+    /// Frequencies are carried over to the folded words.
+    pub fn fold_diacritics(self) -> Dictionary {
+        if self.iter().all(|w| w.is_ascii()) {
+            return self;
+        }
+        let words = self.iter().map(|w| fold_diacritics(w)).collect();
+        let mut dict = self.rebuild(words);
+        // A folded word is a proper noun if every word folded into it was
+        let common: HashSet<String> = self
+            .iter()
+            .filter(|w| !self.proper_nouns.contains(*w))
+            .map(|w| fold_diacritics(w))
+            .collect();
+        dict.proper_nouns = self
+            .proper_nouns
+            .iter()
+            .map(|w| fold_diacritics(w))
+            .filter(|w| !common.contains(w))
+            .collect();
+        for (word, score) in self.frequencies {
+            let entry = dict.frequencies.entry(fold_diacritics(&word)).or_insert(0);
+            *entry = (*entry).max(score);
+        }
+        dict
     }
 
     /// Return all the words that match a template. A template is a set of
     /// letters or a wildcard (_). For example, "__mon" will match "demon" and
     /// "lemon", but not "human".
-    pub(crate) fn search_with_template(&self, template: &str) -> Vec<String> {
-        let tmp = template.to_lowercase();
-        self.words
+    ///
+    /// Matches are returned in alphabetical order.
+    pub fn search_with_template(&self, template: &str) -> Vec<String> {
+        self.iter_with_template(template)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Like `search_with_template`, but yields the matches lazily, so a
+    /// caller that wants only the first few (or only checks for any) does
+    /// not build the whole list.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["demon", "human", "lemon"]);
+    /// assert_eq!(dict.iter_with_template("__mon").next(), Some("demon"));
+    /// ```
+    pub fn iter_with_template(&self, template: &str) -> impl Iterator<Item = &str> + '_ {
+        self.index.matches(&template.to_lowercase())
+    }
+
+    /// Count the words that match a template. This ANDs the precomputed
+    /// bitsets of the words with each fixed letter at its position and
+    /// counts the bits left, so it never scans the word list.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["demon", "human", "lemon", "melon"]);
+    /// assert_eq!(dict.count_with_template("__mon"), 2);
+    /// assert_eq!(dict.count_with_template("_____"), 4);
+    /// ```
+    pub fn count_with_template(&self, template: &str) -> usize {
+        self.index.count(&template.to_lowercase())
+    }
+
+    /// Return the words matching a regular expression anywhere, in
+    /// alphabetical order. Patterns support `.`, classes such as `[aeiou]`
+    /// or `[^a-m]`, groups, `|`, the repeats `*`, `+`, `?` and `{n,m}`, and
+    /// the anchors `^` and `$`, for queries templates cannot express.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["demon", "human", "lemon", "melon"]);
+    /// assert_eq!(dict.search_regex("^[dl]e|an$").unwrap(), ["demon", "human", "lemon"]);
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<String>)` with the matching words.
+    /// * `Err(String)` if the pattern is malformed.
+    pub fn search_regex(&self, pattern: &str) -> Result<Vec<String>, String> {
+        let regex = Regex::new(pattern)?;
+        let mut words: Vec<String> = self.iter().filter(|w| regex.is_match(w)).cloned().collect();
+        words.sort();
+        Ok(words)
+    }
+
+    /// Return the words spelled with exactly the letters of `letters`, in
+    /// alphabetical order: its anagrams, and `letters` itself if it is a
+    /// word. Words are grouped by their sorted letters at load time, so this
+    /// is a single lookup.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["opts", "post", "spot", "stop", "tops", "toss"]);
+    /// assert_eq!(dict.anagrams_of("stop"), ["opts", "post", "spot", "stop", "tops"]);
+    /// assert_eq!(dict.anagrams_of("Pots"), dict.anagrams_of("stop"));
+    /// ```
+    pub fn anagrams_of(&self, letters: &str) -> Vec<String> {
+        let sorted = |word: &str| {
+            let mut letters: Vec<char> = word.chars().collect();
+            letters.sort_unstable();
+            letters
+        };
+        let wanted = sorted(&letters.trim().to_lowercase());
+        let word: String = wanted.iter().collect();
+        let key = (anagram_key(&word, &mut vec![]), wanted.len() as u32);
+        let start = self.anagrams.partition_point(|&(hash, len, _)| (hash, len) < key);
+        let words = self.index.words(wanted.len());
+        // Different letters can share a hash, so check each word
+        self.anagrams[start..]
             .iter()
-            .filter(|word| {
-                // Short-circuit on length:
-                if word.len() != tmp.len() {
-                    return false;
-                }
-                let mut chars = word.chars();
-                for c in tmp.chars() {
-                    if c == '_' {
-                        chars.next();
-                    } else {
-                        if chars.next() != Some(c) {
-                            return false;
-                        }
-                    }
-                }
-                true
+            .take_while(|&&(hash, len, _)| (hash, len) == key)
+            .map(|&(_, _, id)| &words[id as usize])
+            .filter(|w| sorted(w) == wanted)
+            .cloned()
+            .collect()
+    }
+
+    /// Return the words that sound like `word` by their Metaphone key (see
+    /// `phonetic::metaphone`), such as "night" and "knight" for "nite", in
+    /// alphabetical order and of any length. `word` itself is left out.
+    /// This computes the key of every word, so it scans the whole
+    /// dictionary.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["knight", "night", "nite", "note", "nine"]);
+    /// assert!(dict.sounds_like("nite").contains(&"knight".to_string()));
+    /// assert!(!dict.sounds_like("nite").contains(&"nine".to_string()));
+    /// ```
+    pub fn sounds_like(&self, word: &str) -> Vec<String> {
+        let key = metaphone(word);
+        if key.is_empty() {
+            return vec![];
+        }
+        let word = word.trim().to_lowercase();
+        let mut words: Vec<String> = self
+            .iter()
+            .filter(|w| **w != word && metaphone(w) == key)
+            .cloned()
+            .collect();
+        words.sort_unstable();
+        words
+    }
+
+    /// Whether any word matches a template. This stops at the first match,
+    /// so it is cheaper than `count_with_template` when only that matters.
+    pub fn has_match(&self, template: &str) -> bool {
+        self.iter_with_template(template).next().is_some()
+    }
+
+    /// Whether any word of length `len` starts with `prefix`. This walks a
+    /// prefix tree (or word graph), so it costs time proportional to the
+    /// prefix rather than to the size of the dictionary.
+    pub fn has_prefix(&self, prefix: &str, len: usize) -> bool {
+        let prefix = prefix.to_lowercase();
+        match &self.lookup {
+            Lookup::Hashed { tries, .. } => tries
+                .get(&len)
+                .is_some_and(|trie| trie.has_prefix(&prefix)),
+            Lookup::Compact(dawg) => dawg.has_prefix(&prefix, len),
+        }
+    }
+
+    /// Return all the words whose reversal matches a template, reversed (so
+    /// "__mon" matches "nomed", because "demon" is a word).
+    pub fn search_reversed_with_template(&self, template: &str) -> Vec<String> {
+        // A reversed word matches the template exactly when the word matches
+        // the reversed template
+        let tmp: String = template.to_lowercase().chars().rev().collect();
+        self.index
+            .matches(&tmp)
+            .map(|word| word.chars().rev().collect())
+            .collect()
+    }
+
+    pub fn count_reversed_with_template(&self, template: &str) -> usize {
+        let tmp: String = template.to_lowercase().chars().rev().collect();
+        self.index.matches(&tmp).count()
+    }
+
+    /// Whether the word, spelled backwards, is in the dictionary.
+    pub fn contains_reversed(&self, word: &str) -> bool {
+        match &self.lookup {
+            Lookup::Hashed { reversed, .. } => reversed.contains(word),
+            Lookup::Compact(dawg) => dawg.contains(&word.chars().rev().collect::<String>()),
+        }
+    }
+
+    /// Return every rotation of a word that matches a template, rotated to
+    /// match it (so "ple__" matches "pleap", which is "apple" rotated by
+    /// two), in alphabetical order without repeats.
+    pub fn search_rotated_with_template(&self, template: &str) -> Vec<String> {
+        let template: Vec<char> = template.to_lowercase().chars().collect();
+        let mut words: Vec<String> = (0..template.len().max(1))
+            .flat_map(|k| {
+                // A word rotated left by `k` matches the template exactly
+                // when the word matches the template rotated right by `k`
+                let unrotated = rotate(&template, template.len() - k);
+                self.index
+                    .matches(&unrotated)
+                    .map(|word| rotate(&word.chars().collect::<Vec<_>>(), k))
+                    .collect::<Vec<_>>()
             })
-            .map(|s| s.to_string())
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        words
+    }
+
+    /// How many rotations of words match a template. See
+    /// `search_rotated_with_template`.
+    pub fn count_rotated_with_template(&self, template: &str) -> usize {
+        self.search_rotated_with_template(template).len()
+    }
+
+    /// Whether some rotation of some word matches a template, so that a
+    /// line read around a torus can still be completed. This stops at the
+    /// first match.
+    pub fn has_rotated_match(&self, template: &str) -> bool {
+        let template: Vec<char> = template.to_lowercase().chars().collect();
+        (0..template.len().max(1)).any(|k| self.has_match(&rotate(&template, k)))
+    }
+
+    /// Whether some rotation of the word is in the dictionary, as "apple"
+    /// is for "pleap".
+    pub fn contains_rotated(&self, word: &str) -> bool {
+        let chars: Vec<char> = word.chars().collect();
+        (0..chars.len().max(1)).any(|k| self.contains(&rotate(&chars, k)))
+    }
+
+    /// Return the words of a given length that read the same backwards,
+    /// such as "level", in alphabetical order.
+    pub fn palindromes(&self, len: usize) -> Vec<String> {
+        self.words_of_length(len)
+            .iter()
+            .filter(|w| w.chars().eq(w.chars().rev()))
+            .cloned()
             .collect()
     }
 
-    pub(crate) fn count_with_template(&self, template: &str) -> usize {
-        self.search_with_template(template).len()
+    /// Return the words of a given length that spell a different word
+    /// backwards, such as "stop" ("pots"), in alphabetical order.
+    pub fn semordnilaps(&self, len: usize) -> Vec<String> {
+        self.words_of_length(len)
+            .iter()
+            .filter(|w| self.contains_reversed(w))
+            .filter(|w| !w.chars().eq(w.chars().rev()))
+            .cloned()
+            .collect()
+    }
+
+    /// How common a word is, from the frequency list loaded with
+    /// `with_frequencies`. Higher is more common; unlisted words score 0.
+    pub fn frequency(&self, word: &str) -> u64 {
+        self.frequencies.get(word).copied().unwrap_or(0)
+    }
+
+    /// How common a word is on a scale from 0, for a word the frequency
+    /// list lacks, towards 1, which the most common words approach. The
+    /// scale is logarithmic, as word counts are.
+    pub fn commonness(&self, word: &str) -> f64 {
+        1.0 - 1.0 / (1.0 + (self.frequency(word) as f64).ln_1p())
+    }
+
+    /// Whether a frequency list has been loaded.
+    pub fn has_frequencies(&self) -> bool {
+        !self.frequencies.is_empty()
+    }
+
+    /// Whether the source wordlist only ever spelled `word` with capitals,
+    /// as it does names and places. Wordlists that capitalize all nouns,
+    /// as German ones do, mark all of those too.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["Paris", "Polish", "polish", "rose"]);
+    /// assert!(dict.is_proper_noun("paris"));
+    /// assert!(!dict.is_proper_noun("polish") && !dict.is_proper_noun("rose"));
+    /// ```
+    pub fn is_proper_noun(&self, word: &str) -> bool {
+        self.proper_nouns.contains(&word.to_lowercase())
+    }
+
+    /// Drop the proper nouns (see `is_proper_noun`), keep only them, or
+    /// keep everything, rebuilding the indexes if any words were dropped.
+    pub fn with_proper_nouns(mut self, policy: ProperNouns) -> Dictionary {
+        let proper_nouns = self.proper_nouns.clone();
+        match policy {
+            ProperNouns::Keep => {}
+            ProperNouns::Exclude => self.retain(|w| !proper_nouns.contains(w)),
+            ProperNouns::Only => self.retain(|w| proper_nouns.contains(w)),
+        }
+        self
+    }
+
+    /// For each letter, the frequency of the most common word that matches
+    /// `template` and has that letter at `position`.
+    pub(crate) fn best_frequency_by_letter(
+        &self,
+        template: &str,
+        position: usize,
+    ) -> HashMap<char, u64> {
+        let mut best: HashMap<char, u64> = HashMap::new();
+        for word in self.index.matches(template) {
+            let Some(c) = word.chars().nth(position) else {
+                continue;
+            };
+            let score = best.entry(c).or_insert(0);
+            *score = (*score).max(self.frequency(word));
+        }
+        best
+    }
+
+    /// For each letter, how many words that match `template` have that
+    /// letter at `position`.
+    pub(crate) fn count_by_letter(&self, template: &str, position: usize) -> HashMap<char, usize> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for word in self.index.matches(template) {
+            if let Some(c) = word.chars().nth(position) {
+                *counts.entry(c).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Narrow the letters allowed at each position of a word to those
+    /// that some word of that length, using only allowed letters, has
+    /// there. A position left with no letters means no word fits.
+    pub(crate) fn narrow(&self, allowed: &[Vec<char>]) -> Vec<Vec<char>> {
+        self.index.supported(allowed)
+    }
+
+    /// The words matching a template, as a bitset of their IDs among the
+    /// words of its length, to narrow a letter at a time with
+    /// `letter_bits` as letters are placed.
+    pub(crate) fn match_bits(&self, template: &str) -> Vec<u64> {
+        self.index.match_bits(&template.to_lowercase())
+    }
+
+    /// The bitset of the words of length `len` with `c` at `pos`, or
+    /// `None` if none has.
+    pub(crate) fn letter_bits(&self, len: usize, pos: usize, c: char) -> Option<&[u64]> {
+        self.index.letter_bits(len, pos, c)
+    }
+
+    /// Attach a word-frequency list, so the solver prefers common words.
+    ///
+    /// Each line holds a word, optionally followed by whitespace and a count
+    /// (as in `the 23135851162`). Lines without a count are ranked instead:
+    /// the list is taken to be sorted most common first. Words that are not
+    /// in the dictionary are ignored. Compressed lists are read as in
+    /// `from_file`.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the frequency list.
+    ///
+    /// # Returns
+    /// * Ok(The dictionary, with a score for every listed word)
+    /// * Err(String) if the file could not be read or a count is malformed.
+    pub fn with_frequencies(mut self, path: &str) -> Result<Dictionary, String> {
+        let _span = Span::enter(Level::Info, "load frequencies");
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let bytes = decompress(path, bytes)?;
+        let text = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+
+        for (rank, line) in lines.iter().enumerate() {
+            let mut fields = line.split_whitespace();
+            let Some(word) = fields.next().map(str::to_lowercase) else {
+                continue;
+            };
+            let score = match fields.next() {
+                Some(count) => count
+                    .parse::<u64>()
+                    .map_err(|_| format!("{}: bad count {} for {}", path, count, word))?,
+                None => (lines.len() - rank) as u64,
+            };
+            if self.contains(&word) {
+                let entry = self.frequencies.entry(word).or_insert(0);
+                *entry = (*entry).max(score);
+            }
+        }
+        info!("loaded frequencies for {} words from {}", self.frequencies.len(), path);
+        Ok(self)
+    }
+
+    /// A stable 64-bit fingerprint of the word list (FNV-1a over the words
+    /// in length-then-alphabetical order), for recognizing the dictionary a
+    /// saved puzzle was made with. Unlike `std`'s hashers, it does not change
+    /// between Rust releases.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for len in self.index.lengths() {
+            for word in self.words_of_length(len) {
+                for byte in word.bytes().chain(std::iter::once(b'\n')) {
+                    hash ^= byte as u64;
+                    hash = hash.wrapping_mul(0x100000001b3);
+                }
+            }
+        }
+        hash
+    }
+
+    /// Add words to the dictionary, rebuilding its indexes if any of them
+    /// were missing. Frequencies are kept.
+    pub fn with_words<'a, I: IntoIterator<Item = &'a str>>(self, added: I) -> Dictionary {
+        let added: HashSet<String> = added
+            .into_iter()
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| !w.is_empty() && !self.contains(w))
+            .collect();
+        if added.is_empty() {
+            return self;
+        }
+        debug!("adding {} words", added.len());
+        let mut dict = self.edit(&HashSet::new(), &added);
+        dict.frequencies = self.frequencies;
+        dict
+    }
+
+    /// Remove words from the dictionary, rebuilding its indexes if any of
+    /// them were in it. Frequencies of the remaining words are kept.
+    pub fn without_words<'a, I: IntoIterator<Item = &'a str>>(self, blocked: I) -> Dictionary {
+        let blocked: HashSet<String> = blocked
+            .into_iter()
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| self.contains(w))
+            .collect();
+        if blocked.is_empty() {
+            return self;
+        }
+        debug!("removing {} blocked words", blocked.len());
+        let mut dict = self.edit(&blocked, &HashSet::new());
+        dict.frequencies = self.frequencies;
+        dict.frequencies.retain(|word, _| !blocked.contains(word));
+        dict
+    }
+
+    /// The words in either dictionary. A word listed in both keeps the
+    /// higher of its two frequencies.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use magic_square::dictionary::Dictionary;
+    ///
+    /// let common = Dictionary::from_file("common.txt").unwrap();
+    /// let names = Dictionary::from_file("proper-nouns.txt").unwrap();
+    /// let theme = Dictionary::from_file("theme.txt").unwrap();
+    /// let dict = common.difference(&names).union(&theme);
+    /// ```
+    pub fn union(&self, other: &Dictionary) -> Dictionary {
+        let words = self.word_set().union(&other.word_set()).cloned().collect();
+        let mut dict = self.rebuild(words);
+        // A word is a proper noun if neither dictionary has it as a common word
+        let is_common = |d: &Dictionary, w: &str| d.contains(w) && !d.is_proper_noun(w);
+        dict.proper_nouns = self
+            .proper_nouns
+            .union(&other.proper_nouns)
+            .filter(|w| !is_common(self, w) && !is_common(other, w))
+            .cloned()
+            .collect();
+        dict.frequencies = self.frequencies.clone();
+        for (word, score) in other.frequencies.iter() {
+            let entry = dict.frequencies.entry(word.clone()).or_insert(0);
+            *entry = (*entry).max(*score);
+        }
+        dict
+    }
+
+    /// The words in both dictionaries, each with the higher of its two
+    /// frequencies.
+    pub fn intersection(&self, other: &Dictionary) -> Dictionary {
+        let words = self.iter().filter(|w| other.contains(w)).cloned().collect();
+        let mut dict = self.rebuild(words);
+        for (word, score) in self.frequencies.iter().chain(other.frequencies.iter()) {
+            if dict.contains(word) {
+                let entry = dict.frequencies.entry(word.clone()).or_insert(0);
+                *entry = (*entry).max(*score);
+            }
+        }
+        dict
+    }
+
+    /// The words in this dictionary that are not in `other`, with their
+    /// frequencies from this dictionary.
+    pub fn difference(&self, other: &Dictionary) -> Dictionary {
+        let words = self.iter().filter(|w| !other.contains(w)).cloned().collect();
+        let mut dict = self.rebuild(words);
+        dict.frequencies = self.frequencies.clone();
+        dict.frequencies.retain(|word, _| !other.contains(word));
+        dict
+    }
+
+    /// Keep only the words for which `keep` returns true, rebuilding the
+    /// indexes if any were dropped. Frequencies of the kept words are kept.
+    ///
+    /// # Example
+    /// ```
+    /// use magic_square::dictionary::Dictionary;
+    ///
+    /// let mut dict = Dictionary::from_words(["cat", "dog", "horse", "x-ray"]);
+    /// dict.retain(|word| word.len() <= 4 && word.chars().all(|c| c.is_ascii_lowercase()));
+    /// assert!(dict.contains("dog"));
+    /// assert!(!dict.contains("horse") && !dict.contains("x-ray"));
+    /// ```
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+        let dropped: HashSet<String> = self
+            .iter()
+            .filter(|w| !keep(w))
+            .cloned()
+            .collect();
+        if dropped.is_empty() {
+            return;
+        }
+        debug!("dropping {} words", dropped.len());
+        let mut frequencies = std::mem::take(&mut self.frequencies);
+        frequencies.retain(|word, _| !dropped.contains(word));
+        *self = self.edit(&dropped, &HashSet::new());
+        self.frequencies = frequencies;
+    }
+
+    /// Remove slurs and crude words, from a blocklist built into the crate,
+    /// so generated puzzles never contain them by accident.
+    pub fn filter_offensive(self) -> Dictionary {
+        self.without_words(BLOCKLIST.lines())
+    }
+
+    /// The entries of the source wordlist that could never be used, as
+    /// counted when it was loaded. Blank lines and duplicates were skipped
+    /// then; words with non-letters are still here unless dropped with
+    /// `without_unusable`.
+    ///
+    /// ```
+    /// # use magic_square::dictionary::Dictionary;
+    /// let dict = Dictionary::from_words(["rose", "", "Rose", "don't"]);
+    /// let hygiene = dict.hygiene();
+    /// assert_eq!((hygiene.blank, hygiene.duplicates, hygiene.non_alphabetic), (1, 1, 1));
+    /// assert_eq!(dict.without_unusable().len(), 1);
+    /// ```
+    pub fn hygiene(&self) -> Hygiene {
+        self.hygiene
+    }
+
+    /// Remove the words with characters other than letters, such as
+    /// "don't" and "e-mail", which no grid cell can hold.
+    pub fn without_unusable(mut self) -> Dictionary {
+        self.retain(|word| word.chars().all(char::is_alphabetic));
+        self
+    }
+
+    /// Remove the words listed in a file, one per line.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the blocklist.
+    ///
+    /// # Returns
+    /// * Ok(The dictionary without the listed words)
+    /// * Err(String) if the file could not be read.
+    pub fn without_words_in_file<P: AsRef<Path>>(self, path: P) -> Result<Dictionary, String> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(self.without_words(text.lines()))
+    }
+
+    /// The personal blocklist, `blocklist.txt` in the config directory, if
+    /// it exists.
+    pub fn user_blocklist_path() -> Option<PathBuf> {
+        config_dir()
+            .ok()
+            .map(|dir| dir.join("blocklist.txt"))
+            .filter(|path| path.is_file())
+    }
+
+    /// Build a dictionary, and its indexes, from a set of words.
+    fn new(words: HashSet<String>) -> Dictionary {
+        // The parts that do not depend on each other are built at once
+        let (reversed, index) = parallel::join(
+            || words.iter().map(|w| w.chars().rev().collect()).collect(),
+            || PositionIndex::new(words.iter()),
+        );
+        let (anagrams, tries) = parallel::join(
+            || anagram_index(&index),
+            || build_tries(&index, index.lengths()).into_iter().collect(),
+        );
+        let alphabet = alphabet(&index);
+        Dictionary {
+            lookup: Lookup::Hashed {
+                words,
+                reversed,
+                tries,
+            },
+            anagrams,
+            index,
+            frequencies: HashMap::new(),
+            alphabet,
+            proper_nouns: HashSet::new(),
+            hygiene: Hygiene::default(),
+        }
+    }
+
+    /// Build a dictionary from the lines of a wordlist, trimming and
+    /// lowercasing them and remembering which words were only ever spelled
+    /// with capitals. Blank lines and duplicates are skipped and counted
+    /// (see `Hygiene`).
+    fn from_cased<I: IntoIterator<Item = String>>(lines: I) -> Dictionary {
+        // Trimming and lowercasing is done a chunk of lines at a time, in
+        // parallel; only the deduplication is left for this thread
+        let mut lines = lines.into_iter().peekable();
+        let mut chunks = vec![];
+        while lines.peek().is_some() {
+            chunks.push(lines.by_ref().take(LOAD_CHUNK_LINES).collect::<Vec<String>>());
+        }
+        let chunks = parallel::map(chunks, |chunk| {
+            chunk
+                .into_iter()
+                .map(|line| {
+                    let word = line.trim();
+                    if word.is_empty() {
+                        return None;
+                    }
+                    let alphabetic = word.chars().all(char::is_alphabetic);
+                    // Most lines are already lowercase and trimmed, so keep
+                    // their strings rather than copying them
+                    let unchanged = |c: char| {
+                        let mut lower = c.to_lowercase();
+                        lower.next() == Some(c) && lower.next().is_none()
+                    };
+                    if !word.chars().all(unchanged) {
+                        return Some((true, alphabetic, word.to_lowercase()));
+                    }
+                    let word = if word.len() == line.len() {
+                        line
+                    } else {
+                        word.to_string()
+                    };
+                    Some((false, alphabetic, word))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut lowercase = HashSet::new();
+        let mut capitalized = HashSet::new();
+        let mut hygiene = Hygiene::default();
+        for entry in chunks.into_iter().flatten() {
+            let Some((cased, alphabetic, lower)) = entry else {
+                hygiene.blank += 1;
+                continue;
+            };
+            // A duplicate still counts towards whether the word is a proper noun
+            if lowercase.contains(&lower) || capitalized.contains(&lower) {
+                hygiene.duplicates += 1;
+            } else if !alphabetic {
+                hygiene.non_alphabetic += 1;
+            }
+            if cased {
+                capitalized.insert(lower);
+            } else {
+                lowercase.insert(lower);
+            }
+        }
+        let proper_nouns = capitalized.difference(&lowercase).cloned().collect();
+        lowercase.extend(capitalized);
+        let mut dict = Dictionary::new(lowercase);
+        dict.proper_nouns = proper_nouns;
+        if !hygiene.is_clean() {
+            info!("unusable entries in the wordlist: {}", hygiene);
+        }
+        dict.hygiene = hygiene;
+        dict
+    }
+
+    /// Create a dictionary from words held in memory. Words are trimmed and
+    /// lowercased, and blank ones are skipped. Words only ever given with
+    /// capitals are recorded as proper nouns.
+    pub fn from_words<'a, I: IntoIterator<Item = &'a str>>(words: I) -> Dictionary {
+        Dictionary::from_cased(words.into_iter().map(str::to_string))
     }
 
     /// Create a new dictionary from a file.
-    /// The file should contain one word per line.
-    /// The words should be lowercase.
+    /// The file should contain one word per line. Words are lowercased, and
+    /// those only ever spelled with capitals are recorded as proper nouns.
+    ///
+    /// Gzip (`.gz`) and zstd (`.zst`) compressed files are decompressed,
+    /// recognized by their first bytes or their extension. Gzip needs
+    /// nothing installed; zstd needs the `zstd` command.
+    ///
+    /// The compiled indexes of large wordlists are cached in `cache_dir`,
+    /// keyed by a hash of the file's contents, so later loads of the same
+    /// list skip building them. An unreadable cache is ignored and rebuilt.
     ///
     /// # Arguments
     /// * `path` - The path to the dictionary file.
@@ -54,86 +1214,583 @@ impl Dictionary {
     /// # Returns
     /// * Ok(A new dictionary)
     /// * Err(String) if the file could not be read.
-    pub(crate) fn from_file(path: &str) -> Result<Dictionary, String> {
-        use std::fs::File;
-        use std::io::{BufRead, BufReader};
+    pub fn from_file(path: &str) -> Result<Dictionary, String> {
+        let _span = Span::enter(Level::Info, "load dictionary");
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let cache = cache_dir()
+            .ok()
+            .map(|dir| dir.join(format!("{:016x}.idx", fnv1a(&bytes))));
+        if let Some(dict) = cache.as_deref().and_then(Dictionary::read_index_cache) {
+            info!("loaded {} words from the index cache for {}", dict.len(), path);
+            return Ok(dict);
+        }
 
-        let file = File::open(path).map_err(|e| e.to_string())?;
-        let reader = BufReader::new(file);
+        let bytes = decompress(path, bytes)?;
+        let text = String::from_utf8(bytes).map_err(|e| format!("{}: {}", path, e))?;
+        let dict = Dictionary::from_cased(text.lines().map(str::to_string));
+        info!("loaded {} words from {}", dict.len(), path);
 
-        let mut words = HashSet::new();
-        for line in reader.lines() {
-            words.insert(line.map_err(|e| e.to_string())?);
+        if let Some(cache) = cache.filter(|_| dict.len() >= INDEX_CACHE_MIN_WORDS) {
+            if let Err(e) = dict.write_index_cache(&cache) {
+                debug!("could not write the index cache: {}", e);
+            }
         }
+        Ok(dict)
+    }
 
-        Ok(Dictionary { words })
+    /// Load a dictionary from an index cache written by
+    /// `write_index_cache`, or `None` if there is none or it is unreadable.
+    fn read_index_cache(path: &Path) -> Option<Dictionary> {
+        let bytes = std::fs::read(path).ok()?;
+        let mut input = Reader::new(&bytes);
+        let decode = |input: &mut Reader| -> Result<Dictionary, String> {
+            input.expect(INDEX_CACHE_MAGIC)?;
+            let index = PositionIndex::decode(input)?;
+            let mut tries = HashMap::new();
+            for _ in 0..input.count()? {
+                let len = input.usize()?;
+                tries.insert(len, Arc::new(Trie::decode(input)?));
+            }
+            let alphabet = (0..input.count()?)
+                .map(|_| input.char())
+                .collect::<Result<Vec<char>, String>>()?;
+            let proper_nouns = (0..input.count()?)
+                .map(|_| input.string())
+                .collect::<Result<HashSet<String>, String>>()?;
+            let hygiene = Hygiene {
+                blank: input.usize()?,
+                duplicates: input.usize()?,
+                non_alphabetic: input.usize()?,
+            };
+            if !input.is_empty() {
+                return Err("index cache has trailing bytes".to_string());
+            }
+            let ((words, reversed), anagrams) = parallel::join(
+                || {
+                    let words: HashSet<String> = index
+                        .lengths()
+                        .into_iter()
+                        .flat_map(|len| index.words(len).iter().cloned())
+                        .collect();
+                    let reversed = words.iter().map(|w| w.chars().rev().collect()).collect();
+                    (words, reversed)
+                },
+                || anagram_index(&index),
+            );
+            Ok(Dictionary {
+                lookup: Lookup::Hashed {
+                    words,
+                    reversed,
+                    tries,
+                },
+                anagrams,
+                index,
+                frequencies: HashMap::new(),
+                alphabet,
+                proper_nouns,
+                hygiene,
+            })
+        };
+        match decode(&mut input) {
+            Ok(dict) => Some(dict),
+            Err(e) => {
+                debug!("ignoring the index cache {}: {}", path.display(), e);
+                None
+            }
+        }
     }
 
-    /// Create a new dictionary from the OS dictionary.
+    /// Save the compiled indexes, so the next load of the same wordlist can
+    /// skip building them. Written to a temporary file first, so a crash
+    /// never leaves a partial cache behind.
+    fn write_index_cache(&self, path: &Path) -> Result<(), String> {
+        let Lookup::Hashed { tries, .. } = &self.lookup else {
+            return Err("only hashed lookups are cached".to_string());
+        };
+        let _span = Span::enter(Level::Debug, "write index cache");
+        let mut out = Writer::default();
+        out.bytes.extend_from_slice(INDEX_CACHE_MAGIC);
+        self.index.encode(&mut out);
+        out.usize(tries.len());
+        for (&len, trie) in tries.iter() {
+            out.usize(len);
+            trie.encode(&mut out);
+        }
+        out.usize(self.alphabet.len());
+        for &c in self.alphabet.iter() {
+            out.char(c);
+        }
+        let mut proper_nouns: Vec<&String> = self.proper_nouns.iter().collect();
+        proper_nouns.sort();
+        out.usize(proper_nouns.len());
+        for word in proper_nouns {
+            out.str(word);
+        }
+        out.usize(self.hygiene.blank);
+        out.usize(self.hygiene.duplicates);
+        out.usize(self.hygiene.non_alphabetic);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let partial = path.with_extension("idx.part");
+        std::fs::write(&partial, &out.bytes)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Create a new dictionary from a SCOWL wordlist directory: an
+    /// unpacked SCOWL release, or its `final` directory, which splits the
+    /// words into files such as `english-words.35` and `american-words.50`
+    /// by how common they are. Every file of `SCOWL_CATEGORIES` at `size`
+    /// or below is read, so smaller sizes give fewer, more familiar words,
+    /// and larger ones more, more obscure words.
+    ///
+    /// # Arguments
+    /// * `dir` - The SCOWL directory.
+    /// * `size` - One of `SCOWL_SIZES`.
     ///
     /// # Returns
     /// * Ok(A new dictionary)
-    /// * Err(String) if the OS dictionary could not be read.
-    pub(crate) fn from_os_dict() -> Result<Dictionary, String> {
-        use std::process::Command;
+    /// * Err(String) if the size is not a SCOWL size, the directory has no
+    ///   SCOWL files, or a file could not be read.
+    pub fn from_scowl<P: AsRef<Path>>(dir: P, size: u32) -> Result<Dictionary, String> {
+        let _span = Span::enter(Level::Info, "load SCOWL wordlists");
+        if !SCOWL_SIZES.contains(&size) {
+            return Err(format!(
+                "{} is not a SCOWL size (expected one of {})",
+                size,
+                SCOWL_SIZES
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        let dir = dir.as_ref();
+        let dir = if dir.join("final").is_dir() {
+            dir.join("final")
+        } else {
+            dir.to_path_buf()
+        };
+        let entries = std::fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let Some((category, tier)) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.rsplit_once('.'))
+                else {
+                    return false;
+                };
+                SCOWL_CATEGORIES.contains(&category)
+                    && tier.parse::<u32>().is_ok_and(|tier| tier <= size)
+            })
+            .collect();
+        if files.is_empty() {
+            return Err(format!(
+                "{} has no SCOWL wordlists (such as english-words.{})",
+                dir.display(),
+                size
+            ));
+        }
+        files.sort();
 
-        let output = Command::new("cat")
-            .arg("/usr/share/dict/words")
-            .output()
-            .map_err(|e| e.to_string())?;
+        let mut words = vec![];
+        for path in files.iter() {
+            let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            // SCOWL files are Latin-1
+            let text: String = match String::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+            };
+            words.extend(text.lines().map(str::to_string));
+        }
+        info!(
+            "loaded {} words from {} SCOWL files up to size {}",
+            words.len(),
+            files.len(),
+            size
+        );
+        Ok(Dictionary::from_cased(words))
+    }
 
-        if !output.status.success() {
-            return Err("Could not read OS dictionary".to_string());
+    /// Create a new dictionary from a hunspell spellcheck dictionary, such
+    /// as `/usr/share/hunspell/en_US.dic`. The affix rules in the `.aff`
+    /// file beside it are expanded into every form they allow, so a stem
+    /// such as `walk/DGS` gives "walk", "walked", "walking" and "walks".
+    /// Without an `.aff` file only the stems are read. Compound words are
+    /// not generated.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the `.dic` file.
+    ///
+    /// # Returns
+    /// * Ok(A new dictionary)
+    /// * Err(String) if either file could not be read or parsed.
+    pub fn from_hunspell<P: AsRef<Path>>(path: P) -> Result<Dictionary, String> {
+        let path = path.as_ref();
+        let _span = Span::enter(Level::Info, "load hunspell dictionary");
+        let words = hunspell::read(path)?;
+        info!("expanded {} words from {}", words.len(), path.display());
+        Ok(Dictionary::from_cased(
+            words
+                .into_iter()
+                .filter(|word| word.chars().all(char::is_alphabetic)),
+        ))
+    }
+
+    /// Create a new dictionary from a wordlist file, or from a wordlist
+    /// downloaded from `location` if it is an `http://` or `https://` URL,
+    /// or from a hunspell dictionary if it is a `.dic` file, or from a SCOWL
+    /// directory at `DEFAULT_SCOWL_SIZE` if it is a directory.
+    ///
+    /// # Arguments
+    /// * `location` - A path or URL to a wordlist with one word per line.
+    ///
+    /// # Returns
+    /// * Ok(A new dictionary)
+    /// * Err(String) if the wordlist could not be read or downloaded.
+    pub fn load(location: &str) -> Result<Dictionary, String> {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            Dictionary::from_url(location)
+        } else if location.ends_with(".dic") {
+            Dictionary::from_hunspell(location)
+        } else if Path::new(location).is_dir() {
+            Dictionary::from_scowl(location, DEFAULT_SCOWL_SIZE)
+        } else {
+            Dictionary::from_file(location)
         }
+    }
+
+    /// Create a new dictionary from a wordlist downloaded from a URL, such as
+    /// a SCOWL or dwyl/english-words release.
+    ///
+    /// The download is cached under the XDG cache directory along with its
+    /// ETag. Later calls revalidate the cached copy with the server, and only
+    /// download it again if it changed. If the server cannot be reached, the
+    /// cached copy is used as-is.
+    ///
+    /// Downloads use the `curl` command-line tool, which must be installed
+    /// and on the `PATH`; without it, only a URL downloaded before loads,
+    /// from the cache.
+    ///
+    /// # Arguments
+    /// * `url` - The URL of a wordlist with one word per line.
+    ///
+    /// # Returns
+    /// * Ok(A new dictionary)
+    /// * Err(String) if the wordlist could not be downloaded and no cached
+    ///   copy exists.
+    pub fn from_url(url: &str) -> Result<Dictionary, String> {
+        let _span = Span::enter(Level::Info, "load dictionary from URL");
+        let dir = cache_dir()?;
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let name = cache_name(url);
+        let cached = dir.join(format!("{}.txt", name));
+        let etag = dir.join(format!("{}.etag", name));
+        let partial = dir.join(format!("{}.part", name));
 
-        let words = String::from_utf8(output.stdout).map_err(|e| e.to_string())?;
-        let words = words
-            .lines()
-            .map(|s| s.to_lowercase())
-            .collect::<HashSet<String>>();
+        match download(url, &partial, &etag, cached.exists()) {
+            Ok(()) if partial.exists() => {
+                info!("downloaded {}", url);
+                std::fs::rename(&partial, &cached)
+                    .map_err(|e| format!("{}: {}", cached.display(), e))?;
+            }
+            // The server answered 304 Not Modified, so nothing was written
+            Ok(()) => info!("cached copy of {} is up to date", url),
+            Err(e) if cached.exists() => {
+                let _ = std::fs::remove_file(&partial);
+                info!("could not revalidate {} ({}); using the cached copy", url, e);
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(format!("{}: {}", url, e));
+            }
+        }
 
-        Ok(Dictionary { words })
+        let bytes = std::fs::read(&cached).map_err(|e| format!("{}: {}", cached.display(), e))?;
+        let dict = Dictionary::from_cased(wordlist_words(&bytes));
+        info!("loaded {} words from {}", dict.len(), cached.display());
+        Ok(dict)
     }
-}
 
-struct TemplateTreeNode {
-    word: String,
-    subtemplates: Vec<TemplateTreeNode>,
-}
-struct TemplateTree {
-    root_template: TemplateTreeNode,
-}
+    /// Create a new dictionary from the wordlist built into the binary, for
+    /// platforms with no OS dictionary and no filesystem, such as WASM.
+    ///
+    /// Only available with the `embedded-dict` feature.
+    #[cfg(feature = "embedded-dict")]
+    pub fn embedded() -> Dictionary {
+        let dict = Dictionary::from_cased(wordlist_words(EMBEDDED_WORDS));
+        info!("loaded {} built-in words", dict.len());
+        dict
+    }
 
-impl TemplateTreeNode {
-    pub(crate) fn matches(&self, template: &str) -> bool {
-        let tmp = template.to_lowercase();
+    /// The OS dictionary `from_os_dict` would load: the first file among
+    /// `OS_DICT_PATHS` that exists.
+    pub fn os_dict_path() -> Option<&'static str> {
+        OS_DICT_PATHS
+            .iter()
+            .copied()
+            .find(|path| Path::new(path).is_file())
+    }
 
-        // Short-circuit on length:
-        if self.word.len() != tmp.len() {
-            return false;
+    /// Create a new dictionary from the OS dictionary.
+    ///
+    /// The first readable file among `OS_DICT_PATHS` is used.
+    ///
+    /// # Returns
+    /// * Ok(A new dictionary)
+    /// * Err(String) listing the paths tried if none could be read.
+    pub fn from_os_dict() -> Result<Dictionary, String> {
+        let _span = Span::enter(Level::Info, "load OS dictionary");
+
+        let mut tried = vec![];
+        for path in OS_DICT_PATHS {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    let dict = Dictionary::from_cased(wordlist_words(&bytes));
+                    info!("loaded {} words from {}", dict.len(), path);
+                    return Ok(dict);
+                }
+                Err(e) => tried.push(format!("  {}: {}", path, e)),
+            }
         }
-        let mut chars = self.word.chars();
-        for c in tmp.chars() {
-            if c == '_' {
-                chars.next();
-            } else {
-                if chars.next() != Some(c) {
-                    return false;
+
+        Err(format!(
+            "Could not find an OS dictionary; pass a wordlist file instead. Tried:\n{}",
+            tried.join("\n")
+        ))
+    }
+
+    /// Create a new dictionary for a language, such as `"de"`, or a locale,
+    /// such as `"de_AT"` or `"pt-BR"`.
+    ///
+    /// Three kinds of installed wordlist are tried, in order:
+    ///
+    /// * plain wordlists under `/usr/share/dict` (`ngerman`, `french`, ...),
+    /// * hunspell and myspell `.dic` files, preferring the exact locale,
+    ///   with their affix rules expanded (see `from_hunspell`),
+    /// * as a last resort, the `aspell` dictionary for the language, which
+    ///   is stored compiled and so is listed by running the `aspell`
+    ///   command, if it is installed.
+    ///
+    /// # Arguments
+    /// * `locale` - A language code, optionally followed by a region.
+    ///
+    /// # Returns
+    /// * Ok(A new dictionary)
+    /// * Err(String) listing the places tried, `aspell` included, if no
+    ///   wordlist was found.
+    pub fn for_locale(locale: &str) -> Result<Dictionary, String> {
+        let _span = Span::enter(Level::Info, "load locale dictionary");
+        let locale = locale.replace('-', "_");
+        let (lang, region) = match locale.split_once('_') {
+            Some((lang, region)) => (lang.to_lowercase(), Some(region.to_uppercase())),
+            None => (locale.to_lowercase(), None),
+        };
+
+        let mut tried = vec![];
+        let names = LOCALE_WORDLISTS
+            .iter()
+            .find(|(l, _)| *l == lang)
+            .map_or(&[][..], |(_, names)| *names);
+        for name in names {
+            let path = format!("/usr/share/dict/{}", name);
+            match std::fs::read(&path) {
+                Ok(bytes) => {
+                    let dict = Dictionary::from_cased(wordlist_words(&bytes));
+                    info!("loaded {} words from {}", dict.len(), path);
+                    return Ok(dict);
                 }
+                Err(e) => tried.push(format!("  {}: {}", path, e)),
             }
         }
-        true
+
+        let mut dirs: Vec<PathBuf> = HUNSPELL_DIRS.iter().map(PathBuf::from).collect();
+        if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+            dirs.push(home.join("Library/Spelling"));
+            dirs.push(home.join(".local/share/hunspell"));
+        }
+        for dir in dirs.iter() {
+            for path in hunspell_files(dir, &lang, region.as_deref()) {
+                match Dictionary::from_hunspell(&path) {
+                    Ok(dict) => return Ok(dict),
+                    Err(e) => tried.push(format!("  {}", e)),
+                }
+            }
+        }
+        tried.push(format!(
+            "  no {}*.dic file in {}",
+            lang,
+            dirs.iter()
+                .map(|d| d.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+
+        let aspell_name = match &region {
+            Some(region) => format!("{}_{}", lang, region),
+            None => lang.clone(),
+        };
+        match aspell_words(&aspell_name) {
+            Ok(words) => {
+                info!("loaded {} words from aspell {}", words.len(), aspell_name);
+                return Ok(Dictionary::from_cased(words));
+            }
+            Err(e) => tried.push(format!("  aspell {}: {}", aspell_name, e)),
+        }
+
+        Err(format!(
+            "Could not find a {} wordlist; install one or pass a wordlist file instead. Tried:\n{}",
+            locale,
+            tried.join("\n")
+        ))
     }
 }
 
-impl TemplateTree {
-    fn from_dict(dict: Dictionary) -> TemplateTree {
-        //
-        TemplateTree {
-            root_template: TemplateTreeNode {
-                word: "____".to_string(),
-                subtemplates: vec![],
-            },
+/// Read a plain wordlist, one word per line. Some distros ship Latin-1
+/// wordlists; keep what decodes.
+fn wordlist_words(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The hunspell `.dic` files in `dir` for a language: the exact locale
+/// first (`de_DE` for plain `de`), then the bare language, then every other
+/// region alphabetically.
+fn hunspell_files(dir: &Path, lang: &str, region: Option<&str>) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let exact = match region {
+        Some(region) => format!("{}_{}", lang, region),
+        None => format!("{}_{}", lang, lang.to_uppercase()),
+    };
+    let mut files: Vec<(u8, String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?.to_string();
+            if path.extension()? != "dic" {
+                return None;
+            }
+            let rank = if stem == exact {
+                0
+            } else if stem == lang {
+                1
+            } else if stem.starts_with(&format!("{}_", lang)) {
+                2
+            } else {
+                return None;
+            };
+            Some((rank, stem, path))
+        })
+        .collect();
+    files.sort();
+    files.into_iter().map(|(_, _, path)| path).collect()
+}
+
+/// List every word of an aspell dictionary, with its affixes expanded, by
+/// piping `aspell dump master` through `aspell expand`.
+fn aspell_words(name: &str) -> Result<HashSet<String>, String> {
+    let run_error = |e: std::io::Error| match e.kind() {
+        std::io::ErrorKind::NotFound => "the aspell command is not installed".to_string(),
+        _ => format!("could not run aspell: {}", e),
+    };
+    let dump = Command::new("aspell")
+        .args(["-d", name, "dump", "master"])
+        .output()
+        .map_err(run_error)?;
+    if !dump.status.success() {
+        return Err(String::from_utf8_lossy(&dump.stderr).trim().to_string());
+    }
+
+    let mut expand = Command::new("aspell")
+        .args(["-d", name, "expand"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(run_error)?;
+    // Feed the dump from another thread, so a full stdout pipe cannot stall it
+    let mut stdin = expand.stdin.take().ok_or("could not write to aspell")?;
+    let writer = std::thread::spawn(move || stdin.write_all(&dump.stdout));
+    let expanded = expand.wait_with_output().map_err(|e| e.to_string())?;
+    let _ = writer.join();
+    if !expanded.status.success() {
+        return Err(String::from_utf8_lossy(&expanded.stderr).trim().to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&expanded.stdout)
+        .split_whitespace()
+        .map(str::to_string)
+        .filter(|word| word.chars().all(char::is_alphabetic))
+        .collect())
+}
+
+/// Decompress the contents of a wordlist file if it is gzip or zstd
+/// compressed, judging by its magic bytes or, failing that, its extension.
+/// Other files are returned unchanged. Gzip is decoded here; zstd needs the
+/// `zstd` command.
+fn decompress(path: &str, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    if gzip::is_gzip(&bytes) || path.ends_with(".gz") {
+        debug!("decompressing {} as gzip", path);
+        return gzip::decode(&bytes).map_err(|e| format!("{}: {}", path, e));
+    }
+    if !bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) && !path.ends_with(".zst") {
+        return Ok(bytes);
+    }
+    debug!("decompressing {} with zstd", path);
+    let output = Command::new("zstd")
+        .args(["-d", "-c", "--"])
+        .arg(path)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => format!(
+                "{}: reading a zstd-compressed wordlist needs the zstd command, which is not installed; \
+                 install it or decompress the file first",
+                path
+            ),
+            _ => format!("could not run zstd to decompress {}: {}", path, e),
+        })?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!(
+            "{}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Download `url` to `dest` with `curl`, sending the ETag saved in `etag`
+/// (if `revalidate` is set) and saving the new one. `dest` is only written if
+/// the server sent a body.
+fn download(url: &str, dest: &Path, etag: &Path, revalidate: bool) -> Result<(), String> {
+    let mut curl = Command::new("curl");
+    curl.args(["--fail", "--silent", "--show-error", "--location"])
+        .arg("--etag-save")
+        .arg(etag)
+        .arg("--output")
+        .arg(dest);
+    if revalidate && etag.exists() {
+        curl.arg("--etag-compare").arg(etag);
+    }
+    let output = curl.arg(url).output().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            "downloading a wordlist needs the curl command, which is not installed; \
+             install it or download the file and pass its path"
+                .to_string()
         }
+        _ => format!("could not run curl: {}", e),
+    })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
     }
 }
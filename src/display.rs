@@ -0,0 +1,209 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::exit;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use magic_square::dictionary::Dictionary;
+use magic_square::{LiveView, MagicSquare};
+
+use crate::cli::Args;
+
+/// Seconds each view is shown for when `--interval` is not given.
+const DEFAULT_INTERVAL: u64 = 30;
+
+/// How many letters one seed word may try before the next is picked, so a
+/// hard seed cannot stall the day's puzzle.
+const SEED_ATTEMPTS: usize = 200_000;
+
+/// How many seed words to try before giving up on a day.
+const MAX_SEEDS: usize = 100;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The `YYYY-MM-DD` calendar date of a day counted from 1970-01-01.
+fn date(day: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, for days after the epoch
+    let z = day + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + u64::from(m <= 2);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Scramble a day number into a well-spread index, so consecutive days
+/// start from unrelated seed words.
+fn mix(day: u64) -> u64 {
+    // The splitmix64 finalizer
+    let mut x = day.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Fill the `size` by `size` square for `day`: its top row is a word picked
+/// from the day number, and if that square is too hard or unsolvable the
+/// following words are tried in turn.
+///
+/// # Returns
+///
+/// * `Ok(MagicSquare)` filled, with the seed word as its top row.
+/// * `Err(String)` if no seed word filled in time.
+fn daily_square(
+    dict: &Dictionary,
+    args: &Args,
+    size: usize,
+    day: u64,
+) -> Result<MagicSquare, String> {
+    let words = dict.words_of_length(size);
+    if words.is_empty() {
+        return Err(format!("the dictionary has no {}-letter words", size));
+    }
+    let start = (mix(day) % words.len() as u64) as usize;
+    let seeds = MAX_SEEDS.min(words.len());
+    for offset in 0..seeds {
+        let seed = &words[(start + offset) % words.len()];
+        let mut square = MagicSquare::from_template(seed, size, dict);
+        square.set_direction(args.direction);
+        square.set_mirror_rows(args.mirror_rows);
+        square.set_overlap(args.overlap);
+        square.set_distinct(args.distinct);
+        square.set_diagonals(args.diagonals);
+        square.set_live_view(LiveView::Off);
+        square.set_attempt_limit(Some(SEED_ATTEMPTS));
+        if square.fill().is_ok() {
+            return Ok(square);
+        }
+    }
+    Err(format!(
+        "none of {} seed words filled a {}x{} square",
+        seeds, size, size
+    ))
+}
+
+/// The text of one screen: the date, then the grid with its top row given
+/// and the rest blank, or the whole solution.
+fn view(square: &MagicSquare, day: u64, solution: bool) -> Vec<String> {
+    let mut lines = vec![date(day), String::new()];
+    for (r, row) in square.grid().iter().enumerate() {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|&c| {
+                if solution || r == 0 {
+                    c.to_uppercase().to_string()
+                } else {
+                    "_".to_string()
+                }
+            })
+            .collect();
+        lines.push(cells.join(" "));
+    }
+    lines.push(String::new());
+    lines.push(if solution { "solution" } else { "puzzle" }.to_string());
+    lines
+}
+
+/// A terminal dimension from the environment, as shells export it.
+fn terminal_size(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(default)
+}
+
+/// Clear the terminal and draw `lines` centered in it.
+fn draw(lines: &[String]) {
+    let cols = terminal_size("COLUMNS", 80);
+    let rows = terminal_size("LINES", 24);
+    let mut out = String::from("\x1b[2J\x1b[H");
+    out.push_str(&"\n".repeat(rows.saturating_sub(lines.len()) / 2));
+    for line in lines {
+        if !line.is_empty() {
+            let width = line.chars().count();
+            out.push_str(&" ".repeat(cols.saturating_sub(width) / 2));
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    print!("{}", out);
+    let _ = std::io::stdout().flush();
+}
+
+/// Write `lines` to `path`, replacing it in one step so a display reading
+/// the file never sees half of it.
+fn save(path: &str, lines: &[String]) -> Result<(), String> {
+    let path = Path::new(path);
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, lines.join("\n") + "\n")
+        .and_then(|()| std::fs::rename(&partial, path))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// `display [dictionary] [size]` shows today's square full-screen, for an
+/// always-on monitor: the puzzle (the top row given) and the solution
+/// alternate every `--interval` seconds, and a new square is made at
+/// midnight UTC. Each day's square is picked from the date alone, so every
+/// display with the same dictionary shows the same one. With `--save`, each
+/// screen is written to that file as text instead, for e-ink frames and
+/// other displays that render a file.
+pub(crate) fn run(args: &Args) {
+    let (dict, rest) = crate::load_dictionary(args, &args.positional[1..]);
+    let size = match rest.first().map(|n| n.parse::<usize>()) {
+        None => 5,
+        Some(Ok(size)) if size > 0 => size,
+        Some(_) => {
+            println!(
+                "Usage: magicsquare display [dictionary] [size] [--interval secs] [--save path]"
+            );
+            exit(1);
+        }
+    };
+    let interval = args.interval.unwrap_or(DEFAULT_INTERVAL);
+
+    let mut shown: Option<(u64, MagicSquare)> = None;
+    let mut solution = false;
+    loop {
+        let now = now();
+        let today = now / SECS_PER_DAY;
+        if shown.as_ref().is_none_or(|(day, _)| *day != today) {
+            match daily_square(&dict, args, size, today) {
+                Ok(square) => shown = Some((today, square)),
+                Err(e) => {
+                    println!("Could not make the square for {}: {}", date(today), e);
+                    exit(1);
+                }
+            }
+            solution = false;
+        }
+        let (day, square) = shown.as_ref().unwrap();
+        let lines = view(square, *day, solution);
+        match &args.save {
+            Some(path) => {
+                if let Err(e) = save(path, &lines) {
+                    println!("Could not save the display: {}", e);
+                    exit(1);
+                }
+            }
+            None => draw(&lines),
+        }
+
+        // Wake at midnight even in the middle of an interval
+        let until_midnight = (today + 1) * SECS_PER_DAY - now;
+        sleep(Duration::from_secs(interval.min(until_midnight)));
+        solution = !solution;
+    }
+}
@@ -0,0 +1,508 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::dictionary::{Dictionary, ProperNouns};
+use crate::normalize::fold_diacritics;
+use crate::overrides::Overrides;
+use crate::{CellOrder, Diagonals, LetterOrder, LiveView, MagicSquare, Overlap, Strategy};
+
+/// Where a `Generator` gets its words from.
+enum DictSource {
+    Os,
+    Path(String),
+    Loaded(Box<Dictionary>),
+}
+
+/// A builder for the common "load a dictionary, fill a square" program.
+///
+/// Create one with [`generate`], chain options, and call [`Generator::solve`].
+pub struct Generator {
+    rows: usize,
+    cols: usize,
+    template: Option<String>,
+    themes: Vec<String>,
+    frequencies: Option<String>,
+    allow_offensive: bool,
+    proper_nouns: ProperNouns,
+    fold_accents: bool,
+    compact: bool,
+    overlays: Vec<String>,
+    added_words: Vec<String>,
+    overlap: Overlap,
+    distinct: bool,
+    diagonals: Diagonals,
+    min_word_len: usize,
+    toroidal: bool,
+    strategy: Strategy,
+    beam_width: usize,
+    population: usize,
+    mutation_rate: f64,
+    generations: usize,
+    cell_order: CellOrder,
+    letter_order: LetterOrder,
+    propagate: bool,
+    forward_check: bool,
+    nogoods: bool,
+    parallel: bool,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    deterministic: bool,
+    restarts: Option<usize>,
+    max_attempts: Option<usize>,
+    time_limit: Option<Duration>,
+    checkpoint: Option<PathBuf>,
+    resume: Option<PathBuf>,
+    dict: DictSource,
+}
+
+/// Start building a `rows` x `cols` magic square.
+///
+/// The square uses the OS dictionary unless `with_dict` or `with_dictionary`
+/// is called. With the `embedded-dict` feature, the built-in wordlist is used
+/// if the OS has no dictionary. Offensive words, and words on the personal
+/// blocklist, are removed unless `allow_offensive` is called, and then any
+/// overlays, the personal [`Overrides`] and the `add_word` words are
+/// applied, in that order.
+///
+/// # Arguments
+///
+/// * `rows` - The number of rows in the square.
+/// * `cols` - The number of columns in the square.
+pub fn generate(rows: usize, cols: usize) -> Generator {
+    Generator {
+        rows,
+        cols,
+        template: None,
+        themes: vec![],
+        frequencies: None,
+        allow_offensive: false,
+        proper_nouns: ProperNouns::Keep,
+        fold_accents: false,
+        compact: false,
+        overlays: vec![],
+        added_words: vec![],
+        overlap: Overlap::Allowed,
+        distinct: false,
+        diagonals: Diagonals::Off,
+        min_word_len: 2,
+        toroidal: false,
+        strategy: Strategy::Letters,
+        beam_width: 100,
+        population: 200,
+        mutation_rate: 0.02,
+        generations: 1000,
+        cell_order: CellOrder::Reading,
+        letter_order: LetterOrder::Alphabet,
+        propagate: true,
+        forward_check: false,
+        nogoods: false,
+        parallel: false,
+        threads: None,
+        seed: None,
+        deterministic: false,
+        restarts: None,
+        max_attempts: None,
+        time_limit: None,
+        checkpoint: None,
+        resume: None,
+        dict: DictSource::Os,
+    }
+}
+
+impl Generator {
+    /// Load the dictionary from a wordlist file (one word per line) or an
+    /// `http(s)` URL, which is downloaded and cached.
+    pub fn with_dict(mut self, path: &str) -> Generator {
+        self.dict = DictSource::Path(path.to_string());
+        self
+    }
+
+    /// Use an already loaded dictionary.
+    pub fn with_dictionary(mut self, dict: Dictionary) -> Generator {
+        self.dict = DictSource::Loaded(Box::new(dict));
+        self
+    }
+
+    /// Load a word-frequency list, so the square prefers common words. See
+    /// [`Dictionary::with_frequencies`].
+    pub fn with_frequencies(mut self, path: &str) -> Generator {
+        self.frequencies = Some(path.to_string());
+        self
+    }
+
+    /// Fold accented letters to plain ones, in the dictionary and the
+    /// template. See [`Dictionary::fold_diacritics`].
+    pub fn fold_accents(mut self) -> Generator {
+        self.fold_accents = true;
+        self
+    }
+
+    /// Use a word graph for dictionary lookups, to save memory on large
+    /// wordlists. See [`Dictionary::compact`].
+    pub fn compact(mut self) -> Generator {
+        self.compact = true;
+        self
+    }
+
+    /// Keep words on the built-in offensive-word blocklist, which are
+    /// otherwise removed. See [`Dictionary::filter_offensive`].
+    pub fn allow_offensive(mut self) -> Generator {
+        self.allow_offensive = true;
+        self
+    }
+
+    /// Keep, drop, or keep only the words the wordlist spells with capitals.
+    /// See [`Dictionary::with_proper_nouns`].
+    pub fn proper_nouns(mut self, policy: ProperNouns) -> Generator {
+        self.proper_nouns = policy;
+        self
+    }
+
+    /// Apply an overlay file of `+word` / `-word` edits after the
+    /// blocklists. Overlays are applied in the order they are added. See
+    /// [`Overrides::read`].
+    pub fn overlay(mut self, path: &str) -> Generator {
+        self.overlays.push(path.to_string());
+        self
+    }
+
+    /// Add a word to the dictionary for this square, even if a blocklist or
+    /// overlay removes it.
+    pub fn add_word(mut self, word: &str) -> Generator {
+        self.added_words.push(word.to_string());
+        self
+    }
+
+    /// Fix letters before filling. The template uses the same syntax as the
+    /// command line: letters, underscores and `#` blocks, with `/`
+    /// separating rows. Rows beyond those given are left blank. The widest
+    /// row must be as wide as the square, and shorter ones are padded on the
+    /// right with blocks, as in a ragged grid (see
+    /// [`MagicSquare::from_template`]).
+    pub fn template(mut self, template: &str) -> Generator {
+        self.template = Some(template.to_lowercase());
+        self
+    }
+
+    /// Add a theme word, to be placed at symmetric slots before filling. See
+    /// [`MagicSquare::fill_with_themes`].
+    pub fn theme(mut self, word: &str) -> Generator {
+        self.themes.push(word.to_lowercase());
+        self
+    }
+
+    /// Make each row the same word as its column. See
+    /// [`MagicSquare::set_symmetric`].
+    pub fn symmetric(mut self) -> Generator {
+        self.overlap = Overlap::Symmetric;
+        self
+    }
+
+    /// Choose whether a word may fill both a row and a column. See
+    /// [`MagicSquare::set_overlap`].
+    pub fn overlap(mut self, overlap: Overlap) -> Generator {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Make every row and column a different word. See
+    /// [`MagicSquare::set_distinct`].
+    pub fn distinct(mut self) -> Generator {
+        self.distinct = true;
+        self
+    }
+
+    /// Choose which diagonals must be words too. See
+    /// [`MagicSquare::set_diagonals`].
+    pub fn diagonals(mut self, diagonals: Diagonals) -> Generator {
+        self.diagonals = diagonals;
+        self
+    }
+
+    /// Set the fewest letters a word between blocked cells may have. See
+    /// [`MagicSquare::set_min_word_len`].
+    pub fn min_word_len(mut self, len: usize) -> Generator {
+        self.min_word_len = len;
+        self
+    }
+
+    /// Read rows and columns around a torus. See
+    /// [`MagicSquare::set_toroidal`].
+    pub fn toroidal(mut self) -> Generator {
+        self.toroidal = true;
+        self
+    }
+
+    /// Choose whether the solver places letters or whole rows. See
+    /// [`MagicSquare::set_strategy`].
+    pub fn strategy(mut self, strategy: Strategy) -> Generator {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set how many partial grids a beam search keeps. See
+    /// [`MagicSquare::set_beam_width`].
+    pub fn beam_width(mut self, width: usize) -> Generator {
+        self.beam_width = width;
+        self
+    }
+
+    /// Set how many grids a genetic search evolves. See
+    /// [`MagicSquare::set_population`].
+    pub fn population(mut self, size: usize) -> Generator {
+        self.population = size;
+        self
+    }
+
+    /// Set the chance a genetic search changes each letter. See
+    /// [`MagicSquare::set_mutation_rate`].
+    pub fn mutation_rate(mut self, rate: f64) -> Generator {
+        self.mutation_rate = rate;
+        self
+    }
+
+    /// Set how many generations a genetic search breeds. See
+    /// [`MagicSquare::set_generations`].
+    pub fn generations(mut self, generations: usize) -> Generator {
+        self.generations = generations;
+        self
+    }
+
+    /// Choose which empty cell the solver fills next. See
+    /// [`MagicSquare::set_cell_order`].
+    pub fn cell_order(mut self, order: CellOrder) -> Generator {
+        self.cell_order = order;
+        self
+    }
+
+    /// Choose the order the solver tries letters in at a cell. See
+    /// [`MagicSquare::set_letter_order`].
+    pub fn letter_order(mut self, order: LetterOrder) -> Generator {
+        self.letter_order = order;
+        self
+    }
+
+    /// Place letters without narrowing every cell's candidate letters
+    /// after each placement. See [`MagicSquare::set_propagate`].
+    pub fn no_propagate(mut self) -> Generator {
+        self.propagate = false;
+        self
+    }
+
+    /// Reject letters that leave a cell in their row or column with no
+    /// possible letter. See [`MagicSquare::set_forward_check`].
+    pub fn forward_check(mut self) -> Generator {
+        self.forward_check = true;
+        self
+    }
+
+    /// Remember partial squares the search shows lead nowhere, and skip
+    /// them when it reaches them again. See [`MagicSquare::set_nogoods`].
+    pub fn nogoods(mut self) -> Generator {
+        self.nogoods = true;
+        self
+    }
+
+    /// Split the search over the machine's cores. See
+    /// [`MagicSquare::set_parallel`].
+    pub fn parallel(mut self) -> Generator {
+        self.parallel = true;
+        self
+    }
+
+    /// Split a parallel search over `threads` threads rather than one per
+    /// core. See [`MagicSquare::set_threads`].
+    pub fn threads(mut self, threads: usize) -> Generator {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Shuffle the order the solver tries letters in, so different seeds
+    /// give different squares and the same seed always gives the same one.
+    /// See [`MagicSquare::set_seed`].
+    pub fn seed(mut self, seed: u64) -> Generator {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Search in reading order trying letters a to z, whatever the other
+    /// settings say. See [`MagicSquare::set_deterministic`].
+    pub fn deterministic(mut self) -> Generator {
+        self.deterministic = true;
+        self
+    }
+
+    /// Restart a search that has tried `unit` letters in a new order, with
+    /// a growing allowance each time. See [`MagicSquare::set_restarts`].
+    pub fn restarts(mut self, unit: usize) -> Generator {
+        self.restarts = Some(unit);
+        self
+    }
+
+    /// Give up after trying `limit` letters. See
+    /// [`MagicSquare::set_attempt_limit`].
+    pub fn max_attempts(mut self, limit: usize) -> Generator {
+        self.max_attempts = Some(limit);
+        self
+    }
+
+    /// Give up after searching for `limit`. See
+    /// [`MagicSquare::set_time_limit`].
+    pub fn time_limit(mut self, limit: Duration) -> Generator {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Save the search's place to `path` every so often. See
+    /// [`MagicSquare::set_checkpoint`].
+    pub fn checkpoint(mut self, path: &str) -> Generator {
+        self.checkpoint = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Carry on from the checkpoint at `path`, saving the search's place
+    /// there as it goes unless `checkpoint` says elsewhere. See
+    /// [`MagicSquare::resume_from`].
+    pub fn resume(mut self, path: &str) -> Generator {
+        self.resume = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Load the dictionary and fill the square.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(MagicSquare)` with every cell filled.
+    /// * `Err(String)` if the dictionary could not be loaded, the template
+    ///   does not fit the dimensions, or the square could not be filled.
+    pub fn solve(self) -> Result<MagicSquare, String> {
+        let dict = match self.dict {
+            DictSource::Os => {
+                let dict = Dictionary::from_os_dict();
+                #[cfg(feature = "embedded-dict")]
+                let dict = dict.or_else(|_| Ok::<_, String>(Dictionary::embedded()));
+                dict?
+            }
+            DictSource::Path(path) => Dictionary::load(&path)?,
+            DictSource::Loaded(dict) => *dict,
+        };
+        let dict = if self.fold_accents {
+            dict.fold_diacritics()
+        } else {
+            dict
+        };
+        let dict = dict.with_proper_nouns(self.proper_nouns);
+        let mut dict = if self.allow_offensive {
+            dict
+        } else {
+            dict.filter_offensive()
+        };
+        if let Some(path) = Dictionary::user_blocklist_path() {
+            dict = dict.without_words_in_file(path)?;
+        }
+        for path in self.overlays.iter() {
+            dict = Overrides::read(Path::new(path))?.apply(dict);
+        }
+        let dict = Overrides::personal()?.apply(dict);
+        let added: Vec<String> = self
+            .added_words
+            .iter()
+            .map(|word| {
+                if self.fold_accents {
+                    fold_diacritics(word)
+                } else {
+                    word.clone()
+                }
+            })
+            .collect();
+        let dict = dict.with_words(added.iter().map(String::as_str));
+        let dict = match &self.frequencies {
+            Some(path) => dict.with_frequencies(path)?,
+            None => dict,
+        };
+        let dict = if self.compact { dict.compact() } else { dict };
+
+        let template = match self.template {
+            Some(template) if self.fold_accents => Some(fold_diacritics(&template)),
+            template => template,
+        };
+        let mut square = match &template {
+            Some(template) => {
+                // Rows may be shorter than the square, to be padded with
+                // blocks, but the widest sets its width
+                let widths: Vec<usize> = template.split('/').map(|r| r.chars().count()).collect();
+                if widths.len() > self.rows || widths.iter().max() != Some(&self.cols) {
+                    return Err(format!(
+                        "template {} does not fit a {}x{} square",
+                        template, self.rows, self.cols
+                    ));
+                }
+                MagicSquare::from_template(template, self.rows, &dict)
+            }
+            None => MagicSquare::empty(self.rows, self.cols, &dict),
+        };
+        square.set_live_view(LiveView::Off);
+        square.set_overlap(self.overlap);
+        square.set_distinct(self.distinct);
+        square.set_diagonals(self.diagonals);
+        square.set_min_word_len(self.min_word_len);
+        square.set_toroidal(self.toroidal);
+        square.set_strategy(self.strategy);
+        square.set_beam_width(self.beam_width);
+        square.set_population(self.population);
+        square.set_mutation_rate(self.mutation_rate);
+        square.set_generations(self.generations);
+        square.set_cell_order(self.cell_order);
+        square.set_letter_order(self.letter_order);
+        square.set_propagate(self.propagate);
+        square.set_forward_check(self.forward_check);
+        square.set_nogoods(self.nogoods);
+        square.set_parallel(self.parallel);
+        square.set_threads(self.threads);
+        square.set_seed(self.seed);
+        square.set_deterministic(self.deterministic);
+        square.set_restarts(self.restarts);
+        square.set_attempt_limit(self.max_attempts);
+        square.set_time_limit(self.time_limit);
+        square.set_checkpoint(self.checkpoint.or_else(|| self.resume.clone()));
+        if let Some(path) = &self.resume {
+            square.resume_from(path)?;
+        }
+        if self.themes.is_empty() {
+            square.fill()?;
+        } else {
+            square.fill_with_themes(&self.themes)?;
+        }
+        Ok(square)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+    use crate::test_util::{assert_square, words};
+
+    #[test]
+    fn fills_ragged_templates() {
+        let square = generate(3, 5)
+            .with_dictionary(words())
+            .template("_____/____/___")
+            .min_word_len(3)
+            .solve()
+            .unwrap();
+        assert_square(&square);
+        assert_eq!(square.get_row(1)[4], '#');
+        assert_eq!(square.get_row(2)[3..], ['#', '#']);
+    }
+
+    #[test]
+    fn rejects_templates_that_do_not_fit() {
+        for template in ["___/___", "_____/___", "____/____/____/____/____"] {
+            let result = generate(4, 4).with_dictionary(words()).template(template).solve();
+            assert_eq!(
+                result.err(),
+                Some(format!("template {} does not fit a 4x4 square", template))
+            );
+        }
+    }
+}
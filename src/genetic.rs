@@ -0,0 +1,223 @@
+//! Filling a square with a genetic algorithm.
+//!
+//! A population of full grids evolves towards a square. The first grids
+//! have random words that fit each row. Each generation keeps the best
+//! grids as they are, and breeds the rest: two parents, each the fitter of
+//! two grids picked at random, give a child each of whose rows comes from
+//! one or the other, and then some of the child's letters change at
+//! random. A grid is fitter the more of its rows and columns are words,
+//! and, with word frequencies, the more common those words are. With
+//! mirrored rows, a row only counts if it is also a word backwards.
+
+use crate::rng::Rng;
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// How many of the fittest grids go into the next generation unchanged.
+const ELITE: usize = 2;
+/// A grid of the population, and how fit it is.
+#[derive(Clone)]
+struct Individual {
+    grid: Vec<Vec<char>>,
+    /// How many of its rows and columns are words.
+    words: usize,
+    /// `words`, plus less than one more for how common the words are.
+    fitness: f64,
+}
+
+impl MagicSquare {
+    /// Fill the square by evolving a population of grids.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if some grid's rows and columns all became words.
+    /// * `Err(String)` if some row has no word, or the last generation had
+    ///   no square, in which case the square is left as it was and
+    ///   [`best_fill`](MagicSquare::best_fill) has the fittest grid, with
+    ///   the rows and columns that are not words blanked.
+    pub(crate) fn fill_genetic(&mut self) -> Result<(), String> {
+        let start = self.square.clone();
+        let mut rng = Rng::new(self.search_seed().unwrap_or(0));
+        let alphabet = self.dict.alphabet().to_vec();
+        let slots = Slot::all(&self.square).len();
+
+        // The words each row may start as, in grid order
+        let mut rows: Vec<Vec<Vec<char>>> = vec![];
+        for row in 0..self.square.len() {
+            let template: String = self.word(Slot::Row(row));
+            let words: Vec<Vec<char>> = self
+                .dict
+                .iter_with_template(&template)
+                .filter(|word| !self.mirror_rows || self.dict.contains_reversed(word))
+                .map(|word| Slot::Row(row).orient(self.direction, word.chars().collect()))
+                .collect();
+            if words.is_empty() {
+                return Err(format!("No word fits row {}", row));
+            }
+            rows.push(words);
+        }
+        let mut population: Vec<Individual> = (0..self.population)
+            .map(|_| {
+                let grid = rows.iter().map(|words| words[rng.below(words.len())].clone());
+                self.individual(grid.collect())
+            })
+            .collect();
+
+        for generation in 0..self.generations {
+            population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            self.square.clone_from(&population[0].grid);
+            self.draw_grid();
+            if population[0].words == slots {
+                self.note_depth();
+                return Ok(());
+            }
+            trace!("generation {}: best fitness {:.2}", generation, population[0].fitness);
+
+            let mut next: Vec<Individual> = population.iter().take(ELITE).cloned().collect();
+            while next.len() < self.population {
+                if self.should_stop() {
+                    return self.give_up_evolving(start, &population[0]);
+                }
+                self._attempt += 1;
+                let mother = Self::tournament(&population, &mut rng);
+                let father = Self::tournament(&population, &mut rng);
+                let mut child: Vec<Vec<char>> = mother
+                    .grid
+                    .iter()
+                    .zip(&father.grid)
+                    .map(|(a, b)| if rng.below(2) == 0 { a } else { b }.clone())
+                    .collect();
+                for (row, cells) in child.iter_mut().enumerate() {
+                    for (col, cell) in cells.iter_mut().enumerate() {
+                        if self.editable_mask[row][col] && rng.unit() < self.mutation_rate {
+                            *cell = alphabet[rng.below(alphabet.len())];
+                        }
+                    }
+                }
+                next.push(self.individual(child));
+            }
+            population = next;
+        }
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        self.gave_up = true;
+        self.give_up_evolving(start, &population[0])
+    }
+
+    /// A grid and its fitness: how many of its rows and columns are words,
+    /// plus how common those words are if the dictionary has word
+    /// frequencies.
+    fn individual(&self, grid: Vec<Vec<char>>) -> Individual {
+        let slots = Slot::all(&grid);
+        let mut words = 0;
+        let mut commonness = 0.0;
+        for &slot in &slots {
+            let word: String = slot.read(&grid, self.direction).into_iter().collect();
+            self.note_query();
+            if self.fills(slot, &word) {
+                words += 1;
+                commonness += self.dict.commonness(&word);
+            }
+        }
+        // Each word adds less than one over the number of slots, so common
+        // words never make up for a missing one
+        Individual {
+            grid,
+            words,
+            fitness: words as f64 + commonness / (slots.len() + 1) as f64,
+        }
+    }
+
+    /// Whether `word`, read out of `slot`, is a word there: in the
+    /// dictionary and, in a row of a square with mirrored rows, in it
+    /// backwards as well.
+    fn fills(&self, slot: Slot, word: &str) -> bool {
+        let mirrored = self.mirror_rows && matches!(slot, Slot::Row(_));
+        self.dict.contains(word) && (!mirrored || self.dict.contains_reversed(word))
+    }
+
+    /// The fitter of two grids picked at random.
+    fn tournament<'a>(population: &'a [Individual], rng: &mut Rng) -> &'a Individual {
+        let a = &population[rng.below(population.len())];
+        let b = &population[rng.below(population.len())];
+        if a.fitness >= b.fitness {
+            a
+        } else {
+            b
+        }
+    }
+
+    /// Give up, putting the square back to `start`, and remember the
+    /// fittest grid with its rows and columns that are not words blanked.
+    fn give_up_evolving(&mut self, start: Vec<Vec<char>>, best: &Individual) -> Result<(), String> {
+        self.square.clone_from(&best.grid);
+        let broken: Vec<Slot> = Slot::all(&self.square)
+            .into_iter()
+            .filter(|&slot| !self.fills(slot, &self.word(slot)))
+            .collect();
+        for slot in &broken {
+            for row in 0..self.square.len() {
+                for col in 0..self.square[row].len() {
+                    let on_slot = match *slot {
+                        Slot::Row(r) => r == row,
+                        Slot::Col(c) => c == col,
+                    };
+                    if on_slot && self.editable_mask[row][col] {
+                        self.square[row][col] = '_';
+                    }
+                }
+            }
+        }
+        self.note_depth();
+        self.square = start;
+        Err(format!("No square after {} generations", self.generations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dictionary::Dictionary;
+    use crate::test_util::assert_fills;
+    use crate::{Direction, LiveView, MagicSquare, Strategy};
+
+    #[test]
+    fn mirrored_rows_are_words_backwards() {
+        // "ab/cd" is a square of these words, but "cd" is not a word
+        // backwards; "ab/ba" is the only square with mirrored rows
+        let dict = Dictionary::from_words(["ab", "ba", "cd", "ac", "bd"]);
+        for seed in 0..8 {
+            let mut square = MagicSquare::from_template("__", 2, &dict);
+            square.set_live_view(LiveView::Off);
+            square.set_strategy(Strategy::Genetic);
+            square.set_mirror_rows(true);
+            square.set_seed(Some(seed));
+            square.fill().unwrap();
+            for row in square.grid() {
+                let word: String = row.iter().rev().collect();
+                assert!(dict.contains(&word), "seed {}: {} backwards is not a word", seed, word);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_blank_and_started_squares() {
+        for seed in 0..4 {
+            let seeded = |square: &mut MagicSquare| square.set_seed(Some(seed));
+            assert_fills("___", 3, Strategy::Genetic, seeded);
+            assert_fills("_f_/_u_/_n_", 3, Strategy::Genetic, seeded);
+        }
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_directions() {
+        assert_fills("___", 3, Strategy::Genetic, |square| {
+            square.set_seed(Some(1));
+            square.set_mirror_rows(true);
+        });
+        for direction in [Direction::BottomUp, Direction::Boustrophedon] {
+            assert_fills("___", 3, Strategy::Genetic, |square| {
+                square.set_seed(Some(1));
+                square.set_direction(direction);
+            });
+        }
+    }
+}
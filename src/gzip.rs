@@ -0,0 +1,365 @@
+//! Reading gzip files without a gzip tool: the members' headers, and the
+//! deflate blocks inside them (stored, fixed and dynamic Huffman), checked
+//! against each member's CRC-32 and length. Only decompression is needed,
+//! so there is no encoder.
+
+const MAGIC: &[u8] = &[0x1f, 0x8b];
+/// The only compression method gzip defines: deflate.
+const DEFLATE: u8 = 8;
+
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+/// The longest Huffman code deflate uses.
+const MAX_BITS: usize = 15;
+
+/// The shortest match of each length code (257 to 285), and how many extra
+/// bits add to it.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// The shortest distance of each distance code, and how many extra bits
+/// add to it.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order a dynamic block lists the lengths of the code-length code in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Whether `bytes` start like a gzip file.
+pub(crate) fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Decompress a gzip file: every member in it, one after another, as the
+/// `gzip` tool does, ignoring anything after the last.
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with the decompressed bytes.
+/// * `Err(String)` if the file is not gzip, is truncated, or fails its
+///   checks.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut rest = bytes;
+    loop {
+        rest = member(rest, &mut out)?;
+        if !is_gzip(rest) {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decompress the gzip member at the start of `bytes` onto `out`, and
+/// return what follows it.
+fn member<'a>(bytes: &'a [u8], out: &mut Vec<u8>) -> Result<&'a [u8], String> {
+    if !is_gzip(bytes) {
+        return Err("not gzip data".to_string());
+    }
+    let header = bytes.get(..10).ok_or("truncated gzip header")?;
+    if header[2] != DEFLATE {
+        return Err(format!("unknown gzip compression method {}", header[2]));
+    }
+    let flags = header[3];
+    let mut at = 10;
+    let truncated = || "truncated gzip header".to_string();
+    if flags & FLAG_EXTRA != 0 {
+        let len = bytes.get(at..at + 2).ok_or_else(truncated)?;
+        at += 2 + usize::from(u16::from_le_bytes([len[0], len[1]]));
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let end = bytes
+                .get(at..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(truncated)?;
+            at += end + 1;
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        at += 2;
+    }
+    if at > bytes.len() {
+        return Err(truncated());
+    }
+
+    let start = out.len();
+    let mut input = Bits::new(&bytes[at..]);
+    inflate(&mut input, out)?;
+    let at = at + input.consumed();
+    let trailer = bytes.get(at..at + 8).ok_or("truncated gzip trailer")?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc32(&out[start..]) != crc {
+        return Err("gzip data fails its CRC check".to_string());
+    }
+    // The length is kept modulo 2^32
+    if (out.len() - start) as u32 != size {
+        return Err("gzip data has the wrong length".to_string());
+    }
+    Ok(&bytes[at + 8..])
+}
+
+/// Reads a deflate stream a few bits at a time, least significant first.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(bytes: &'a [u8]) -> Bits<'a> {
+        Bits {
+            bytes,
+            at: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// The next `n` bits, up to 16.
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.bytes.get(self.at).ok_or("truncated deflate data")?;
+            self.at += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skip to the next byte boundary, and take the next `n` bytes.
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        self.buffer = 0;
+        self.count = 0;
+        let bytes = self
+            .bytes
+            .get(self.at..self.at + n)
+            .ok_or("truncated deflate data")?;
+        self.at += n;
+        Ok(bytes)
+    }
+
+    /// How many bytes have been read, counting a partly read one.
+    fn consumed(&self) -> usize {
+        self.at
+    }
+}
+
+/// A canonical Huffman code: how many symbols have a code of each length,
+/// and the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// The code giving each symbol a code of the length in `lengths`, or
+    /// none for a length of 0. Codes may leave some bit patterns unused, as
+    /// a distance code with one symbol does, but not claim too many.
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err("invalid Huffman code in deflate data".to_string());
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                let slot = &mut offsets[usize::from(len)];
+                symbols[usize::from(*slot)] = symbol as u16;
+                *slot += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    /// Read one symbol from `input`.
+    fn decode(&self, input: &mut Bits) -> Result<usize, String> {
+        // The first code of each length, and the index of its symbol
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &self.counts[1..] {
+            code |= input.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(usize::from(self.symbols[(index + code - first) as usize]));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code in deflate data".to_string())
+    }
+}
+
+/// Decompress a deflate stream onto `out`, which holds what came before it
+/// in the file, as matches may not reach back past the start of a member.
+fn inflate(input: &mut Bits, out: &mut Vec<u8>) -> Result<(), String> {
+    let start = out.len();
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                let header = input.bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let check = u16::from_le_bytes([header[2], header[3]]);
+                if len != !check {
+                    return Err("corrupt stored block in deflate data".to_string());
+                }
+                out.extend_from_slice(input.bytes(usize::from(len))?);
+            }
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                codes(input, out, start, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(input)?;
+                codes(input, out, start, &literals, &distances)?;
+            }
+            _ => return Err("invalid block type in deflate data".to_string()),
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+/// The codes a fixed Huffman block uses.
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Read the codes a dynamic Huffman block describes in its header.
+fn dynamic_codes(input: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let length_count = input.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err("too many codes in deflate data".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..length_count] {
+        code_lengths[symbol] = input.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let (len, repeat) = match code_lengths.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or("deflate data repeats a length before the first")?;
+                (previous, 3 + input.bits(2)?)
+            }
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return Err("too many code lengths in deflate data".to_string());
+        }
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        return Err("deflate data has no end-of-block code".to_string());
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals)?, Huffman::new(distances)?))
+}
+
+/// Decompress the literals and matches of a Huffman block onto `out`, up
+/// to its end-of-block code.
+fn codes(
+    input: &mut Bits,
+    out: &mut Vec<u8>,
+    start: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(input)?;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let code = symbol - 257;
+        if code >= LENGTH_BASE.len() {
+            return Err("invalid length code in deflate data".to_string());
+        }
+        let len =
+            usize::from(LENGTH_BASE[code]) + input.bits(u32::from(LENGTH_EXTRA[code]))? as usize;
+        let code = distances.decode(input)?;
+        if code >= DISTANCE_BASE.len() {
+            return Err("invalid distance code in deflate data".to_string());
+        }
+        let distance = usize::from(DISTANCE_BASE[code])
+            + input.bits(u32::from(DISTANCE_EXTRA[code]))? as usize;
+        if distance > out.len() - start {
+            return Err("deflate data reaches back before its start".to_string());
+        }
+        // A match may overlap the bytes it copies, so copy one at a time
+        let from = out.len() - distance;
+        for i in 0..len {
+            out.push(out[from + i]);
+        }
+    }
+}
+
+/// The CRC-32 gzip checks each member's data against.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        table[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
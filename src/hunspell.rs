@@ -0,0 +1,376 @@
+//! Reading hunspell dictionaries: a `.dic` file of stems, each with flags
+//! naming the affix rules that apply to it, and an `.aff` file defining
+//! those rules. The rules are expanded into every surface form, so
+//! `walk/DGS` with the usual English rules gives "walk", "walked",
+//! "walking" and "walks".
+//!
+//! Supported: `SET`, `FLAG` (single characters, `long`, `num` and
+//! `UTF-8`), flag aliases (`AF`), prefixes and suffixes with strip
+//! strings and conditions, cross products of a prefix and a suffix, one
+//! level of suffix continuation (`SFX ... ed/S`), and the `NEEDAFFIX`,
+//! `FORBIDDENWORD` and `ONLYINCOMPOUND` flags. Compounding rules are
+//! ignored, so compounds are not generated.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// An affix flag, whatever its spelling in the `.aff` file.
+type Flag = u32;
+
+/// How flags are written in the `.aff` and `.dic` files.
+#[derive(Clone, Copy)]
+enum FlagFormat {
+    /// One character per flag (the default).
+    Char,
+    /// Two characters per flag.
+    Long,
+    /// Decimal numbers separated by commas.
+    Num,
+}
+
+impl FlagFormat {
+    fn parse(self, text: &str) -> Vec<Flag> {
+        match self {
+            FlagFormat::Char => text.chars().map(|c| c as Flag).collect(),
+            FlagFormat::Long => {
+                let chars: Vec<char> = text.chars().collect();
+                chars
+                    .chunks(2)
+                    .map(|pair| pair.iter().fold(0, |flag, &c| (flag << 16) | c as Flag))
+                    .collect()
+            }
+            FlagFormat::Num => text
+                .split(',')
+                .filter_map(|n| n.trim().parse::<Flag>().ok())
+                .collect(),
+        }
+    }
+}
+
+/// One element of an affix condition.
+#[derive(Clone, Debug)]
+enum CondChar {
+    Any,
+    Is(char),
+    OneOf(Vec<char>, bool),
+}
+
+impl CondChar {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CondChar::Any => true,
+            CondChar::Is(expected) => c == *expected,
+            CondChar::OneOf(set, negated) => set.contains(&c) != *negated,
+        }
+    }
+}
+
+/// Parse a condition such as `[^aeiou]y`, or `.` for none.
+fn parse_condition(text: &str) -> Result<Vec<CondChar>, String> {
+    let mut condition = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        condition.push(match c {
+            '.' => CondChar::Any,
+            '[' => {
+                let mut set = vec![];
+                let mut negated = false;
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('^') if set.is_empty() && !negated => negated = true,
+                        Some(c) => set.push(c),
+                        None => return Err(format!("unclosed [ in condition {}", text)),
+                    }
+                }
+                CondChar::OneOf(set, negated)
+            }
+            c => CondChar::Is(c),
+        });
+    }
+    Ok(condition)
+}
+
+/// One affix rule: strip some letters from one end of a stem and add
+/// others, if the stem's letters at that end match the condition.
+#[derive(Clone, Debug)]
+struct Affix {
+    strip: String,
+    add: String,
+    /// Flags of further affixes the result takes.
+    continuation: Vec<Flag>,
+    condition: Vec<CondChar>,
+    /// Whether it combines with an affix of the other kind.
+    cross_product: bool,
+}
+
+impl Affix {
+    /// Add this suffix to `word`, if it applies.
+    fn suffix(&self, word: &str) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < self.condition.len() || !word.ends_with(&self.strip) {
+            return None;
+        }
+        let tail = &chars[chars.len() - self.condition.len()..];
+        if !tail
+            .iter()
+            .zip(self.condition.iter())
+            .all(|(&c, cond)| cond.matches(c))
+        {
+            return None;
+        }
+        let stem = &word[..word.len() - self.strip.len()];
+        // Stripping the whole stem leaves nothing to attach to
+        (!stem.is_empty() || !self.add.is_empty()).then(|| format!("{}{}", stem, self.add))
+    }
+
+    /// Add this prefix to `word`, if it applies.
+    fn prefix(&self, word: &str) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < self.condition.len() || !word.starts_with(&self.strip) {
+            return None;
+        }
+        if !chars
+            .iter()
+            .zip(self.condition.iter())
+            .all(|(&c, cond)| cond.matches(c))
+        {
+            return None;
+        }
+        let stem = &word[self.strip.len()..];
+        (!stem.is_empty() || !self.add.is_empty()).then(|| format!("{}{}", self.add, stem))
+    }
+}
+
+/// The rules of an `.aff` file.
+pub(crate) struct Affixes {
+    format: FlagFormat,
+    /// Flag sets from `AF` lines, which `.dic` entries may name by number
+    /// (starting at 1) instead of spelling out.
+    aliases: Vec<Vec<Flag>>,
+    prefixes: HashMap<Flag, Vec<Affix>>,
+    suffixes: HashMap<Flag, Vec<Affix>>,
+    need_affix: Option<Flag>,
+    forbidden: Option<Flag>,
+    only_in_compound: Option<Flag>,
+}
+
+impl Affixes {
+    /// Parse the text of an `.aff` file. Unknown directives are skipped.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Affixes)` with the rules.
+    /// * `Err(String)` describing the first malformed rule.
+    pub(crate) fn parse(text: &str) -> Result<Affixes, String> {
+        let mut affixes = Affixes {
+            format: FlagFormat::Char,
+            aliases: vec![],
+            prefixes: HashMap::new(),
+            suffixes: HashMap::new(),
+            need_affix: None,
+            forbidden: None,
+            only_in_compound: None,
+        };
+        // Each PFX/SFX block starts with a header, `SFX flag Y|N count`,
+        // giving the cross-product setting its entries share and how many
+        // entries follow
+        let mut blocks: HashMap<(bool, Flag), (bool, usize)> = HashMap::new();
+        // Likewise `AF count` comes before the aliases
+        let mut aliases_left = 0;
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some(&directive) = fields.first() else {
+                continue;
+            };
+            let format = affixes.format;
+            let flag = |field: usize| {
+                fields
+                    .get(field)
+                    .and_then(|text| format.parse(text).first().copied())
+            };
+            match directive {
+                "FLAG" => {
+                    affixes.format = match fields.get(1).copied() {
+                        Some("long") => FlagFormat::Long,
+                        Some("num") => FlagFormat::Num,
+                        _ => FlagFormat::Char,
+                    }
+                }
+                "NEEDAFFIX" => affixes.need_affix = flag(1),
+                "FORBIDDENWORD" => affixes.forbidden = flag(1),
+                "ONLYINCOMPOUND" => affixes.only_in_compound = flag(1),
+                "AF" if fields.len() >= 2 => {
+                    if aliases_left == 0 {
+                        aliases_left = fields[1].parse::<usize>().unwrap_or(0);
+                    } else {
+                        aliases_left -= 1;
+                        affixes.aliases.push(format.parse(fields[1]));
+                    }
+                }
+                "PFX" | "SFX" => {
+                    let is_prefix = directive == "PFX";
+                    let Some(flag) = flag(1) else {
+                        return Err(format!("line {}: {} needs a flag", number + 1, directive));
+                    };
+                    let block = blocks.entry((is_prefix, flag)).or_insert((false, 0));
+                    if block.1 == 0 {
+                        let count = fields.get(3).and_then(|n| n.parse::<usize>().ok());
+                        let Some(count) = count else {
+                            return Err(format!(
+                                "line {}: malformed {} header",
+                                number + 1,
+                                directive
+                            ));
+                        };
+                        *block = (fields[2] == "Y", count);
+                        continue;
+                    }
+                    block.1 -= 1;
+                    let cross_product = block.0;
+                    if fields.len() < 4 {
+                        return Err(format!("line {}: malformed {} rule", number + 1, directive));
+                    }
+                    let (add, continuation) = match fields[3].split_once('/') {
+                        Some((add, flags)) => (add, affixes.format_flags(flags)),
+                        None => (fields[3], vec![]),
+                    };
+                    let empty = |s: &str| {
+                        if s == "0" {
+                            String::new()
+                        } else {
+                            s.to_string()
+                        }
+                    };
+                    let affix = Affix {
+                        strip: empty(fields[2]),
+                        add: empty(add),
+                        continuation,
+                        condition: parse_condition(fields.get(4).copied().unwrap_or("."))?,
+                        cross_product,
+                    };
+                    let rules = if is_prefix {
+                        &mut affixes.prefixes
+                    } else {
+                        &mut affixes.suffixes
+                    };
+                    rules.entry(flag).or_default().push(affix);
+                }
+                _ => {}
+            }
+        }
+        Ok(affixes)
+    }
+
+    /// The flags written as `text`, or the alias it names by number.
+    fn format_flags(&self, text: &str) -> Vec<Flag> {
+        match text.parse::<usize>() {
+            Ok(alias) if !self.aliases.is_empty() => self
+                .aliases
+                .get(alias.wrapping_sub(1))
+                .cloned()
+                .unwrap_or_default(),
+            _ => self.format.parse(text),
+        }
+    }
+
+    fn has(flags: &[Flag], flag: Option<Flag>) -> bool {
+        flag.is_some_and(|flag| flags.contains(&flag))
+    }
+
+    /// Every rule of `rules` that some flag in `flags` names.
+    fn rules<'a>(
+        rules: &'a HashMap<Flag, Vec<Affix>>,
+        flags: &'a [Flag],
+    ) -> impl Iterator<Item = &'a Affix> + 'a {
+        flags.iter().filter_map(|flag| rules.get(flag)).flatten()
+    }
+
+    /// Every surface form of a stem with the given flags.
+    fn expand_stem(&self, stem: &str, flags: &[Flag], out: &mut HashSet<String>) {
+        if Affixes::has(flags, self.forbidden) || Affixes::has(flags, self.only_in_compound) {
+            return;
+        }
+        if !Affixes::has(flags, self.need_affix) {
+            out.insert(stem.to_string());
+        }
+
+        let mut suffixed = vec![];
+        for suffix in Affixes::rules(&self.suffixes, flags) {
+            let Some(word) = suffix.suffix(stem) else {
+                continue;
+            };
+            if !Affixes::has(&suffix.continuation, self.need_affix) {
+                out.insert(word.clone());
+            }
+            // One more suffix, as in "-ize" then "-s"
+            for next in Affixes::rules(&self.suffixes, &suffix.continuation) {
+                if let Some(word) = next.suffix(&word) {
+                    out.insert(word);
+                }
+            }
+            if suffix.cross_product {
+                suffixed.push(word);
+            }
+        }
+
+        for prefix in Affixes::rules(&self.prefixes, flags) {
+            if let Some(word) = prefix.prefix(stem) {
+                if !Affixes::has(&prefix.continuation, self.need_affix) {
+                    out.insert(word);
+                }
+            }
+            if prefix.cross_product {
+                out.extend(suffixed.iter().filter_map(|word| prefix.prefix(word)));
+            }
+        }
+    }
+
+    /// Expand the entries of a `.dic` file. The first line is a count of
+    /// the entries, and each entry is a stem, optionally followed by `/`
+    /// and its flags, and then by morphological fields, which are ignored.
+    pub(crate) fn expand(&self, dic: &str) -> HashSet<String> {
+        let mut words = HashSet::new();
+        for line in dic.lines().skip(1) {
+            let entry = line.split(['\t', ' ']).next().unwrap_or("");
+            let (stem, flags) = match entry.split_once('/') {
+                Some((stem, flags)) => (stem, self.format_flags(flags)),
+                None => (entry, vec![]),
+            };
+            if !stem.is_empty() {
+                self.expand_stem(stem, &flags, &mut words);
+            }
+        }
+        words
+    }
+}
+
+/// Read a hunspell dictionary, expanding the affix rules in the `.aff` file
+/// next to `path` if there is one; without one, only the stems are read.
+/// Files whose `.aff` declares `SET ISO8859-*` are decoded as Latin-1; all
+/// others as UTF-8.
+pub(crate) fn read(path: &Path) -> Result<HashSet<String>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let aff_path = path.with_extension("aff");
+    let aff = std::fs::read(&aff_path).ok();
+    let latin1 = aff.as_ref().is_some_and(|aff| {
+        String::from_utf8_lossy(aff)
+            .lines()
+            .any(|line| line.trim().starts_with("SET ISO8859"))
+    });
+    let decode = |bytes: &[u8]| -> String {
+        if latin1 {
+            bytes.iter().map(|&b| b as char).collect()
+        } else {
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    };
+
+    let affixes = match &aff {
+        Some(aff) => {
+            Affixes::parse(&decode(aff)).map_err(|e| format!("{}: {}", aff_path.display(), e))?
+        }
+        None => Affixes::parse("")?,
+    };
+    Ok(affixes.expand(&decode(&bytes)))
+}
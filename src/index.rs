@@ -0,0 +1,338 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::codec::{Reader, Writer};
+use crate::parallel;
+
+/// Words of one length, with a bitset of word IDs for every
+/// (position, letter) pair.
+struct LengthIndex {
+    /// The words, in sorted order; a word's ID is its position here.
+    words: Vec<String>,
+    /// Bit `id` of `letters[&(pos, c)]` is set if word `id` has `c` at `pos`.
+    letters: HashMap<(usize, char), Vec<u64>>,
+    /// How many different letters the words have at each position.
+    letter_counts: Vec<usize>,
+}
+
+impl LengthIndex {
+    fn new(mut words: Vec<String>) -> LengthIndex {
+        words.sort_unstable();
+        let chunks = words.len().div_ceil(64);
+        let mut letters: HashMap<(usize, char), Vec<u64>> = HashMap::new();
+        for (id, word) in words.iter().enumerate() {
+            for (pos, c) in word.chars().enumerate() {
+                letters.entry((pos, c)).or_insert_with(|| vec![0; chunks])[id / 64] |=
+                    1 << (id % 64);
+            }
+        }
+        LengthIndex::with_letters(words, letters)
+    }
+
+    fn with_letters(words: Vec<String>, letters: HashMap<(usize, char), Vec<u64>>) -> LengthIndex {
+        let len = words.first().map_or(0, |w| w.chars().count());
+        let mut letter_counts = vec![0; len];
+        for &(pos, _) in letters.keys() {
+            letter_counts[pos] += 1;
+        }
+        LengthIndex {
+            words,
+            letters,
+            letter_counts,
+        }
+    }
+}
+
+/// An iterator over the words matching a template, from
+/// `PositionIndex::matches`.
+pub(crate) struct Matches<'a> {
+    words: &'a [String],
+    /// The bitsets every match must be in.
+    constraints: Vec<&'a [u64]>,
+    /// The next chunk of 64 word IDs to intersect.
+    chunk: usize,
+    /// The matches left in the chunk before `chunk`.
+    bits: u64,
+}
+
+impl<'a> Matches<'a> {
+    /// The match bitset of the chunk holding word IDs `64 * chunk` onwards.
+    fn chunk_bits(&self, chunk: usize) -> u64 {
+        // Mask off the IDs past the end of the last chunk
+        let mut bits = match self.words.len() - chunk * 64 {
+            left if left < 64 => (1u64 << left) - 1,
+            _ => u64::MAX,
+        };
+        for set in self.constraints.iter() {
+            bits &= set[chunk];
+        }
+        bits
+    }
+
+    fn chunks(&self) -> usize {
+        self.words.len().div_ceil(64)
+    }
+}
+
+impl<'a> Iterator for Matches<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        while self.bits == 0 {
+            if self.chunk == self.chunks() {
+                return None;
+            }
+            self.bits = self.chunk_bits(self.chunk);
+            self.chunk += 1;
+        }
+        let id = (self.chunk - 1) * 64 + self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some(&self.words[id])
+    }
+
+    /// Count the remaining matches by popcount, without visiting them.
+    fn count(self) -> usize {
+        let rest: usize = (self.chunk..self.chunks())
+            .map(|chunk| self.chunk_bits(chunk).count_ones() as usize)
+            .sum();
+        self.bits.count_ones() as usize + rest
+    }
+}
+
+/// A positional letter index: template queries become intersections of
+/// bitsets rather than scans over the word list.
+#[derive(Clone)]
+pub(crate) struct PositionIndex {
+    /// Shared between copies of a dictionary, so an edit to a few words
+    /// only rebuilds the lengths of those words.
+    lengths: HashMap<usize, Arc<LengthIndex>>,
+}
+
+impl PositionIndex {
+    pub(crate) fn new<'a, I: IntoIterator<Item = &'a String>>(words: I) -> PositionIndex {
+        let mut by_length: HashMap<usize, Vec<String>> = HashMap::new();
+        for word in words {
+            by_length
+                .entry(word.chars().count())
+                .or_default()
+                .push(word.clone());
+        }
+
+        PositionIndex {
+            lengths: HashMap::new(),
+        }
+        .with_lengths(by_length)
+    }
+
+    /// A copy of the index with the words of some lengths replaced: each
+    /// length in `changed` gets those words (none, to remove it), and the
+    /// other lengths are shared with this index.
+    pub(crate) fn with_lengths(&self, changed: HashMap<usize, Vec<String>>) -> PositionIndex {
+        let mut lengths = self.lengths.clone();
+        // Each length is indexed on its own, so they are spread over threads
+        let (changed, words): (Vec<usize>, Vec<Vec<String>>) = changed.into_iter().unzip();
+        let indexes = parallel::map(words, |words| {
+            (!words.is_empty()).then(|| Arc::new(LengthIndex::new(words)))
+        });
+        for (len, index) in changed.into_iter().zip(indexes) {
+            match index {
+                Some(index) => lengths.insert(len, index),
+                None => lengths.remove(&len),
+            };
+        }
+        PositionIndex { lengths }
+    }
+
+    /// The bitsets a template's fixed letters must all be in, or `None` if
+    /// some fixed letter never appears at its position (so nothing matches).
+    fn constraints<'a>(index: &'a LengthIndex, template: &[char]) -> Option<Vec<&'a [u64]>> {
+        template
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != '_')
+            .map(|(pos, c)| index.letters.get(&(pos, *c)).map(Vec::as_slice))
+            .collect()
+    }
+
+    /// The word lengths present, shortest first.
+    pub(crate) fn lengths(&self) -> Vec<usize> {
+        let mut lengths: Vec<usize> = self.lengths.keys().copied().collect();
+        lengths.sort();
+        lengths
+    }
+
+    /// Every character at any position of any word, in order.
+    pub(crate) fn letters(&self) -> Vec<char> {
+        let mut letters: Vec<char> = self
+            .lengths
+            .values()
+            .flat_map(|index| index.letters.keys().map(|&(_, c)| c))
+            .collect::<HashSet<char>>()
+            .into_iter()
+            .collect();
+        letters.sort_unstable();
+        letters
+    }
+
+    /// The words of a given length, in sorted order.
+    pub(crate) fn words(&self, len: usize) -> &[String] {
+        self.lengths.get(&len).map_or(&[], |index| &index.words)
+    }
+
+    /// For each position in words of length `len`, how many of them have
+    /// each letter there, counted by popcount of the letter bitsets.
+    pub(crate) fn letter_counts(&self, len: usize) -> Vec<HashMap<char, usize>> {
+        let mut counts = vec![HashMap::new(); len];
+        if let Some(index) = self.lengths.get(&len) {
+            for (&(pos, c), bits) in index.letters.iter() {
+                let count = bits.iter().map(|chunk| chunk.count_ones() as usize).sum();
+                counts[pos].insert(c, count);
+            }
+        }
+        counts
+    }
+
+    /// The words matching a template of letters and `_` wildcards, in
+    /// sorted order, found lazily a 64-word chunk at a time.
+    pub(crate) fn matches(&self, template: &str) -> Matches<'_> {
+        let template: Vec<char> = template.chars().collect();
+        let index = self.lengths.get(&template.len());
+        // A fixed letter that never appears at its position matches nothing
+        let constraints = index.and_then(|index| PositionIndex::constraints(index, &template));
+        match (index, constraints) {
+            (Some(index), Some(constraints)) => Matches {
+                words: &index.words,
+                constraints,
+                chunk: 0,
+                bits: 0,
+            },
+            _ => Matches {
+                words: &[],
+                constraints: vec![],
+                chunk: 0,
+                bits: 0,
+            },
+        }
+    }
+
+    /// How many words match a template: the popcount of the letter
+    /// bitsets of its fixed letters ANDed together, a 64-word chunk at a
+    /// time, without visiting a word.
+    pub(crate) fn count(&self, template: &str) -> usize {
+        let matches = self.matches(template);
+        (0..matches.chunks())
+            .map(|chunk| matches.chunk_bits(chunk).count_ones() as usize)
+            .sum()
+    }
+
+    /// The IDs of the words matching a template, as a bitset over the
+    /// words of its length: empty if none match.
+    pub(crate) fn match_bits(&self, template: &str) -> Vec<u64> {
+        let matches = self.matches(template);
+        (0..matches.chunks())
+            .map(|chunk| matches.chunk_bits(chunk))
+            .collect()
+    }
+
+    /// The bitset of the words of length `len` with `c` at `pos`, or
+    /// `None` if none has.
+    pub(crate) fn letter_bits(&self, len: usize, pos: usize, c: char) -> Option<&[u64]> {
+        let index = self.lengths.get(&len)?;
+        index.letters.get(&(pos, c)).map(Vec::as_slice)
+    }
+
+    /// For each position of a word, the letters allowed there that some
+    /// word of `allowed.len()` letters has there, given that every one of its
+    /// letters must be allowed at its position. Each position's letters
+    /// become a union of bitsets, and the words fitting them all their
+    /// intersection.
+    pub(crate) fn supported(&self, allowed: &[Vec<char>]) -> Vec<Vec<char>> {
+        let Some(index) = self.lengths.get(&allowed.len()) else {
+            return vec![vec![]; allowed.len()];
+        };
+        let chunks = index.words.len().div_ceil(64);
+        let mut fits = vec![u64::MAX; chunks];
+        for (pos, letters) in allowed.iter().enumerate() {
+            // A position allowing every letter its words have rules nothing
+            // out
+            let present = letters
+                .iter()
+                .filter(|&&c| index.letters.contains_key(&(pos, c)))
+                .count();
+            if present == index.letter_counts[pos] {
+                continue;
+            }
+            let mut any = vec![0u64; chunks];
+            for c in letters.iter() {
+                if let Some(bits) = index.letters.get(&(pos, *c)) {
+                    any.iter_mut().zip(bits).for_each(|(a, b)| *a |= b);
+                }
+            }
+            fits.iter_mut().zip(any).for_each(|(f, a)| *f &= a);
+        }
+        allowed
+            .iter()
+            .enumerate()
+            .map(|(pos, letters)| {
+                letters
+                    .iter()
+                    .copied()
+                    .filter(|c| {
+                        index
+                            .letters
+                            .get(&(pos, *c))
+                            .is_some_and(|bits| bits.iter().zip(&fits).any(|(b, f)| b & f != 0))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Append the index to an index cache.
+    pub(crate) fn encode(&self, out: &mut Writer) {
+        out.usize(self.lengths.len());
+        for (&len, index) in self.lengths.iter() {
+            out.usize(len);
+            out.usize(index.words.len());
+            for word in index.words.iter() {
+                out.str(word);
+            }
+            out.usize(index.letters.len());
+            for (&(pos, c), bits) in index.letters.iter() {
+                out.usize(pos);
+                out.char(c);
+                for chunk in bits.iter() {
+                    out.u64(*chunk);
+                }
+            }
+        }
+    }
+
+    /// Read an index written by `encode`.
+    pub(crate) fn decode(input: &mut Reader) -> Result<PositionIndex, String> {
+        let mut lengths = HashMap::new();
+        for _ in 0..input.count()? {
+            let len = input.usize()?;
+            let words = (0..input.count()?)
+                .map(|_| input.string())
+                .collect::<Result<Vec<String>, String>>()?;
+            if words.iter().any(|w| w.chars().count() != len) {
+                return Err("index cache has a word of the wrong length".to_string());
+            }
+            let chunks = words.len().div_ceil(64);
+            let mut letters = HashMap::new();
+            for _ in 0..input.count()? {
+                let key = (input.usize()?, input.char()?);
+                let bits = (0..chunks)
+                    .map(|_| input.u64())
+                    .collect::<Result<Vec<u64>, String>>()?;
+                letters.insert(key, bits);
+            }
+            if letters.keys().any(|&(pos, _)| pos >= len) {
+                return Err("index cache has a letter past the end of a word".to_string());
+            }
+            lengths.insert(len, Arc::new(LengthIndex::with_letters(words, letters)));
+        }
+        Ok(PositionIndex { lengths })
+    }
+}
@@ -0,0 +1,76 @@
+//! This crate generates word magic squares, which are NxM matrices of letters
+//! arranged such that every row and every column is a valid dictionary word.
+//!
+//! The user can pass in a custom dictionary file, or the default OS dict will
+//! be used. For the common case, [`generate`] wraps the whole object graph:
+//!
+//! ```no_run
+//! use magic_square::prelude::*;
+//!
+//! let square = generate(4, 4).with_dict("wordlist.txt").solve()?;
+//! square.print();
+//! # Ok::<(), String>(())
+//! ```
+
+#[macro_use]
+pub mod logging;
+
+pub mod alternates;
+mod anneal;
+mod beam;
+pub mod check;
+mod checkpoint;
+mod codec;
+pub mod count;
+mod cover;
+mod dawg;
+pub mod dictionary;
+mod generate;
+mod genetic;
+mod gzip;
+mod hunspell;
+mod index;
+mod letters;
+pub mod mapped;
+mod nogood;
+pub mod normalize;
+mod optimize;
+pub mod overrides;
+mod parallel;
+pub mod phonetic;
+pub mod prelude;
+mod progress;
+mod propagate;
+pub mod puzzle;
+mod regex;
+pub mod render;
+pub mod report;
+mod restart;
+mod rng;
+mod rows;
+#[cfg(feature = "sat")]
+mod sat;
+pub mod shrink;
+pub mod slot;
+mod solutions;
+mod solve_stats;
+mod split;
+pub mod square;
+pub mod stats;
+mod tally;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod theme;
+mod trie;
+pub mod unique;
+mod words;
+
+pub use generate::{generate, Generator};
+pub use nogood::NogoodStats;
+pub use optimize::SoftRule;
+pub use slot::{Direction, Slot};
+pub use solutions::Solutions;
+pub use solve_stats::SolveStats;
+pub use square::{
+    CellOrder, Diagonals, LetterOrder, LiveView, MagicSquare, Overlap, Strategy,
+};
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// How much diagnostic output to write to stderr, from least to most.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Off,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Off => "OFF",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    /// Parse a `RUST_LOG`-style level name. Levels more severe than `info`
+    /// are treated as `off`, since nothing is logged above `info`.
+    fn parse(name: &str) -> Option<Level> {
+        match name.trim().to_lowercase().as_str() {
+            "off" | "error" | "warn" => Some(Level::Off),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Off as u8);
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Set the log level from the number of `--verbose` flags, falling back to
+/// the `RUST_LOG` environment variable when no flag was given.
+///
+/// # Arguments
+///
+/// * `verbosity` - How many times `--verbose` (or `-v`) was passed.
+pub fn init(verbosity: u8) {
+    START.get_or_init(Instant::now);
+    let level = match verbosity {
+        0 => std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|v| Level::parse(&v))
+            .unwrap_or(Level::Off),
+        1 => Level::Info,
+        2 => Level::Debug,
+        _ => Level::Trace,
+    };
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// The name of the level set by `init`, such as `"debug"`.
+pub fn level_name() -> &'static str {
+    match LEVEL.load(Ordering::Relaxed) {
+        l if l == Level::Trace as u8 => "trace",
+        l if l == Level::Debug as u8 => "debug",
+        l if l == Level::Info as u8 => "info",
+        _ => "off",
+    }
+}
+
+/// Whether events at `level` are currently written.
+pub(crate) fn enabled(level: Level) -> bool {
+    level != Level::Off && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Write an event to stderr, stamped with the time since startup.
+/// Use the `info!`, `debug!` and `trace!` macros rather than calling this.
+pub(crate) fn log(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    eprintln!(
+        "[{:>9.3}s {:<5}] {}",
+        elapsed.as_secs_f64(),
+        level.name(),
+        args
+    );
+}
+
+/// A named region of work. Logs when it is entered and, on drop, how long
+/// it took.
+pub(crate) struct Span {
+    name: &'static str,
+    level: Level,
+    start: Instant,
+}
+
+impl Span {
+    pub(crate) fn enter(level: Level, name: &'static str) -> Span {
+        log(level, format_args!("{} started", name));
+        Span {
+            name,
+            level,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        log(
+            self.level,
+            format_args!("{} finished in {:.3?}", self.name, self.start.elapsed()),
+        );
+    }
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Trace, format_args!($($arg)*))
+    };
+}
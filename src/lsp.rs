@@ -0,0 +1,413 @@
+//! The `--lsp` mode: a small language server for grid files, speaking the
+//! Language Server Protocol over stdin and stdout so any LSP-capable editor
+//! can show problems while a square is being built by hand.
+//!
+//! A grid document is a bare grid: one row of letters and `_` blanks per
+//! line, with blank lines ignored. On every open or change the server
+//! publishes diagnostics for
+//!
+//! * characters that are not letters or `_`, and rows of the wrong length,
+//! * complete rows and columns that are not words,
+//! * rows and columns with blanks that no word fits, and
+//! * blank cells that no letter fits,
+//!
+//! and code actions on a blank cell offer each letter that still fits it.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use magic_square::check;
+use magic_square::dictionary::Dictionary;
+use magic_square::{MagicSquare, Slot};
+
+use crate::cli::Args;
+use crate::json::Json;
+
+/// LSP diagnostic severities.
+const ERROR: f64 = 1.0;
+const WARNING: f64 = 2.0;
+
+/// One row of a grid document.
+struct Row {
+    /// The line the row is on.
+    line: usize,
+    /// The offset of the row's first cell in the line, in UTF-16 code units
+    /// (as LSP positions count).
+    start: usize,
+    cells: Vec<char>,
+}
+
+impl Row {
+    /// The UTF-16 range `(start, end)` of the cell in column `col`.
+    fn cell_range(&self, col: usize) -> (usize, usize) {
+        let start = self.start
+            + self.cells[..col]
+                .iter()
+                .map(|c| c.len_utf16())
+                .sum::<usize>();
+        (start, start + self.cells[col].len_utf16())
+    }
+
+    fn end(&self) -> usize {
+        self.cell_range(self.cells.len() - 1).1
+    }
+}
+
+/// Split a grid document into its rows.
+fn parse_rows(text: &str) -> Vec<Row> {
+    text.split('\n')
+        .enumerate()
+        .filter_map(|(line, content)| {
+            let trimmed = content.trim_start();
+            let cells: Vec<char> = trimmed.trim_end().chars().collect();
+            let leading = &content[..content.len() - trimmed.len()];
+            (!cells.is_empty()).then(|| Row {
+                line,
+                start: leading.encode_utf16().count(),
+                cells,
+            })
+        })
+        .collect()
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json {
+    let position = |character: usize| {
+        Json::object([
+            ("line", Json::Number(line as f64)),
+            ("character", Json::Number(character as f64)),
+        ])
+    };
+    Json::object([("start", position(start)), ("end", position(end))])
+}
+
+fn diagnostic(range: Json, severity: f64, message: String) -> Json {
+    Json::object([
+        ("range", range),
+        ("severity", Json::Number(severity)),
+        ("source", Json::String("magicsquare".to_string())),
+        ("message", Json::String(message)),
+    ])
+}
+
+/// Diagnostics for rows that cannot be read as part of a grid. If there
+/// are any, the grid is not checked further.
+fn syntax_diagnostics(rows: &[Row]) -> Vec<Json> {
+    let mut diagnostics = vec![];
+    let width = rows.first().map_or(0, |row| row.cells.len());
+    for row in rows.iter() {
+        for (col, &c) in row.cells.iter().enumerate() {
+            if c != '_' && !c.is_alphabetic() {
+                let (start, end) = row.cell_range(col);
+                diagnostics.push(diagnostic(
+                    range(row.line, start, end),
+                    ERROR,
+                    format!("'{}' is not a letter or _", c),
+                ));
+            }
+        }
+        if row.cells.len() != width {
+            diagnostics.push(diagnostic(
+                range(row.line, row.start, row.end()),
+                ERROR,
+                format!(
+                    "row has {} cells, but the first row has {}",
+                    row.cells.len(),
+                    width
+                ),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Diagnostics for a well-formed grid: slots that are not words or that no
+/// word fits, and cells that no letter fits.
+fn grid_diagnostics(rows: &[Row], square: &MagicSquare) -> Vec<Json> {
+    let feasibility = check::check(square);
+    let mut diagnostics = vec![];
+    for report in feasibility.slots.iter().filter(|s| s.candidates == 0) {
+        let message = if report.template.contains('_') {
+            format!("no word fits {} ({})", report.slot, report.template)
+        } else {
+            format!("{} is not a word ({})", report.template, report.slot)
+        };
+        match report.slot {
+            Slot::Row(row) => diagnostics.push(diagnostic(
+                range(rows[row].line, rows[row].start, rows[row].end()),
+                ERROR,
+                message,
+            )),
+            // A range cannot span a column, so mark each of its cells
+            Slot::Col(col) => {
+                for row in rows.iter() {
+                    let (start, end) = row.cell_range(col);
+                    diagnostics.push(diagnostic(
+                        range(row.line, start, end),
+                        ERROR,
+                        message.clone(),
+                    ));
+                }
+            }
+        }
+    }
+    for &(row, col) in feasibility.dead_cells.iter() {
+        let (start, end) = rows[row].cell_range(col);
+        diagnostics.push(diagnostic(
+            range(rows[row].line, start, end),
+            WARNING,
+            format!("no letter fits both row {} and column {}", row, col),
+        ));
+    }
+    diagnostics
+}
+
+/// The square a document describes, or `None` if it is not a grid.
+fn document_square(rows: &[Row], args: &Args, dict: &Dictionary) -> Option<MagicSquare> {
+    if rows.is_empty() || !syntax_diagnostics(rows).is_empty() {
+        return None;
+    }
+    let rows: Vec<String> = rows.iter().map(|row| row.cells.iter().collect()).collect();
+    let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+    Some(crate::stdio::grid_square(&rows, args, dict))
+}
+
+fn diagnostics(text: &str, args: &Args, dict: &Dictionary) -> Vec<Json> {
+    let rows = parse_rows(text);
+    match document_square(&rows, args, dict) {
+        Some(square) => grid_diagnostics(&rows, &square),
+        None => syntax_diagnostics(&rows),
+    }
+}
+
+/// A position's `(line, character)`, or `None` if it is malformed.
+fn position(value: Option<&Json>) -> Option<(usize, usize)> {
+    let value = value?;
+    Some((
+        value.get("line")?.as_usize()?,
+        value.get("character")?.as_usize()?,
+    ))
+}
+
+/// Code actions filling each blank cell in `params.range` with each letter
+/// that fits it.
+fn code_actions(uri: &str, text: &str, params: &Json, args: &Args, dict: &Dictionary) -> Json {
+    let rows = parse_rows(text);
+    let Some(square) = document_square(&rows, args, dict) else {
+        return Json::Array(vec![]);
+    };
+    let selection = params.get("range");
+    let (Some(from), Some(to)) = (
+        position(selection.and_then(|r| r.get("start"))),
+        position(selection.and_then(|r| r.get("end"))),
+    ) else {
+        return Json::Array(vec![]);
+    };
+
+    let mut actions = vec![];
+    for ((row, col), letters) in check::hints(&square) {
+        let line = rows[row].line;
+        let (start, end) = rows[row].cell_range(col);
+        // A cursor selects the cell it is in; a selection, every cell it
+        // overlaps
+        let selected = if from == to {
+            from.0 == line && start <= from.1 && from.1 < end
+        } else {
+            (line, end) > from && (line, start) < to
+        };
+        if !selected {
+            continue;
+        }
+        for letter in letters {
+            let edit = Json::object([
+                ("range", range(line, start, end)),
+                ("newText", Json::String(letter.to_string())),
+            ]);
+            actions.push(Json::object([
+                (
+                    "title",
+                    Json::String(format!("Fill ({}, {}) with '{}'", row, col, letter)),
+                ),
+                ("kind", Json::String("quickfix".to_string())),
+                (
+                    "edit",
+                    Json::object([(
+                        "changes",
+                        Json::Object(vec![(uri.to_string(), Json::Array(vec![edit]))]),
+                    )]),
+                ),
+            ]));
+        }
+    }
+    Json::Array(actions)
+}
+
+/// Read one message: `Content-Length` and other headers, a blank line, then
+/// the body. Returns `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Option<Result<Json, String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Some(Err("message has no Content-Length header".to_string()));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).ok()?;
+    Some(
+        String::from_utf8(body)
+            .map_err(|e| e.to_string())
+            .and_then(|body| Json::parse(&body)),
+    )
+}
+
+fn write_message(out: &mut impl Write, message: &Json) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object([
+        ("jsonrpc", Json::String("2.0".to_string())),
+        ("id", id),
+        ("result", result),
+    ])
+}
+
+fn error_response(id: Json, code: f64, message: String) -> Json {
+    Json::object([
+        ("jsonrpc", Json::String("2.0".to_string())),
+        ("id", id),
+        (
+            "error",
+            Json::object([
+                ("code", Json::Number(code)),
+                ("message", Json::String(message)),
+            ]),
+        ),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object([
+        ("jsonrpc", Json::String("2.0".to_string())),
+        (
+            "method",
+            Json::String("textDocument/publishDiagnostics".to_string()),
+        ),
+        (
+            "params",
+            Json::object([
+                ("uri", Json::String(uri.to_string())),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        ),
+    ])
+}
+
+/// `--lsp [dictionary]` runs the language server until the editor sends
+/// `exit` or closes stdin.
+pub(crate) fn run(args: &Args) {
+    let (dict, _) = crate::load_dictionary(args, &args.positional);
+    let mut input = std::io::stdin().lock();
+    let mut out = std::io::stdout();
+    // The text of each open document, by URI
+    let mut documents: HashMap<String, String> = HashMap::new();
+
+    while let Some(message) = read_message(&mut input) {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("warning: skipping bad message: {}", e);
+                continue;
+            }
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let document = params.get("textDocument");
+        let uri = document
+            .and_then(|d| d.get("uri"))
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+
+        // Notifications (no id) get no reply; documents that changed get
+        // fresh diagnostics
+        let reply = match (message.get("id").cloned(), method) {
+            (_, "exit") => break,
+            (None, "textDocument/didOpen") => {
+                let text = document.and_then(|d| d.get("text")).and_then(Json::as_str);
+                documents.insert(uri.clone(), text.unwrap_or("").to_string());
+                Some(publish(&uri, diagnostics(&documents[&uri], args, &dict)))
+            }
+            (None, "textDocument/didChange") => {
+                // Full sync: the last change holds the whole new text
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                if let Some(text) = text {
+                    documents.insert(uri.clone(), text.to_string());
+                }
+                let text = documents.get(&uri).map_or("", String::as_str);
+                Some(publish(&uri, diagnostics(text, args, &dict)))
+            }
+            (None, "textDocument/didClose") => {
+                documents.remove(&uri);
+                Some(publish(&uri, vec![]))
+            }
+            (None, _) => None,
+            (Some(id), "initialize") => Some(response(
+                id,
+                Json::object([
+                    (
+                        "capabilities",
+                        Json::object([
+                            ("textDocumentSync", Json::Number(1.0)),
+                            ("codeActionProvider", Json::Bool(true)),
+                        ]),
+                    ),
+                    (
+                        "serverInfo",
+                        Json::object([
+                            ("name", Json::String("magicsquare".to_string())),
+                            (
+                                "version",
+                                Json::String(env!("CARGO_PKG_VERSION").to_string()),
+                            ),
+                        ]),
+                    ),
+                ]),
+            )),
+            (Some(id), "shutdown") => Some(response(id, Json::Null)),
+            (Some(id), "textDocument/codeAction") => {
+                let text = documents.get(&uri).map_or("", String::as_str);
+                Some(response(id, code_actions(&uri, text, &params, args, &dict)))
+            }
+            (Some(id), _) => Some(error_response(
+                id,
+                -32601.0,
+                format!("unsupported method {}", method),
+            )),
+        };
+        if let Some(reply) = reply {
+            if write_message(&mut out, &reply).is_err() {
+                break;
+            }
+        }
+    }
+}
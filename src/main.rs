@@ -1,330 +1,1006 @@
-mod dictionary;
-
+mod batch;
+mod cli;
+mod config;
+mod display;
+mod json;
+mod lsp;
+mod stdio;
+mod wizard;
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
-use dictionary::Dictionary;
+use cli::{Args, Format};
+use magic_square::alternates;
+use magic_square::check;
+use magic_square::count;
+use magic_square::dictionary::Dictionary;
+use magic_square::logging;
+use magic_square::normalize::fold_diacritics;
+use magic_square::overrides::Overrides;
+use magic_square::puzzle::Puzzle;
+use magic_square::render;
+use magic_square::report;
+use magic_square::shrink::{self, Behavior, Shrunk};
+use magic_square::stats;
+use magic_square::unique::{self, Uniqueness};
+use magic_square::{Diagonals, Direction, LiveView, MagicSquare, Overlap, Slot, Strategy};
 
-const ATTEMPT_RENDER_FREQ: usize = 5;
+fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+    logging::init(args.verbose);
 
-///*
-/// This tool generates word magic squares, which are NxM matrices of letters
-/// arranged such that every row and every column is a valid dictionary word.
-///
-/// The user can pass in a custom dictionary file, or the default OS dict will
-/// be used.
-/// */
+    if args.stdio {
+        stdio::run(&args);
+        return;
+    }
+    if args.lsp {
+        lsp::run(&args);
+        return;
+    }
+    match args.positional.first().map(String::as_str) {
+        Some("analyze") => run_analyze(&args),
+        Some("batch") => run_batch(&args),
+        Some("check") => run_check(&args),
+        Some("config") => run_config(&args),
+        Some("count") => run_count(&args),
+        Some("dict") => run_dict(&args),
+        Some("display") => display::run(&args),
+        Some("search") => run_search(&args),
+        Some("shrink") => run_shrink(&args),
+        Some("unique") => run_unique(&args),
+        Some("wizard") => wizard::run(&args),
+        Some("words") => run_words(&args),
+        _ => run_solve(&args),
+    }
+}
 
-/// Check if a word is a valid dictionary word.
-///
-/// # Arguments
-///
-/// * `word` - The word to check.
-/// * `dict` - The dictionary to check against.
-///
-/// # Returns
-///
-/// * `true` if the word is valid, `false` otherwise.
-fn is_valid_word(word: &str, dict: &Dictionary) -> bool {
-    // All letters are alphanumeric, longer than 2 chars, and in the dictionary
-    word.len() > 2 && word.chars().all(|c| c.is_alphanumeric()) && dict.contains(word)
+/// `batch <jobs.toml>` runs every job in a spec file against one dictionary.
+fn run_batch(args: &Args) {
+    let Some(spec) = args.positional.get(1) else {
+        println!("Usage: magicsquare batch <jobs.toml>");
+        exit(1);
+    };
+    if let Err(e) = batch::run(spec, args) {
+        println!("Batch failed: {}", e);
+        exit(1);
+    }
 }
 
-/// A magic square is a NxM matrix of letters arranged such that every row and
-/// every column is a valid dictionary word.
-/// This struct represents a magic square.
-/// It is a wrapper around a 2D vector of chars.
-/// The `fill` method will fill the square with letters.
-/// The `print` method will print the square to stdout.
-/// The `empty` method will create an empty square.
-struct MagicSquare {
-    square: Vec<Vec<char>>,
-    editable_mask: Vec<Vec<bool>>,
-    dict: Dictionary,
-    _attempt: usize,
+/// `config show [dictionary] [template] [rows]` prints the settings a solve
+/// with the same arguments would use, and where each came from.
+fn run_config(args: &Args) {
+    if args.positional.get(1).map(String::as_str) != Some("show") {
+        println!("Usage: magicsquare config show [dictionary] [template] [rows]");
+        exit(1);
+    }
+    config::show(args, &args.positional[2..]);
 }
 
-impl MagicSquare {
-    /// Create an empty magic square.
-    ///
-    /// # Arguments
-    ///
-    /// * `rows` - The number of rows in the square.
-    /// * `cols` - The number of columns in the square.
-    /// * `dict` - The dictionary to use.
-    ///
-    /// # Returns
-    ///
-    /// * A new empty magic square.
-    fn empty(rows: usize, cols: usize, dict: &Dictionary) -> MagicSquare {
-        MagicSquare {
-            square: vec![vec!['_'; cols]; rows],
-            editable_mask: vec![vec![true; cols]; rows],
-            dict: dict.clone(),
-            _attempt: 0,
-        }
-    }
-
-    fn set(&mut self, row: usize, col: usize, c: char) {
-        self.square[row][col] = c;
-    }
-    fn set_and_harden(&mut self, row: usize, col: usize, c: char) {
-        self.square[row][col] = c;
-        if c != '_' {
-            self.editable_mask[row][col] = false;
-        }
-    }
-
-    fn get(&self, row: usize, col: usize) -> char {
-        self.square[row][col]
-    }
-
-    /// Fill the square with letters.
-    ///
-    /// # Returns
-    ///
-    /// * `Ok(())` if the square was filled successfully.
-    /// * `Err(String)` if the square could not be filled.
-    fn fill(&mut self) -> Result<(), String> {
-        // Starting at the top left, fill the square with letters such that
-        // every row and column is a valid dictionary word. This is done by
-        // recursively filling the square with letters, and backtracking if
-        // any of the crosswords become a template with no valid matches.
-
-        // Get the first un-filled square
-        let (row, col) = self.find_first_empty_square().unwrap();
-
-        // Fill the square with letters
-        self.fill_helper(row, col)
-    }
-
-    fn find_first_empty_square(&self) -> Option<(usize, usize)> {
-        for (row, row_vec) in self.square.iter().enumerate() {
-            for (col, c) in row_vec.iter().enumerate() {
-                if *c == '_' && self.editable_mask[row][col] {
-                    return Some((row, col));
-                }
-            }
-        }
+/// How many letters `dict stats` lists for each position.
+const STATS_LETTERS: usize = 8;
 
-        None
+/// `dict stats [dictionary]` prints how many words there are of each
+/// length, the most common letters at each position, and the largest square
+/// the words can plausibly fill, to judge a wordlist before a long search.
+fn run_dict(args: &Args) {
+    if args.positional.get(1).map(String::as_str) != Some("stats") {
+        println!("Usage: magicsquare dict stats [dictionary]");
+        exit(1);
     }
+    let (dict, _) = load_dictionary(args, &args.positional[2..]);
+    let stats = stats::stats(&dict);
 
-    /// Helper function for `fill`.
-    /// Recursively fill the square with letters.
-    /// If any of the crosswords become a template with no valid matches,
-    /// backtrack and try a different letter.
-    /// If all letters have been tried and none of them work, return an error.
-    /// If the square is filled successfully, return `Ok(())`.
-    /// This function is recursive.
-    fn fill_helper(&mut self, row: usize, col: usize) -> Result<(), String> {
-        // If we've reached the end of the square, we're done
-        if row == self.square.len() {
-            return Ok(());
-        }
+    println!("{} words", dict.len());
+    if !dict.hygiene().is_clean() {
+        println!("Unusable entries in the wordlist: {}", dict.hygiene());
+    }
+    println!();
+    println!("{:>6} {:>8} {:>16}", "length", "words", "squares (log10)");
+    for &(len, count) in stats.lengths.iter() {
+        let expected = stats
+            .expected_squares
+            .iter()
+            .find(|(size, _)| *size == len)
+            .map_or("-".to_string(), |(_, log10)| format!("{:.1}", log10));
+        println!("{:>6} {:>8} {:>16}", len, count, expected);
+    }
+    println!();
+    println!("Most common letters by position:");
+    for (pos, counts) in stats.letters_by_position.iter().enumerate() {
+        let total: usize = counts.iter().map(|(_, n)| n).sum();
+        let letters: Vec<String> = counts
+            .iter()
+            .take(STATS_LETTERS)
+            .map(|(c, n)| format!("{} {:4.1}%", c, 100.0 * *n as f64 / total as f64))
+            .collect();
+        println!("{:>3}  {}", pos + 1, letters.join("  "));
+    }
+    println!();
+    match stats.largest_square() {
+        Some(size) => println!("Largest square that plausibly fills: {}x{}", size, size),
+        None => println!("No square size plausibly fills."),
+    }
+}
 
-        // If we've reached the end of the row, move to the next row
-        if col == self.square[row].len() {
-            return self.fill_helper(row + 1, 0);
+/// `words <add | ban | forget> <word>...` edits the personal overrides
+/// applied on top of every dictionary, and `words list` prints them.
+fn run_words(args: &Args) {
+    let usage = "Usage: magicsquare words <add | ban | forget> <word>... | words list";
+    let path = match Overrides::default_path() {
+        Ok(path) => path,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
         }
-
-        // If this is a masked cell, move on to the next one:
-        if !self.editable_mask[row][col] {
-            // let (nrow, ncol) = self.find_first_empty_square().unwrap();
-            // return self.fill_helper(nrow, ncol);
-            return Ok(());
+    };
+    let mut overrides = match Overrides::load(&path) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
         }
+    };
 
-        // Try every letter in the alphabet.
-        // TODO: Randomized order??
-        for c in 'a'..='z' {
-            self._attempt += 1;
-            // If the letter is valid, set it and try to fill the rest of the square
-            if self.is_valid_letter(row, col, c) {
-                // Only draw every Nth attempt
-                if self._attempt % ATTEMPT_RENDER_FREQ == 0 {
-                    self.clear_and_print();
-                }
-                self.set(row, col, c);
-                if self.find_first_empty_square().is_none() {
-                    return Ok(());
-                }
-                let (nrow, ncol) = self.find_first_empty_square().unwrap();
-                if let Ok(()) = self.fill_helper(nrow, ncol) {
-                    return Ok(());
+    let words = args.positional.get(2..).unwrap_or(&[]);
+    match args.positional.get(1).map(String::as_str) {
+        Some("list") => {
+            for (word, added) in overrides.edits() {
+                println!("{}{}", if added { '+' } else { '-' }, word);
+            }
+            return;
+        }
+        Some("add") if !words.is_empty() => words.iter().for_each(|w| overrides.add(w)),
+        Some("ban") if !words.is_empty() => words.iter().for_each(|w| overrides.ban(w)),
+        Some("forget") if !words.is_empty() => {
+            for word in words {
+                if !overrides.forget(word) {
+                    println!("{} had no override", word);
                 }
-                // if let Ok(()) = self.fill_helper(row, col + 1) {
-                //     return Ok(());
-                // }
             }
         }
+        _ => {
+            println!("{}", usage);
+            exit(1);
+        }
+    }
+    if let Err(e) = overrides.save(&path) {
+        println!("Could not save overrides: {}", e);
+        exit(1);
+    }
+    println!("Saved to {}", path.display());
+}
 
-        // If we've tried every letter and none of them work, backtrack
-        self.set(row, col, '_');
-        Err(format!("Could not fill square at ({}, {})", row, col))
+/// Load the dictionary named by `--dict`, or for the `--lang` language, or
+/// named by the first positional argument (a file or URL), or the OS
+/// dictionary if none of these is given. Blocked words are removed and
+/// overlays applied (see `filter_dictionary`), the `--freq` frequency list is
+/// attached, and the lookups are compacted with `--compact`. Exits with the
+/// error if any of these files cannot be read.
+///
+/// # Returns
+///
+/// * The dictionary, and the positional arguments that follow it.
+fn load_dictionary<'a>(args: &Args, positional: &'a [String]) -> (Dictionary, &'a [String]) {
+    let (dict, rest) = if let Some(path) = &args.dict {
+        // With --dict, there is no dictionary argument
+        (load_location(args, path), positional)
+    } else if let Some(lang) = &args.lang {
+        // With a language, there is no dictionary argument
+        (Dictionary::for_locale(lang), positional)
+    } else if let Some((filename, rest)) = positional.split_first() {
+        // If called with a file name or URL, use that as the dictionary
+        (load_location(args, filename), rest)
+    } else {
+        // Otherwise, use the default OS dictionary, falling back to the
+        // built-in wordlist or a download if there is none
+        (Dictionary::from_os_dict().or_else(|_| fallback_dictionary()), positional)
+    };
+    let dict = dict.map(|dict| clean_dictionary(dict, args));
+    let dict = if args.fold_accents {
+        dict.map(Dictionary::fold_diacritics)
+    } else {
+        dict
+    };
+    let dict = dict.and_then(|dict| filter_dictionary(dict, args));
+    let dict = match &args.freq {
+        Some(path) => dict.and_then(|dict| dict.with_frequencies(path)),
+        None => dict,
+    };
+    let dict = if args.compact {
+        dict.map(Dictionary::compact)
+    } else {
+        dict
+    };
+    match dict {
+        Ok(dict) => (dict, rest),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
     }
+}
 
-    /// Check if a letter is valid at a given position in the square.
-    /// A letter is valid if its crosswords are valid words or valid templates.
-    fn is_valid_letter(&self, row: usize, col: usize, c: char) -> bool {
-        // Check if the letter is valid in the row
-        let ww = self.get_row(row);
-        // Set the col'th letter to c
-        let ww = ww
-            .iter()
-            .enumerate()
-            .map(|(i, &x)| if i == col { c } else { x })
-            .collect::<Vec<char>>();
-        if !self.is_valid_word_or_template(&ww) {
-            return false;
+/// Drop the words no grid can hold with `--clean`, or warn that they were
+/// kept. Blank lines and duplicates were already skipped on load.
+fn clean_dictionary(dict: Dictionary, args: &Args) -> Dictionary {
+    if args.clean {
+        return dict.without_unusable();
+    }
+    let unusable = dict.hygiene().non_alphabetic;
+    if unusable > 0 {
+        eprintln!(
+            "warning: the dictionary has {} words with non-letters, which no grid can hold \
+             (pass --clean to drop them)",
+            unusable
+        );
+    }
+    dict
+}
+
+/// Load a dictionary file, URL or SCOWL directory (see `Dictionary::load`),
+/// reading a SCOWL directory at the `--vocab` size if one is given.
+fn load_location(args: &Args, location: &str) -> Result<Dictionary, String> {
+    match args.vocab {
+        Some(size) => Dictionary::from_scowl(location, size),
+        None => Dictionary::load(location),
+    }
+}
+
+/// Remove unwanted words from a freshly loaded dictionary: proper nouns as
+/// `--proper-nouns` asks, the built-in offensive-word list (unless
+/// `--allow-offensive`), the personal blocklist in the config directory,
+/// and any `--blocklist` files. Then apply each `--overlay` file in order,
+/// the personal overrides (see `words`), and finally add the `--add-word`
+/// words.
+fn filter_dictionary(dict: Dictionary, args: &Args) -> Result<Dictionary, String> {
+    let dict = dict.with_proper_nouns(args.proper_nouns);
+    let mut dict = if args.allow_offensive {
+        dict
+    } else {
+        dict.filter_offensive()
+    };
+    if let Some(path) = Dictionary::user_blocklist_path() {
+        dict = dict.without_words_in_file(path)?;
+    }
+    for path in args.blocklists.iter() {
+        dict = dict.without_words_in_file(path)?;
+    }
+    for path in args.overlays.iter() {
+        dict = Overrides::read(Path::new(path))?.apply(dict);
+    }
+    // Personal edits come last, so adding a word overrides any blocklist
+    // or overlay
+    let overrides = Overrides::personal()?;
+    let dict = overrides.apply(dict);
+    // Words given for this run beat everything else
+    let added: Vec<String> = args
+        .added_words
+        .iter()
+        .map(|word| {
+            if args.fold_accents {
+                fold_diacritics(word)
+            } else {
+                word.clone()
+            }
+        })
+        .collect();
+    Ok(dict.with_words(added.iter().map(String::as_str)))
+}
+
+/// The dictionary to use when the OS has none: the built-in wordlist if it
+/// was compiled in, and otherwise a download.
+#[cfg(feature = "embedded-dict")]
+fn fallback_dictionary() -> Result<Dictionary, String> {
+    Ok(Dictionary::embedded())
+}
+
+#[cfg(not(feature = "embedded-dict"))]
+fn fallback_dictionary() -> Result<Dictionary, String> {
+    use magic_square::dictionary::DEFAULT_WORDLIST_URL;
+
+    eprintln!("No OS dictionary found; using {}", DEFAULT_WORDLIST_URL);
+    Dictionary::from_url(DEFAULT_WORDLIST_URL)
+}
+
+/// Build the square described by the positional arguments
+/// `[dictionary] [template] [rows]`.
+fn load_square(args: &Args, positional: &[String]) -> MagicSquare {
+    let (dict, positional) = load_dictionary(args, positional);
+
+    // If called with a string word, use that as the first word (comes before
+    // the dict path). A lone "-" reads a puzzle file (or a bare grid, one row
+    // per line) from stdin instead.
+    let from_stdin = positional.first().map(String::as_str) == Some("-");
+    let fixed_chars = if from_stdin {
+        let mut text = String::new();
+        let puzzle = std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| e.to_string())
+            .and_then(|_| Puzzle::parse(&text));
+        match puzzle {
+            Ok(puzzle) => {
+                if puzzle.dictionary_mismatch(&dict) {
+                    eprintln!("warning: this puzzle was saved with a different dictionary");
+                }
+                puzzle.template()
+            }
+            Err(e) => {
+                println!("Could not read grid from stdin: {}", e);
+                exit(1);
+            }
         }
+    } else if let Some(word) = positional.first() {
+        word.clone()
+    } else {
+        "_____".to_string()
+    };
 
-        // Check if the letter is valid in the column
-        let ww = self.get_col(col);
-        // Set the row'th letter to c
-        let www = ww
-            .iter()
-            .enumerate()
-            .map(|(i, &x)| if i == row { c } else { x })
-            .collect::<Vec<char>>();
-        if !self.is_valid_word_or_template(&www) {
-            return false;
+    // If called with an integer as 3rd argument, use that as the number of
+    // rows in the puzzle. A grid read from stdin defaults to its own height.
+    let row_count = if let Some(rows) = positional.get(1) {
+        match rows.trim().parse::<usize>() {
+            Ok(count) if count > 0 => count,
+            _ => {
+                println!("The rows argument needs a number of rows, not {}", rows);
+                exit(1);
+            }
         }
+    } else if from_stdin {
+        fixed_chars.split('/').count()
+    } else {
+        4
+    };
 
-        true
-    }
+    let fixed_chars = if args.fold_accents {
+        fold_diacritics(&fixed_chars)
+    } else {
+        fixed_chars
+    };
 
-    /// Get the row at a given index.
-    fn get_row(&self, row: usize) -> Vec<char> {
-        self.square[row].clone()
+    let mut square = MagicSquare::from_template(&fixed_chars, row_count, &dict);
+    if let Err(problem) = apply_solver_flags(&mut square, args, args.seed()) {
+        println!("{}", problem);
+        exit(1);
     }
+    square
+}
 
-    /// Get the column at a given index.
-    fn get_col(&self, col: usize) -> Vec<char> {
-        self.square.iter().map(|r| r[col]).collect()
+/// Set up `square` to search as the solver flags say, seeding it with
+/// `seed`.
+///
+/// # Returns
+///
+/// * `Ok(())` if the flags can be used together on this grid.
+/// * `Err(String)` naming the first that cannot.
+fn apply_solver_flags(
+    square: &mut MagicSquare,
+    args: &Args,
+    seed: Option<u64>,
+) -> Result<(), String> {
+    let row_count = square.grid().len();
+    let cols = square.grid()[0].len();
+    if args.overlap == Overlap::Symmetric {
+        let problem = if row_count != cols {
+            Some(format!("--symmetric needs a square grid, not {}x{}", row_count, cols))
+        } else if args.direction != Direction::Standard {
+            Some("--symmetric needs the standard --direction".to_string())
+        } else if !matches!(args.strategy, Strategy::Letters | Strategy::Rows) {
+            Some("--symmetric needs --strategy letters or rows".to_string())
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(problem);
+        }
+    }
+    if args.diagonals != Diagonals::Off {
+        let problem = if row_count != cols {
+            Some(format!("--diagonals needs a square grid, not {}x{}", row_count, cols))
+        } else if !matches!(args.strategy, Strategy::Letters | Strategy::Rows) {
+            Some("--diagonals needs --strategy letters or rows".to_string())
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(problem);
+        }
     }
+    let splits = matches!(
+        args.strategy,
+        Strategy::Letters | Strategy::Rows | Strategy::Words | Strategy::Beam
+    );
+    let blocked = square.grid().iter().flatten().any(|&c| c == '#');
+    if blocked && !splits {
+        return Err("Blocked cells (#) need --strategy letters, rows, words or beam".to_string());
+    }
+    if args.toroidal && (args.strategy != Strategy::Letters || args.forward_check) {
+        return Err("--toroidal needs --strategy letters, without --forward-check".to_string());
+    }
+    if args.checkpoint.is_some() || args.resume.is_some() {
+        let flag = if args.resume.is_some() { "--resume" } else { "--checkpoint" };
+        if args.strategy != Strategy::Letters || args.restarts.is_some() {
+            return Err(format!("{} needs --strategy letters, without --restarts", flag));
+        }
+    }
+    if args.optimize_for.is_some() {
+        let problem = if args.freq.is_none() {
+            Some("--optimize-for needs --freq to score squares")
+        } else if !matches!(args.strategy, Strategy::Letters | Strategy::Rows) {
+            Some("--optimize-for needs --strategy letters or rows")
+        } else if args.checkpoint.is_some() || args.resume.is_some() || !args.themes.is_empty() {
+            Some("--optimize-for cannot be used with --checkpoint, --resume or --theme")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(problem.to_string());
+        }
+    }
+    if !args.soft.is_empty() && args.optimize_for.is_none() {
+        return Err("--soft needs --optimize-for, which alone weighs soft rules".to_string());
+    }
+    if args.distinct || args.overlap == Overlap::Forbidden {
+        let flag = if args.distinct { "--distinct" } else { "--overlap forbid" };
+        let problem = if args.overlap == Overlap::Symmetric {
+            Some("--distinct cannot be used with --symmetric, which repeats every word".to_string())
+        } else if !matches!(args.strategy, Strategy::Letters | Strategy::Rows) {
+            Some(format!("{} needs --strategy letters or rows", flag))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(problem);
+        }
+    }
+    square.set_direction(args.direction);
+    square.set_mirror_rows(args.mirror_rows);
+    square.set_overlap(args.overlap);
+    square.set_distinct(args.distinct);
+    square.set_soft_rules(args.soft.clone());
+    square.set_diagonals(args.diagonals);
+    square.set_min_word_len(args.min_word_len);
+    square.set_toroidal(args.toroidal);
+    square.set_strategy(args.strategy);
+    square.set_beam_width(args.beam_width);
+    square.set_population(args.population);
+    square.set_mutation_rate(args.mutation_rate);
+    square.set_generations(args.generations);
+    square.set_cell_order(args.cell_order);
+    square.set_letter_order(args.letter_order);
+    square.set_propagate(args.propagate);
+    square.set_forward_check(args.forward_check);
+    square.set_nogoods(args.nogoods);
+    square.set_parallel(args.parallel);
+    square.set_threads(args.threads);
+    square.set_seed(seed);
+    square.set_deterministic(args.deterministic);
+    square.set_restarts(args.restarts);
+    square.set_attempt_limit(args.max_attempts);
+    square.set_time_limit(args.time_limit);
+    Ok(())
+}
+
+/// `check [dictionary] [template] [rows]` reports whether the puzzle is
+/// plausibly solvable without running the search. Exits non-zero if not.
+fn run_check(args: &Args) {
+    let square = load_square(args, &args.positional[1..]);
+    let feasibility = check::check(&square);
 
-    /// Check if a word or template is valid.
-    /// A word is valid if it is a valid dictionary word or has nonzero
-    /// template matches.
-    fn is_valid_word_or_template(&self, word: &Vec<char>) -> bool {
-        let word_as_str = word.iter().collect::<String>();
-        // Check if the word is a valid dictionary word
-        if self.dict.contains(word_as_str.as_str()) || self.dict.count_with_template(word_as_str.as_str()) > 0 {
-            return true;
+    if args.format == Format::Json {
+        println!("{}", report::check_json(&feasibility));
+    } else {
+        for slot in feasibility.slots.iter() {
+            println!(
+                "{:<10} {:<12} {} candidates",
+                slot.slot.to_string(),
+                slot.template,
+                slot.candidates
+            );
+        }
+        for (row, col) in feasibility.dead_cells.iter() {
+            println!("no letter fits cell ({}, {})", row, col);
+        }
+        if feasibility.is_plausible() {
+            println!("Plausibly solvable.");
+        } else {
+            println!("Not solvable.");
         }
+    }
 
-        false
+    if !feasibility.is_plausible() {
+        exit(1);
     }
+}
 
-    /// Print the square to stdout.
-    fn print(&self) {
-        for row in self.square.iter() {
-            for &c in row.iter() {
-                print!("{} ", c);
+/// `count [dictionary] [template] [rows]` prints how many solutions the
+/// puzzle has, or that it has at least `--stop-at` of them.
+fn run_count(args: &Args) {
+    let mut square = load_square(args, &args.positional[1..]);
+    square.set_live_view(if args.progress {
+        LiveView::Progress
+    } else {
+        LiveView::Off
+    });
+    let counted = count::count(&mut square, args.stop_at);
+    if args.format == Format::Json {
+        println!("{}", report::count_json(&counted));
+        return;
+    }
+    let noun = if counted.solutions == 1 {
+        "solution"
+    } else {
+        "solutions"
+    };
+    if counted.exact {
+        println!("{} {}", counted.solutions, noun);
+    } else {
+        println!("at least {} {}", counted.solutions, noun);
+    }
+}
+
+/// `unique [dictionary] [template] [rows]` confirms the puzzle has exactly
+/// one solution, or prints the first two it found. Exits non-zero unless
+/// the solution is unique.
+fn run_unique(args: &Args) {
+    let mut square = load_square(args, &args.positional[1..]);
+    square.set_live_view(if args.progress {
+        LiveView::Progress
+    } else {
+        LiveView::Off
+    });
+    let uniqueness = match unique::verify_unique(&mut square) {
+        Ok(uniqueness) => uniqueness,
+        Err(e) => {
+            if args.format == Format::Json {
+                println!("{}", report::failure_json(&square));
+            } else {
+                println!("Could not tell: {}", e);
             }
-            println!("");
+            exit(1);
         }
-    }
+    };
 
-    // Print, clearing the screen first
-    fn clear_and_print(&self) {
-        print!("{}[2J", 27 as char);
-        self.print();
+    if args.format == Format::Json {
+        println!("{}", report::unique_json(&uniqueness));
+    } else {
+        let print_grid = |grid: &[Vec<char>]| {
+            for row in grid {
+                println!("{}", row.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "));
+            }
+        };
+        match &uniqueness {
+            Uniqueness::Unsolvable => println!("No solution."),
+            Uniqueness::Unique(grid) => {
+                println!("Unique solution:");
+                print_grid(grid);
+            }
+            Uniqueness::Ambiguous(first, second) => {
+                println!("More than one solution:");
+                print_grid(first);
+                println!();
+                print_grid(second);
+                let cells: Vec<String> = Uniqueness::differences(first, second)
+                    .iter()
+                    .map(|(row, col)| format!("({}, {})", row, col))
+                    .collect();
+                println!("They differ at {}.", cells.join(", "));
+            }
+        }
+    }
+    if !matches!(uniqueness, Uniqueness::Unique(_)) {
+        exit(1);
     }
 }
 
-fn main() {
-    // If called with a file name, use that file as the dictionary
-    let dict = if let Some(filename) = std::env::args().nth(1) {
-        Dictionary::from_file(filename.as_str()).unwrap()
+/// `analyze [dictionary] [template] [rows]` lists, for every blank cell,
+/// the letters some solution puts there, marking which cells are forced.
+fn run_analyze(args: &Args) {
+    let mut square = load_square(args, &args.positional[1..]);
+    square.set_live_view(if args.progress {
+        LiveView::Progress
     } else {
-        // Otherwise, use the default OS dictionary
-        Dictionary::from_os_dict().unwrap()
+        LiveView::Off
+    });
+    let cells = match alternates::alternates(&mut square) {
+        Ok(cells) => cells,
+        Err(_) => {
+            if args.format == Format::Json {
+                println!("{}", report::failure_json(&square));
+            } else {
+                println!("Could not fill square.");
+            }
+            exit(1);
+        }
     };
 
-    // If called with a string word, use that as the first word (comes before
-    // the dict path)
-    let fixed_chars = if let Some(word) = std::env::args().nth(2) {
-        word
+    if args.format == Format::Json {
+        println!("{}", report::alternates_json(&square, &cells));
+        return;
+    }
+    // The grid, with a `?` in every cell that has more than one letter
+    let mut grid = square.grid().to_vec();
+    for cell in cells.iter().filter(|cell| !cell.is_forced()) {
+        grid[cell.row][cell.col] = '?';
+    }
+    for row in grid.iter() {
+        println!("{}", row.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "));
+    }
+    let ambiguous: Vec<_> = cells.iter().filter(|cell| !cell.is_forced()).collect();
+    for cell in ambiguous.iter() {
+        let letters: String = cell.letters.iter().collect();
+        println!("({}, {}) could be any of {}", cell.row, cell.col, letters);
+    }
+    if ambiguous.is_empty() {
+        println!("Every cell is forced: the solution is unique.");
     } else {
-        "_____".to_string()
+        println!(
+            "{} of {} blank cells are ambiguous.",
+            ambiguous.len(),
+            cells.len()
+        );
+    }
+}
+
+/// `shrink [dictionary] [template] [rows]` minimizes a square that makes
+/// the solver misbehave (see `--behavior`), and saves a small reproducer
+/// to attach to a bug report in the `--save` directory.
+fn run_shrink(args: &Args) {
+    let square = load_square(args, &args.positional[1..]);
+    // Panics are expected while shrinking one, so keep them quiet
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let behavior = match args.behavior {
+        Some(behavior) => behavior,
+        None => match Behavior::observe(&square) {
+            Some(behavior) => behavior,
+            None => {
+                println!(
+                    "The square solves correctly, so there is nothing to shrink. \
+                     To shrink a slow search, pass --behavior slow:<attempts>."
+                );
+                exit(1);
+            }
+        },
     };
 
-    // If called with an integer as 3rd argument, use that as the number of
-    // rows in the puzzle:
-    let row_count = if let Some(rows) = std::env::args().nth(3) {
-        rows.parse::<usize>().unwrap()
-    } else {
-        4
+    println!("Shrinking ({})...", behavior);
+    let shrunk = shrink::shrink(&square, behavior);
+    std::panic::set_hook(hook);
+    let shrunk = match shrunk {
+        Ok(shrunk) => shrunk,
+        Err(e) => {
+            println!("Could not shrink: {}", e);
+            exit(1);
+        }
     };
 
-    let fixed_char_words: Vec<&str> = fixed_chars.split("/").collect();
-
-    // Create a dictionary from the default OS dictionary
-    // let dict = Dictionary::from_os_dict().unwrap();
-    let column_count = fixed_char_words[0].len();
-
-    // Create a 4x4 magic square
-    let mut square = MagicSquare::empty(row_count, column_count, &dict);
-
-    // Set the first row:
-    // square.set(0, 0, 'j');
-    // square.set(0, 1, 'o');
-    // square.set(0, 2, 'i');
-    // square.set(0, 3, 'n');
-    // square.set(0, 4, 't');
-
-    // let w1 = first_word.chars().collect::<Vec<char>>();
-    // for (i, &c) in w1.iter().enumerate() {
-    //     square.set_and_harden(0, i, c);
-    // }
-    for (i, &c) in fixed_chars
-        .chars()
-        .collect::<Vec<char>>()
-        .iter()
-        .filter(|x| **x != '/')
-        .enumerate()
-    {
-        let row = i / column_count;
-        let col = i % column_count;
-        square.set_and_harden(row, col, c);
-    }
-    let fillres = square.fill();
-    if fillres.is_err() {
-        println!("Could not fill square.");
+    let dir = args.save.as_deref().unwrap_or("shrink-repro");
+    if let Err(e) = write_reproducer(Path::new(dir), args, behavior, &shrunk, square.seed()) {
+        println!("Could not save reproducer to {}: {}", dir, e);
         exit(1);
     }
+    for row in shrunk.grid.iter() {
+        println!("{}", row);
+    }
+    println!(
+        "Shrunk to a {}x{} grid and {} words in {} runs; saved to {}",
+        shrunk.grid.len(),
+        shrunk.grid[0].chars().count(),
+        shrunk.words.len(),
+        shrunk.runs,
+        dir
+    );
+}
+
+/// Save a shrunk instance as a directory holding its wordlist, its
+/// frequencies (if any), its puzzle and a README with the command that
+/// reproduces it.
+fn write_reproducer(
+    dir: &Path,
+    args: &Args,
+    behavior: Behavior,
+    shrunk: &Shrunk,
+    seed: Option<u64>,
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let write = |name: &str, text: String| {
+        std::fs::write(dir.join(name), text).map_err(|e| e.to_string())
+    };
+
+    write("words.txt", shrunk.words.iter().map(|w| format!("{}\n", w)).collect())?;
+    let mut command = "magicsquare words.txt - --allow-offensive".to_string();
+    if shrunk.dict.has_frequencies() {
+        let counts = shrunk
+            .words
+            .iter()
+            .filter(|w| shrunk.dict.frequency(w) > 0)
+            .map(|w| format!("{} {}\n", w, shrunk.dict.frequency(w)))
+            .collect();
+        write("freq.txt", counts)?;
+        command.push_str(" --freq freq.txt");
+    }
+    let template = shrunk.grid.join("/");
+    let square = MagicSquare::from_template(&template, shrunk.grid.len(), &shrunk.dict);
+    write("puzzle.txt", Puzzle::from_square(&square).to_string())?;
+    if args.direction != magic_square::Direction::Standard {
+        command.push_str(&format!(" --direction {}", cli::direction_name(args.direction)));
+    }
+    if args.mirror_rows {
+        command.push_str(" --mirror-rows");
+    }
+    if args.overlap != Overlap::Allowed {
+        command.push_str(&format!(" --overlap {}", cli::overlap_name(args.overlap)));
+    }
+    if args.distinct {
+        command.push_str(" --distinct");
+    }
+    if args.diagonals != magic_square::Diagonals::Off {
+        command.push_str(&format!(" --diagonals {}", cli::diagonals_name(args.diagonals)));
+    }
+    if args.min_word_len != 2 {
+        command.push_str(&format!(" --min-word-len {}", args.min_word_len));
+    }
+    if args.toroidal {
+        command.push_str(" --toroidal");
+    }
+    if args.strategy != magic_square::Strategy::Letters {
+        command.push_str(&format!(" --strategy {}", cli::strategy_name(args.strategy)));
+    }
+    if args.beam_width != 100 {
+        command.push_str(&format!(" --beam-width {}", args.beam_width));
+    }
+    if args.population != 200 {
+        command.push_str(&format!(" --population {}", args.population));
+    }
+    if args.mutation_rate != 0.02 {
+        command.push_str(&format!(" --mutation-rate {}", args.mutation_rate));
+    }
+    if args.generations != 1000 {
+        command.push_str(&format!(" --generations {}", args.generations));
+    }
+    if args.cell_order != magic_square::CellOrder::Reading {
+        command.push_str(&format!(" --cell-order {}", cli::cell_order_name(args.cell_order)));
+    }
+    if args.letter_order != magic_square::LetterOrder::Alphabet {
+        let name = cli::letter_order_name(args.letter_order);
+        command.push_str(&format!(" --letter-order {}", name));
+    }
+    if !args.propagate {
+        command.push_str(" --no-propagate");
+    }
+    if args.forward_check {
+        command.push_str(" --forward-check");
+    }
+    if args.nogoods {
+        command.push_str(" --nogoods");
+    }
+    if args.parallel {
+        command.push_str(" --parallel");
+    }
+    if let Some(count) = args.threads {
+        command.push_str(&format!(" --threads {}", count));
+    }
+    if let Some(seed) = seed {
+        command.push_str(&format!(" --seed {}", seed));
+    }
+    if args.deterministic {
+        command.push_str(" --deterministic");
+    }
+    if let Some(unit) = args.restarts {
+        command.push_str(&format!(" --restarts {}", unit));
+    }
 
-    // Print the square
-    print!("{}[2J", 27 as char);
+    let expected = match behavior {
+        Behavior::Panic => "The solver panics.".to_string(),
+        Behavior::Invalid => {
+            "The solver reports a solution with a row or column that is not a word.".to_string()
+        }
+        Behavior::Unsolvable => "The solver finds no solution.".to_string(),
+        Behavior::Slow(limit) => format!("The solver tries more than {} letters.", limit),
+    };
+    write(
+        "README.txt",
+        format!(
+            "Reproducer shrunk by magicsquare {} (behavior {}).\n\n{}\n\n\
+             Run:\n\n    {} < puzzle.txt\n",
+            env!("CARGO_PKG_VERSION"),
+            behavior,
+            expected,
+            command
+        ),
+    )
+}
 
-    for row in 0..square.square.len() {
-        let rowv = square.get_row(row);
-        let rowstr: Vec<String> = rowv.iter().map(|f| f.to_string()).collect();
-        println!("{}", rowstr.join(""));
+/// Print the templates the search checked most often to stderr, with how
+/// many words match each.
+fn print_hot_templates(square: &MagicSquare, count: usize) {
+    let hottest = square.hot_templates(count);
+    if hottest.is_empty() {
+        return;
     }
-    for col in 0..square.square[0].len() {
-        let colv = square.get_col(col);
-        let colstr: Vec<String> = colv.iter().map(|f| f.to_string()).collect();
-        println!("{}", colstr.join(""));
+    eprintln!("{:<12} {:>10} {:>10}", "template", "checks", "candidates");
+    for (template, checks, candidates) in hottest.iter() {
+        eprintln!("{:<12} {:>10} {:>10}", template, checks, candidates);
     }
-    println!("");
+}
+
+/// `search <dictionary | --lang code> <query>` lists the dictionary words matching a query:
+///
+/// * `<template>` - words matching a template, e.g. `__mon`
+/// * `reversed <template>` - words that match the template when spelled
+///   backwards
+/// * `rotations <template>` - rotations of words that match the template,
+///   as a toroidal square reads them
+/// * `regex <pattern>` - words a regular expression matches
+/// * `anagrams <letters>` - words spelled with exactly those letters
+/// * `sounds-like <word>` - words that sound like the word
+/// * `palindromes <len>` - words that read the same backwards
+/// * `semordnilaps <len>` - words that spell another word backwards
+fn run_search(args: &Args) {
+    let usage = "Usage: magicsquare search <dictionary | --dict <path> | --lang <code>> \
+                 <template | reversed <template> | rotations <template> | regex <pattern> | anagrams <letters> | \
+                 sounds-like <word> | palindromes <len> | semordnilaps <len>>";
+    if args.positional.len() < 3 && args.lang.is_none() && args.dict.is_none() {
+        println!("{}", usage);
+        exit(1);
+    }
+    let (dict, rest) = load_dictionary(args, &args.positional[1..]);
+    let Some(query) = rest.first() else {
+        println!("{}", usage);
+        exit(1);
+    };
 
-    square.print();
+    let argument = rest.get(1);
+    let length = || match argument.map(|n| n.parse::<usize>()) {
+        Some(Ok(len)) => len,
+        _ => {
+            println!("{}", usage);
+            exit(1);
+        }
+    };
+    let mut words = match (query.as_str(), argument) {
+        ("palindromes", _) => dict.palindromes(length()),
+        ("semordnilaps", _) => dict.semordnilaps(length()),
+        ("reversed", Some(template)) => dict
+            .search_reversed_with_template(template)
+            .into_iter()
+            .map(|w| w.chars().rev().collect())
+            .collect(),
+        ("rotations", Some(template)) => dict.search_rotated_with_template(template),
+        ("regex", Some(pattern)) => match dict.search_regex(pattern) {
+            Ok(words) => words,
+            Err(e) => {
+                println!("Bad pattern: {}", e);
+                exit(1);
+            }
+        },
+        ("anagrams", Some(letters)) => dict.anagrams_of(letters),
+        ("sounds-like", Some(word)) => dict.sounds_like(word),
+        (template, _) => dict.search_with_template(template),
+    };
+    words.sort();
+
+    for word in words.iter() {
+        println!("{}", word);
+    }
+}
 
-    // Print the capitalized letters all concatenated
-    let mut capitalized = String::new();
-    for row in square.square.iter() {
-        for &c in row.iter() {
-            capitalized.push(c.to_ascii_uppercase());
+/// Fill the square described by `[dictionary] [template] [rows]` and print it.
+fn run_solve(args: &Args) {
+    let mut square = load_square(args, &args.positional);
+    square.set_live_view(if args.progress {
+        LiveView::Progress
+    } else if args.format == Format::Text {
+        LiveView::Grid
+    } else {
+        LiveView::Off
+    });
+    square.set_template_stats(args.hot_templates.is_some());
+    // A resumed search keeps saving its place to the same file
+    let checkpoint = args.checkpoint.as_ref().or(args.resume.as_ref());
+    square.set_checkpoint(checkpoint.map(PathBuf::from));
+    if let Some(every) = args.checkpoint_every {
+        square.set_checkpoint_interval(every);
+    }
+    if let Some(path) = &args.resume {
+        if let Err(e) = square.resume_from(Path::new(path)) {
+            println!("{}", e);
+            exit(1);
         }
     }
-    println!("\n{}", capitalized);
+    let fillres = if let Some(budget) = args.optimize_for {
+        square.optimize(budget).map(|()| vec![])
+    } else if args.themes.is_empty() {
+        square.fill().map(|()| vec![])
+    } else {
+        square.fill_with_themes(&args.themes)
+    };
+    if let Some(count) = args.hot_templates {
+        render::exclusive(|| print_hot_templates(&square, count));
+    }
+    if args.stats {
+        render::exclusive(|| eprintln!("{}", square.solve_stats()));
+    }
+    if fillres.is_ok() {
+        if let Some(path) = &args.save {
+            if let Err(e) = std::fs::write(path, Puzzle::from_square(&square).to_string()) {
+                println!("Could not save puzzle to {}: {}", path, e);
+                exit(1);
+            }
+        }
+    }
+    if args.format == Format::Json {
+        if let Err(e) = &fillres {
+            if !args.themes.is_empty() {
+                eprintln!("{}", e);
+            }
+            println!("{}", report::failure_json(&square));
+            exit(1);
+        }
+        println!("{}", report::solved_json(&square));
+        return;
+    }
+    let themes = match fillres {
+        Ok(themes) => themes,
+        // Including a checkpoint that does not fit the settings
+        Err(e) if !args.themes.is_empty() || args.resume.is_some() => {
+            println!("Could not fill square: {}", e);
+            exit(1);
+        }
+        Err(e) if square.gave_up() => {
+            // The best the search managed, blanks and all
+            render::exclusive(|| {
+                println!("Could not fill square: {}", e);
+                for row in square.best_fill() {
+                    println!("{}", row.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "));
+                }
+            });
+            exit(1);
+        }
+        Err(_) => {
+            println!("Could not fill square.");
+            exit(1);
+        }
+    };
+    if args.format == Format::Chat {
+        print!("{}", report::chat(&square));
+        return;
+    }
+
+    // The final frame, which no live frame may split
+    render::exclusive(|| {
+        // Print the square
+        print!("{}[2J", 27 as char);
+
+        for slot in Slot::all(square.grid()) {
+            println!("{}", square.word(slot));
+        }
+        println!();
 
-    // // Satisfy the "_ _ M O " template
-    // let re = dict.search_with_template("aaru");
-    // println!("{} words satisfy the \"_ _ M O \" template", re.len());
-    // for word in re {
-    //     println!("{}", word);
-    // }
+        square.print();
+
+        // Print the capitalized letters all concatenated
+        let mut capitalized = String::new();
+        for row in square.grid().iter() {
+            for &c in row.iter() {
+                capitalized.push(c.to_ascii_uppercase());
+            }
+        }
+        println!("\n{}", capitalized);
+
+        for (slot, word) in themes.iter() {
+            println!("theme: {} = {}", slot, word);
+        }
+        for (rule, count) in square.violations() {
+            println!("broke: {} ({} times)", rule, count);
+        }
+    });
 }
@@ -0,0 +1,294 @@
+//! A read-only dictionary that answers queries straight from a memory-mapped
+//! wordlist file, for machines that cannot spare the memory `Dictionary`
+//! takes for a large list.
+//!
+//! Where `Dictionary` copies each word into several indexes, this keeps only
+//! the start and end of each word in the file, sorted: about 8 bytes a word.
+//! Membership and prefix queries are binary searches, and template queries
+//! scan the words of the template's length (after narrowing them to its
+//! leading letters), so they are slower than `Dictionary`'s.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::logging::{Level, Span};
+
+/// The bytes of a file: mapped into memory where the platform allows it,
+/// and read into a buffer otherwise.
+enum Bytes {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    Mapped(sys::Mapping),
+    Owned(Vec<u8>),
+}
+
+impl Bytes {
+    fn open(path: &Path) -> Result<Bytes, String> {
+        let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        {
+            let file = std::fs::File::open(path).map_err(error)?;
+            let len = file.metadata().map_err(error)?.len();
+            // Nothing to map in an empty file
+            if len > 0 {
+                let len =
+                    usize::try_from(len).map_err(|_| format!("{} is too large", path.display()))?;
+                return sys::Mapping::new(&file, len)
+                    .map(Bytes::Mapped)
+                    .map_err(error);
+            }
+        }
+        std::fs::read(path).map(Bytes::Owned).map_err(error)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Bytes::Mapped(mapping) => mapping.as_slice(),
+            Bytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Raw `mmap` bindings. The standard library links the C library on every
+/// Unix, so these need no extra dependency.
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod sys {
+    use std::ffi::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+
+    // These values are the same on Linux, macOS and the BSDs
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    /// A whole file mapped read-only, unmapped on drop.
+    pub(super) struct Mapping {
+        ptr: *mut c_void,
+        len: usize,
+    }
+
+    // The mapping is never written to, so it can be shared between threads
+    unsafe impl Send for Mapping {}
+    unsafe impl Sync for Mapping {}
+
+    impl Mapping {
+        /// Map the first `len` bytes of `file`, which must not be 0.
+        pub(super) fn new(file: &std::fs::File, len: usize) -> std::io::Result<Mapping> {
+            // SAFETY: a fresh private read-only mapping of an open file
+            // aliases no Rust memory; failure is reported as MAP_FAILED.
+            let ptr = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    PROT_READ,
+                    MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr as usize == usize::MAX {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Mapping { ptr, len })
+        }
+
+        pub(super) fn as_slice(&self) -> &[u8] {
+            // SAFETY: `ptr` is a live mapping of `len` readable bytes until
+            // drop.
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            // SAFETY: `ptr` and `len` are exactly what `mmap` returned, and
+            // no slice of the mapping outlives `self`.
+            unsafe {
+                munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+/// A dictionary over a wordlist file mapped into memory. See the module
+/// documentation.
+///
+/// Words are used as the file spells them, without copying: lines are
+/// trimmed, and lines that are blank, not UTF-8, or not entirely lowercase
+/// letters are skipped, so lowercase the file first if it has capitalized
+/// words you want. The file must not be changed or truncated while it is
+/// open.
+pub struct MappedDictionary {
+    bytes: Bytes,
+    /// For each word length, the byte range of every word of that length in
+    /// `bytes`, sorted by the word and without duplicates.
+    lengths: BTreeMap<usize, Vec<(u32, u32)>>,
+}
+
+impl MappedDictionary {
+    /// Map a wordlist file with one word per line.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the wordlist.
+    ///
+    /// # Returns
+    /// * Ok(The dictionary)
+    /// * Err(String) if the file could not be read, or is 4 GiB or larger.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedDictionary, String> {
+        let path = path.as_ref();
+        let _span = Span::enter(Level::Info, "map dictionary");
+        let bytes = Bytes::open(path)?;
+        let data = bytes.as_slice();
+        if u32::try_from(data.len()).is_err() {
+            return Err(format!(
+                "{} is too large to map (4 GiB or more)",
+                path.display()
+            ));
+        }
+
+        let mut lengths: BTreeMap<usize, Vec<(u32, u32)>> = BTreeMap::new();
+        let mut start = 0;
+        for line in data.split(|&b| b == b'\n') {
+            let end = start + line.len();
+            if let Some((from, to, len)) = word_range(line) {
+                lengths
+                    .entry(len)
+                    .or_default()
+                    .push(((start + from) as u32, (start + to) as u32));
+            }
+            start = end + 1;
+        }
+        for words in lengths.values_mut() {
+            words.sort_unstable_by(|a, b| slice(data, *a).cmp(slice(data, *b)));
+            words.dedup_by(|a, b| slice(data, *a) == slice(data, *b));
+        }
+        let dict = MappedDictionary { bytes, lengths };
+        info!("mapped {} words from {}", dict.len(), path.display());
+        Ok(dict)
+    }
+
+    /// The word stored at `range`.
+    fn word(&self, range: (u32, u32)) -> &str {
+        // Only ranges that were valid UTF-8 are stored
+        std::str::from_utf8(slice(self.bytes.as_slice(), range)).unwrap_or("")
+    }
+
+    /// The byte ranges of the words of a given length.
+    fn ranges(&self, len: usize) -> &[(u32, u32)] {
+        self.lengths.get(&len).map_or(&[], Vec::as_slice)
+    }
+
+    /// The ranges of the words of length `len` that start with `prefix`.
+    fn with_prefix(&self, prefix: &str, len: usize) -> &[(u32, u32)] {
+        let ranges = self.ranges(len);
+        let prefix = prefix.as_bytes();
+        let data = self.bytes.as_slice();
+        let head = |range: &(u32, u32)| {
+            let word = slice(data, *range);
+            word[..prefix.len().min(word.len())].cmp(prefix)
+        };
+        let from = ranges.partition_point(|r| head(r) == Ordering::Less);
+        let to = ranges.partition_point(|r| head(r) != Ordering::Greater);
+        &ranges[from..to]
+    }
+
+    /// The number of words.
+    pub fn len(&self) -> usize {
+        self.lengths.values().map(Vec::len).sum()
+    }
+
+    /// Whether the dictionary has no words.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every word, shortest first and then in byte order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        self.lengths
+            .values()
+            .flat_map(move |ranges| ranges.iter().map(move |&r| self.word(r)))
+    }
+
+    /// The words of a given length, in byte order.
+    pub fn words_of_length(&self, len: usize) -> impl Iterator<Item = &str> + '_ {
+        self.ranges(len).iter().map(move |&r| self.word(r))
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let data = self.bytes.as_slice();
+        self.ranges(word.chars().count())
+            .binary_search_by(|&r| slice(data, r).cmp(word.as_bytes()))
+            .is_ok()
+    }
+
+    /// Whether any word of length `len` starts with `prefix`.
+    pub fn has_prefix(&self, prefix: &str, len: usize) -> bool {
+        !self.with_prefix(&prefix.to_lowercase(), len).is_empty()
+    }
+
+    /// The words matching a template of letters and `_` wildcards (so
+    /// "c_t" matches "cat" and "cut"), in byte order, found lazily.
+    pub fn iter_with_template(&self, template: &str) -> impl Iterator<Item = &str> + '_ {
+        let template: Vec<char> = template.to_lowercase().chars().collect();
+        let prefix: String = template.iter().take_while(|&&c| c != '_').collect();
+        self.with_prefix(&prefix, template.len())
+            .iter()
+            .map(move |&r| self.word(r))
+            .filter(move |word| {
+                word.chars()
+                    .zip(template.iter())
+                    .all(|(c, &t)| t == '_' || t == c)
+            })
+    }
+
+    /// Return all the words matching a template, in byte order.
+    ///
+    /// ```no_run
+    /// # use magic_square::mapped::MappedDictionary;
+    /// let dict = MappedDictionary::open("/usr/share/dict/words").unwrap();
+    /// assert!(dict.search_with_template("c_t").contains(&"cat".to_string()));
+    /// ```
+    pub fn search_with_template(&self, template: &str) -> Vec<String> {
+        self.iter_with_template(template)
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn count_with_template(&self, template: &str) -> usize {
+        self.iter_with_template(template).count()
+    }
+
+    /// Whether any word matches a template.
+    pub fn has_match(&self, template: &str) -> bool {
+        self.iter_with_template(template).next().is_some()
+    }
+}
+
+/// The bytes of `data` in a stored word range.
+fn slice(data: &[u8], (from, to): (u32, u32)) -> &[u8] {
+    &data[from as usize..to as usize]
+}
+
+/// The byte range of the word in a line, without surrounding whitespace,
+/// and its length in characters, or `None` if the line has no word made
+/// only of lowercase letters.
+fn word_range(line: &[u8]) -> Option<(usize, usize, usize)> {
+    let from = line.iter().position(|b| !b.is_ascii_whitespace())?;
+    let to = line.iter().rposition(|b| !b.is_ascii_whitespace())? + 1;
+    let word = std::str::from_utf8(&line[from..to]).ok()?;
+    word.chars()
+        .all(|c| c.is_alphabetic() && !c.is_uppercase())
+        .then(|| (from, to, word.chars().count()))
+}
@@ -0,0 +1,220 @@
+//! Remembering the partial squares a search has shown lead nowhere.
+//!
+//! When the search reaches the start of a row, every row above it is
+//! filled and none below it is, so whether the rest of the square can be
+//! filled depends only on what each column can still become: the endings
+//! of the words that fit it. Two grids whose columns have the same endings
+//! have the same squares below them, or none, however different their
+//! letters. The search keys each row it starts on those endings, and once
+//! it has tried everything below a row, records the key, so that reaching
+//! the same state again by another path backtracks at once.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use crate::slot::Slot;
+use crate::square::{CellOrder, Diagonals, Strategy};
+use crate::MagicSquare;
+
+/// The state of a search at the start of a row: the row, and a hash of
+/// the endings each column can still take.
+pub(crate) type NogoodKey = (usize, Vec<u64>);
+
+/// The states a search has shown lead nowhere, and how often it looked
+/// one up.
+#[derive(Default)]
+pub(crate) struct Nogoods {
+    dead: HashSet<NogoodKey>,
+    pub(crate) stats: NogoodStats,
+}
+
+/// How much the nogood cache saved a search. See
+/// [`MagicSquare::set_nogoods`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NogoodStats {
+    /// How many times the search started a row and looked it up.
+    pub lookups: usize,
+    /// How many of those found a state already shown to lead nowhere, and
+    /// so backtracked without searching below it.
+    pub hits: usize,
+    /// How many states were recorded as leading nowhere.
+    pub recorded: usize,
+}
+
+impl NogoodStats {
+    /// Add the counts of another search, such as one on another thread.
+    pub(crate) fn add(&mut self, other: NogoodStats) {
+        self.lookups += other.lookups;
+        self.hits += other.hits;
+        self.recorded += other.recorded;
+    }
+}
+
+impl MagicSquare {
+    /// The key of the search's state, if the cache is on and `(row, col)`
+    /// is the first cell the solver may write in its row: every such cell
+    /// above is filled, and none in this row or below but those
+    /// propagation left one letter for. The key leaves those out, since
+    /// the endings force them anyway.
+    ///
+    /// Only searches that fill the grid in reading order, or a row at a
+    /// time, start rows like this. Searching the most constrained cell
+    /// first fills cells all over the grid, so it has no keys. Nor does a
+    /// symmetric square, whose rows below are already started by the
+    /// letters mirrored from the rows above, which the endings leave out,
+    /// or one whose words must differ or whose diagonals must be words,
+    /// which depends on more of the rows above than the endings, or one
+    /// with blocked cells, whose columns are more than one word, or a
+    /// toroidal one, whose columns need not start with their top letter.
+    pub(crate) fn nogood_key(&self, row: usize, col: usize) -> Option<NogoodKey> {
+        self.nogoods.as_ref()?;
+        if self.symmetric
+            || self.words_differ()
+            || self.diagonals != Diagonals::Off
+            || self.has_blocks()
+            || self.toroidal
+        {
+            return None;
+        }
+        let reading = match self.strategy {
+            Strategy::Rows => true,
+            Strategy::Letters => self.cell_order() == CellOrder::Reading,
+            Strategy::ExactCover
+            | Strategy::Words
+            | Strategy::Beam
+            | Strategy::Anneal
+            | Strategy::Genetic => false,
+            #[cfg(feature = "sat")]
+            Strategy::Sat => false,
+        };
+        if !reading || (0..col).any(|c| self.editable_mask[row][c]) {
+            return None;
+        }
+        let grid: Vec<Vec<char>> = (0..self.square.len())
+            .map(|r| {
+                let blank = |(c, &letter): (usize, &char)| {
+                    if r >= row && self.editable_mask[r][c] {
+                        '_'
+                    } else {
+                        letter
+                    }
+                };
+                self.square[r].iter().enumerate().map(blank).collect()
+            })
+            .collect();
+        let columns = (0..self.square[row].len())
+            .map(|col| self.column_endings(&grid, Slot::Col(col)))
+            .collect();
+        Some((row, columns))
+    }
+
+    /// A hash of the letters the words fitting a column of `grid` put in
+    /// its blank cells. The words are sorted, so the same endings always
+    /// give the same hash whatever letters are above them.
+    fn column_endings(&self, grid: &[Vec<char>], slot: Slot) -> u64 {
+        let letters = slot.read(grid, self.direction);
+        let template: String = letters.iter().collect();
+        let blanks: Vec<usize> = (0..letters.len()).filter(|&i| letters[i] == '_').collect();
+        let mut endings: Vec<String> = self
+            .dict
+            .iter_with_template(&template)
+            .map(|word| {
+                let chars: Vec<char> = word.chars().collect();
+                blanks.iter().map(|&i| chars[i]).collect()
+            })
+            .collect();
+        endings.sort_unstable();
+        endings.dedup();
+        let mut hasher = DefaultHasher::new();
+        endings.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether a state has already been shown to lead nowhere, counting
+    /// the lookup.
+    pub(crate) fn is_nogood(&mut self, key: &NogoodKey) -> bool {
+        let Some(nogoods) = self.nogoods.as_mut() else {
+            return false;
+        };
+        nogoods.stats.lookups += 1;
+        let dead = nogoods.dead.contains(key);
+        if dead {
+            nogoods.stats.hits += 1;
+        }
+        dead
+    }
+
+    /// Record that a search tried everything below a state and found no
+    /// square. A search that gave up or was cancelled has not shown that.
+    pub(crate) fn record_nogood(&mut self, key: NogoodKey) {
+        if let Some(nogoods) = self.nogoods.as_mut() {
+            if nogoods.dead.insert(key) {
+                nogoods.stats.recorded += 1;
+            }
+        }
+    }
+
+    /// Forget every recorded state, keeping the cache on if it is on. The
+    /// keys hold only what the search can change, so they are only good
+    /// for one `fill`.
+    pub(crate) fn clear_nogoods(&mut self) {
+        if let Some(nogoods) = self.nogoods.as_mut() {
+            *nogoods = Nogoods::default();
+        }
+    }
+
+    /// Log the counts so far, if the cache is on.
+    pub(crate) fn log_nogood_stats(&self) {
+        if let Some(stats) = self.nogood_stats() {
+            info!(
+                "nogoods: {} lookups, {} hits, {} recorded",
+                stats.lookups, stats.hits, stats.recorded
+            );
+        }
+    }
+
+    /// Add the counts of another search to this one's.
+    pub(crate) fn add_nogood_stats(&mut self, stats: NogoodStats) {
+        if let Some(nogoods) = self.nogoods.as_mut() {
+            nogoods.stats.add(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{CellOrder, Strategy};
+
+    #[test]
+    fn keeps_the_square_found_without_the_cache() {
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            for (template, rows) in [("____", 4), ("_____", 4)] {
+                let setup = |square: &mut crate::MagicSquare| {
+                    square.set_cell_order(CellOrder::Reading);
+                    square.set_propagate(false);
+                };
+                let plain = assert_fills(template, rows, strategy, setup);
+                let cached = assert_fills(template, rows, strategy, |square| {
+                    setup(square);
+                    square.set_nogoods(true);
+                });
+                assert_eq!(cached.grid(), plain.grid(), "{:?} {}", strategy, template);
+                let stats = cached.nogood_stats().unwrap();
+                assert!(stats.lookups > 0, "{:?} {} never looked up", strategy, template);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_mirrored_rows() {
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            assert_fills("____", 4, strategy, |square| {
+                square.set_cell_order(CellOrder::Reading);
+                square.set_nogoods(true);
+                square.set_mirror_rows(true);
+            });
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/// Accented and stroked lowercase letters, grouped by the letter they fold
+/// to.
+const FOLDS: &[(&str, char)] = &[
+    ("àáâãäåāăąǎ", 'a'),
+    ("çćĉċč", 'c'),
+    ("ďđ", 'd'),
+    ("èéêëēĕėęě", 'e'),
+    ("ĝğġģ", 'g'),
+    ("ĥħ", 'h'),
+    ("ìíîïĩīĭįı", 'i'),
+    ("ĵ", 'j'),
+    ("ķ", 'k'),
+    ("ĺļľŀł", 'l'),
+    ("ñńņňŉ", 'n'),
+    ("òóôõöøōŏő", 'o'),
+    ("ŕŗř", 'r'),
+    ("śŝşšș", 's'),
+    ("ţťŧț", 't'),
+    ("ùúûüũūŭůűų", 'u'),
+    ("ŵ", 'w'),
+    ("ýÿŷ", 'y'),
+    ("źżž", 'z'),
+];
+
+/// Ligatures that compatibility decomposition splits into plain letters.
+const LIGATURES: &[(char, &str)] = &[
+    ('ﬀ', "ff"),
+    ('ﬁ', "fi"),
+    ('ﬂ', "fl"),
+    ('ﬃ', "ffi"),
+    ('ﬄ', "ffl"),
+    ('ﬅ', "st"),
+    ('ﬆ', "st"),
+];
+
+/// Push the folded form of a lowercase character onto `out`.
+fn fold_lowercase(c: char, out: &mut String) {
+    if let Some((_, base)) = FOLDS.iter().find(|(accented, _)| accented.contains(c)) {
+        out.push(*base);
+    } else if let Some((_, letters)) = LIGATURES.iter().find(|(ligature, _)| *ligature == c) {
+        out.push_str(letters);
+    } else {
+        out.push(c);
+    }
+}
+
+/// Fold a word to unaccented letters, so "café" becomes "cafe".
+///
+/// This approximates NFKD normalization followed by stripping combining
+/// marks, for Latin scripts: accented Latin-1 and Latin Extended-A letters
+/// lose their accents (letters with a stroke, such as "ø" and "ł", are
+/// folded too), combining marks are dropped, ligatures such as "ﬁ" are
+/// split, and fullwidth forms become ASCII. Letters with no decomposition,
+/// such as "ß" and "æ", are kept. Case is preserved.
+pub fn fold_diacritics(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    for c in word.chars() {
+        match c {
+            c if c.is_ascii() => out.push(c),
+            // Combining diacritical marks, left over from decomposed input
+            '\u{0300}'..='\u{036f}' => {}
+            // Fullwidth ASCII
+            '\u{ff01}'..='\u{ff5e}' => {
+                out.extend(char::from_u32(c as u32 - 0xfee0));
+            }
+            c if c.is_uppercase() => {
+                let mut folded = String::new();
+                c.to_lowercase()
+                    .for_each(|c| fold_lowercase(c, &mut folded));
+                out.push_str(&folded.to_uppercase());
+            }
+            c => fold_lowercase(c, &mut out),
+        }
+    }
+    out
+}
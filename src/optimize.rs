@@ -0,0 +1,351 @@
+//! Looking for the best square in a time budget, rather than the first.
+//!
+//! A square scores the commonness of its words, from the dictionary's
+//! word frequencies. Optimizing searches again and again in new random
+//! orders, each search allowed the attempts of a restarted one (see
+//! `restart`), and keeps the best square found so far, the incumbent,
+//! until the time is up. A search also skips any letter or row after
+//! which even the most common words fitting every line would not beat the
+//! incumbent (branch and bound), so each square found is better than the
+//! last, and a search that ends without one proves the incumbent is the
+//! best there is.
+//!
+//! Soft rules cost a square some of its score each time it breaks them,
+//! rather than ruling it out. The bound counts only what the words already
+//! complete cost, which more words can only add to, so it still never
+//! skips a better square.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::restart::luby;
+use crate::square::Strategy;
+use crate::MagicSquare;
+
+/// The attempts to allow the first search, unless restarts set a unit.
+const DEFAULT_UNIT: usize = 1000;
+
+/// A rule [`optimize`](MagicSquare::optimize) may break at a cost, set with
+/// [`set_soft_rules`](MagicSquare::set_soft_rules), where the hard rules
+/// must be kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SoftRule {
+    /// No word repeats another, as [`set_distinct`](MagicSquare::set_distinct)
+    /// requires. Broken once for each repeat.
+    Distinct,
+    /// Every word is listed at least this many times in the frequency list.
+    /// Broken once for each word below it.
+    MinFrequency(u64),
+    /// Some word of the square is this one, as a theme word must be.
+    Theme(String),
+}
+
+impl fmt::Display for SoftRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SoftRule::Distinct => write!(f, "distinct"),
+            SoftRule::MinFrequency(count) => write!(f, "min-freq={}", count),
+            SoftRule::Theme(word) => write!(f, "theme={}", word),
+        }
+    }
+}
+
+/// The best square an `optimize` has found so far, and what it has learned
+/// about beating it.
+#[derive(Default)]
+pub(crate) struct Incumbent {
+    /// The best square's score, once there is one.
+    score: Option<f64>,
+    /// How common the most common word fitting each template looked up
+    /// is.
+    best_fits: HashMap<String, f64>,
+}
+
+impl MagicSquare {
+    /// Search for squares until `budget` is spent, and keep the one that
+    /// scores best (see [`score`](MagicSquare::score)). Searches follow
+    /// the restart unit if there is one (see
+    /// [`set_restarts`](MagicSquare::set_restarts)), and the time and
+    /// attempt limits cut the budget short. It runs on one thread, with
+    /// the letter-by-letter or whole-row strategy and word frequencies.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if some square was found, which the square is left
+    ///   holding.
+    /// * `Err(String)` if there is no square, none was found in the
+    ///   budget, or the square cannot be scored.
+    pub fn optimize(&mut self, budget: Duration) -> Result<(), String> {
+        if !self.dict.has_frequencies() {
+            return Err("Optimizing needs word frequencies to score squares".to_string());
+        }
+        if !matches!(self.strategy, Strategy::Letters | Strategy::Rows) {
+            return Err("Optimizing needs the letters or rows strategy".to_string());
+        }
+        self.begin_fill();
+        let end = Instant::now() + budget;
+        self.deadline = Some(self.deadline.map_or(end, |at| at.min(end)));
+        let seed = self.seed;
+        let unit = self.restart_unit.unwrap_or(DEFAULT_UNIT);
+        let fixed = self.fixed_grid();
+        let give_up_at = self.attempt_limit.map(|limit| self._attempt + limit);
+        let mut best: Option<(f64, Vec<Vec<char>>, Option<u64>)> = None;
+        let mut found = 0;
+        self.incumbent = Some(Incumbent::default());
+        let mut run = 1;
+        let result = loop {
+            let mut limit = unit.max(1).saturating_mul(luby(run));
+            if let Some(at) = give_up_at {
+                limit = limit.min(at.saturating_sub(self._attempt));
+            }
+            self.reset_to(&fixed);
+            let result = self.search(Some(limit));
+            if result.is_ok() {
+                let score = self.score();
+                found += 1;
+                debug!("search {} found a square scoring {:.3}", run, score);
+                if best.as_ref().is_none_or(|(best, _, _)| score > *best) {
+                    best = Some((score, self.square.clone(), self.search_seed()));
+                    if let Some(incumbent) = self.incumbent.as_mut() {
+                        incumbent.score = Some(score);
+                    }
+                }
+            } else if !self.gave_up() {
+                // Nothing was cut short, so no square beats the incumbent
+                break result;
+            }
+            if self.out_of_time() || give_up_at.is_some_and(|at| self._attempt >= at) {
+                self.gave_up = true;
+                break result;
+            }
+            // The first search keeps the order it was given, and each after
+            // it tries a new one
+            self.seed = Some(seed.unwrap_or(0).wrapping_add(run as u64));
+            run += 1;
+        };
+        self.seed = seed;
+        self.incumbent = None;
+        let result = match best {
+            Some((score, grid, seed)) => {
+                info!(
+                    "best of {} squares over {} searches scores {:.3}{}",
+                    found,
+                    run,
+                    score,
+                    if self.gave_up { "" } else { ", which no square beats" }
+                );
+                self.gave_up = false;
+                self.square = grid;
+                self.note_seed(seed);
+                for (rule, count) in self.violations() {
+                    info!("broke soft rule {} {} times", rule, count);
+                }
+                Ok(())
+            }
+            None => result,
+        };
+        self.finish_stats();
+        match result {
+            Err(_) if self.gave_up => Err(self.gave_up_error()),
+            result => result,
+        }
+    }
+
+    /// How good the square is: the sum, over every word it must spell, of
+    /// how common the word is, from 0 for a word the frequency list lacks
+    /// towards 1 for the most common, less the weight of each soft rule
+    /// for every time the square breaks it.
+    pub fn score(&self) -> f64 {
+        let words = self.spelled();
+        let commonness: f64 = words.iter().map(|word| self.dict.commonness(word)).sum();
+        let penalty: f64 = self
+            .soft_rules
+            .iter()
+            .map(|(rule, weight)| weight * self.times_broken(rule, &words) as f64)
+            .sum();
+        commonness - penalty
+    }
+
+    /// The soft rules the square breaks, each with how many times it
+    /// breaks it.
+    pub fn violations(&self) -> Vec<(SoftRule, usize)> {
+        let words = self.spelled();
+        self.soft_rules
+            .iter()
+            .map(|(rule, _)| (rule.clone(), self.times_broken(rule, &words)))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+
+    /// How many times `words`, those of a square, break `rule`.
+    fn times_broken(&self, rule: &SoftRule, words: &[String]) -> usize {
+        match rule {
+            SoftRule::Distinct => words.len() - words.iter().collect::<HashSet<_>>().len(),
+            SoftRule::MinFrequency(count) => words
+                .iter()
+                .filter(|word| self.dict.frequency(word) < *count)
+                .count(),
+            SoftRule::Theme(theme) => usize::from(!words.contains(theme)),
+        }
+    }
+
+    /// Every word the square spells, in reading order, or the template of
+    /// each if it is not yet full.
+    fn spelled(&self) -> Vec<String> {
+        self.entries()
+            .into_iter()
+            .map(|entry| {
+                let cells = entry.slot.cells(&self.square)[entry.cells].to_vec();
+                entry.slot.orient(self.direction, cells).into_iter().collect()
+            })
+            .collect()
+    }
+
+    /// Whether the search skips squares that cannot beat the incumbent:
+    /// whether it is optimizing, and has found a square.
+    pub(crate) fn bounds_score(&self) -> bool {
+        self.incumbent.as_ref().is_some_and(|incumbent| incumbent.score.is_some())
+    }
+
+    /// Whether the grid can still lead to a square scoring more than the
+    /// incumbent, if there is one: whether the most common words fitting
+    /// each line would, crossings aside, less what the words already
+    /// complete are sure to cost.
+    pub(crate) fn may_beat_incumbent(&mut self) -> bool {
+        if !self.bounds_score() {
+            return true;
+        }
+        let templates = self.spelled();
+        let complete: Vec<String> =
+            templates.iter().filter(|template| !template.contains('_')).cloned().collect();
+        // More words only break the other rules more often, but a theme
+        // may yet be spelled
+        let penalty: f64 = self
+            .soft_rules
+            .iter()
+            .filter(|(rule, _)| {
+                !matches!(rule, SoftRule::Theme(_)) || complete.len() == templates.len()
+            })
+            .map(|(rule, weight)| weight * self.times_broken(rule, &complete) as f64)
+            .sum();
+        let Some(Incumbent { score: Some(score), best_fits }) = self.incumbent.as_mut() else {
+            return true;
+        };
+        let mut bound = 0.0;
+        for template in templates {
+            bound += *best_fits.entry(template).or_insert_with_key(|template| {
+                self.queries.fetch_add(1, Ordering::Relaxed);
+                self.dict
+                    .iter_with_template(template)
+                    .map(|word| self.dict.commonness(word))
+                    .fold(0.0, f64::max)
+            });
+        }
+        // Summed in the order `score` sums a full grid, so a square exactly
+        // as good as the incumbent does not look better by rounding
+        bound - penalty > *score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::dictionary::Dictionary;
+    use crate::test_util::assert_square;
+    use crate::{LiveView, MagicSquare, SoftRule, Strategy};
+
+    /// A dictionary whose squares are the rotations of two: one with the
+    /// most common word of all, which a fill tries first, but otherwise
+    /// rare words, and a better one of fairly common words.
+    fn ranked() -> Dictionary {
+        let counts = [
+            ("abc", 1_000_000),
+            ("bca", 1),
+            ("cab", 1),
+            ("xyz", 1000),
+            ("yzx", 1000),
+            ("zxy", 1000),
+        ];
+        let path = std::env::temp_dir().join(format!("magic-square-ranked-{}", std::process::id()));
+        let lines: Vec<String> = counts.iter().map(|(word, n)| format!("{} {}", word, n)).collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let dict = Dictionary::from_words(counts.iter().map(|(word, _)| *word))
+            .with_frequencies(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        dict.unwrap()
+    }
+
+    #[test]
+    fn finds_the_best_square_not_the_first() {
+        let dict = ranked();
+        let best = 6.0 * dict.commonness("xyz");
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            let mut square = MagicSquare::from_template("___", 3, &dict);
+            square.set_live_view(LiveView::Off);
+            square.set_strategy(strategy);
+            square.fill().unwrap();
+            assert!(square.score() < best, "{:?}", strategy);
+
+            let mut square = MagicSquare::from_template("___", 3, &dict);
+            square.set_live_view(LiveView::Off);
+            square.set_strategy(strategy);
+            square.set_restarts(Some(1));
+            square.optimize(Duration::from_secs(5)).unwrap();
+            assert_square(&square);
+            assert!((square.score() - best).abs() < 1e-9, "{:?}", strategy);
+            // Proving no square beats it takes much less than the budget
+            assert!(square.solve_stats().elapsed < Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn trades_soft_rules_against_the_score() {
+        let dict = ranked();
+        let theme = SoftRule::Theme("abc".to_string());
+        // Worth more than the better words: the theme is kept
+        let mut square = MagicSquare::from_template("___", 3, &dict);
+        square.set_live_view(LiveView::Off);
+        square.set_soft_rules(vec![(theme.clone(), 5.0)]);
+        square.optimize(Duration::from_secs(5)).unwrap();
+        assert!(square.grid().iter().any(|row| row.iter().collect::<String>() == "abc"));
+        assert_eq!(square.violations(), vec![]);
+
+        // Worth less: it is broken, and the rare words it brings are too
+        let floor = SoftRule::MinFrequency(1000);
+        let mut square = MagicSquare::from_template("___", 3, &dict);
+        square.set_live_view(LiveView::Off);
+        square.set_soft_rules(vec![(theme.clone(), 0.5), (floor.clone(), 1.0)]);
+        square.optimize(Duration::from_secs(5)).unwrap();
+        assert_eq!(square.violations(), vec![(theme, 1)]);
+        assert!((square.score() - (6.0 * dict.commonness("xyz") - 0.5)).abs() < 1e-9);
+
+        // Every square repeats its rows in its columns
+        let mut square = MagicSquare::from_template("___", 3, &dict);
+        square.set_live_view(LiveView::Off);
+        square.set_soft_rules(vec![(SoftRule::Distinct, 1.0), (floor, 1.0)]);
+        square.optimize(Duration::from_secs(5)).unwrap();
+        assert_eq!(square.violations(), vec![(SoftRule::Distinct, 3)]);
+    }
+
+    #[test]
+    fn shows_there_is_no_square() {
+        let dict = ranked();
+        let mut square = MagicSquare::from_template("a__/_b_", 3, &dict);
+        square.set_live_view(LiveView::Off);
+        assert!(square.optimize(Duration::from_secs(5)).is_err());
+        assert!(!square.gave_up());
+    }
+
+    #[test]
+    fn needs_word_frequencies() {
+        let dict = Dictionary::from_words(["ace", "bat"]);
+        let mut square = MagicSquare::from_template("___", 3, &dict);
+        assert_eq!(
+            square.optimize(Duration::from_secs(1)),
+            Err("Optimizing needs word frequencies to score squares".to_string())
+        );
+    }
+}
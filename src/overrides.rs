@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::dictionary::{config_dir, Dictionary};
+
+/// Personal edits to the dictionary: words to add that it is missing, and
+/// words to ban. They are kept in `overrides.txt` in the config directory
+/// and applied on top of whichever dictionary is loaded.
+///
+/// The file has one edit per line: `+word` adds a word and `-word` bans
+/// one. Blank lines and lines starting with `#` are ignored, and a later
+/// edit to the same word replaces an earlier one.
+///
+/// Overlay files (`--overlay`) use the same format, so a team can share a
+/// small curation diff instead of a forked wordlist. Edits are applied in
+/// this order, each able to undo the ones before it:
+///
+/// 1. the base dictionary, with the blocklists removed;
+/// 2. each overlay, in the order given;
+/// 3. the personal overrides.
+#[derive(Default)]
+pub struct Overrides {
+    /// Each edited word, and whether it is added (`true`) or banned.
+    edits: BTreeMap<String, bool>,
+}
+
+impl Overrides {
+    /// Where the personal overrides are kept: `overrides.txt` in the config
+    /// directory.
+    pub fn default_path() -> Result<PathBuf, String> {
+        Ok(config_dir()?.join("overrides.txt"))
+    }
+
+    /// Read an overrides file. A missing file has no overrides.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the overrides file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Overrides)` with the edits in the file.
+    /// * `Err(String)` if the file exists but could not be read, or has a
+    ///   line that is not an edit.
+    pub fn load(path: &Path) -> Result<Overrides, String> {
+        if !path.exists() {
+            return Ok(Overrides::default());
+        }
+        Overrides::read(path)
+    }
+
+    /// The personal overrides every dictionary is loaded with. With no
+    /// config directory to keep them in, there are none, as with a missing
+    /// file; only editing them needs one.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Overrides)` with the edits in the overrides file, if any.
+    /// * `Err(String)` if the file exists but could not be read, or has a
+    ///   line that is not an edit.
+    pub fn personal() -> Result<Overrides, String> {
+        match Overrides::default_path() {
+            Ok(path) => Overrides::load(&path),
+            Err(e) => {
+                debug!("no personal overrides: {}", e);
+                Ok(Overrides::default())
+            }
+        }
+    }
+
+    /// Read an overlay file: a shared set of edits in the same `+word` /
+    /// `-word` format. Unlike `load`, the file must exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the overlay file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Overrides)` with the edits in the file.
+    /// * `Err(String)` if the file could not be read, or has a line that is
+    ///   not an edit.
+    pub fn read(path: &Path) -> Result<Overrides, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut overrides = Overrides::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(word) = line.strip_prefix('+') {
+                overrides.add(word);
+            } else if let Some(word) = line.strip_prefix('-') {
+                overrides.ban(word);
+            } else {
+                return Err(format!(
+                    "{}:{}: expected +word or -word, found {}",
+                    path.display(),
+                    number + 1,
+                    line
+                ));
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// Write the overrides to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let mut text = String::from("# Personal dictionary edits: +word adds, -word bans.\n");
+        for (word, added) in self.edits.iter() {
+            text.push(if *added { '+' } else { '-' });
+            text.push_str(word);
+            text.push('\n');
+        }
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Add a word the dictionary is missing, replacing any ban on it.
+    pub fn add(&mut self, word: &str) {
+        self.edits.insert(word.trim().to_lowercase(), true);
+    }
+
+    /// Ban a word, replacing any addition of it.
+    pub fn ban(&mut self, word: &str) {
+        self.edits.insert(word.trim().to_lowercase(), false);
+    }
+
+    /// Drop any edit to a word, so the base dictionary decides again.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the word had an edit.
+    pub fn forget(&mut self, word: &str) -> bool {
+        self.edits.remove(&word.trim().to_lowercase()).is_some()
+    }
+
+    /// The edits, in alphabetical order, as `(word, added)`.
+    pub fn edits(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.edits
+            .iter()
+            .map(|(word, added)| (word.as_str(), *added))
+    }
+
+    /// Apply the edits on top of a dictionary.
+    pub fn apply(&self, dict: Dictionary) -> Dictionary {
+        if self.edits.is_empty() {
+            return dict;
+        }
+        let added = self.edits().filter(|(_, added)| *added).map(|(w, _)| w);
+        let banned = self.edits().filter(|(_, added)| !*added).map(|(w, _)| w);
+        dict.with_words(added).without_words(banned)
+    }
+}
@@ -0,0 +1,128 @@
+//! Spreading work over the machine's cores with scoped threads, for the
+//! parts of loading a dictionary that split into independent pieces.
+//!
+//! Where threads cannot be started (WebAssembly, or a process at its thread
+//! limit), the work runs on the calling thread instead, so results never
+//! depend on how many threads were used.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How many threads to split work over, unless told otherwise: one per
+/// core.
+pub(crate) fn cores() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Take the value out of a mutex, ignoring poisoning: a panic on another
+/// thread is re-raised by `join` anyway.
+fn take<T>(slot: &Mutex<Option<T>>) -> Option<T> {
+    slot.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
+
+/// Run `a` and `b` at the same time, returning both results.
+pub(crate) fn join<A, B, FA, FB>(a: FA, b: FB) -> (A, B)
+where
+    A: Send,
+    FA: FnOnce() -> A + Send,
+    FB: FnOnce() -> B,
+{
+    if cores() == 1 {
+        return (a(), b());
+    }
+    // The closure waits here, so it can still be run on this thread if the
+    // other one never starts
+    let slot = Mutex::new(Some(a));
+    thread::scope(|scope| {
+        let spawned = thread::Builder::new().spawn_scoped(scope, || take(&slot).map(|a| a()));
+        let b = b();
+        let a = match spawned {
+            Ok(handle) => handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e)),
+            Err(e) => {
+                debug!("could not start a thread: {}", e);
+                None
+            }
+        };
+        match a.or_else(|| take(&slot).map(|a| a())) {
+            Some(a) => (a, b),
+            None => unreachable!("the closure runs on exactly one thread"),
+        }
+    })
+}
+
+/// Apply `f` to every item, on as many threads as there are cores, and
+/// return the results in the order of `items`. Threads take the next
+/// unclaimed item as they finish, so a few slow items do not hold the
+/// rest up.
+pub(crate) fn map<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    map_init(items, None, || (), |_, item| f(item))
+}
+
+/// Like `map`, but on `threads` threads if given, and each thread first
+/// makes some state with `init`, such as a scratch copy of something
+/// expensive to make, and passes it to `f` for every item it takes.
+pub(crate) fn map_init<T, S, R, I, F>(
+    items: Vec<T>,
+    threads: Option<usize>,
+    init: I,
+    f: F,
+) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    I: Fn() -> S + Sync,
+    F: Fn(&mut S, T) -> R + Sync,
+{
+    let workers = threads.unwrap_or_else(cores).min(items.len());
+    if workers <= 1 {
+        let mut state = init();
+        return items.into_iter().map(|item| f(&mut state, item)).collect();
+    }
+    let slots: Vec<Mutex<Option<T>>> = items
+        .into_iter()
+        .map(|item| Mutex::new(Some(item)))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut state = None;
+        let mut done = vec![];
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(slot) = slots.get(i) else {
+                return done;
+            };
+            if let Some(item) = take(slot) {
+                let state = state.get_or_insert_with(&init);
+                done.push((i, f(state, item)));
+            }
+        }
+    };
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        // This thread works too, and finishes whatever threads that failed
+        // to start would have done
+        let handles: Vec<_> = (1..workers)
+            .filter_map(|_| thread::Builder::new().spawn_scoped(scope, work).ok())
+            .collect();
+        let mut results = work();
+        for handle in handles {
+            results.extend(
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e)),
+            );
+        }
+        results
+    });
+    results.sort_unstable_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
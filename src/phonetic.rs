@@ -0,0 +1,106 @@
+//! Phonetic keys for finding words that sound alike, such as "nite" and
+//! "night".
+//!
+//! This uses Lawrence Philips' original Metaphone rather than Soundex:
+//! Soundex keeps every consonant it sees, so it gives "nite" and "night"
+//! different codes, where Metaphone knows that "gh" before a consonant and
+//! the "k" in "kn" are silent. Both are tuned for English spelling.
+
+use crate::normalize::fold_diacritics;
+
+fn is_vowel(c: Option<char>) -> bool {
+    matches!(c, Some('a' | 'e' | 'i' | 'o' | 'u'))
+}
+
+/// The Metaphone key of a word: a short uppercase string of consonant
+/// sounds (with `0` for "th"), the same for words that sound alike.
+///
+/// Accents are folded first and anything but the letters a to z is
+/// ignored, so a word with none of those letters has an empty key.
+///
+/// ```
+/// # use magic_square::phonetic::metaphone;
+/// assert_eq!(metaphone("nite"), "NT");
+/// assert_eq!(metaphone("Knight"), "NT");
+/// assert_eq!(metaphone("phase"), metaphone("faze"));
+/// assert_eq!(metaphone("thumb"), "0M");
+/// ```
+pub fn metaphone(word: &str) -> String {
+    let mut letters: Vec<char> = vec![];
+    for c in fold_diacritics(&word.to_lowercase()).chars() {
+        // Doubled letters sound like one, except "cc" as in "accept"
+        if c.is_ascii_lowercase() && (letters.last() != Some(&c) || c == 'c') {
+            letters.push(c);
+        }
+    }
+    // Silent first letters
+    if let [a, b, ..] = letters[..] {
+        if matches!(
+            (a, b),
+            ('k', 'n') | ('g', 'n') | ('p', 'n') | ('a', 'e') | ('w', 'r')
+        ) {
+            letters.remove(0);
+        } else if (a, b) == ('w', 'h') {
+            letters.remove(1);
+        }
+    }
+    if letters.first() == Some(&'x') {
+        letters[0] = 's';
+    }
+
+    let at = |i: usize| letters.get(i).copied();
+    let mut key = String::new();
+    for (i, &c) in letters.iter().enumerate() {
+        let prev = i.checked_sub(1).and_then(at);
+        let next = at(i + 1);
+        let after = at(i + 2);
+        let sound = match c {
+            'a' | 'e' | 'i' | 'o' | 'u' => (i == 0).then_some(c.to_ascii_uppercase()),
+            // "b" is silent in a final "mb"
+            'b' => (prev != Some('m') || next.is_some()).then_some('B'),
+            'c' => match (next, after) {
+                (Some('i'), Some('a')) => Some('X'),
+                (Some('h'), _) if prev == Some('s') => Some('K'),
+                (Some('h'), _) => Some('X'),
+                (Some('i' | 'e' | 'y'), _) if prev == Some('s') => None,
+                (Some('i' | 'e' | 'y'), _) => Some('S'),
+                _ => Some('K'),
+            },
+            'd' if next == Some('g') && matches!(after, Some('e' | 'i' | 'y')) => Some('J'),
+            'd' => Some('T'),
+            'g' => match next {
+                // "gh" is silent unless it ends the word or comes before a
+                // vowel
+                Some('h') if after.is_some() && !is_vowel(after) => None,
+                // As is "g" in a final "gn" or "gned"
+                Some('n') if i + 2 == letters.len() => None,
+                Some('n') if letters[i + 2..] == ['e', 'd'] => None,
+                Some('i' | 'e' | 'y') => Some('J'),
+                _ => Some('K'),
+            },
+            // "h" is only sounded before a vowel, and after "c", "g", "p",
+            // "s" and "t" it is part of their sound
+            'h' => (is_vowel(next) && !matches!(prev, Some('c' | 'g' | 'p' | 's' | 't')))
+                .then_some('H'),
+            'k' => (prev != Some('c')).then_some('K'),
+            'p' if next == Some('h') => Some('F'),
+            'q' => Some('K'),
+            's' if next == Some('h') => Some('X'),
+            's' if next == Some('i') && matches!(after, Some('o' | 'a')) => Some('X'),
+            't' if next == Some('i') && matches!(after, Some('o' | 'a')) => Some('X'),
+            't' if next == Some('h') => Some('0'),
+            // Silent in "tch"
+            't' if next == Some('c') && after == Some('h') => None,
+            'v' => Some('F'),
+            'w' | 'y' => is_vowel(next).then_some(c.to_ascii_uppercase()),
+            'x' => {
+                key.push('K');
+                Some('S')
+            }
+            'z' => Some('S'),
+            c => Some(c.to_ascii_uppercase()),
+        };
+        key.extend(sound);
+    }
+    key
+}
@@ -0,0 +1,13 @@
+//! The types most programs need, for glob import:
+//!
+//! ```
+//! use magic_square::prelude::*;
+//! ```
+
+pub use crate::dictionary::Dictionary;
+pub use crate::generate::{generate, Generator};
+pub use crate::slot::{Direction, Slot};
+pub use crate::solutions::Solutions;
+pub use crate::square::{
+    CellOrder, Diagonals, LetterOrder, LiveView, MagicSquare, Overlap, Strategy,
+};
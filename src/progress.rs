@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use crate::render::{self, Target};
+
+/// How often the progress line is redrawn.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A single, continuously rewritten status line on stderr.
+pub(crate) struct Progress {
+    start: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    pub(crate) fn new() -> Progress {
+        Progress {
+            start: Instant::now(),
+            last_draw: None,
+        }
+    }
+
+    /// Whether enough time has passed since the last draw to draw again.
+    pub(crate) fn due(&self) -> bool {
+        self.last_draw
+            .is_none_or(|last| last.elapsed() >= REDRAW_INTERVAL)
+    }
+
+    /// Redraw the status line.
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts` - Letters tried so far.
+    /// * `depth` - Cells currently filled by the search.
+    /// * `backtracks` - Dead ends backed out of so far.
+    /// * `explored` - Estimated fraction (0 to 1) of the search tree covered.
+    pub(crate) fn draw(&mut self, attempts: usize, depth: usize, backtracks: usize, explored: f64) {
+        self.last_draw = Some(Instant::now());
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            attempts as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if explored > 0.0 {
+            format_duration(elapsed / explored - elapsed)
+        } else {
+            "?".to_string()
+        };
+
+        render::draw(Target::Stderr, || {
+            format!(
+                "\r\x1b[2K{} attempts | depth {} | {} backtracks | {:.0} attempts/s | {:.2}% explored, worst case {} left",
+                attempts,
+                depth,
+                backtracks,
+                rate,
+                explored * 100.0,
+                eta
+            )
+        });
+    }
+
+    /// Erase the status line so that later output starts on a clean line.
+    pub(crate) fn finish(&self) {
+        render::end_live();
+    }
+}
+
+/// Format a number of seconds as e.g. `42s`, `3m05s` or `2h10m`.
+fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "?".to_string();
+    }
+    let seconds = seconds.max(0.0) as u64;
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}h{:02}m", s / 3600, (s % 3600) / 60),
+    }
+}
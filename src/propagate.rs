@@ -0,0 +1,369 @@
+//! Candidate letters for every cell, kept consistent with the dictionary
+//! as the solver places letters, so dead ends show up as soon as they are
+//! made instead of cells later.
+//!
+//! Each cell holds the letters that could still go there. A row or column
+//! is revised by keeping only the letters that some word fits, where the
+//! word may only use letters its cells still hold; when a revision takes
+//! letters away from a cell, the slot crossing it is revised in turn (the
+//! AC-3 algorithm, with whole words as the constraints).
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::dictionary::Dictionary;
+use crate::letters::LetterSet;
+use crate::slot::{runs, Direction, Slot, BLOCK};
+
+/// What a grid's words must be: the dictionary, and how words are read.
+#[derive(Clone, Copy)]
+pub(crate) struct Rules<'a> {
+    pub(crate) dict: &'a Dictionary,
+    /// Counts every question put to the dictionary.
+    pub(crate) queries: &'a AtomicUsize,
+    pub(crate) direction: Direction,
+    /// Whether rows must be words backwards too.
+    pub(crate) mirror_rows: bool,
+    /// The fewest letters a word between blocks may have.
+    pub(crate) min_word_len: usize,
+}
+
+impl Rules<'_> {
+    /// Narrow the letters allowed at each position of a word, counting
+    /// the query. See `Dictionary::narrow`.
+    fn narrow(&self, allowed: &[Vec<char>]) -> Vec<Vec<char>> {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.dict.narrow(allowed)
+    }
+
+    /// Whether a run of cells between blocks, in a line of `len` cells,
+    /// must spell a word: always if it is the whole line, otherwise if it
+    /// is long enough.
+    pub(crate) fn is_word(&self, run: &Range<usize>, len: usize) -> bool {
+        run.len() == len || run.len() >= self.min_word_len
+    }
+}
+
+/// The letters each cell may still take, as sets over the dictionary's
+/// alphabet.
+#[derive(Clone)]
+pub(crate) struct Domains {
+    alphabet: Arc<[char]>,
+    /// The letter of each cell that was not blank to begin with, which
+    /// need not be in the alphabet, or a block.
+    fixed: Arc<Vec<Vec<Option<char>>>>,
+    cells: Vec<Vec<LetterSet>>,
+}
+
+/// The letters each cell of a slot could take with nothing placed but
+/// the grid's own letters, in grid order: the alphabet for a blank cell,
+/// and otherwise the letter in it.
+fn grid_letters(grid: &[Vec<char>], slot: Slot, alphabet: &[char]) -> Vec<Vec<char>> {
+    slot.cells(grid)
+        .into_iter()
+        .map(|c| if c == '_' { alphabet.to_vec() } else { vec![c] })
+        .collect()
+}
+
+/// The letters of each cell of a slot, in grid order, that some word
+/// fitting the slot as the grid stands has there.
+pub(crate) fn allowed(rules: Rules, grid: &[Vec<char>], slot: Slot) -> Vec<Vec<char>> {
+    let letters = grid_letters(grid, slot, rules.dict.alphabet());
+    Domains::narrow(rules, slot, letters)
+}
+
+/// Forward checking for one blank cell: whether a letter placed there
+/// leaves every other blank cell in its row and column some letter that
+/// both of that cell's slots allow. This catches a placement that starves
+/// a neighbouring cell at less cost than `Domains::propagate`, and what the
+/// crossing slots allow is worked out once per cell rather than per letter,
+/// since placing the letter does not change them.
+pub(crate) struct ForwardCheck {
+    /// The letters the cell's row and column could take before it.
+    in_row: Vec<Vec<char>>,
+    in_col: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    /// By column, what each column allows in the row (`None` for cells
+    /// that are not blank, and for this one).
+    across: Vec<Option<Vec<char>>>,
+    /// By row, what each row allows in the column.
+    down: Vec<Option<Vec<char>>>,
+}
+
+impl ForwardCheck {
+    pub(crate) fn new(
+        grid: &[Vec<char>],
+        rules: Rules,
+        (row, col): (usize, usize),
+    ) -> ForwardCheck {
+        let open = |r: usize, c: usize| (r, c) != (row, col) && grid[r][c] == '_';
+        let across = (0..grid[row].len())
+            .map(|c| open(row, c).then(|| allowed(rules, grid, Slot::Col(c)).swap_remove(row)))
+            .collect();
+        let down = (0..grid.len())
+            .map(|r| open(r, col).then(|| allowed(rules, grid, Slot::Row(r)).swap_remove(col)))
+            .collect();
+        let alphabet = rules.dict.alphabet();
+        ForwardCheck {
+            in_row: grid_letters(grid, Slot::Row(row), alphabet),
+            in_col: grid_letters(grid, Slot::Col(col), alphabet),
+            row,
+            col,
+            across,
+            down,
+        }
+    }
+
+    /// Whether `c` in the cell leaves every blank cell in its row and
+    /// column a letter.
+    pub(crate) fn allows(&self, rules: Rules, c: char) -> bool {
+        let fits = |letters: &[char], allowed: &Option<Vec<char>>| {
+            allowed
+                .as_ref()
+                .is_none_or(|allowed| letters.iter().any(|x| allowed.contains(x)))
+        };
+        let mut in_row = self.in_row.clone();
+        in_row[self.col] = vec![c];
+        let in_row = Domains::narrow(rules, Slot::Row(self.row), in_row);
+        if !in_row
+            .iter()
+            .zip(&self.across)
+            .all(|(letters, allowed)| fits(letters, allowed))
+        {
+            return false;
+        }
+        let mut in_col = self.in_col.clone();
+        in_col[self.row] = vec![c];
+        let in_col = Domains::narrow(rules, Slot::Col(self.col), in_col);
+        in_col
+            .iter()
+            .zip(&self.down)
+            .all(|(letters, allowed)| fits(letters, allowed))
+    }
+}
+
+impl Domains {
+    /// Every blank cell may take any letter of the alphabet, and every
+    /// other cell only the letter it holds. `None` if the alphabet has
+    /// more letters than a `LetterSet` holds.
+    pub(crate) fn new(grid: &[Vec<char>], alphabet: &[char]) -> Option<Domains> {
+        if alphabet.len() > LetterSet::CAPACITY {
+            return None;
+        }
+        let full = LetterSet::full(alphabet.len());
+        let cells = grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&c| if c == '_' { full } else { LetterSet::EMPTY })
+                    .collect()
+            })
+            .collect();
+        let fixed = grid
+            .iter()
+            .map(|row| row.iter().map(|&c| (c != '_').then_some(c)).collect())
+            .collect();
+        Some(Domains {
+            alphabet: alphabet.into(),
+            fixed: Arc::new(fixed),
+            cells,
+        })
+    }
+
+    /// How many letters a cell may still take.
+    pub(crate) fn count(&self, row: usize, col: usize) -> usize {
+        match self.fixed[row][col] {
+            Some(_) => 1,
+            None => self.cells[row][col].len(),
+        }
+    }
+
+    /// Whether a cell may still take `c`.
+    pub(crate) fn allows(&self, row: usize, col: usize, c: char) -> bool {
+        match self.fixed[row][col] {
+            Some(fixed) => fixed == c,
+            None => self
+                .alphabet
+                .binary_search(&c)
+                .is_ok_and(|i| self.cells[row][col].contains(i)),
+        }
+    }
+
+    /// The letters a cell may still take, in alphabet order.
+    pub(crate) fn cell_letters(&self, row: usize, col: usize) -> Vec<char> {
+        match self.fixed[row][col] {
+            Some(fixed) => vec![fixed],
+            None => self.cells[row][col].letters(&self.alphabet),
+        }
+    }
+
+    /// The one letter a blank cell may still take, if it is down to one.
+    pub(crate) fn only(&self, row: usize, col: usize) -> Option<char> {
+        if self.fixed[row][col].is_some() {
+            return None;
+        }
+        self.cells[row][col].only().map(|i| self.alphabet[i])
+    }
+
+    /// Place a letter of the alphabet in a blank cell, leaving it no
+    /// other.
+    pub(crate) fn assign(&mut self, row: usize, col: usize, c: char) {
+        self.cells[row][col] = LetterSet::of(&self.alphabet, &[c]);
+    }
+
+    /// The grid positions of a slot's cells, in grid order.
+    fn positions(&self, slot: Slot) -> Vec<(usize, usize)> {
+        match slot {
+            Slot::Row(row) => (0..self.cells[row].len()).map(|col| (row, col)).collect(),
+            Slot::Col(col) => (0..self.cells.len()).map(|row| (row, col)).collect(),
+        }
+    }
+
+    /// The candidate letters of a slot's cells, in grid order.
+    fn letters(&self, slot: Slot) -> Vec<Vec<char>> {
+        self.positions(slot)
+            .iter()
+            .map(|&(row, col)| self.cell_letters(row, col))
+            .collect()
+    }
+
+    /// The letters of each cell of a slot, in grid order, that some word
+    /// fitting the slot's candidates has there.
+    fn revise(&self, rules: Rules, slot: Slot) -> Vec<Vec<char>> {
+        Domains::narrow(rules, slot, self.letters(slot))
+    }
+
+    /// Narrow candidate letters for a slot's cells, in grid order, to
+    /// those some word fitting them all has there, each run between blocks
+    /// on its own.
+    fn narrow(rules: Rules, slot: Slot, mut letters: Vec<Vec<char>>) -> Vec<Vec<char>> {
+        let blocked = |cell: &Vec<char>| cell.as_slice() == [BLOCK];
+        if !letters.iter().any(blocked) {
+            return Domains::narrow_run(rules, slot, letters);
+        }
+        let len = letters.len();
+        for run in runs(&letters, blocked) {
+            if rules.is_word(&run, len) {
+                let narrowed = Domains::narrow_run(rules, slot, letters[run.clone()].to_vec());
+                letters.splice(run, narrowed);
+            }
+        }
+        letters
+    }
+
+    /// Narrow candidate letters for the cells of a slot, or of a run of it
+    /// between blocks, in grid order, to those some word fitting them all
+    /// has there.
+    fn narrow_run(rules: Rules, slot: Slot, mut letters: Vec<Vec<char>>) -> Vec<Vec<char>> {
+        if slot.is_reversed(rules.direction) {
+            letters.reverse();
+        }
+        let mut narrowed = rules.narrow(&letters);
+        // A mirrored row is two constraints on the same cells, so revise by
+        // each in turn until both hold
+        if rules.mirror_rows && matches!(slot, Slot::Row(_)) {
+            loop {
+                let mut both: Vec<Vec<char>> = narrowed.iter().rev().cloned().collect();
+                both = rules.narrow(&both);
+                both.reverse();
+                both = rules.narrow(&both);
+                if both == narrowed {
+                    break;
+                }
+                narrowed = both;
+            }
+        }
+        if slot.is_reversed(rules.direction) {
+            narrowed.reverse();
+        }
+        narrowed
+    }
+
+    /// Revise `slots`, and every slot crossing a cell that loses letters,
+    /// until nothing changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - What the grid's words must be.
+    /// * `slots` - The slots to revise first, such as those crossing a
+    ///   cell that was just assigned.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if every cell still has a letter.
+    /// * `false` if some cell has none left, so the grid cannot be
+    ///   completed. The domains are then partly revised, and should be
+    ///   thrown away.
+    pub(crate) fn propagate(&mut self, rules: Rules, slots: Vec<Slot>) -> bool {
+        let mut queue: VecDeque<Slot> = VecDeque::new();
+        for slot in slots {
+            if !queue.contains(&slot) {
+                queue.push_back(slot);
+            }
+        }
+        while let Some(slot) = queue.pop_front() {
+            let positions = self.positions(slot);
+            let narrowed = self.revise(rules, slot);
+            for (&(row, col), letters) in positions.iter().zip(narrowed) {
+                if letters.len() == self.count(row, col) {
+                    continue;
+                }
+                if letters.is_empty() {
+                    return false;
+                }
+                // Only a blank cell can lose letters without losing all
+                let kept = LetterSet::of(&self.alphabet, &letters);
+                self.cells[row][col] = self.cells[row][col].intersect(kept);
+                let crossing = match slot {
+                    Slot::Row(_) => Slot::Col(col),
+                    Slot::Col(_) => Slot::Row(row),
+                };
+                if !queue.contains(&crossing) {
+                    queue.push_back(crossing);
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{CellOrder, Diagonals, MagicSquare, Strategy};
+
+    #[test]
+    fn keeps_the_square_found_without_propagating() {
+        for (template, rows) in [("____", 4), ("_____", 5), ("___#/____/____/#___", 4)] {
+            let fill = |propagate| {
+                assert_fills(template, rows, Strategy::Letters, |square| {
+                    square.set_cell_order(CellOrder::Reading);
+                    square.set_propagate(propagate);
+                })
+            };
+            assert_eq!(fill(true).grid(), fill(false).grid(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn keeps_every_rule() {
+        let setups: [fn(&mut MagicSquare); 4] = [
+            |square| square.set_mirror_rows(true),
+            |square| square.set_diagonals(Diagonals::Both),
+            |square| square.set_symmetric(true),
+            |square| square.set_forward_check(true),
+        ];
+        for setup in setups {
+            for order in [CellOrder::Reading, CellOrder::MostConstrained] {
+                assert_fills("____", 4, Strategy::Letters, |square| {
+                    square.set_cell_order(order);
+                    square.set_propagate(true);
+                    setup(square);
+                });
+            }
+        }
+    }
+}
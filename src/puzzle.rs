@@ -0,0 +1,199 @@
+use std::fmt;
+
+use crate::dictionary::Dictionary;
+use crate::MagicSquare;
+
+/// The version of the puzzle file format written by this release.
+///
+/// * Version 0 is a bare grid: one row of letters and underscores per line.
+/// * Version 1 adds a header naming the format version, the solver version
+///   and a fingerprint of the dictionary, and an optional solution.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The first word(s) of a versioned puzzle file, followed by the version.
+const MAGIC: &str = "magic-square puzzle";
+
+/// A puzzle as saved to disk: its fixed letters, and optionally the
+/// solution found for it.
+pub struct Puzzle {
+    /// The format version the puzzle was read from (or `FORMAT_VERSION` for
+    /// puzzles created in memory).
+    pub version: u32,
+    /// The version of the solver that wrote the file, if recorded.
+    pub solver_version: Option<String>,
+    /// The `Dictionary::fingerprint` of the dictionary used, if recorded.
+    pub dictionary_hash: Option<u64>,
+    /// Rows of letters, underscores (blanks) and `#` (blocks).
+    pub grid: Vec<String>,
+    /// Rows of the filled square, if it was solved.
+    pub solution: Option<Vec<String>>,
+}
+
+/// Check a block of grid rows: letters, underscores and `#` blocks only,
+/// all the same length. Blank lines are skipped.
+fn parse_rows<'a, I: Iterator<Item = &'a str>>(lines: I) -> Result<Vec<String>, String> {
+    let mut rows: Vec<String> = vec![];
+    for line in lines {
+        let row = line.trim();
+        if row.is_empty() {
+            continue;
+        }
+        if let Some(c) = row
+            .chars()
+            .find(|c| *c != '_' && *c != '#' && !c.is_alphabetic())
+        {
+            return Err(format!("unexpected character '{}' in row \"{}\"", c, row));
+        }
+        if let Some(first) = rows.first() {
+            if first.chars().count() != row.chars().count() {
+                return Err(format!(
+                    "row \"{}\" has a different length than row \"{}\"",
+                    row, first
+                ));
+            }
+        }
+        rows.push(row.to_lowercase());
+    }
+
+    if rows.is_empty() {
+        return Err("no rows given".to_string());
+    }
+    Ok(rows)
+}
+
+/// Migrate a version 0 file (a bare grid) to the current model.
+fn migrate_v0(text: &str) -> Result<Puzzle, String> {
+    Ok(Puzzle {
+        version: 0,
+        solver_version: None,
+        dictionary_hash: None,
+        grid: parse_rows(text.lines())?,
+        solution: None,
+    })
+}
+
+/// Parse the body (everything after the magic line) of a version 1 file.
+fn parse_v1<'a, I: Iterator<Item = &'a str>>(lines: I) -> Result<Puzzle, String> {
+    let mut puzzle = Puzzle {
+        version: 1,
+        solver_version: None,
+        dictionary_hash: None,
+        grid: vec![],
+        solution: None,
+    };
+    let mut grid: Vec<&str> = vec![];
+    let mut solution: Vec<&str> = vec![];
+    // Which block the following lines belong to, once a block has started
+    let mut block: Option<&str> = None;
+
+    for line in lines {
+        let line = line.trim();
+        match (block, line.split_once(' ')) {
+            (_, None) if line == "[grid]" || line == "[solution]" => block = Some(line),
+            (Some("[grid]"), _) => grid.push(line),
+            (Some(_), _) => solution.push(line),
+            (None, Some(("solver", version))) => {
+                puzzle.solver_version = Some(version.to_string());
+            }
+            (None, Some(("dictionary", hash))) => {
+                let hash = u64::from_str_radix(hash, 16)
+                    .map_err(|_| format!("bad dictionary fingerprint {}", hash))?;
+                puzzle.dictionary_hash = Some(hash);
+            }
+            (None, _) if line.is_empty() => {}
+            (None, _) => return Err(format!("unexpected header line {}", line)),
+        }
+    }
+
+    puzzle.grid = parse_rows(grid.into_iter())?;
+    if !solution.is_empty() {
+        puzzle.solution = Some(parse_rows(solution.into_iter())?);
+    }
+    Ok(puzzle)
+}
+
+impl Puzzle {
+    /// Describe a square as a puzzle: its fixed letters, plus the filled
+    /// grid as the solution if every cell has a letter.
+    pub fn from_square(square: &MagicSquare) -> Puzzle {
+        let rows = |grid: &[Vec<char>]| -> Vec<String> {
+            grid.iter().map(|row| row.iter().collect()).collect()
+        };
+        let solved = square.grid().iter().flatten().all(|c| *c != '_');
+        Puzzle {
+            version: FORMAT_VERSION,
+            solver_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            dictionary_hash: Some(square.dict.fingerprint()),
+            grid: rows(&square.fixed_grid()),
+            solution: solved.then(|| rows(square.grid())),
+        }
+    }
+
+    /// Parse a puzzle file of any supported version, migrating older
+    /// versions to the current model.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Puzzle)` if the file is well-formed.
+    /// * `Err(String)` if it is malformed or from a newer release.
+    pub fn parse(text: &str) -> Result<Puzzle, String> {
+        let mut lines = text.lines().skip_while(|l| l.trim().is_empty());
+        let Some(version) = lines
+            .next()
+            .and_then(|first| first.trim().strip_prefix(MAGIC))
+        else {
+            // No header: a bare grid from before files were versioned
+            return migrate_v0(text);
+        };
+
+        let version = version
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("bad format version {}", version.trim()))?;
+        match version {
+            1 => parse_v1(lines),
+            v if v > FORMAT_VERSION => Err(format!(
+                "puzzle format {} was written by a newer release (this one reads up to {})",
+                v, FORMAT_VERSION
+            )),
+            v => Err(format!("unknown puzzle format {}", v)),
+        }
+    }
+
+    /// The grid in the slash-separated template form used on the command
+    /// line and by `MagicSquare::from_template`.
+    pub fn template(&self) -> String {
+        self.grid.join("/")
+    }
+
+    /// Whether the puzzle was saved with a different dictionary than `dict`.
+    /// Puzzles that did not record a dictionary never mismatch.
+    pub fn dictionary_mismatch(&self, dict: &Dictionary) -> bool {
+        self.dictionary_hash
+            .is_some_and(|hash| hash != dict.fingerprint())
+    }
+}
+
+impl fmt::Display for Puzzle {
+    /// Write the puzzle in the current format version.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", MAGIC, FORMAT_VERSION)?;
+        if let Some(version) = &self.solver_version {
+            writeln!(f, "solver {}", version)?;
+        }
+        if let Some(hash) = self.dictionary_hash {
+            writeln!(f, "dictionary {:016x}", hash)?;
+        }
+        writeln!(f, "[grid]")?;
+        for row in self.grid.iter() {
+            writeln!(f, "{}", row)?;
+        }
+        if let Some(solution) = &self.solution {
+            writeln!(f, "[solution]")?;
+            for row in solution.iter() {
+                writeln!(f, "{}", row)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+//! A small regular-expression engine for dictionary queries richer than
+//! templates: literals, `.`, classes such as `[aeiou]`, `[a-f]` and
+//! `[^xyz]`, groups, alternation with `|`, the repeats `*`, `+`, `?`,
+//! `{n}`, `{n,}` and `{n,m}`, and the anchors `^` and `$`. A backslash
+//! makes the next character literal.
+//!
+//! Patterns are compiled to a nondeterministic automaton and run by
+//! tracking every state at once, so matching takes time linear in the
+//! length of the word whatever the pattern.
+
+/// The most copies a counted repeat such as `{n,m}` may expand to.
+const MAX_REPEAT: usize = 100;
+
+/// A set of characters: ranges, possibly negated.
+#[derive(Clone, Debug)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn literal(c: char) -> Class {
+        Class {
+            ranges: vec![(c, c)],
+            negated: false,
+        }
+    }
+
+    fn any() -> Class {
+        Class {
+            ranges: vec![],
+            negated: true,
+        }
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+    }
+}
+
+/// A parsed pattern.
+#[derive(Clone, Debug)]
+enum Node {
+    Empty,
+    Class(Class),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>),
+    Start,
+    End,
+}
+
+/// A state of the automaton. States are numbered by their index in
+/// `Regex::states`.
+#[derive(Clone, Debug)]
+enum State {
+    /// Consume a character in the class, then go to the state.
+    Char(Class, usize),
+    /// Go to both states without consuming anything.
+    Split(usize, usize),
+    /// Go to the state only at the start of the word.
+    AssertStart(usize),
+    /// Go to the state only at the end of the word.
+    AssertEnd(usize),
+    Match,
+}
+
+/// A compiled pattern.
+pub(crate) struct Regex {
+    states: Vec<State>,
+    start: usize,
+}
+
+/// A recursive-descent parser over the characters of a pattern.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// alternation := concat ('|' concat)*
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    /// concat := repeat*
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut items = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            items.push(self.repeat()?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    /// repeat := atom ('*' | '+' | '?' | '{' n (',' m?)? '}')*
+    fn repeat(&mut self) -> Result<Node, String> {
+        let mut node = self.atom()?;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => {
+                    self.pos += 1;
+                    self.counts()?
+                }
+                _ => return Ok(node),
+            };
+            // Past the operator, or the `}` of a count
+            self.pos += 1;
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+
+    /// The `n`, `n,` or `n,m` of a counted repeat, up to (not past) `}`.
+    fn counts(&mut self) -> Result<(usize, Option<usize>), String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != '}') {
+            self.pos += 1;
+        }
+        if self.peek().is_none() {
+            return Err("unterminated {".to_string());
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let number = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("bad repeat {{{}}}", text))
+        };
+        let (min, max) = match text.split_once(',') {
+            None => (number(&text)?, Some(number(&text)?)),
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+        };
+        if max.is_some_and(|max| max < min) || min.max(max.unwrap_or(0)) > MAX_REPEAT {
+            return Err(format!("bad repeat {{{}}}", text));
+        }
+        Ok((min, max))
+    }
+
+    /// atom := '(' alternation ')' | '[' class ']' | '.' | '^' | '$' | '\' c | c
+    fn atom(&mut self) -> Result<Node, String> {
+        let at = self.pos;
+        match self.next() {
+            Some('(') => {
+                let node = self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err(format!("unclosed ( at offset {}", at));
+                }
+                Ok(node)
+            }
+            Some('[') => self.class(at),
+            Some('.') => Ok(Node::Class(Class::any())),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => match self.next() {
+                Some(c) => Ok(Node::Class(Class::literal(c))),
+                None => Err("pattern ends with \\".to_string()),
+            },
+            Some(c @ ('*' | '+' | '?' | '{')) => {
+                Err(format!("{} at offset {} has nothing to repeat", c, at))
+            }
+            Some(')') => Err(format!("unmatched ) at offset {}", at)),
+            Some(c) => Ok(Node::Class(Class::literal(c))),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    /// The rest of a `[...]` class, after the `[`.
+    fn class(&mut self, at: usize) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = vec![];
+        // A `]` right after the `[` (or `[^`) is a literal
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                None => return Err(format!("unclosed [ at offset {}", at)),
+                Some(']') if !first => break,
+                Some('\\') => self.next().ok_or("pattern ends with \\")?,
+                Some(c) => c,
+            };
+            first = false;
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let hi = match self.next() {
+                    Some('\\') => self.next().ok_or("pattern ends with \\")?,
+                    Some(hi) => hi,
+                    None => return Err(format!("unclosed [ at offset {}", at)),
+                };
+                if hi < c {
+                    return Err(format!("bad range {}-{}", c, hi));
+                }
+                ranges.push((c, hi));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Node::Class(Class { ranges, negated }))
+    }
+}
+
+impl Regex {
+    /// Compile a pattern.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Regex)` if the pattern is well-formed.
+    /// * `Err(String)` describing the first error in it.
+    pub(crate) fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unmatched ) at offset {}", parser.pos));
+        }
+        let mut regex = Regex {
+            states: vec![State::Match],
+            start: 0,
+        };
+        regex.start = regex.compile(&node, 0);
+        Ok(regex)
+    }
+
+    fn push(&mut self, state: State) -> usize {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    /// Add the states for `node`, continuing to state `next`, and return
+    /// the state to enter them by.
+    fn compile(&mut self, node: &Node, next: usize) -> usize {
+        match node {
+            Node::Empty => next,
+            Node::Class(class) => self.push(State::Char(class.clone(), next)),
+            Node::Start => self.push(State::AssertStart(next)),
+            Node::End => self.push(State::AssertEnd(next)),
+            Node::Concat(items) => items
+                .iter()
+                .rev()
+                .fold(next, |next, item| self.compile(item, next)),
+            Node::Alternate(branches) => {
+                let entries: Vec<usize> = branches.iter().map(|b| self.compile(b, next)).collect();
+                entries
+                    .into_iter()
+                    .reduce(|a, b| self.push(State::Split(a, b)))
+                    .unwrap_or(next)
+            }
+            Node::Repeat(body, min, max) => {
+                // The optional copies come after the required ones
+                let mut entry = match max {
+                    None => {
+                        let split = self.push(State::Split(next, next));
+                        let body = self.compile(body, split);
+                        self.states[split] = State::Split(body, next);
+                        split
+                    }
+                    Some(max) => (*min..*max).fold(next, |next, _| {
+                        let body = self.compile(body, next);
+                        self.push(State::Split(body, next))
+                    }),
+                };
+                for _ in 0..*min {
+                    entry = self.compile(body, entry);
+                }
+                entry
+            }
+        }
+    }
+
+    /// Add `state` and every state reachable from it without consuming a
+    /// character at position `pos` of a word of `len` characters.
+    fn close(&self, state: usize, pos: usize, len: usize, set: &mut Vec<usize>, seen: &mut [bool]) {
+        if seen[state] {
+            return;
+        }
+        seen[state] = true;
+        match self.states[state] {
+            State::Split(a, b) => {
+                self.close(a, pos, len, set, seen);
+                self.close(b, pos, len, set, seen);
+            }
+            State::AssertStart(next) if pos == 0 => self.close(next, pos, len, set, seen),
+            State::AssertEnd(next) if pos == len => self.close(next, pos, len, set, seen),
+            _ => set.push(state),
+        }
+    }
+
+    /// Whether the pattern matches anywhere in `word`.
+    pub(crate) fn is_match(&self, word: &str) -> bool {
+        let chars: Vec<char> = word.chars().collect();
+        let len = chars.len();
+        let mut seen = vec![false; self.states.len()];
+        let mut current = vec![];
+        for pos in 0..=len {
+            // A match may start at any position
+            self.close(self.start, pos, len, &mut current, &mut seen);
+            if current
+                .iter()
+                .any(|&s| matches!(self.states[s], State::Match))
+            {
+                return true;
+            }
+            let Some(&c) = chars.get(pos) else {
+                break;
+            };
+            seen.iter_mut().for_each(|s| *s = false);
+            let mut next = vec![];
+            for &state in current.iter() {
+                if let State::Char(class, to) = &self.states[state] {
+                    if class.contains(c) {
+                        self.close(*to, pos + 1, len, &mut next, &mut seen);
+                    }
+                }
+            }
+            current = next;
+        }
+        false
+    }
+}
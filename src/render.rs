@@ -0,0 +1,100 @@
+//! One place for everything the solver draws on the terminal while it
+//! searches, so that live output from any number of searches never
+//! interleaves.
+//!
+//! Live frames (the redrawn grid, the progress line) go through `draw`,
+//! which writes at most one frame per `FRAME_INTERVAL` and otherwise keeps
+//! only the newest, dropping the rest. `end_live` discards any frame still
+//! pending and clears the progress line, and `exclusive` runs a closure
+//! with the terminal to itself, so a final solution or a stats report is
+//! never split by a frame.
+
+use std::io::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The least time between two live frames.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where a frame is drawn.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Target {
+    Stdout,
+    Stderr,
+}
+
+/// The state of the terminal, shared by every search in the process.
+struct Terminal {
+    /// When the last frame was written.
+    last_draw: Option<Instant>,
+    /// Whether the last frame was a status line on stderr that has not
+    /// been erased yet.
+    status_line: bool,
+}
+
+static TERMINAL: Mutex<Terminal> = Mutex::new(Terminal {
+    last_draw: None,
+    status_line: false,
+});
+
+/// Lock the terminal. A panic while it was held leaves nothing half
+/// updated that matters, so a poisoned lock is taken anyway.
+fn lock() -> MutexGuard<'static, Terminal> {
+    TERMINAL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Write `text` to `target` and flush it.
+fn write(target: Target, text: &str) {
+    match target {
+        Target::Stdout => {
+            let mut out = std::io::stdout().lock();
+            let _ = out.write_all(text.as_bytes());
+            let _ = out.flush();
+        }
+        Target::Stderr => {
+            let mut out = std::io::stderr().lock();
+            let _ = out.write_all(text.as_bytes());
+            let _ = out.flush();
+        }
+    }
+}
+
+/// Draw a live frame, unless one was drawn less than `FRAME_INTERVAL` ago.
+/// `frame` is only called to build the frame if it will be written.
+pub(crate) fn draw<F: FnOnce() -> String>(target: Target, frame: F) {
+    let mut terminal = lock();
+    if terminal
+        .last_draw
+        .is_some_and(|last| last.elapsed() < FRAME_INTERVAL)
+    {
+        return;
+    }
+    terminal.last_draw = Some(Instant::now());
+    terminal.status_line = target == Target::Stderr;
+    write(target, &frame());
+}
+
+/// End live output: erase the status line if one is showing, so later
+/// output starts on a clean line, and let the next search draw at once.
+pub(crate) fn end_live() {
+    let mut terminal = lock();
+    if terminal.status_line {
+        write(Target::Stderr, "\r\x1b[2K");
+        terminal.status_line = false;
+    }
+    terminal.last_draw = None;
+}
+
+/// Run `f` with the terminal to itself: no live frame from any search is
+/// drawn until it returns, so everything it prints stays together. Use it
+/// for final results and reports.
+///
+/// `f` must not start a search with a live view, which would wait for the
+/// terminal forever.
+pub fn exclusive<R, F: FnOnce() -> R>(f: F) -> R {
+    end_live();
+    let _terminal = lock();
+    f()
+}
@@ -0,0 +1,288 @@
+use crate::alternates::CellLetters;
+use crate::check::{self, check, Feasibility};
+use crate::count::SolutionCount;
+use crate::slot::Slot;
+use crate::unique::Uniqueness;
+use crate::MagicSquare;
+
+/// A row or column of a grid, and how many dictionary words fit it.
+struct SlotReport {
+    name: String,
+    template: String,
+    candidates: usize,
+}
+
+/// Compute the candidate count of every row and column of `grid`, read in
+/// the square's direction.
+fn slot_reports(square: &MagicSquare, grid: &[Vec<char>]) -> Vec<SlotReport> {
+    Slot::all(grid)
+        .into_iter()
+        .map(|slot| {
+            let template: String = slot.read(grid, square.direction).into_iter().collect();
+            SlotReport {
+                candidates: check::candidates(square, &template),
+                name: slot.to_string(),
+                template,
+            }
+        })
+        .collect()
+}
+
+/// Quote and escape a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_grid(grid: &[Vec<char>]) -> String {
+    let rows: Vec<String> = grid
+        .iter()
+        .map(|r| json_string(&r.iter().collect::<String>()))
+        .collect();
+    format!("[{}]", rows.join(","))
+}
+
+fn json_slots(slots: &[SlotReport]) -> String {
+    let slots: Vec<String> = slots
+        .iter()
+        .map(|s| {
+            format!(
+                "{{\"slot\":{},\"template\":{},\"candidates\":{}}}",
+                json_string(&s.name),
+                json_string(&s.template),
+                s.candidates
+            )
+        })
+        .collect();
+    format!("[{}]", slots.join(","))
+}
+
+fn json_cells(cells: &[(usize, usize)]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|(row, col)| format!("[{},{}]", row, col))
+        .collect();
+    format!("[{}]", cells.join(","))
+}
+
+/// Render the result of a feasibility [`check`] as a JSON object.
+pub fn check_json(feasibility: &Feasibility) -> String {
+    let slots: Vec<SlotReport> = feasibility
+        .slots
+        .iter()
+        .map(|s| SlotReport {
+            name: s.slot.to_string(),
+            template: s.template.clone(),
+            candidates: s.candidates,
+        })
+        .collect();
+    format!(
+        "{{\"feasible\":{},\"slots\":{},\"dead_cells\":{}}}",
+        feasibility.is_plausible(),
+        json_slots(&slots),
+        json_cells(&feasibility.dead_cells)
+    )
+}
+
+/// Render the result of a solution [`count`](crate::count::count) as a
+/// JSON object.
+pub fn count_json(counted: &SolutionCount) -> String {
+    format!(
+        "{{\"solutions\":{},\"exact\":{}}}",
+        counted.solutions, counted.exact
+    )
+}
+
+/// Render the result of a [`verify_unique`](crate::unique::verify_unique)
+/// check as a JSON object: its `status` is `unique` with the `grid`,
+/// `ambiguous` with the first two `grids` found and the `cells` they
+/// differ at, or `unsolvable`.
+pub fn unique_json(uniqueness: &Uniqueness) -> String {
+    match uniqueness {
+        Uniqueness::Unsolvable => "{\"status\":\"unsolvable\"}".to_string(),
+        Uniqueness::Unique(grid) => {
+            format!("{{\"status\":\"unique\",\"grid\":{}}}", json_grid(grid))
+        }
+        Uniqueness::Ambiguous(first, second) => format!(
+            "{{\"status\":\"ambiguous\",\"grids\":[{},{}],\"cells\":{}}}",
+            json_grid(first),
+            json_grid(second),
+            json_cells(&Uniqueness::differences(first, second))
+        ),
+    }
+}
+
+/// Render a filled square as a JSON object, with the seed it was shuffled
+/// with if it was (see [`SolveStats::seed`](crate::SolveStats::seed)), and
+/// the soft rules it breaks if it has any (see
+/// [`MagicSquare::violations`]).
+pub fn solved_json(square: &MagicSquare) -> String {
+    let seed = match square.solve_stats().seed {
+        Some(seed) => format!(",\"seed\":{}", seed),
+        None => String::new(),
+    };
+    // Present, if empty, whenever there are soft rules to break
+    let violations = if square.soft_rules.is_empty() {
+        String::new()
+    } else {
+        let violations: Vec<String> = square
+            .violations()
+            .iter()
+            .map(|(rule, count)| {
+                format!("{{\"rule\":{},\"count\":{}}}", json_string(&rule.to_string()), count)
+            })
+            .collect();
+        format!(",\"violations\":[{}]", violations.join(","))
+    };
+    format!(
+        "{{\"status\":\"solved\",\"grid\":{}{}{}}}",
+        json_grid(&square.square),
+        seed,
+        violations
+    )
+}
+
+/// Render a structured report explaining why a square could not be filled.
+///
+/// Its `status` is `gave_up` if the search stopped at its attempt or time
+/// limit, so the square may yet have a solution, or `unsolvable` if it
+/// tried everything. The report holds:
+///
+/// * `feasibility` - the candidate count of every row and column given only
+///   the fixed letters, and any blank cells no letter can fill, as computed
+///   by [`check`]; either makes the puzzle infeasible outright.
+/// * `deepest` - the most complete grid the search reached, with the
+///   candidate counts of its rows and columns and the slots it left
+///   unfilled.
+/// * `suggestions` - constraints worth relaxing, most specific first.
+pub fn failure_json(square: &MagicSquare) -> String {
+    let feasibility = check(square);
+    let deepest_slots = slot_reports(square, &square.deepest);
+    let unfilled: Vec<String> = square
+        .unfilled_slots()
+        .iter()
+        .map(|slot| json_string(&slot.to_string()))
+        .collect();
+
+    let mut suggestions = vec![];
+    if square.gave_up() {
+        suggestions.push("allow the search more attempts or time".to_string());
+    }
+    for slot in feasibility.slots.iter().filter(|s| s.candidates == 0) {
+        suggestions.push(format!(
+            "{} ({}) matches no words; unfix some of its letters",
+            slot.slot, slot.template
+        ));
+    }
+    for (row, col) in feasibility.dead_cells.iter() {
+        suggestions.push(format!(
+            "no letter fits both row {} and column {} at ({}, {}); unfix letters that cross it",
+            row, col, row, col
+        ));
+    }
+    if feasibility.is_plausible() {
+        for slot in deepest_slots.iter().filter(|s| s.candidates == 0) {
+            suggestions.push(format!(
+                "{} ran out of candidates at {}; relax the fixed letters that cross it",
+                slot.name, slot.template
+            ));
+        }
+    }
+    suggestions.push("use a larger dictionary".to_string());
+    if square.square.len() > 2 {
+        suggestions.push(format!("try {} rows", square.square.len() - 1));
+    }
+
+    let suggestions: Vec<String> = suggestions.iter().map(|s| json_string(s)).collect();
+    format!(
+        concat!(
+            "{{\"status\":\"{}\",",
+            "\"feasibility\":{},",
+            "\"deepest\":{{\"filled\":{},\"grid\":{},\"slots\":{},\"unfilled\":[{}]}},",
+            "\"attempts\":{},",
+            "\"suggestions\":[{}]}}"
+        ),
+        if square.gave_up() {
+            "gave_up"
+        } else {
+            "unsolvable"
+        },
+        check_json(&feasibility),
+        square.deepest_depth,
+        json_grid(&square.deepest),
+        json_slots(&deepest_slots),
+        unfilled.join(","),
+        square._attempt,
+        suggestions.join(",")
+    )
+}
+
+/// Render the result of an [`alternates`](crate::alternates::alternates)
+/// analysis as a JSON object: the first solution found, and every blank
+/// cell with its possible letters and whether it is forced.
+pub fn alternates_json(square: &MagicSquare, cells: &[CellLetters]) -> String {
+    let unique = cells.iter().all(CellLetters::is_forced);
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| {
+            let letters: Vec<String> = cell
+                .letters
+                .iter()
+                .map(|c| json_string(&c.to_string()))
+                .collect();
+            format!(
+                "{{\"cell\":[{},{}],\"forced\":{},\"letters\":[{}]}}",
+                cell.row,
+                cell.col,
+                cell.is_forced(),
+                letters.join(",")
+            )
+        })
+        .collect();
+    format!(
+        "{{\"status\":\"solved\",\"unique\":{},\"grid\":{},\"cells\":[{}]}}",
+        unique,
+        json_grid(&square.square),
+        cells.join(",")
+    )
+}
+
+/// Spell a grid row with spaces between the letters, in capitals.
+fn spaced_row(row: &[char]) -> String {
+    let letters: Vec<String> = row.iter().map(|c| c.to_uppercase().to_string()).collect();
+    letters.join(" ")
+}
+
+/// Render a filled square for pasting into a chat: the puzzle (its fixed
+/// letters, with blanks) in a monospace code block, the number of words
+/// to find, and the solution one row per line in Discord-style `||...||`
+/// spoiler tags, so readers reveal it only when they want to.
+pub fn chat(square: &MagicSquare) -> String {
+    let grid = square.fixed_grid();
+    let (rows, cols) = (square.square.len(), square.square[0].len());
+    let mut out = String::from("```\n");
+    for row in grid.iter() {
+        out.push_str(&spaced_row(row));
+        out.push('\n');
+    }
+    out.push_str("```\n");
+    out.push_str(&format!(
+        "{} words ({} across, {} down)\n",
+        rows + cols,
+        rows,
+        cols
+    ));
+    for row in square.square.iter() {
+        out.push_str(&format!("||`{}`||\n", spaced_row(row)));
+    }
+    out
+}
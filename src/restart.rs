@@ -0,0 +1,124 @@
+//! Restarting a search that is taking too long, in a new random order.
+//!
+//! How long a backtracking search takes depends heavily on the order it
+//! tries letters in: most orders find a square quickly, but a few make an
+//! early choice with no square below it and spend a very long time proving
+//! that. Searching again in another order, with a limit on attempts, gets
+//! past those. The limits follow the Luby sequence (1, 1, 2, 1, 1, 2, 4,
+//! ...) times a unit, which is never far from the best fixed limit without
+//! needing to know it (Luby, Sinclair and Zuckerman, 1993), and keeps
+//! growing, so a search long enough to finish is always reached.
+
+use crate::MagicSquare;
+
+/// The `i`th term of the Luby sequence, counting from 1.
+pub(crate) fn luby(mut i: usize) -> usize {
+    loop {
+        // The sequence up to 2^k - 1 is two copies of the sequence up to
+        // 2^(k - 1) - 1, then 2^(k - 1)
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+impl MagicSquare {
+    /// Search until a square is found or shown not to exist, restarting in
+    /// a new order whenever a search uses up its attempts. See
+    /// [`MagicSquare::set_restarts`].
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - The attempts to allow the first search.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled.
+    /// * `Err(String)` if some search ended without filling it, or the
+    ///   attempt or time limit was reached.
+    pub(crate) fn fill_with_restarts(&mut self, unit: usize) -> Result<(), String> {
+        let seed = self.seed;
+        // A search that gives up leaves its letters where they are
+        let fixed = self.fixed_grid();
+        let give_up_at = self.attempt_limit.map(|limit| self._attempt + limit);
+        let mut run = 1;
+        let result = loop {
+            let mut limit = unit.max(1).saturating_mul(luby(run));
+            if let Some(at) = give_up_at {
+                limit = limit.min(at.saturating_sub(self._attempt));
+            }
+            debug!(
+                "search {} with seed {:?}, up to {} attempts",
+                run, self.seed, limit
+            );
+            self.reset_to(&fixed);
+            let result = self.search(Some(limit));
+            if !self.gave_up() || self.out_of_time() {
+                break result;
+            }
+            if let (Some(at), Some(total)) = (give_up_at, self.attempt_limit) {
+                if self._attempt >= at {
+                    break Err(format!("Gave up after {} attempts", total));
+                }
+            }
+            // The first search keeps the order it was given, and each after
+            // it tries a new one
+            self.seed = Some(seed.unwrap_or(0).wrapping_add(run as u64));
+            run += 1;
+        };
+        // A search racing others reports to the one that started it
+        if self.cancel.is_none() {
+            info!(
+                "{} attempts, {} backtracks, over {} searches",
+                self._attempt,
+                self.backtracks.iter().sum::<usize>(),
+                run
+            );
+            self.log_nogood_stats();
+        }
+        self.seed = seed;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::luby;
+    use crate::test_util::{assert_fills, UNSOLVABLE};
+    use crate::{Diagonals, Strategy};
+
+    #[test]
+    fn luby_sequence() {
+        let terms: Vec<usize> = (1..=15).map(luby).collect();
+        assert_eq!(terms, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn restarted_fills_keep_every_rule() {
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            assert_fills("____", 4, strategy, |square| square.set_restarts(Some(5)));
+            assert_fills("___", 3, strategy, |square| {
+                square.set_restarts(Some(5));
+                square.set_mirror_rows(true);
+            });
+            assert_fills("____", 4, strategy, |square| {
+                square.set_restarts(Some(5));
+                square.set_diagonals(Diagonals::Main);
+            });
+        }
+    }
+
+    #[test]
+    fn shows_there_is_no_square() {
+        let dict = UNSOLVABLE.dictionary();
+        let mut square = UNSOLVABLE.square(&dict);
+        square.set_restarts(Some(1));
+        assert!(square.fill().is_err());
+        assert!(!square.gave_up());
+    }
+}
@@ -0,0 +1,48 @@
+//! A small seedable random number generator, for shuffling the solver's
+//! letter order reproducibly without depending on a crate.
+
+/// A splitmix64 generator: fast, statistically sound for shuffling, and
+/// the same sequence on every platform for a given seed. Not for anything
+/// that needs to be unpredictable.
+#[derive(Clone, Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// Where the generator has got to: `Rng::new` of it carries on with
+    /// the same sequence.
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut x = self.state;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    /// A number in `0..n`, which must not be 0. The slight bias of taking a
+    /// remainder is far too small to matter for `n` in the dozens.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A number in `0.0..1.0`, from the top 53 bits of the next output.
+    pub(crate) fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Put `items` in a random order (a Fisher-Yates shuffle).
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
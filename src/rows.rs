@@ -0,0 +1,237 @@
+//! Filling a square a whole row at a time.
+//!
+//! Where the letter-by-letter search checks a row and a column after every
+//! letter, this tries each word that fits a row's fixed letters and leaves
+//! every column the start of some word. A step costs more, but a square
+//! takes only one step per row.
+
+use crate::propagate;
+use crate::slot::{runs, Slot, BLOCK};
+use crate::square::Diagonals;
+use crate::MagicSquare;
+
+impl MagicSquare {
+    /// Fill the rows from `row` down with words, so that every column stays
+    /// a word or a template some word matches.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The first row to fill. Rows above it are already filled.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the rest of the square was filled.
+    /// * `Err(String)` if no word fits some row, leaving the rows from
+    ///   `row` down as they were.
+    pub(crate) fn fill_rows(&mut self, row: usize) -> Result<(), String> {
+        if row == self.square.len() {
+            return Ok(());
+        }
+        // A row the solver may not write was checked before the search
+        if !self.has_blank(row) {
+            return self.fill_rows(row + 1);
+        }
+
+        let first = (0..self.square[row].len()).find(|&col| self.editable_mask[row][col]);
+        let key = first.and_then(|col| self.nogood_key(row, col));
+        if key.as_ref().is_some_and(|key| self.is_nogood(key)) {
+            return Err(format!("Could not fill row {}", row));
+        }
+
+        let words = self.row_words(row);
+        let choices = words.len();
+        let before = self.square[row].clone();
+        for (i, word) in words.into_iter().enumerate() {
+            // Out of attempts: unwind without trying anything else
+            if self.should_stop() {
+                return Err(format!("Gave up at row {}", row));
+            }
+            self._attempt += 1;
+            let letters = Slot::Row(row).orient(self.direction, word.chars().collect());
+            self.set_row(row, letters);
+            if !self.may_beat_incumbent() {
+                continue;
+            }
+            self.draw_grid();
+            self.path.push((i, choices));
+            self.draw_progress();
+            self.note_depth();
+            if let Ok(()) = self.fill_rows(row + 1) {
+                return Ok(());
+            }
+            self.path.pop();
+        }
+
+        // If no word fits, backtrack, remembering why unless the search
+        // below stopped early. Rows too poor to beat the incumbent are no
+        // reason: the rows above count towards the score, and the state
+        // does not record them
+        if let Some(key) = key {
+            if !self.should_stop() && !self.bounds_score() {
+                self.record_nogood(key);
+            }
+        }
+        self.set_row(row, before);
+        let depth = self.note_backtrack();
+        trace!("backtracking from row {} at depth {}", row, depth);
+        Err(format!("Could not fill row {}", row))
+    }
+
+    /// Whether a row has a cell the solver may write that is still blank.
+    pub(crate) fn has_blank(&self, row: usize) -> bool {
+        (0..self.square[row].len())
+            .any(|col| self.editable_mask[row][col] && self.square[row][col] == '_')
+    }
+
+    /// The words that fit a row as it stands and leave every column a word
+    /// or a template some word matches, in the order to try them:
+    /// alphabetical order, or shuffled if a seed is set, and most common
+    /// first if the dictionary has word frequencies.
+    pub(crate) fn row_words(&mut self, row: usize) -> Vec<String> {
+        let template: String = Slot::Row(row)
+            .read(&self.square, self.direction)
+            .into_iter()
+            .collect();
+        // The letters each column can take in this row, found once for all
+        // the row's words, in the order the row is read
+        let mut allowed: Vec<Vec<char>> = (0..self.square[row].len())
+            .map(|col| self.column_allows(row, col))
+            .collect();
+        if Slot::Row(row).is_reversed(self.direction) {
+            allowed.reverse();
+        }
+        let cells: Vec<char> = template.chars().collect();
+        let mut words = if cells.contains(&BLOCK) {
+            // Each run between blocks is filled on its own, and the row is
+            // every way to put them together
+            let mut words = vec![String::new()];
+            let mut end = 0;
+            for run in runs(&cells, |&c| c == BLOCK) {
+                let blocks: String = cells[end..run.start].iter().collect();
+                let word = self.rules().is_word(&run, cells.len());
+                let fills = self.run_words(&cells[run.clone()], &allowed[run.clone()], word);
+                words = words
+                    .iter()
+                    .flat_map(|start| {
+                        let blocks = &blocks;
+                        fills
+                            .iter()
+                            .map(move |fill| format!("{}{}{}", start, blocks, fill))
+                    })
+                    .collect();
+                end = run.end;
+            }
+            let blocks: String = cells[end..].iter().collect();
+            words.iter_mut().for_each(|word| word.push_str(&blocks));
+            words
+        } else {
+            self.run_words(&cells, &allowed, true)
+        };
+        if self.words_differ() || self.diagonals != Diagonals::Off {
+            let mut grid = self.square.clone();
+            words.retain(|word| {
+                let letters = Slot::Row(row).orient(self.direction, word.chars().collect());
+                if self.symmetric {
+                    for (cells, &c) in grid.iter_mut().zip(&letters) {
+                        cells[row] = c;
+                    }
+                }
+                grid[row] = letters;
+                let repeats = self.words_differ() && self.repeated_word(&grid).is_some();
+                !repeats && self.diagonals_fit(&grid)
+            });
+        }
+        self.order_words(&mut words);
+        words
+    }
+
+    /// The letters a column allows in `row`. When the column reads as
+    /// letters down to this row and blanks from it on, as it does whenever
+    /// rows are filled from the top in reading order, each letter costs
+    /// one prefix query; otherwise this finds every word fitting the
+    /// column.
+    fn column_allows(&self, row: usize, col: usize) -> Vec<char> {
+        let slot = Slot::Col(col);
+        let letters = slot.read(&self.square, self.direction);
+        let at = if slot.is_reversed(self.direction) {
+            letters.len() - 1 - row
+        } else {
+            row
+        };
+        let (above, below) = letters.split_at(at);
+        let is_prefix = above.iter().all(|&c| c != '_' && c != BLOCK)
+            && below.iter().all(|&c| c == '_');
+        if !is_prefix {
+            return propagate::allowed(self.rules(), &self.square, slot).swap_remove(row);
+        }
+        let mut prefix: String = above.iter().collect();
+        self.dict
+            .alphabet()
+            .iter()
+            .copied()
+            .filter(|&c| {
+                self.note_query();
+                prefix.push(c);
+                let fits = self.dict.has_prefix(&prefix, letters.len());
+                prefix.pop();
+                fits
+            })
+            .collect()
+    }
+
+    /// The ways to fill a row, or a run of it between blocks, in reading
+    /// order: the words that fit its letters and the letters its columns
+    /// allow, or if it need not be a word, every string of those letters.
+    fn run_words(&self, template: &[char], allowed: &[Vec<char>], word: bool) -> Vec<String> {
+        if !word {
+            return allowed.iter().fold(vec![String::new()], |starts, letters| {
+                starts
+                    .iter()
+                    .flat_map(|start| letters.iter().map(move |c| format!("{}{}", start, c)))
+                    .collect()
+            });
+        }
+        let template: String = template.iter().collect();
+        self.note_query();
+        self.dict
+            .iter_with_template(&template)
+            .filter(|word| {
+                word.chars()
+                    .zip(allowed)
+                    .all(|(c, letters)| letters.contains(&c))
+            })
+            .filter(|word| {
+                if !self.mirror_rows {
+                    return true;
+                }
+                self.note_query();
+                self.dict.contains(&word.chars().rev().collect::<String>())
+            })
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{Diagonals, Strategy};
+
+    #[test]
+    fn fills_blank_squares() {
+        assert_fills("___", 3, Strategy::Rows, |_| {});
+        assert_fills("____", 4, Strategy::Rows, |_| {});
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_diagonals() {
+        assert_fills("___", 3, Strategy::Rows, |square| square.set_mirror_rows(true));
+        assert_fills("___", 3, Strategy::Rows, |square| square.set_diagonals(Diagonals::Both));
+        assert_fills("____", 4, Strategy::Rows, |square| square.set_symmetric(true));
+    }
+
+    #[test]
+    fn fills_around_blocks() {
+        assert_fills("___#/____/____/#___", 4, Strategy::Rows, |_| {});
+    }
+}
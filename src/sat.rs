@@ -0,0 +1,603 @@
+//! Filling a square with a SAT solver. Only available with the `sat`
+//! feature.
+//!
+//! The grid becomes a formula in conjunctive normal form: a variable for
+//! each letter each blank cell could take, at most one of them true, and a
+//! variable for each word each slot could take, at least one of them true.
+//! A word implies its letters, and a letter implies one of the words with
+//! it in each of the cell's two slots. The solver is a small conflict-driven
+//! clause-learning one: it learns a clause from every dead end, so a
+//! contradiction between a few fixed letters is found once rather than
+//! under every arrangement of the rest of the grid.
+
+use std::collections::HashMap;
+
+use crate::restart::luby;
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// A variable, or its negation: the variable's number times two, plus one
+/// if negated.
+type Lit = u32;
+
+fn lit(var: usize, positive: bool) -> Lit {
+    ((var as u32) << 1) | u32::from(!positive)
+}
+
+fn var(lit: Lit) -> usize {
+    (lit >> 1) as usize
+}
+
+fn neg(lit: Lit) -> Lit {
+    lit ^ 1
+}
+
+/// 1 if a literal is true, -1 if false, and 0 if its variable is not set.
+fn lit_value(values: &[i8], lit: Lit) -> i8 {
+    let value = values[var(lit)];
+    if lit & 1 == 1 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// No clause: the variable was decided, not implied.
+const DECIDED: usize = usize::MAX;
+
+/// The conflicts to allow the first run between restarts.
+const RESTART_UNIT: usize = 100;
+
+/// The conflicts before learned clauses are first cut down, and how many
+/// more to allow each time after.
+const REDUCE_FIRST: usize = 2000;
+const REDUCE_STEP: usize = 300;
+
+/// A conflict-driven clause-learning SAT solver.
+#[derive(Default)]
+pub(crate) struct Solver {
+    /// Every clause, emptied once deleted.
+    clauses: Vec<Vec<Lit>>,
+    /// For each learned clause, how many decision levels its literals were
+    /// set at when it was learned (fewer is more useful), and 0 for the
+    /// clauses added.
+    lbd: Vec<usize>,
+    /// The learned clauses not deleted, and the conflict count at which to
+    /// delete the less useful half.
+    live: Vec<usize>,
+    reduce_at: usize,
+    /// The clauses watching each literal, which must be looked at when it
+    /// becomes false. Every clause watches its first two literals.
+    watches: Vec<Vec<usize>>,
+    values: Vec<i8>,
+    /// The decision level each variable was set at, and the clause that
+    /// implied it.
+    level: Vec<usize>,
+    reason: Vec<usize>,
+    /// The literals made true, in order, and where each decision level
+    /// starts in it.
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    /// How much of the trail has been propagated.
+    head: usize,
+    /// How often each variable took part in a conflict lately, and the
+    /// unset variables by that activity.
+    activity: Vec<f64>,
+    bump: f64,
+    order: Heap,
+    /// The value each variable last had, to try first.
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    /// Whether each variable may be guessed.
+    decide: Vec<bool>,
+    /// Set once the clauses added contradict each other.
+    unsat: bool,
+    reductions: usize,
+    pub(crate) conflicts: usize,
+    pub(crate) learned: usize,
+}
+
+impl Solver {
+    pub(crate) fn new() -> Solver {
+        Solver {
+            bump: 1.0,
+            reduce_at: REDUCE_FIRST,
+            ..Solver::default()
+        }
+    }
+
+    /// A new variable, numbered from 0. Only variables marked `decide` are
+    /// ever guessed; the rest must follow from them.
+    pub(crate) fn new_var(&mut self, decide: bool) -> usize {
+        let v = self.values.len();
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.values.push(0);
+        self.level.push(0);
+        self.reason.push(DECIDED);
+        self.activity.push(0.0);
+        self.phase.push(true);
+        self.seen.push(false);
+        self.decide.push(decide);
+        if decide {
+            self.order.insert(v, &self.activity);
+        }
+        v
+    }
+
+    /// Require one of `lits` to be true. Only before solving.
+    pub(crate) fn add_clause(&mut self, mut lits: Vec<Lit>) {
+        lits.sort_unstable();
+        lits.dedup();
+        if lits.windows(2).any(|pair| pair[0] == neg(pair[1])) {
+            return;
+        }
+        if lits.iter().any(|&l| lit_value(&self.values, l) == 1) {
+            return;
+        }
+        lits.retain(|&l| lit_value(&self.values, l) == 0);
+        match lits.len() {
+            0 => self.unsat = true,
+            1 => self.enqueue(lits[0], DECIDED),
+            _ => {
+                self.attach(lits);
+            }
+        }
+    }
+
+    /// Whether a variable is true in the solution found.
+    pub(crate) fn value(&self, var: usize) -> bool {
+        self.values[var] == 1
+    }
+
+    /// Look for values that make every clause true, asking `stop` before
+    /// each decision whether to give up.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(true)` if there are some, which `value` then gives.
+    /// * `Some(false)` if there are none.
+    /// * `None` if `stop` said to give up.
+    pub(crate) fn solve(&mut self, mut stop: impl FnMut() -> bool) -> Option<bool> {
+        if self.unsat {
+            return Some(false);
+        }
+        let mut run = 1;
+        let mut allowed = RESTART_UNIT;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+                if self.trail_lim.is_empty() {
+                    return Some(false);
+                }
+                let (learned, level) = self.analyze(conflict);
+                let lbd = self.lbd_of(&learned);
+                self.backtrack(level);
+                if learned.len() == 1 {
+                    self.enqueue(learned[0], DECIDED);
+                } else {
+                    let first = learned[0];
+                    let clause = self.attach(learned);
+                    self.lbd[clause] = lbd;
+                    self.live.push(clause);
+                    self.enqueue(first, clause);
+                    self.learned += 1;
+                }
+                self.bump /= 0.95;
+                allowed -= 1;
+                if allowed == 0 {
+                    run += 1;
+                    allowed = RESTART_UNIT * luby(run);
+                    self.backtrack(0);
+                    // No clause is the reason for anything above level 0
+                    // now, so any can go
+                    if self.conflicts >= self.reduce_at {
+                        self.reduce();
+                    }
+                }
+                continue;
+            }
+            if stop() {
+                return None;
+            }
+            let Some(v) = self.pick() else {
+                return Some(true);
+            };
+            self.trail_lim.push(self.trail.len());
+            self.enqueue(lit(v, self.phase[v]), DECIDED);
+        }
+    }
+
+    /// Add a clause of two or more literals, watching its first two, and
+    /// return its number.
+    fn attach(&mut self, lits: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[lits[0] as usize].push(index);
+        self.watches[lits[1] as usize].push(index);
+        self.clauses.push(lits);
+        self.lbd.push(0);
+        index
+    }
+
+    /// How many decision levels a clause's literals were set at.
+    fn lbd_of(&self, lits: &[Lit]) -> usize {
+        let mut levels: Vec<usize> = lits.iter().map(|&l| self.level[var(l)]).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels.len()
+    }
+
+    /// Delete the less useful half of the learned clauses, keeping any
+    /// set at two levels or fewer. Watches on them go when next visited.
+    fn reduce(&mut self) {
+        let mut live = std::mem::take(&mut self.live);
+        live.sort_by_key(|&clause| self.lbd[clause]);
+        let keep = live.len() / 2;
+        for &clause in live[keep..].iter() {
+            if self.lbd[clause] > 2 {
+                self.clauses[clause] = vec![];
+            }
+        }
+        live.retain(|&clause| !self.clauses[clause].is_empty());
+        self.live = live;
+        self.reduce_at = self.conflicts + REDUCE_FIRST + REDUCE_STEP * self.reductions;
+        self.reductions += 1;
+    }
+
+    fn enqueue(&mut self, l: Lit, reason: usize) {
+        let v = var(l);
+        self.values[v] = if l & 1 == 1 { -1 } else { 1 };
+        self.level[v] = self.trail_lim.len();
+        self.reason[v] = reason;
+        self.trail.push(l);
+    }
+
+    /// Set every literal the clauses imply, returning a clause made false
+    /// if there is one.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let false_lit = neg(self.trail[self.head]);
+            self.head += 1;
+            let mut watching = std::mem::take(&mut self.watches[false_lit as usize]);
+            let mut kept = 0;
+            let mut conflict = None;
+            for i in 0..watching.len() {
+                let index = watching[i];
+                if conflict.is_some() {
+                    watching[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                let clause = &mut self.clauses[index];
+                if clause.is_empty() {
+                    continue;
+                }
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                if lit_value(&self.values, clause[0]) == 1 {
+                    watching[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                // Watch another literal that is not false, if there is one
+                let other = (2..clause.len()).find(|&k| lit_value(&self.values, clause[k]) != -1);
+                if let Some(k) = other {
+                    clause.swap(1, k);
+                    self.watches[clause[1] as usize].push(index);
+                    continue;
+                }
+                watching[kept] = index;
+                kept += 1;
+                let first = clause[0];
+                if lit_value(&self.values, first) == -1 {
+                    conflict = Some(index);
+                } else {
+                    self.enqueue(first, index);
+                }
+            }
+            watching.truncate(kept);
+            self.watches[false_lit as usize] = watching;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// The clause to learn from a conflict, cut at the first literal
+    /// every path to the conflict at this level goes through, and the
+    /// level to go back to, where the clause implies that literal.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let current = self.trail_lim.len();
+        let mut learned = vec![0];
+        let mut open = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut start = 0;
+        loop {
+            for k in start..self.clauses[clause].len() {
+                let q = self.clauses[clause][k];
+                let v = var(q);
+                if self.seen[v] || self.level[v] == 0 {
+                    continue;
+                }
+                self.seen[v] = true;
+                self.bump_activity(v);
+                if self.level[v] == current {
+                    open += 1;
+                } else {
+                    learned.push(q);
+                }
+            }
+            // The latest literal on the trail that led here
+            loop {
+                index -= 1;
+                if self.seen[var(self.trail[index])] {
+                    break;
+                }
+            }
+            let p = self.trail[index];
+            self.seen[var(p)] = false;
+            open -= 1;
+            if open == 0 {
+                learned[0] = neg(p);
+                break;
+            }
+            clause = self.reason[var(p)];
+            // The implied literal comes first in its reason
+            start = 1;
+        }
+        for &q in learned.iter() {
+            self.seen[var(q)] = false;
+        }
+
+        // Watch the literal set last, after the first, so the clause
+        // wakes up when going back past it
+        let mut level = 0;
+        for k in 1..learned.len() {
+            if self.level[var(learned[k])] > level {
+                level = self.level[var(learned[k])];
+                learned.swap(1, k);
+            }
+        }
+        (learned, level)
+    }
+
+    fn bump_activity(&mut self, v: usize) {
+        self.activity[v] += self.bump;
+        if self.activity[v] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+        self.order.increase(v, &self.activity);
+    }
+
+    /// Undo every decision above `level`.
+    fn backtrack(&mut self, level: usize) {
+        if self.trail_lim.len() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for &l in self.trail[start..].iter() {
+            let v = var(l);
+            self.phase[v] = l & 1 == 0;
+            self.values[v] = 0;
+            if self.decide[v] {
+                self.order.insert(v, &self.activity);
+            }
+        }
+        self.trail.truncate(start);
+        self.trail_lim.truncate(level);
+        self.head = start;
+    }
+
+    /// The most active unset variable that may be guessed, if any.
+    fn pick(&mut self) -> Option<usize> {
+        while let Some(v) = self.order.pop(&self.activity) {
+            if self.values[v] == 0 {
+                return Some(v);
+            }
+        }
+        None
+    }
+}
+
+/// The variables, most active at the root, to decide the next one fast.
+#[derive(Default)]
+struct Heap {
+    items: Vec<usize>,
+    /// Where each variable is in `items`, if it is.
+    position: Vec<Option<usize>>,
+}
+
+impl Heap {
+    fn insert(&mut self, v: usize, activity: &[f64]) {
+        if self.position.len() <= v {
+            self.position.resize(v + 1, None);
+        }
+        if self.position[v].is_some() {
+            return;
+        }
+        self.position[v] = Some(self.items.len());
+        self.items.push(v);
+        self.up(self.items.len() - 1, activity);
+    }
+
+    fn increase(&mut self, v: usize, activity: &[f64]) {
+        if let Some(i) = self.position.get(v).copied().flatten() {
+            self.up(i, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.items.first()?;
+        let last = self.items.pop()?;
+        self.position[top] = None;
+        if !self.items.is_empty() {
+            self.items[0] = last;
+            self.position[last] = Some(0);
+            self.down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.items[parent]] >= activity[self.items[i]] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.items.len()
+                    && activity[self.items[child]] > activity[self.items[largest]]
+                {
+                    largest = child;
+                }
+            }
+            if largest == i {
+                break;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.items.swap(a, b);
+        self.position[self.items[a]] = Some(a);
+        self.position[self.items[b]] = Some(b);
+    }
+}
+
+impl MagicSquare {
+    /// Fill every blank cell by solving the grid as a SAT formula.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled.
+    /// * `Err(String)` if the formula has no solution, so no square
+    ///   exists, or the attempt limit was reached.
+    pub(crate) fn fill_sat(&mut self) -> Result<(), String> {
+        let mut solver = Solver::new();
+        // The variable for each letter of each blank cell, made as words
+        // use them. The letters settle the words, so only they are guessed
+        let mut letters: HashMap<(usize, usize, char), usize> = HashMap::new();
+        // For each letter variable and slot, the words with that letter
+        let mut support: HashMap<(usize, Slot), Vec<usize>> = HashMap::new();
+
+        let slots: Vec<Slot> = Slot::all(&self.square)
+            .into_iter()
+            .filter(|&slot| {
+                self.slot_cells(slot)
+                    .iter()
+                    .any(|&(r, c)| self.is_blank(r, c))
+            })
+            .collect();
+        let mut words = 0;
+        for &slot in slots.iter() {
+            let cells = self.slot_cells(slot);
+            let mut any = vec![];
+            for word in self.slot_words(slot) {
+                let w = solver.new_var(false);
+                any.push(lit(w, true));
+                for (&(row, col), &c) in cells.iter().zip(word.iter()) {
+                    if !self.is_blank(row, col) {
+                        continue;
+                    }
+                    let x = *letters
+                        .entry((row, col, c))
+                        .or_insert_with(|| solver.new_var(true));
+                    solver.add_clause(vec![lit(w, false), lit(x, true)]);
+                    support.entry((x, slot)).or_default().push(w);
+                }
+            }
+            words += any.len();
+            solver.add_clause(any);
+        }
+
+        // A letter needs a word with it in both of its slots, and a cell
+        // takes at most one letter
+        let mut by_cell: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (&(row, col, _), &x) in letters.iter() {
+            by_cell.entry((row, col)).or_default().push(x);
+            for slot in [Slot::Row(row), Slot::Col(col)] {
+                let mut clause = vec![lit(x, false)];
+                if let Some(words) = support.get(&(x, slot)) {
+                    clause.extend(words.iter().map(|&w| lit(w, true)));
+                }
+                solver.add_clause(clause);
+            }
+        }
+        for cell in by_cell.values() {
+            for (i, &a) in cell.iter().enumerate() {
+                for &b in cell[i + 1..].iter() {
+                    solver.add_clause(vec![lit(a, false), lit(b, false)]);
+                }
+            }
+        }
+        debug!(
+            "{} slots, {} words and {} letters as variables",
+            slots.len(),
+            words,
+            letters.len()
+        );
+
+        let result = solver.solve(|| {
+            // Out of attempts: give up
+            if self.should_stop() {
+                return true;
+            }
+            self._attempt += 1;
+            false
+        });
+        debug!(
+            "{} conflicts, {} clauses learned",
+            solver.conflicts, solver.learned
+        );
+        match result {
+            Some(true) => {
+                for (&(row, col, c), &x) in letters.iter() {
+                    if solver.value(x) {
+                        self.square[row][col] = c;
+                    }
+                }
+                self.note_depth();
+                Ok(())
+            }
+            Some(false) => Err("No words fill every slot".to_string()),
+            None => Err("Gave up solving".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::assert_fills;
+    use crate::{Direction, Strategy};
+
+    #[test]
+    fn fills_blank_and_started_squares() {
+        assert_fills("___", 3, Strategy::Sat, |_| {});
+        assert_fills("____", 4, Strategy::Sat, |_| {});
+        assert_fills("_f_/_u_/_n_", 3, Strategy::Sat, |_| {});
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_directions() {
+        assert_fills("____", 4, Strategy::Sat, |square| square.set_mirror_rows(true));
+        for direction in [Direction::BottomUp, Direction::Boustrophedon] {
+            assert_fills("____", 4, Strategy::Sat, |square| square.set_direction(direction));
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Shrinking failing instances into small reproducers for bug reports.
+//!
+//! Given a square that misbehaves, `shrink` repeatedly tries smaller
+//! versions of it (fewer rows and columns, fewer fixed letters, fewer
+//! words) and keeps each one that still misbehaves the same way, until no
+//! single step keeps the behavior. Every step is tried in a fixed order and
+//! the solver is deterministic, so the same input always shrinks to the
+//! same reproducer.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::dictionary::Dictionary;
+use crate::slot::Slot;
+use crate::{LiveView, MagicSquare};
+
+/// What a failing instance does that shrinking must keep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behavior {
+    /// `fill` panics.
+    Panic,
+    /// `fill` succeeds, but some row or column is not a word.
+    Invalid,
+    /// `fill` finds no solution.
+    Unsolvable,
+    /// `fill` tries more than this many letters.
+    Slow(usize),
+}
+
+impl Behavior {
+    /// Parse a behavior: `panic`, `invalid`, `unsolvable` or `slow:N`.
+    pub fn parse(name: &str) -> Result<Behavior, String> {
+        match name {
+            "panic" => Ok(Behavior::Panic),
+            "invalid" => Ok(Behavior::Invalid),
+            "unsolvable" => Ok(Behavior::Unsolvable),
+            _ => name
+                .strip_prefix("slow:")
+                .and_then(|n| n.parse::<usize>().ok())
+                .map(Behavior::Slow)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown behavior {} (expected panic, invalid, unsolvable or slow:N)",
+                        name
+                    )
+                }),
+        }
+    }
+
+    /// What filling `square` does that is worth shrinking, if anything:
+    /// a panic, an invalid fill or no solution. Slowness is never
+    /// detected, since any search takes some number of attempts.
+    pub fn observe(square: &MagicSquare) -> Option<Behavior> {
+        let grid = square.fixed_grid();
+        [Behavior::Panic, Behavior::Invalid, Behavior::Unsolvable]
+            .into_iter()
+            .find(|behavior| behavior.holds(square, &grid, &square.dict))
+    }
+
+    /// Whether filling `grid`'s blanks with `dict`, read as `square` reads
+    /// its words, behaves this way.
+    fn holds(self, square: &MagicSquare, grid: &[Vec<char>], dict: &Dictionary) -> bool {
+        let mut trial = MagicSquare::empty(grid.len(), grid[0].len(), dict);
+        trial.reset_to(grid);
+        trial.set_direction(square.direction);
+        trial.set_mirror_rows(square.mirror_rows);
+        trial.set_overlap(square.overlap());
+        trial.set_distinct(square.distinct);
+        trial.set_diagonals(square.diagonals);
+        trial.set_min_word_len(square.min_word_len);
+        trial.set_toroidal(square.toroidal);
+        trial.set_strategy(square.strategy);
+        trial.set_beam_width(square.beam_width);
+        trial.set_population(square.population);
+        trial.set_mutation_rate(square.mutation_rate);
+        trial.set_generations(square.generations);
+        trial.set_cell_order(square.cell_order);
+        trial.set_letter_order(square.letter_order);
+        trial.set_propagate(square.propagate);
+        trial.set_forward_check(square.forward_check);
+        trial.set_nogoods(square.nogoods.is_some());
+        trial.set_parallel(square.parallel);
+        trial.set_threads(square.threads);
+        trial.set_seed(square.seed);
+        trial.set_deterministic(square.deterministic);
+        trial.set_restarts(square.restart_unit);
+        trial.set_live_view(LiveView::Off);
+        if let Behavior::Slow(limit) = self {
+            trial.set_attempt_limit(Some(limit));
+        }
+
+        let result = catch_unwind(AssertUnwindSafe(|| trial.fill()));
+        match (self, result) {
+            (Behavior::Panic, result) => result.is_err(),
+            (Behavior::Invalid, Ok(Ok(()))) => Slot::all(trial.grid()).into_iter().any(|slot| {
+                let mirrored = matches!(slot, Slot::Row(_)) && trial.mirror_rows;
+                trial
+                    .words_in(slot)
+                    .iter()
+                    .any(|word| {
+                        let known = if trial.toroidal {
+                            dict.contains_rotated(word)
+                        } else {
+                            dict.contains(word)
+                        };
+                        !known || (mirrored && !dict.contains_reversed(word))
+                    })
+            }),
+            (Behavior::Unsolvable, Ok(Err(_))) => !trial.gave_up(),
+            (Behavior::Slow(_), Ok(Err(_))) => trial.gave_up(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Behavior {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Behavior::Panic => write!(f, "panic"),
+            Behavior::Invalid => write!(f, "invalid"),
+            Behavior::Unsolvable => write!(f, "unsolvable"),
+            Behavior::Slow(limit) => write!(f, "slow:{}", limit),
+        }
+    }
+}
+
+/// A shrunk instance that still shows the behavior it was shrunk for.
+pub struct Shrunk {
+    /// The fixed letters, one string per row, with `_` for blanks.
+    pub grid: Vec<String>,
+    /// The words left, in alphabetical order.
+    pub words: Vec<String>,
+    /// The dictionary of `words`, with the original frequencies.
+    pub dict: Dictionary,
+    /// How many times the solver was run while shrinking.
+    pub runs: usize,
+}
+
+/// The search for a smaller instance: the current instance, and how many
+/// candidates have been tried.
+struct Shrinker<'a> {
+    square: &'a MagicSquare,
+    behavior: Behavior,
+    grid: Vec<Vec<char>>,
+    dict: Dictionary,
+    runs: usize,
+}
+
+impl Shrinker<'_> {
+    /// Keep `grid` and `dict` if they still show the behavior.
+    fn try_instance(&mut self, grid: Vec<Vec<char>>, dict: Option<Dictionary>) -> bool {
+        // A grid with no blanks makes a different failure than the original
+        let all_fixed = |g: &[Vec<char>]| g.iter().flatten().all(|c| *c != '_');
+        if grid.is_empty() || grid[0].is_empty() || all_fixed(&grid) && !all_fixed(&self.grid) {
+            return false;
+        }
+        self.runs += 1;
+        let dict_ref = dict.as_ref().unwrap_or(&self.dict);
+        if !self.behavior.holds(self.square, &grid, dict_ref) {
+            return false;
+        }
+        self.grid = grid;
+        if let Some(dict) = dict {
+            self.dict = dict;
+        }
+        true
+    }
+
+    /// Try dropping each row and each column, then unfixing each fixed
+    /// letter.
+    fn shrink_grid(&mut self) -> bool {
+        let mut shrunk = false;
+        // A smaller grid needs words of other lengths, so almost any
+        // square becomes unsolvable by shrinking it; for that behavior only
+        // the fixed letters are shrunk
+        if self.behavior != Behavior::Unsolvable {
+            for row in (0..self.grid.len()).rev() {
+                let mut grid = self.grid.clone();
+                grid.remove(row);
+                shrunk |= self.try_instance(grid, None);
+            }
+            for col in (0..self.grid[0].len()).rev() {
+                let mut grid = self.grid.clone();
+                for letters in grid.iter_mut() {
+                    letters.remove(col);
+                }
+                shrunk |= self.try_instance(grid, None);
+            }
+        }
+        for row in 0..self.grid.len() {
+            for col in 0..self.grid[0].len() {
+                if self.grid[row][col] != '_' {
+                    let mut grid = self.grid.clone();
+                    grid[row][col] = '_';
+                    shrunk |= self.try_instance(grid, None);
+                }
+            }
+        }
+        shrunk
+    }
+
+    /// Drop the words whose lengths fit no row or column, then remove ever
+    /// smaller runs of words while the behavior holds (delta debugging).
+    fn shrink_dictionary(&mut self) -> bool {
+        let lengths = [self.grid.len(), self.grid[0].len()];
+        let mut dict = self.dict.clone();
+        dict.retain(|w| lengths.contains(&w.chars().count()));
+        let mut shrunk =
+            dict.len() < self.dict.len() && self.try_instance(self.grid.clone(), Some(dict));
+
+        // Removing words can only keep a square unsolvable, so shrinking
+        // further would just empty the dictionary
+        if self.behavior == Behavior::Unsolvable {
+            return shrunk;
+        }
+
+        let mut words: Vec<String> = self.dict.iter().cloned().collect();
+        let mut chunk = words.len().div_ceil(2);
+        while chunk > 0 && !words.is_empty() {
+            let mut start = 0;
+            let mut removed = false;
+            while start < words.len() {
+                let end = (start + chunk).min(words.len());
+                let dropped: HashSet<&str> = words[start..end].iter().map(String::as_str).collect();
+                let mut dict = self.dict.clone();
+                dict.retain(|w| !dropped.contains(w));
+                if self.try_instance(self.grid.clone(), Some(dict)) {
+                    words.drain(start..end);
+                    removed = true;
+                } else {
+                    start = end;
+                }
+            }
+            shrunk |= removed;
+            if chunk == 1 && !removed {
+                break;
+            }
+            chunk = if removed {
+                chunk.min(words.len())
+            } else {
+                chunk / 2
+            };
+        }
+        shrunk
+    }
+}
+
+/// Shrink a misbehaving square to a small instance that misbehaves the same
+/// way, for attaching to a bug report.
+///
+/// This runs the solver once per candidate, and there are many candidates
+/// (roughly the number of words times the log of it), so it is slow on big
+/// dictionaries; `Behavior::Slow` bounds each run by its attempt limit.
+///
+/// # Arguments
+///
+/// * `square` - The square, with its fixed letters, dictionary, direction
+///   and mirroring set as they were when it misbehaved.
+/// * `behavior` - What it does that the reproducer must keep.
+///
+/// # Returns
+///
+/// * `Ok(Shrunk)` with the smallest instance found.
+/// * `Err(String)` if the square does not behave that way to begin with.
+pub fn shrink(square: &MagicSquare, behavior: Behavior) -> Result<Shrunk, String> {
+    let grid = square.fixed_grid();
+    if grid.is_empty() || grid[0].is_empty() {
+        return Err("the square has no cells".to_string());
+    }
+    if !behavior.holds(square, &grid, &square.dict) {
+        return Err(format!(
+            "the square does not show the behavior {}",
+            behavior
+        ));
+    }
+
+    let mut shrinker = Shrinker {
+        square,
+        behavior,
+        grid,
+        dict: square.dict.clone(),
+        runs: 1,
+    };
+    // Each kind of step can open up more of the other, so alternate until
+    // neither makes progress
+    loop {
+        let grid_shrunk = shrinker.shrink_grid();
+        let dict_shrunk = shrinker.shrink_dictionary();
+        debug!(
+            "{}x{} grid, {} words after {} runs",
+            shrinker.grid.len(),
+            shrinker.grid[0].len(),
+            shrinker.dict.len(),
+            shrinker.runs
+        );
+        if !grid_shrunk && !dict_shrunk {
+            break;
+        }
+    }
+
+    let mut words: Vec<String> = shrinker.dict.iter().cloned().collect();
+    words.sort();
+    Ok(Shrunk {
+        grid: shrinker
+            .grid
+            .iter()
+            .map(|row| row.iter().collect())
+            .collect(),
+        words,
+        dict: shrinker.dict,
+        runs: shrinker.runs,
+    })
+}
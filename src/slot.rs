@@ -0,0 +1,130 @@
+use std::fmt;
+use std::ops::Range;
+
+/// A blocked cell, which holds no letter and ends the words on either side
+/// of it, as in a crossword.
+pub(crate) const BLOCK: char = '#';
+
+/// The order in which words are read out of the grid.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    /// Rows left to right, columns top to bottom.
+    Standard,
+    /// Rows left to right, columns bottom to top.
+    BottomUp,
+    /// Columns top to bottom, rows alternating left to right and right to
+    /// left (every odd row is reversed), like an ox ploughing a field.
+    Boustrophedon,
+}
+
+/// A line of the grid that must spell a word.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Slot {
+    Row(usize),
+    Col(usize),
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Slot::Row(row) => write!(f, "row {}", row),
+            Slot::Col(col) => write!(f, "column {}", col),
+        }
+    }
+}
+
+impl Slot {
+    /// Every row, then every column, of a grid.
+    pub fn all(grid: &[Vec<char>]) -> Vec<Slot> {
+        let cols = grid.first().map_or(0, |r| r.len());
+        (0..grid.len())
+            .map(Slot::Row)
+            .chain((0..cols).map(Slot::Col))
+            .collect()
+    }
+
+    /// The letters of this slot in grid order (left to right, top to
+    /// bottom), ignoring the reading direction.
+    pub fn cells(self, grid: &[Vec<char>]) -> Vec<char> {
+        match self {
+            Slot::Row(row) => grid[row].clone(),
+            Slot::Col(col) => grid.iter().map(|r| r[col]).collect(),
+        }
+    }
+
+    /// Whether this slot is read backwards (right to left or bottom to top)
+    /// under `direction`.
+    pub fn is_reversed(self, direction: Direction) -> bool {
+        match (direction, self) {
+            (Direction::BottomUp, Slot::Col(_)) => true,
+            (Direction::Boustrophedon, Slot::Row(row)) => row % 2 == 1,
+            _ => false,
+        }
+    }
+
+    /// Put letters taken in grid order into reading order.
+    pub fn orient(self, direction: Direction, mut letters: Vec<char>) -> Vec<char> {
+        if self.is_reversed(direction) {
+            letters.reverse();
+        }
+        letters
+    }
+
+    /// The letters of this slot in reading order.
+    pub fn read(self, grid: &[Vec<char>], direction: Direction) -> Vec<char> {
+        self.orient(direction, self.cells(grid))
+    }
+}
+
+/// A word of the grid: a row or column, or a run of one between blocks
+/// long enough to be a word, as its slot and its positions along the slot
+/// in grid order.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Entry {
+    pub(crate) slot: Slot,
+    pub(crate) cells: Range<usize>,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} from {} to {}", self.slot, self.cells.start, self.cells.end - 1)
+    }
+}
+
+impl Entry {
+    /// The `(row, col)` of each of this entry's cells, in grid order.
+    pub(crate) fn positions(&self) -> Vec<(usize, usize)> {
+        self.cells
+            .clone()
+            .map(|i| match self.slot {
+                Slot::Row(row) => (row, i),
+                Slot::Col(col) => (i, col),
+            })
+            .collect()
+    }
+
+    /// The letters of this entry in reading order.
+    pub(crate) fn read(&self, grid: &[Vec<char>], direction: Direction) -> Vec<char> {
+        let cells = self.slot.cells(grid);
+        self.slot.orient(direction, cells[self.cells.clone()].to_vec())
+    }
+}
+
+/// The runs of cells between blocks, and between a block and the end of the
+/// line, as ranges of positions. A line with no blocks is one run.
+pub(crate) fn runs<T>(cells: &[T], is_block: impl Fn(&T) -> bool) -> Vec<Range<usize>> {
+    let mut runs = vec![];
+    let mut start = 0;
+    for (i, cell) in cells.iter().enumerate() {
+        if is_block(cell) {
+            if i > start {
+                runs.push(start..i);
+            }
+            start = i + 1;
+        }
+    }
+    if cells.len() > start {
+        runs.push(start..cells.len());
+    }
+    runs
+}
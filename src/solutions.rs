@@ -0,0 +1,121 @@
+//! Every way to fill a square, found one at a time.
+//!
+//! The letter-by-letter search keeps its choice points on a stack of its
+//! own, so after it fills the square it can carry on from where it was,
+//! trying the next letter of the last cell it filled, just as it would
+//! have had that letter led nowhere. Each square is found only when it is
+//! asked for.
+
+use crate::square::ChoicePoint;
+use crate::MagicSquare;
+
+/// An iterator over every way to fill a square. See
+/// [`MagicSquare::solutions`].
+pub struct Solutions<'a> {
+    square: &'a mut MagicSquare,
+    /// The search's choice points, kept between squares, or `None` before
+    /// the first.
+    stack: Option<Vec<ChoicePoint>>,
+    done: bool,
+    /// Why the search gave up, if it did.
+    error: Option<String>,
+}
+
+impl Solutions<'_> {
+    /// Why the search stopped before trying everything, if it hit the
+    /// attempt or time limit. `None` while it is going, or once it has
+    /// found every square.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<Vec<char>>;
+
+    fn next(&mut self) -> Option<Vec<Vec<char>>> {
+        if self.done {
+            return None;
+        }
+        let square = &mut *self.square;
+        let result = match self.stack.as_mut() {
+            None => {
+                square.begin_fill();
+                square.begin_search(square.attempt_limit);
+                let mut stack = vec![];
+                let result = if let Some(e) = square.setup_error() {
+                    Err(e)
+                } else if square.propagates() && !square.start_domains() {
+                    Err("No letter fits some cell".to_string())
+                } else {
+                    square.start_letters(&mut stack)
+                };
+                self.stack = Some(stack);
+                result
+            }
+            // A grid with no blanks is its only square
+            Some(stack) if stack.is_empty() => Err("No more squares".to_string()),
+            Some(stack) => {
+                square.path.pop();
+                square.search_letters(stack)
+            }
+        };
+        square.finish_stats();
+        match result {
+            Ok(()) => Some(square.grid().to_vec()),
+            Err(_) => {
+                if square.gave_up() {
+                    self.error = Some(square.gave_up_error());
+                }
+                square.end_search();
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl Drop for Solutions<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.square.end_search();
+        }
+    }
+}
+
+impl MagicSquare {
+    /// Every way to fill the square, found lazily: each call to `next`
+    /// carries on the search from the square before, so taking the first
+    /// few of a huge number costs no more than finding them.
+    ///
+    /// The search places a letter at a time whatever the strategy, so the
+    /// squares come in the order `fill` finds them with
+    /// [`Strategy::Letters`](crate::Strategy::Letters). It runs on one
+    /// thread and without restarts, but keeps the cell and letter order,
+    /// propagation, forward checking, nogoods, and the attempt and time
+    /// limits, which cover the whole iteration. While it runs, the square
+    /// holds the last square found; once every square has been found, it
+    /// is back to its fixed letters.
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the filled grids, one `Vec` per row.
+    ///
+    /// ```
+    /// use magic_square::dictionary::Dictionary;
+    /// use magic_square::{LiveView, MagicSquare};
+    ///
+    /// let dict = Dictionary::from_words(["ab", "ad", "bd", "ba", "db", "da"]);
+    /// let mut square = MagicSquare::from_template("a_", 2, &dict);
+    /// square.set_live_view(LiveView::Off);
+    /// assert_eq!(square.solutions().count(), 6);
+    /// ```
+    pub fn solutions(&mut self) -> Solutions<'_> {
+        Solutions {
+            square: self,
+            stack: None,
+            done: false,
+            error: None,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Counting what a search did, to compare heuristics on the same grid.
+
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::MagicSquare;
+
+/// What the last `fill` did. See [`MagicSquare::solve_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SolveStats {
+    /// How many letters, words or grids the search tried: the nodes of
+    /// its search tree, for the searches that have one.
+    pub nodes: usize,
+    /// How many times it ran out of choices somewhere and went back.
+    pub backtracks: usize,
+    /// The most cells it had filled at once, not counting fixed ones.
+    pub max_depth: usize,
+    /// How many times it asked the dictionary whether words fit a row,
+    /// column or template.
+    pub dictionary_queries: usize,
+    /// How long it took.
+    pub elapsed: Duration,
+    /// The seed the square was shuffled with, if it was: for a parallel
+    /// search, that of the choice it was found below, or of the restarted
+    /// search that won the race.
+    pub seed: Option<u64>,
+}
+
+impl fmt::Display for SolveStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<20} {:>12}", "nodes", self.nodes)?;
+        writeln!(f, "{:<20} {:>12}", "backtracks", self.backtracks)?;
+        writeln!(f, "{:<20} {:>12}", "max depth", self.max_depth)?;
+        writeln!(f, "{:<20} {:>12}", "dictionary queries", self.dictionary_queries)?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "{:<20} {:>12}", "seed", seed)?;
+        }
+        write!(f, "{:<20} {:>10.3} s", "elapsed", self.elapsed.as_secs_f64())
+    }
+}
+
+/// The counters as a `fill` started, which its stats are counted from.
+#[derive(Clone, Copy)]
+pub(crate) struct SolveStart {
+    at: Instant,
+    attempts: usize,
+    backtracks: usize,
+    queries: usize,
+    seed: Option<u64>,
+}
+
+impl MagicSquare {
+    /// Note where the counters stand as a `fill` starts.
+    pub(crate) fn start_stats(&mut self) {
+        self.solve_start = Some(SolveStart {
+            at: Instant::now(),
+            attempts: self._attempt,
+            backtracks: self.backtracks.iter().sum(),
+            queries: self.queries.load(Ordering::Relaxed),
+            seed: self.search_seed(),
+        });
+        self.max_depth = 0;
+    }
+
+    /// Note that the square was found with `seed` rather than the seed
+    /// set, as a parallel search's is.
+    pub(crate) fn note_seed(&mut self, seed: Option<u64>) {
+        if let Some(start) = self.solve_start.as_mut() {
+            start.seed = seed;
+        }
+    }
+
+    /// Count one question put to the dictionary.
+    pub(crate) fn note_query(&self) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Work out the stats of the `fill` under way, from where the counters
+    /// stood as it started.
+    pub(crate) fn finish_stats(&mut self) {
+        let Some(start) = self.solve_start else {
+            return;
+        };
+        self.solve_stats = SolveStats {
+            nodes: self._attempt - start.attempts,
+            backtracks: self.backtracks.iter().sum::<usize>() - start.backtracks,
+            max_depth: self.max_depth,
+            dictionary_queries: self.queries.load(Ordering::Relaxed) - start.queries,
+            elapsed: start.at.elapsed(),
+            seed: start.seed,
+        };
+    }
+
+    /// How many nodes the last `fill` visited, how often it backtracked,
+    /// how deep it got, how many dictionary queries it made, and how long
+    /// it took. A parallel search counts the work of every thread, and
+    /// [`solutions`](MagicSquare::solutions) counts everything it has done
+    /// so far.
+    pub fn solve_stats(&self) -> SolveStats {
+        self.solve_stats
+    }
+}
@@ -0,0 +1,448 @@
+//! Splitting a search over threads: each way to fill the first empty cell
+//! (or row) becomes a search of its own, and the searches run side by side.
+//!
+//! The choices are numbered in the order a single search would try them,
+//! and the square kept is the one below the lowest numbered choice that
+//! leads to one, so threads change how fast a square is found but not
+//! which. Once a choice leads to a square, the searches below later
+//! choices are cancelled, while those below earlier ones carry on. A
+//! shuffled search gives each choice a seed of its own, the seed plus the
+//! choice's number, so the square kept does not depend on the threads
+//! either.
+//!
+//! A restarted search is raced rather than split: every thread restarts in
+//! orders of its own, and the first to find a square, or to show there is
+//! none, cancels the others. Which thread wins depends on timing, so the
+//! square does too, but the winner's seed is noted in the stats, and a
+//! restarted search on one thread with that seed finds the same square.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::dictionary::Dictionary;
+use crate::nogood::NogoodStats;
+use crate::parallel;
+use crate::slot::Slot;
+use crate::square::{LiveView, Strategy};
+use crate::MagicSquare;
+
+/// Tells the search below one choice that a search below an earlier
+/// choice has found a square, or a racing search that another has
+/// finished.
+pub(crate) struct Cancel {
+    /// The lowest numbered choice that has led to a square so far.
+    solved: Arc<AtomicUsize>,
+    index: usize,
+}
+
+impl Cancel {
+    pub(crate) fn is_set(&self) -> bool {
+        self.solved.load(Ordering::Relaxed) < self.index
+    }
+}
+
+/// What the search below one choice did.
+struct Branch {
+    solved: bool,
+    gave_up: bool,
+    grid: Vec<Vec<char>>,
+    attempts: usize,
+    backtracks: Vec<usize>,
+    max_depth: usize,
+    queries: usize,
+    deepest: Vec<Vec<char>>,
+    deepest_depth: usize,
+    template_stats: Option<HashMap<String, usize>>,
+    nogood_stats: Option<NogoodStats>,
+    /// The seed the search shuffled with.
+    seed: Option<u64>,
+}
+
+impl MagicSquare {
+    /// Fill the square by searching below each choice for the first cell
+    /// or row on its own thread. See [`MagicSquare::set_parallel`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled, as the search on one thread
+    ///   would have filled it.
+    /// * `Err(String)` if no choice leads to a square.
+    pub(crate) fn fill_parallel(&mut self) -> Result<(), String> {
+        let Some(choices) = self.first_choices() else {
+            return Ok(());
+        };
+        self._attempt += choices.len();
+        // Every choice fills the same cells, which the searches below it
+        // then count as fixed
+        let placed = choices.first().map_or(0, |grid| self.placed(grid));
+        debug!("splitting the search {} ways", choices.len());
+
+        let solved = Arc::new(AtomicUsize::new(usize::MAX));
+        let this = &*self;
+        let branches = parallel::map_init(
+            choices.into_iter().enumerate().collect(),
+            self.threads,
+            || None,
+            |spare: &mut Option<Dictionary>, (index, grid)| {
+                if solved.load(Ordering::Relaxed) < index {
+                    return None;
+                }
+                let cancel = Cancel {
+                    solved: Arc::clone(&solved),
+                    index,
+                };
+                let dict = spare.take().unwrap_or_else(|| this.dict.clone());
+                let seed = this.seed.map(|seed| seed.wrapping_add(index as u64));
+                let mut trial = this.branch(grid, dict, seed, cancel);
+                let result = trial.fill();
+                if result.is_ok() {
+                    solved.fetch_min(index, Ordering::Relaxed);
+                }
+                let (branch, dict) = trial.into_branch(result.is_ok());
+                *spare = Some(dict);
+                Some(branch)
+            },
+        );
+
+        let branches: Vec<Branch> = branches.into_iter().flatten().collect();
+        self.gather(&branches, placed);
+        match branches.into_iter().find(|b| b.solved) {
+            Some(branch) => {
+                self.note_seed(branch.seed);
+                self.square = branch.grid;
+                Ok(())
+            }
+            None => Err("Nothing in the first cell or row leads to a square".to_string()),
+        }
+    }
+
+    /// Fill the square by racing restarted searches (see
+    /// [`MagicSquare::set_restarts`]) on every thread, each in its own
+    /// orders, and keep the square of whichever finds one first. The
+    /// first search tries the orders a restarted search on one thread
+    /// would, and the search numbered `n` starts from the seed plus `n`
+    /// times 2^32, so no two ever try the same order. The moment one finds
+    /// a square, or shows there is none, the rest are cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - The attempts to allow each first search.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled. Which search fills it first
+    ///   depends on timing, but its seed is noted in the stats, and a
+    ///   restarted search on one thread with that seed finds the same
+    ///   square.
+    /// * `Err(String)` if some search showed there is no square, or the
+    ///   time limit was reached.
+    pub(crate) fn fill_racing(&mut self, unit: usize) -> Result<(), String> {
+        if let Some(e) = self.setup_error() {
+            return Err(e);
+        }
+        let racers = self.threads.unwrap_or_else(parallel::cores).max(1);
+        debug!("racing {} restarted searches", racers);
+        let fixed = self.fixed_grid();
+        let finished = Arc::new(AtomicUsize::new(usize::MAX));
+        let this = &*self;
+        let results = parallel::map_init(
+            (0..racers).collect(),
+            self.threads,
+            || None,
+            |spare: &mut Option<Dictionary>, index| {
+                if finished.load(Ordering::Relaxed) != usize::MAX {
+                    return None;
+                }
+                // Every racer stops as soon as any finishes
+                let cancel = Cancel {
+                    solved: Arc::clone(&finished),
+                    index: usize::MAX,
+                };
+                let seed = match index {
+                    0 => this.seed,
+                    _ => Some(this.seed.unwrap_or(0).wrapping_add((index as u64) << 32)),
+                };
+                let dict = spare.take().unwrap_or_else(|| this.dict.clone());
+                let mut trial = this.branch(fixed.clone(), dict, seed, cancel);
+                trial.set_restarts(Some(unit));
+                let result = trial.fill();
+                // A cancelled search ends without giving up too, so only
+                // the first to end either way is kept
+                if result.is_ok() || !trial.gave_up() {
+                    let _ = finished.compare_exchange(
+                        usize::MAX,
+                        index,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    );
+                }
+                let (branch, dict) = trial.into_branch(result.is_ok());
+                *spare = Some(dict);
+                Some((index, branch, result))
+            },
+        );
+
+        // Unless every search gave up, the first to finish has the answer
+        let first = finished.load(Ordering::Relaxed);
+        let mut result = Err("Gave up".to_string());
+        let mut branches = vec![];
+        let mut winner = None;
+        for (index, branch, outcome) in results.into_iter().flatten() {
+            if index == first {
+                result = outcome;
+                winner = result.is_ok().then_some(branches.len());
+            }
+            branches.push(branch);
+        }
+        self.gather(&branches, 0);
+        if let Some(i) = winner {
+            let branch = branches.swap_remove(i);
+            debug!(
+                "search {} found the square after {} of the {} attempts",
+                first, branch.attempts, self._attempt
+            );
+            self.note_seed(branch.seed);
+            self.square = branch.grid;
+        }
+        result
+    }
+
+    /// Add up what the searches in `branches` did, each of which started
+    /// with `placed` more cells filled than this square.
+    fn gather(&mut self, branches: &[Branch], placed: usize) {
+        for branch in branches.iter() {
+            self._attempt += branch.attempts;
+            self.max_depth = self.max_depth.max(branch.max_depth + placed);
+            self.queries.fetch_add(branch.queries, Ordering::Relaxed);
+            for (depth, &count) in branch.backtracks.iter().enumerate() {
+                if self.backtracks.len() <= depth + placed {
+                    self.backtracks.resize(depth + placed + 1, 0);
+                }
+                self.backtracks[depth + placed] += count;
+            }
+            if let (Some(stats), Some(counted)) =
+                (self.template_stats.as_mut(), &branch.template_stats)
+            {
+                for (template, &checks) in counted {
+                    *stats.entry(template.clone()).or_insert(0) += checks;
+                }
+            }
+            if let Some(stats) = branch.nogood_stats {
+                self.add_nogood_stats(stats);
+            }
+        }
+        if let Some(deepest) = branches.iter().max_by_key(|b| b.deepest_depth) {
+            if deepest.deepest_depth + placed > self.deepest_depth {
+                self.deepest_depth = deepest.deepest_depth + placed;
+                self.deepest = deepest.deepest.clone();
+            }
+        }
+        // A search given up on may have had a square ahead of it, so not
+        // finding one proves nothing
+        self.gave_up = branches.iter().any(|b| b.gave_up);
+    }
+
+    /// The grid after each choice the search would make first, in the
+    /// order it would try them: each letter for the first cell, or each
+    /// word for the first row with a blank. `None` if there is nothing
+    /// left to fill.
+    fn first_choices(&mut self) -> Option<Vec<Vec<Vec<char>>>> {
+        match self.strategy {
+            Strategy::Letters => {
+                let (row, col) = self.next_cell()?;
+                let mut letters = self.letter_order(row, col);
+                letters.retain(|&c| self.is_valid_letter(row, col, c));
+                let choices = letters
+                    .into_iter()
+                    .map(|c| {
+                        let mut grid = self.square.clone();
+                        grid[row][col] = c;
+                        if self.symmetric {
+                            grid[col][row] = c;
+                        }
+                        grid
+                    })
+                    .collect();
+                Some(choices)
+            }
+            Strategy::Rows => {
+                let row = (0..self.square.len()).find(|&row| self.has_blank(row))?;
+                let choices = self
+                    .row_words(row)
+                    .into_iter()
+                    .map(|word| {
+                        let mut grid = self.square.clone();
+                        let letters = Slot::Row(row).orient(self.direction, word.chars().collect());
+                        if self.symmetric {
+                            for (line, &c) in grid.iter_mut().zip(&letters) {
+                                line[row] = c;
+                            }
+                        }
+                        grid[row] = letters;
+                        grid
+                    })
+                    .collect();
+                Some(choices)
+            }
+            Strategy::ExactCover => unreachable!("an exact cover search runs on one thread"),
+            Strategy::Words => unreachable!("a word-by-word search runs on one thread"),
+            Strategy::Beam => unreachable!("a beam search runs on one thread"),
+            Strategy::Anneal => unreachable!("annealing runs on one thread"),
+            Strategy::Genetic => unreachable!("a genetic search runs on one thread"),
+            #[cfg(feature = "sat")]
+            Strategy::Sat => unreachable!("a SAT search runs on one thread"),
+        }
+    }
+
+    /// How many blank cells the solver may write are filled in `grid`.
+    fn placed(&self, grid: &[Vec<char>]) -> usize {
+        self.empty_cells()
+            .filter(|&(row, col)| grid[row][col] != '_')
+            .count()
+    }
+
+    /// A square to search below one choice: `grid`, with its letters
+    /// fixed, read and searched as this one is, but on one thread, showing
+    /// nothing, and shuffled with `seed`.
+    fn branch(
+        &self,
+        grid: Vec<Vec<char>>,
+        dict: Dictionary,
+        seed: Option<u64>,
+        cancel: Cancel,
+    ) -> MagicSquare {
+        let mut trial = MagicSquare::with_dictionary(grid.len(), grid[0].len(), dict);
+        trial.reset_to(&grid);
+        trial.deepest = grid;
+        trial.set_live_view(LiveView::Off);
+        trial.set_direction(self.direction);
+        trial.set_mirror_rows(self.mirror_rows);
+        trial.set_overlap(self.overlap());
+        trial.set_distinct(self.distinct);
+        trial.set_diagonals(self.diagonals);
+        trial.set_min_word_len(self.min_word_len);
+        trial.set_toroidal(self.toroidal);
+        trial.set_strategy(self.strategy);
+        trial.set_beam_width(self.beam_width);
+        trial.set_population(self.population);
+        trial.set_mutation_rate(self.mutation_rate);
+        trial.set_generations(self.generations);
+        trial.set_cell_order(self.cell_order);
+        trial.set_letter_order(self.letter_order);
+        trial.set_propagate(self.propagate);
+        trial.set_forward_check(self.forward_check);
+        trial.set_seed(seed);
+        trial.set_deterministic(self.deterministic);
+        trial.set_template_stats(self.template_stats.is_some());
+        trial.set_nogoods(self.nogoods.is_some());
+        // Every search shares the time left
+        trial.set_time_limit(
+            self.deadline
+                .map(|at| at.saturating_duration_since(Instant::now())),
+        );
+        trial.cancel = Some(cancel);
+        trial
+    }
+
+    /// What the search below a choice did, and the dictionary back.
+    fn into_branch(self, solved: bool) -> (Branch, Dictionary) {
+        let seed = self.search_seed();
+        let branch = Branch {
+            solved,
+            gave_up: self.gave_up,
+            grid: self.square,
+            attempts: self._attempt,
+            backtracks: self.backtracks,
+            max_depth: self.max_depth,
+            queries: self.queries.into_inner(),
+            deepest: self.deepest,
+            deepest_depth: self.deepest_depth,
+            template_stats: self.template_stats,
+            nogood_stats: self.nogoods.map(|nogoods| nogoods.stats),
+            seed,
+        };
+        (branch, self.dict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{assert_fills, UNSOLVABLE};
+    use crate::{Diagonals, Strategy};
+
+    #[test]
+    fn keeps_the_square_one_search_would() {
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            let single = assert_fills("____", 4, strategy, |_| {});
+            for threads in [1, 2, 4] {
+                let split = assert_fills("____", 4, strategy, |square| {
+                    square.set_parallel(true);
+                    square.set_threads(Some(threads));
+                });
+                assert_eq!(split.grid(), single.grid(), "{:?} on {} threads", strategy, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_do_not_depend_on_threads() {
+        let fill = |threads| {
+            assert_fills("____", 4, Strategy::Letters, |square| {
+                square.set_parallel(true);
+                square.set_threads(Some(threads));
+                square.set_seed(Some(7));
+            })
+        };
+        let one = fill(1);
+        for threads in [2, 4] {
+            assert_eq!(fill(threads).grid(), one.grid(), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_diagonals() {
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            assert_fills("___", 3, strategy, |square| {
+                square.set_parallel(true);
+                square.set_mirror_rows(true);
+            });
+            assert_fills("____", 4, strategy, |square| {
+                square.set_parallel(true);
+                square.set_diagonals(Diagonals::Main);
+            });
+        }
+    }
+
+    #[test]
+    fn races_restarts_to_a_square_its_seed_reproduces() {
+        for strategy in [Strategy::Letters, Strategy::Rows] {
+            for seed in [None, Some(5)] {
+                let raced = assert_fills("_____", 5, strategy, |square| {
+                    square.set_parallel(true);
+                    square.set_threads(Some(4));
+                    square.set_restarts(Some(20));
+                    square.set_seed(seed);
+                });
+                let winner = raced.solve_stats().seed;
+                let alone = assert_fills("_____", 5, strategy, |square| {
+                    square.set_restarts(Some(20));
+                    square.set_seed(winner);
+                });
+                assert_eq!(alone.grid(), raced.grid(), "{:?} seed {:?}", strategy, winner);
+            }
+        }
+    }
+
+    #[test]
+    fn races_restarts_to_show_there_is_no_square() {
+        let dict = UNSOLVABLE.dictionary();
+        let mut square = UNSOLVABLE.square(&dict);
+        square.set_parallel(true);
+        square.set_threads(Some(4));
+        square.set_restarts(Some(1));
+        assert!(square.fill().is_err());
+        assert!(!square.gave_up());
+    }
+}
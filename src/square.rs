@@ -0,0 +1,2075 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration, Instant};
+
+use crate::checkpoint::{Checkpoint, SavedPoint, DEFAULT_CHECKPOINT_EVERY};
+use crate::dictionary::Dictionary;
+use crate::logging::{Level, Span};
+use crate::nogood::{NogoodKey, NogoodStats, Nogoods};
+use crate::optimize::{Incumbent, SoftRule};
+use crate::progress::Progress;
+use crate::propagate::{Domains, ForwardCheck, Rules};
+use crate::render::{self, Target};
+use crate::rng::Rng;
+use crate::slot::{runs, Direction, Entry, Slot, BLOCK};
+use crate::solve_stats::{SolveStart, SolveStats};
+use crate::split::Cancel;
+use crate::tally::{Narrowed, Tallies};
+
+const ATTEMPT_RENDER_FREQ: usize = 5;
+/// How many attempts a search with a time limit or checkpoints makes
+/// between readings of the clock.
+pub(crate) const CLOCK_CHECK_FREQ: usize = 256;
+
+/// What `fill` shows while it searches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LiveView {
+    /// Nothing.
+    Off,
+    /// Clear the screen and redraw the grid on stdout every few attempts.
+    Grid,
+    /// A one-line status (attempts, depth, backtracks, rate and an estimate
+    /// of how much of the search tree is left) on stderr.
+    Progress,
+}
+
+/// Which empty cell `fill` fills next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CellOrder {
+    /// The first empty cell in reading order, row by row.
+    Reading,
+    /// The empty cell whose row or column template has the fewest matching
+    /// words (the "minimum remaining values" heuristic), so the search
+    /// fails early on the hardest slot instead of deep below an easy one.
+    /// With propagation on, the cell with the fewest candidate letters
+    /// instead. Ties go to the first in reading order.
+    MostConstrained,
+}
+
+/// The order `fill` tries letters in at a cell.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LetterOrder {
+    /// The dictionary's alphabet, shuffled if a seed is set, with letters
+    /// that allow more common words first if it has word frequencies.
+    Alphabet,
+    /// The letters most words fitting the cell's row and column have
+    /// there first, scored by the product of the two counts, so the
+    /// search tries the letters that leave its slots the most words.
+    /// Ties keep their alphabet (or shuffled) order.
+    Matches,
+}
+
+/// How `fill` searches for a square.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    /// One letter at a time, into the cell the cell order picks.
+    Letters,
+    /// One whole row at a time, top to bottom: each word that fits a row's
+    /// fixed letters is tried in turn, and kept if every column can still
+    /// be completed. The cell order, propagation and forward checking do
+    /// not apply.
+    Rows,
+    /// Whole words, as an exact cover problem solved with dancing links:
+    /// every row and column must be covered by exactly one word, and
+    /// crossing words must agree on their shared cell. The search always
+    /// fills the slot with the fewest words left, and each word it places
+    /// strikes out the words it rules out in the crossing slots. The cell
+    /// order, propagation, forward checking and threads do not apply.
+    ExactCover,
+    /// One whole word at a time, crossword style: the grid's words are
+    /// worked out from its blocks, and the one with the fewest words
+    /// fitting it is filled next, with each word that keeps every word
+    /// crossing it completable. Best for grids with blocks, whose rows and
+    /// columns hold several words. The cell order, propagation, forward
+    /// checking and threads do not apply.
+    Words,
+    /// A beam search: one letter at a time in reading order, keeping only
+    /// the most promising partial grids at each cell (see
+    /// [`MagicSquare::set_beam_width`]), scored by the product of how many
+    /// words fit each row and column. It may miss a square that exists, so
+    /// failing proves nothing, but it finds good fills of big grids where
+    /// backtracking stalls. The cell order, propagation, forward checking
+    /// and threads do not apply.
+    Beam,
+    /// Simulated annealing: start from a full grid of random row words,
+    /// and keep swapping a row for another word, keeping swaps that leave
+    /// fewer columns that are not words (and, while the search is hot,
+    /// some that leave more), until every column is a word. With word
+    /// frequencies it also prefers common words. It never proves there is
+    /// no square, so it gives up after a million swaps if nothing stops it
+    /// sooner. Grids with blocks, and the cell order, propagation, forward
+    /// checking and threads, do not apply.
+    Anneal,
+    /// A genetic algorithm: a population of full grids, first of random
+    /// row words, evolves by crossing rows between fit parents and changing
+    /// letters at random, until some grid is a square (see
+    /// [`MagicSquare::set_population`], [`MagicSquare::set_mutation_rate`]
+    /// and [`MagicSquare::set_generations`]). Grids are fitter the more
+    /// words they have, and the more common those are. It never proves
+    /// there is no square. Grids with blocks, and the cell order,
+    /// propagation, forward checking and threads, do not apply.
+    Genetic,
+    /// The whole grid as a SAT formula, solved by a clause-learning
+    /// solver, which learns from every dead end which letters and words
+    /// contradict each other. Best at showing that fixed letters leave no
+    /// square. Only available with the `sat` feature.
+    #[cfg(feature = "sat")]
+    Sat,
+}
+
+/// Which diagonals of a square grid must spell words too, read from the
+/// top row down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Diagonals {
+    /// Neither: only the rows and columns.
+    Off,
+    /// The main diagonal, from the top left corner to the bottom right.
+    Main,
+    /// The main diagonal, and the anti-diagonal from the top right corner
+    /// to the bottom left.
+    Both,
+}
+
+/// Whether a word may fill both a row and a column of the square.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overlap {
+    /// Any word may fill any row and any column.
+    Allowed,
+    /// No row may spell the same word as a column, though rows may repeat
+    /// each other, as may columns.
+    Forbidden,
+    /// Each row must spell the same word as the column with its number:
+    /// the classic word square. See [`MagicSquare::set_symmetric`].
+    Symmetric,
+}
+
+/// A cell the search has reached, and the letters it is trying there.
+pub(crate) struct ChoicePoint {
+    pub(crate) row: usize,
+    pub(crate) col: usize,
+    /// The letters to try, in order.
+    pub(crate) letters: Vec<char>,
+    /// The index in `letters` of the next letter to try.
+    pub(crate) next: usize,
+    /// The candidate letters of every cell when the search reached this
+    /// one, to go back to before each letter, when propagating.
+    pub(crate) saved: Option<Domains>,
+    /// The cell's forward check, made the first time a letter gets that
+    /// far.
+    check: Option<ForwardCheck>,
+    /// The search's state on reaching the cell, if the cell starts a row
+    /// and nogoods are being recorded.
+    nogood: Option<NogoodKey>,
+    /// The cells the current letter left with one candidate, which were
+    /// filled with it, to blank again before the next letter.
+    forced: Vec<(usize, usize)>,
+    /// The words fitting the slots through the cell before the current
+    /// letter, to put back before the next.
+    narrowed: Narrowed,
+}
+
+/// A magic square is a NxM matrix of letters arranged such that every row and
+/// every column is a valid dictionary word.
+/// This struct represents a magic square.
+/// It is a wrapper around a 2D vector of chars.
+/// The `fill` method will fill the square with letters.
+/// The `print` method will print the square to stdout.
+/// The `empty` method will create an empty square.
+pub struct MagicSquare {
+    pub(crate) square: Vec<Vec<char>>,
+    pub(crate) editable_mask: Vec<Vec<bool>>,
+    pub(crate) dict: Dictionary,
+    pub(crate) _attempt: usize,
+    /// How many times the search backtracked out of each depth, where depth
+    /// is the number of cells the solver had filled at the time.
+    pub(crate) backtracks: Vec<usize>,
+    /// What to show while searching.
+    pub(crate) live: LiveView,
+    progress: Option<Progress>,
+    /// The index of the choice being tried at each depth of the current search path (a letter
+    /// in the letter order, or a word for a row), and how many choices there were, used to
+    /// estimate how much of the tree is done.
+    pub(crate) path: Vec<(usize, usize)>,
+    /// How words are read out of rows and columns.
+    pub(crate) direction: Direction,
+    /// Whether every row must also be a word when read backwards.
+    pub(crate) mirror_rows: bool,
+    /// Whether every letter must equal the one mirrored across the main
+    /// diagonal, so each row is the same word as its column.
+    pub(crate) symmetric: bool,
+    /// Whether every row and column must spell a different word.
+    pub(crate) distinct: bool,
+    /// Whether no row may spell the same word as a column.
+    pub(crate) unshared: bool,
+    /// Which diagonals must spell words.
+    pub(crate) diagonals: Diagonals,
+    /// The fewest letters a word between blocks may have.
+    pub(crate) min_word_len: usize,
+    /// Whether rows and columns are read around a torus, so any rotation
+    /// of a word fills them.
+    pub(crate) toroidal: bool,
+    /// Whether to place letters or whole rows.
+    pub(crate) strategy: Strategy,
+    /// How many partial grids a beam search keeps at each cell.
+    pub(crate) beam_width: usize,
+    /// How many grids a genetic search evolves at once.
+    pub(crate) population: usize,
+    /// The chance a genetic search changes each letter of a child grid.
+    pub(crate) mutation_rate: f64,
+    /// How many generations a genetic search breeds before giving up.
+    pub(crate) generations: usize,
+    /// Which empty cell to fill next.
+    pub(crate) cell_order: CellOrder,
+    /// Which letter `fill` tries first at a cell.
+    pub(crate) letter_order: LetterOrder,
+    /// Whether to split the search over threads.
+    pub(crate) parallel: bool,
+    /// How many threads to split it over, if not one per core.
+    pub(crate) threads: Option<usize>,
+    /// Set when the search is one of several, and another has made it
+    /// pointless.
+    pub(crate) cancel: Option<Cancel>,
+    /// Whether to keep candidate letters for every cell and narrow them
+    /// after each placement.
+    pub(crate) propagate: bool,
+    /// Whether to reject a letter that leaves a cell in its row or column
+    /// with no possible letter.
+    pub(crate) forward_check: bool,
+    /// The candidate letters of every cell during a propagating `fill`.
+    pub(crate) domains: Option<Domains>,
+    /// The words fitting each row and column during a letter-by-letter
+    /// `fill` that does not propagate.
+    pub(crate) tallies: Option<Tallies>,
+    /// The seed to shuffle the letter order with, if shuffling.
+    pub(crate) seed: Option<u64>,
+    /// Whether to ignore the seed, the cell and letter orders and word
+    /// frequencies, and fill cells in reading order trying letters in
+    /// alphabet order.
+    pub(crate) deterministic: bool,
+    /// The generator shuffling the current `fill`, started from `seed`.
+    pub(crate) rng: Option<Rng>,
+    /// The most letters `fill` may try before giving up, if limited.
+    pub(crate) attempt_limit: Option<usize>,
+    /// The attempts to allow the first search when restarting, if
+    /// restarting.
+    pub(crate) restart_unit: Option<usize>,
+    /// The attempt count at which the current `fill` gives up.
+    pub(crate) give_up_at: Option<usize>,
+    /// How long `fill` may search before giving up, if limited.
+    pub(crate) time_limit: Option<Duration>,
+    /// When the current `fill` gives up, if it has a time limit.
+    pub(crate) deadline: Option<Instant>,
+    /// Where to save the search's place, if saving it.
+    pub(crate) checkpoint: Option<PathBuf>,
+    /// How often to save the search's place.
+    pub(crate) checkpoint_every: Duration,
+    /// When the current `fill` next saves its place, if saving it.
+    pub(crate) next_checkpoint: Option<Instant>,
+    /// Where the next `fill` carries on from, if resuming.
+    pub(crate) resume: Option<Checkpoint>,
+    /// Whether the last `fill` stopped at its attempt limit.
+    pub(crate) gave_up: bool,
+    /// The best square `optimize` has found so far, if optimizing.
+    pub(crate) incumbent: Option<Incumbent>,
+    /// The rules `optimize` may break, each with what breaking it once
+    /// costs.
+    pub(crate) soft_rules: Vec<(SoftRule, f64)>,
+    /// How many times each template was checked, if counting is on.
+    pub(crate) template_stats: Option<HashMap<String, usize>>,
+    /// The states the current `fill` has shown lead nowhere, if recording
+    /// them.
+    pub(crate) nogoods: Option<Nogoods>,
+    /// The most complete grid reached so far, and how many cells the solver
+    /// had filled in it.
+    pub(crate) deepest: Vec<Vec<char>>,
+    pub(crate) deepest_depth: usize,
+    /// The most cells the current `fill` has had filled at once.
+    pub(crate) max_depth: usize,
+    /// How many times the solver has asked the dictionary about a slot.
+    pub(crate) queries: AtomicUsize,
+    /// The counters as the current `fill` started.
+    pub(crate) solve_start: Option<SolveStart>,
+    /// What the last `fill` did.
+    pub(crate) solve_stats: SolveStats,
+}
+
+impl MagicSquare {
+    /// Create an empty magic square.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The number of rows in the square.
+    /// * `cols` - The number of columns in the square.
+    /// * `dict` - The dictionary to use.
+    ///
+    /// # Returns
+    ///
+    /// * A new empty magic square.
+    pub fn empty(rows: usize, cols: usize, dict: &Dictionary) -> MagicSquare {
+        MagicSquare::with_dictionary(rows, cols, dict.clone())
+    }
+
+    /// Create an empty magic square that owns `dict`, rather than a copy.
+    pub(crate) fn with_dictionary(rows: usize, cols: usize, dict: Dictionary) -> MagicSquare {
+        MagicSquare {
+            square: vec![vec!['_'; cols]; rows],
+            editable_mask: vec![vec![true; cols]; rows],
+            dict,
+            _attempt: 0,
+            backtracks: vec![],
+            live: LiveView::Grid,
+            progress: None,
+            path: vec![],
+            direction: Direction::Standard,
+            mirror_rows: false,
+            symmetric: false,
+            distinct: false,
+            unshared: false,
+            diagonals: Diagonals::Off,
+            min_word_len: 2,
+            toroidal: false,
+            strategy: Strategy::Letters,
+            beam_width: 100,
+            population: 200,
+            mutation_rate: 0.02,
+            generations: 1000,
+            cell_order: CellOrder::Reading,
+            letter_order: LetterOrder::Alphabet,
+            parallel: false,
+            threads: None,
+            cancel: None,
+            propagate: true,
+            forward_check: false,
+            domains: None,
+            tallies: None,
+            seed: None,
+            deterministic: false,
+            rng: None,
+            attempt_limit: None,
+            restart_unit: None,
+            give_up_at: None,
+            time_limit: None,
+            deadline: None,
+            checkpoint: None,
+            checkpoint_every: DEFAULT_CHECKPOINT_EVERY,
+            next_checkpoint: None,
+            resume: None,
+            gave_up: false,
+            incumbent: None,
+            soft_rules: vec![],
+            template_stats: None,
+            nogoods: None,
+            deepest: vec![vec!['_'; cols]; rows],
+            deepest_depth: 0,
+            max_depth: 0,
+            queries: AtomicUsize::new(0),
+            solve_start: None,
+            solve_stats: SolveStats::default(),
+        }
+    }
+
+    /// Create a magic square with the letters of a template already fixed.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - Letters, underscores and `#` blocks, with `/`
+    ///   separating rows. The width of the square is taken from the first
+    ///   row, unless the rows differ in length: then the grid is ragged, as
+    ///   wide as its longest row, and shorter rows are padded on the right
+    ///   with blocks, so each row and column is a word of its own length.
+    /// * `rows` - The number of rows in the square.
+    /// * `dict` - The dictionary to use.
+    ///
+    /// # Returns
+    ///
+    /// * A new magic square with the template letters hardened.
+    pub fn from_template(template: &str, rows: usize, dict: &Dictionary) -> MagicSquare {
+        let lengths: Vec<usize> = template.split('/').map(|row| row.chars().count()).collect();
+        if lengths.iter().any(|&len| len != lengths[0]) {
+            return MagicSquare::from_ragged_template(template, rows, dict);
+        }
+        let column_count = lengths[0];
+        let mut square = MagicSquare::empty(rows, column_count, dict);
+        for (i, c) in template.chars().filter(|x| *x != '/').enumerate() {
+            let row = i / column_count;
+            let col = i % column_count;
+            square.set_and_harden(row, col, c);
+        }
+        square
+    }
+
+    /// Create a square from a template whose rows differ in length, each
+    /// padded on the right with blocks to the longest. Rows below the
+    /// template are as long as the longest.
+    fn from_ragged_template(template: &str, rows: usize, dict: &Dictionary) -> MagicSquare {
+        let lines: Vec<Vec<char>> = template.split('/').map(|row| row.chars().collect()).collect();
+        let column_count = lines.iter().map(Vec::len).max().unwrap_or(0);
+        let mut square = MagicSquare::empty(rows, column_count, dict);
+        for (row, line) in lines.iter().enumerate().take(rows) {
+            for col in 0..column_count {
+                square.set_and_harden(row, col, line.get(col).copied().unwrap_or(BLOCK));
+            }
+        }
+        square
+    }
+
+    fn set(&mut self, row: usize, col: usize, c: char) {
+        self.square[row][col] = c;
+        if self.symmetric {
+            self.square[col][row] = c;
+        }
+    }
+
+    /// Write a row's letters, in grid order, and in a symmetric square the
+    /// column mirroring it too.
+    pub(crate) fn set_row(&mut self, row: usize, letters: Vec<char>) {
+        if self.symmetric {
+            for (col, &c) in letters.iter().enumerate() {
+                self.square[col][row] = c;
+            }
+        }
+        self.square[row] = letters;
+    }
+    pub fn set_and_harden(&mut self, row: usize, col: usize, c: char) {
+        self.square[row][col] = c;
+        if c != '_' {
+            self.editable_mask[row][col] = false;
+        }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> char {
+        self.square[row][col]
+    }
+
+    /// Fill the square with letters.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled successfully.
+    /// * `Err(String)` if the square could not be filled. If the search gave
+    ///   up at its attempt or time limit, the error says which slots the
+    ///   most complete grid it reached left unfilled, and
+    ///   [`best_fill`](MagicSquare::best_fill) returns that grid.
+    pub fn fill(&mut self) -> Result<(), String> {
+        self.begin_fill();
+        let result = match self.restart_unit {
+            Some(unit) if self.races() => self.fill_racing(unit),
+            Some(unit) => self.fill_with_restarts(unit),
+            None => self.search(self.attempt_limit),
+        };
+        self.finish_stats();
+        match result {
+            Err(_) if self.gave_up => Err(self.gave_up_error()),
+            result => result,
+        }
+    }
+
+    /// Whether a restarted `fill` races searches on several threads: only
+    /// with threads on and no attempt limit, as in `search`, and in a
+    /// search that splits, whose orders are shuffled.
+    fn races(&self) -> bool {
+        self.parallel
+            && self.attempt_limit.is_none()
+            && !self.deterministic
+            && matches!(self.strategy, Strategy::Letters | Strategy::Rows)
+    }
+
+    /// Get ready for a `fill`: forget the last one's nogoods, and start
+    /// the clock and the stats.
+    pub(crate) fn begin_fill(&mut self) {
+        self.clear_nogoods();
+        self.start_stats();
+        self.deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        if self.deepest_depth == 0 {
+            self.deepest = self.fixed_grid();
+        }
+    }
+
+    /// Why the last `fill` gave up, and how far it got.
+    pub(crate) fn gave_up_error(&self) -> String {
+        let reason = match (self.time_limit, self.attempt_limit) {
+            (Some(limit), _) if self.out_of_time() => {
+                format!("Gave up after {:.1} s", limit.as_secs_f64())
+            }
+            (_, Some(limit)) => format!("Gave up after {} attempts", limit),
+            _ => "Gave up".to_string(),
+        };
+        let unfilled: Vec<String> = self.unfilled_slots().iter().map(Slot::to_string).collect();
+        format!(
+            "{}; the most complete grid filled {} cells and left {} unfilled",
+            reason,
+            self.deepest_depth,
+            unfilled.join(", ")
+        )
+    }
+
+    /// Search for a way to fill the square, giving up after `limit`
+    /// attempts if there is a limit.
+    pub(crate) fn search(&mut self, limit: Option<usize>) -> Result<(), String> {
+        // Starting at the top left, fill the square with letters such that
+        // every row and column is a valid dictionary word. This is done by
+        // recursively filling the square with letters, and backtracking if
+        // any of the crosswords become a template with no valid matches.
+
+        let _span = Span::enter(Level::Debug, "fill");
+        self.begin_search(limit);
+
+        let result = if let Some(e) = self.setup_error() {
+            Err(e)
+        } else {
+            match self.strategy {
+                Strategy::ExactCover => self.fill_exact_cover(),
+                Strategy::Words => self.fill_words(),
+                Strategy::Beam => self.fill_beam(),
+                Strategy::Anneal => self.fill_anneal(),
+                Strategy::Genetic => self.fill_genetic(),
+                #[cfg(feature = "sat")]
+                Strategy::Sat => self.fill_sat(),
+                // A limit on attempts means the same with threads as
+                // without only if they are counted in one place, so it
+                // keeps to one
+                _ if self.parallel && limit.is_none() && self.checkpoint.is_none() => {
+                    self.fill_parallel()
+                }
+                Strategy::Rows => self.fill_rows(0),
+                Strategy::Letters => {
+                    // Fill the square with letters
+                    if self.propagates() && !self.start_domains() {
+                        Err("No letter fits some cell".to_string())
+                    } else {
+                        self.fill_helper()
+                    }
+                }
+            }
+        };
+        self.end_search();
+
+        // The searches a split, restarted or optimizing search runs report
+        // to it, so it sums them up
+        if self.cancel.is_none() && self.restart_unit.is_none() && self.incumbent.is_none() {
+            info!(
+                "{} attempts, {} backtracks",
+                self._attempt,
+                self.backtracks.iter().sum::<usize>()
+            );
+            for (depth, count) in self.backtracks.iter().enumerate() {
+                if *count > 0 {
+                    debug!("depth {}: {} backtracks", depth, count);
+                }
+            }
+            self.log_nogood_stats();
+        }
+        match limit {
+            Some(limit) if self.gave_up => Err(format!("Gave up after {} attempts", limit)),
+            _ => result,
+        }
+    }
+
+    /// Get ready for a search that gives up after `limit` attempts, if
+    /// there is a limit.
+    pub(crate) fn begin_search(&mut self, limit: Option<usize>) {
+        if self.live == LiveView::Progress {
+            self.progress = Some(Progress::new());
+        }
+        self.gave_up = false;
+        self.path.clear();
+        self.give_up_at = limit.map(|limit| self._attempt + limit);
+        self.next_checkpoint = self
+            .checkpoint
+            .as_ref()
+            .map(|_| Instant::now() + self.checkpoint_every);
+        // Every fill with the same seed tries letters in the same order
+        self.rng = self.search_seed().map(Rng::new);
+    }
+
+    /// Tidy up after a search: drop its candidate letters, and finish its
+    /// live view.
+    pub(crate) fn end_search(&mut self) {
+        self.domains = None;
+        self.tallies = None;
+        if let Some(progress) = self.progress.take() {
+            progress.finish();
+        }
+        if self.live != LiveView::Off {
+            render::end_live();
+        }
+    }
+
+    fn find_first_empty_square(&self) -> Option<(usize, usize)> {
+        for (row, row_vec) in self.square.iter().enumerate() {
+            for (col, c) in row_vec.iter().enumerate() {
+                if *c == '_' && self.editable_mask[row][col] {
+                    return Some((row, col));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The empty cell to fill next, by the cell order, or `None` if the
+    /// square is full.
+    pub(crate) fn next_cell(&self) -> Option<(usize, usize)> {
+        if self.cell_order() == CellOrder::Reading {
+            return self.find_first_empty_square();
+        }
+        // When propagating, the cell with the fewest candidate letters
+        if let Some(domains) = &self.domains {
+            return self
+                .empty_cells()
+                .min_by_key(|&(row, col)| domains.count(row, col));
+        }
+        if let Some(tallies) = &self.tallies {
+            return self.empty_cells().min_by_key(|&(row, col)| {
+                let (across, down) = (tallies.count(Slot::Row(row)), tallies.count(Slot::Col(col)));
+                (across.min(down), across.max(down))
+            });
+        }
+        // How many words fit each row and column as it stands, counted by
+        // popcount, so this costs one index lookup per run between blocks
+        let rows: Vec<Vec<usize>> = (0..self.square.len())
+            .map(|r| self.run_matches(Slot::Row(r)))
+            .collect();
+        let cols: Vec<Vec<usize>> = (0..self.square[0].len())
+            .map(|c| self.run_matches(Slot::Col(c)))
+            .collect();
+        self.empty_cells().min_by_key(|&(row, col)| {
+            let (across, down) = (rows[row][col], cols[col][row]);
+            (across.min(down), across.max(down))
+        })
+    }
+
+    /// The cells the solver may write that are still blank, in reading
+    /// order.
+    pub(crate) fn empty_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.square.iter().enumerate().flat_map(move |(row, row_vec)| {
+            row_vec
+                .iter()
+                .enumerate()
+                .filter(move |&(col, &c)| c == '_' && self.editable_mask[row][col])
+                .map(move |(col, _)| (row, col))
+        })
+    }
+
+    /// Helper function for `fill`.
+    /// Fill the square with letters, one empty cell at a time.
+    /// If any of the crosswords become a template with no valid matches,
+    /// backtrack and try a different letter.
+    /// If all letters have been tried and none of them work, return an error.
+    /// If the square is filled successfully, return `Ok(())`.
+    ///
+    /// The search keeps its own stack of cells, one per letter placed,
+    /// rather than recursing, so a large grid cannot overflow the thread's
+    /// stack however deep it goes.
+    fn fill_helper(&mut self) -> Result<(), String> {
+        let mut stack = vec![];
+        if let Some(checkpoint) = self.resume.take() {
+            return self.resume_letters(&mut stack, checkpoint);
+        }
+        self.start_letters(&mut stack)
+    }
+
+    /// Start a letter-by-letter search, with `stack` empty: push the first
+    /// cell to fill, and search from there.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the square was filled, leaving the choice points that
+    ///   filled it on `stack` (none if there was nothing to fill).
+    /// * `Err(String)` if it could not be.
+    pub(crate) fn start_letters(&mut self, stack: &mut Vec<ChoicePoint>) -> Result<(), String> {
+        self.start_tallies();
+        // Fixed cells are never on the stack: the next cell is always one
+        // the solver may write, and with none left, the square is done
+        let Some((row, col)) = self.next_cell() else {
+            return Ok(());
+        };
+        let first = self.choice_point(row, col);
+        if first.nogood.as_ref().is_some_and(|key| self.is_nogood(key)) {
+            return Err(format!("Could not fill square at ({}, {})", row, col));
+        }
+        stack.push(first);
+        self.search_letters(stack)
+    }
+
+    /// Carry on a letter-by-letter search from the choice points on
+    /// `stack`, trying the next letter of the last one. After a square,
+    /// its last letter must be taken off `path` first.
+    pub(crate) fn search_letters(&mut self, stack: &mut Vec<ChoicePoint>) -> Result<(), String> {
+        let (row, col) = stack.first().map_or((0, 0), |point| (point.row, point.col));
+        loop {
+            self.checkpoint_if_due(stack);
+            let Some(point) = stack.last_mut() else {
+                break;
+            };
+            let (row, col) = (point.row, point.col);
+            let Some(&c) = point.letters.get(point.next) else {
+                // If we've tried every letter and none of them work, backtrack
+                self.clear_forced(&mut point.forced);
+                self.untally(&mut point.narrowed);
+                // Letters too poor to beat the incumbent are no reason: the
+                // cells the state does not record count towards the score
+                let nogood = stack.pop().and_then(|point| point.nogood);
+                if let Some(key) = nogood.filter(|_| !self.bounds_score()) {
+                    self.record_nogood(key);
+                }
+                self.set(row, col, '_');
+                let depth = self.note_backtrack();
+                trace!("backtracking from ({}, {}) at depth {}", row, col, depth);
+                // The letter in the cell before this one leads nowhere
+                if !stack.is_empty() {
+                    self.path.pop();
+                }
+                continue;
+            };
+            let i = point.next;
+            point.next += 1;
+
+            // Out of attempts: unwind without trying anything else, saving
+            // the search's place with this letter still to try
+            if self.should_stop() {
+                point.next = i;
+                if self.gave_up {
+                    self.save_checkpoint(stack);
+                }
+                return Err(format!("Gave up at ({}, {})", row, col));
+            }
+            self._attempt += 1;
+            // Undo whatever the last letter narrowed
+            self.domains.clone_from(&point.saved);
+            self.clear_forced(&mut point.forced);
+            self.untally(&mut point.narrowed);
+            // If the letter is valid, set it and go on to the next cell
+            if !self.accepts(row, col, c, &mut point.check) {
+                continue;
+            }
+            self.set(row, col, c);
+            self.tally(row, col, c, &mut point.narrowed);
+            if !self.assign_forced(&mut point.forced) || !self.may_beat_incumbent() {
+                self.set(row, col, '_');
+                continue;
+            }
+            self.draw_grid();
+            let choices = self.dict.alphabet().len();
+            self.path.push((i, choices));
+            self.draw_progress();
+            self.note_depth();
+            let Some((nrow, ncol)) = self.next_cell() else {
+                // Every state on the way here leads to a square, so none is
+                // a nogood, however the search goes on
+                for point in stack.iter_mut() {
+                    point.nogood = None;
+                }
+                return Ok(());
+            };
+            let next = self.choice_point(nrow, ncol);
+            // A state already shown to lead nowhere: try the next letter
+            if next.nogood.as_ref().is_some_and(|key| self.is_nogood(key)) {
+                self.path.pop();
+                continue;
+            }
+            stack.push(next);
+        }
+        // Every letter in the first cell leads nowhere
+        Err(format!("Could not fill square at ({}, {})", row, col))
+    }
+
+    /// Rebuild the stack of a letter-by-letter search from the cells a
+    /// checkpoint saved: place the letter each cell but the last was
+    /// trying, with the letters it forced, just as the search placed it,
+    /// and leave the last cell ready to try its next letter.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if every letter could be placed again.
+    /// * `Err(String)` if one could not, because the checkpoint was saved
+    ///   with other settings.
+    pub(crate) fn replay(
+        &mut self,
+        stack: &mut Vec<ChoicePoint>,
+        points: Vec<SavedPoint>,
+    ) -> Result<(), String> {
+        let mismatch = || "The checkpoint was saved with other settings".to_string();
+        let last = points.len().saturating_sub(1);
+        for (depth, saved) in points.into_iter().enumerate() {
+            let (row, col) = (saved.row, saved.col);
+            let blank = self.square.get(row).and_then(|r| r.get(col)) == Some(&'_');
+            if !blank || !self.editable_mask[row][col] || saved.next > saved.letters.len() {
+                return Err(mismatch());
+            }
+            let mut point = ChoicePoint {
+                row,
+                col,
+                letters: saved.letters,
+                next: saved.next,
+                saved: self.domains.clone(),
+                check: None,
+                nogood: self.nogood_key(row, col),
+                forced: vec![],
+                narrowed: vec![],
+            };
+            if depth < last {
+                let i = point.next.checked_sub(1).ok_or_else(mismatch)?;
+                let c = point.letters[i];
+                if !self.accepts(row, col, c, &mut point.check) {
+                    return Err(mismatch());
+                }
+                self.set(row, col, c);
+                self.tally(row, col, c, &mut point.narrowed);
+                if !self.assign_forced(&mut point.forced) {
+                    return Err(mismatch());
+                }
+                self.path.push((i, self.dict.alphabet().len()));
+                self.note_depth();
+            }
+            stack.push(point);
+        }
+        Ok(())
+    }
+
+    /// Why the square cannot be filled as it is set up, if it cannot: see
+    /// `symmetry_error`, `distinct_error`, `diagonal_error`, `block_error`,
+    /// `toroidal_error`, `checkpoint_error` and `broken_slot`.
+    pub(crate) fn setup_error(&mut self) -> Option<String> {
+        if let Some(e) = self.symmetry_error().or_else(|| self.distinct_error()) {
+            return Some(e);
+        }
+        if let Some(e) = self.diagonal_error().or_else(|| self.block_error()) {
+            return Some(e);
+        }
+        if let Some(e) = self.toroidal_error().or_else(|| self.checkpoint_error()) {
+            return Some(e);
+        }
+        self.broken_slot().map(|slot| format!("No word fits {}", slot))
+    }
+
+    /// The first slot no word fits as it stands, if any. The search checks
+    /// a slot whenever it places a letter in it, so this is what catches a
+    /// slot whose letters are all fixed, such as a fixed row that is not a
+    /// word.
+    pub(crate) fn broken_slot(&mut self) -> Option<Slot> {
+        Slot::all(&self.square).into_iter().find(|&slot| {
+            let letters = slot.read(&self.square, self.direction);
+            let mirrored = self.mirror_rows && matches!(slot, Slot::Row(_));
+            !self.is_valid_word_or_template(&letters)
+                || (mirrored && !self.is_valid_mirror_template(&letters))
+        })
+    }
+
+    /// A cell for `fill_helper` to try letters in: every letter in the
+    /// alphabet (or every candidate left, when propagating), most promising
+    /// first.
+    fn choice_point(&mut self, row: usize, col: usize) -> ChoicePoint {
+        let mut letters = self.letter_order(row, col);
+        if let Some(domains) = &self.domains {
+            letters.retain(|&c| domains.allows(row, col, c));
+        }
+        ChoicePoint {
+            row,
+            col,
+            letters,
+            next: 0,
+            saved: self.domains.clone(),
+            check: None,
+            nogood: self.nogood_key(row, col),
+            forced: vec![],
+            narrowed: vec![],
+        }
+    }
+
+    /// Whether the current `fill` should stop: because it has tried as
+    /// many letters (or words) as it may, noting that it gave up if so, or
+    /// because it was cancelled.
+    pub(crate) fn should_stop(&mut self) -> bool {
+        let out_of_attempts = self.give_up_at.is_some_and(|at| self._attempt >= at);
+        if out_of_attempts
+            || (self._attempt.is_multiple_of(CLOCK_CHECK_FREQ) && self.out_of_time())
+        {
+            self.gave_up = true;
+        }
+        self.gave_up || self.cancel.as_ref().is_some_and(Cancel::is_set)
+    }
+
+    /// Whether the current `fill` has passed its time limit.
+    pub(crate) fn out_of_time(&self) -> bool {
+        self.deadline.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Redraw the grid, if it is shown live, every Nth attempt.
+    pub(crate) fn draw_grid(&self) {
+        if self.live == LiveView::Grid && self._attempt.is_multiple_of(ATTEMPT_RENDER_FREQ) {
+            render::draw(Target::Stdout, || self.grid_frame());
+        }
+    }
+
+    /// Remember the grid if it is the most complete yet.
+    pub(crate) fn note_depth(&mut self) {
+        let depth = self.filled_cell_count();
+        self.max_depth = self.max_depth.max(depth);
+        if depth > self.deepest_depth {
+            self.deepest_depth = depth;
+            self.deepest = self.square.clone();
+        }
+    }
+
+    /// Count a backtrack out of the current depth, and return the depth.
+    pub(crate) fn note_backtrack(&mut self) -> usize {
+        let depth = self.filled_cell_count();
+        if self.backtracks.len() <= depth {
+            self.backtracks.resize(depth + 1, 0);
+        }
+        self.backtracks[depth] += 1;
+        depth
+    }
+
+    /// Start the candidate letters of every cell from the grid, and narrow
+    /// them until they agree with the dictionary. Returns `false` if some
+    /// cell is left with none. An alphabet too large for a `LetterSet`
+    /// has no candidate letters, and the search goes on without them.
+    pub(crate) fn start_domains(&mut self) -> bool {
+        let Some(mut domains) = Domains::new(&self.square, self.dict.alphabet()) else {
+            info!(
+                "{} letters are too many to propagate; placing letters without it",
+                self.dict.alphabet().len()
+            );
+            self.domains = None;
+            return true;
+        };
+        let ok = domains.propagate(self.rules(), Slot::all(&self.square));
+        self.domains = ok.then_some(domains);
+        ok
+    }
+
+    /// Whether `c` can go at a cell. When propagating, this places it in
+    /// the candidate letters and narrows the rest, which the caller must
+    /// undo if the letter is later taken back.
+    ///
+    /// With forward checking on, `check` holds the cell's `ForwardCheck`,
+    /// made the first time a letter gets that far.
+    fn accepts(
+        &mut self,
+        row: usize,
+        col: usize,
+        c: char,
+        check: &mut Option<ForwardCheck>,
+    ) -> bool {
+        if self.words_differ() && self.repeats_word(row, col, c) {
+            return false;
+        }
+        // Without propagation, `is_valid_letter` checks the diagonals
+        if self.domains.is_some() && !self.fits_diagonals(row, col, c) {
+            return false;
+        }
+        let Some(mut domains) = self.domains.take() else {
+            if !self.is_valid_letter(row, col, c) {
+                return false;
+            }
+            if !self.forward_check {
+                return true;
+            }
+            let check = check
+                .get_or_insert_with(|| ForwardCheck::new(&self.square, self.rules(), (row, col)));
+            return check.allows(self.rules(), c);
+        };
+        domains.assign(row, col, c);
+        let mut slots = vec![Slot::Row(row), Slot::Col(col)];
+        if self.symmetric && row != col {
+            domains.assign(col, row, c);
+            slots.extend([Slot::Row(col), Slot::Col(row)]);
+        }
+        let ok = domains.propagate(self.rules(), slots);
+        self.domains = Some(domains);
+        ok
+    }
+
+    /// Fill every blank cell propagation has left with one candidate
+    /// letter, noting it in `forced`, so the search never has to choose
+    /// there. Each is placed as a chosen letter would be, so what it rules
+    /// out is propagated too, and in a symmetric square the cell mirroring
+    /// it, whose candidates may differ, must take it as well. Returns
+    /// `false` if one of those letters fails; the cells already filled
+    /// stay in `forced` either way.
+    fn assign_forced(&mut self, forced: &mut Vec<(usize, usize)>) -> bool {
+        loop {
+            let Some(domains) = &self.domains else {
+                return true;
+            };
+            let single = self
+                .empty_cells()
+                .find_map(|(row, col)| domains.only(row, col).map(|c| (row, col, c)));
+            let Some((row, col, c)) = single else {
+                return true;
+            };
+            if !self.accepts(row, col, c, &mut None) {
+                return false;
+            }
+            self.set(row, col, c);
+            forced.push((row, col));
+        }
+    }
+
+    /// Blank the cells `assign_forced` filled.
+    fn clear_forced(&mut self, forced: &mut Vec<(usize, usize)>) {
+        for (row, col) in forced.drain(..) {
+            self.set(row, col, '_');
+        }
+    }
+
+    /// Whether `fill` narrows candidate letters as it goes: see
+    /// `set_propagate`. A toroidal square's lines may be any rotation of a
+    /// word, which candidate letters do not follow, so it never does.
+    pub(crate) fn propagates(&self) -> bool {
+        self.propagate && !self.toroidal
+    }
+
+    /// The dictionary and reading rules, for candidate letters.
+    pub(crate) fn rules(&self) -> Rules<'_> {
+        Rules {
+            dict: &self.dict,
+            queries: &self.queries,
+            direction: self.direction,
+            mirror_rows: self.mirror_rows,
+            min_word_len: self.min_word_len,
+        }
+    }
+
+    /// Whether the grid has blocked cells.
+    pub(crate) fn has_blocks(&self) -> bool {
+        self.square.iter().flatten().any(|&c| c == BLOCK)
+    }
+
+    /// Every word the grid must spell: each row, then each column, split
+    /// at blocks into the runs long enough to be words.
+    pub(crate) fn entries(&self) -> Vec<Entry> {
+        Slot::all(&self.square)
+            .into_iter()
+            .flat_map(|slot| {
+                let cells = slot.cells(&self.square);
+                runs(&cells, |&c| c == BLOCK)
+                    .into_iter()
+                    .filter(|run| self.rules().is_word(run, cells.len()))
+                    .map(|cells| Entry { slot, cells })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// The run of `cells`, a slot's letters in grid order, through
+    /// `position`, or `None` if it is a block or the run is too short to be
+    /// a word.
+    fn run_through(&self, cells: &[char], position: usize) -> Option<Range<usize>> {
+        runs(cells, |&c| c == BLOCK)
+            .into_iter()
+            .find(|run| run.contains(&position))
+            .filter(|run| self.rules().is_word(run, cells.len()))
+    }
+
+    /// The letters of the run of a slot through `position`, with `c`
+    /// there, in reading order, or `None` if the run is too short to be a
+    /// word.
+    fn run_with(&self, slot: Slot, position: usize, c: char) -> Option<Vec<char>> {
+        let mut cells = slot.cells(&self.square);
+        cells[position] = c;
+        let run = self.run_through(&cells, position)?;
+        Some(slot.orient(self.direction, cells[run].to_vec()))
+    }
+
+    /// The run of a slot through `position`, in grid order, as a template
+    /// in reading order, and where `position` falls in it, or `None` if the
+    /// run is too short to be a word.
+    fn run_template(&self, slot: Slot, position: usize) -> Option<(String, usize)> {
+        let cells = slot.cells(&self.square);
+        let run = self.run_through(&cells, position)?;
+        let mut offset = position - run.start;
+        if slot.is_reversed(self.direction) {
+            offset = run.len() - 1 - offset;
+        }
+        let template = slot.orient(self.direction, cells[run].to_vec()).into_iter().collect();
+        Some((template, offset))
+    }
+
+    /// How many words fit the run of a slot through each of its cells as
+    /// it stands, in grid order: the same for every cell of a run, and
+    /// `usize::MAX` for a block or a run too short to be a word.
+    fn run_matches(&self, slot: Slot) -> Vec<usize> {
+        let cells = slot.cells(&self.square);
+        let mut counts = vec![usize::MAX; cells.len()];
+        for run in runs(&cells, |&c| c == BLOCK) {
+            if !self.rules().is_word(&run, cells.len()) {
+                continue;
+            }
+            let letters = slot.orient(self.direction, cells[run.clone()].to_vec());
+            let template: String = letters.into_iter().collect();
+            self.note_query();
+            counts[run].fill(self.dict.count_with_template(&template));
+        }
+        counts
+    }
+
+    /// The order to try letters in at a cell: the dictionary's alphabet in
+    /// order, or shuffled if a seed is set, unless the dictionary has word
+    /// frequencies, in which case letters that allow more common words in
+    /// the cell's row and column come first. See [`LetterOrder`] for the
+    /// other order.
+    pub(crate) fn letter_order(&mut self, row: usize, col: usize) -> Vec<char> {
+        let mut letters = self.dict.alphabet().to_vec();
+        self.shuffle(&mut letters);
+        if self.deterministic
+            || self.letter_order == LetterOrder::Alphabet && !self.dict.has_frequencies()
+        {
+            return letters;
+        }
+        // A run too short to be a word has no say
+        let slots = [(Slot::Row(row), col), (Slot::Col(col), row)]
+            .map(|(slot, position)| self.run_template(slot, position));
+
+        let mut scores: HashMap<char, u64> = HashMap::new();
+        match self.letter_order {
+            LetterOrder::Alphabet => {
+                for (template, position) in slots.iter().flatten() {
+                    for (c, best) in self.dict.best_frequency_by_letter(template, *position) {
+                        *scores.entry(c).or_insert(0) += best;
+                    }
+                }
+            }
+            LetterOrder::Matches => {
+                let [across, down] = slots.map(|slot| {
+                    slot.map(|(template, position)| self.dict.count_by_letter(&template, position))
+                });
+                match (across, down) {
+                    (Some(across), Some(down)) => {
+                        for (c, count) in across {
+                            let crossing = down.get(&c).copied().unwrap_or(0);
+                            scores.insert(c, (count * crossing) as u64);
+                        }
+                    }
+                    (Some(counts), None) | (None, Some(counts)) => {
+                        for (c, count) in counts {
+                            scores.insert(c, count as u64);
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        // Stable, so equally scored letters stay in alphabet (or shuffled)
+        // order
+        letters.sort_by_key(|c| std::cmp::Reverse(scores.get(c).copied().unwrap_or(0)));
+        letters
+    }
+
+    /// Put `items` in a random order, if a seed is set.
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        if let Some(rng) = self.rng.as_mut() {
+            rng.shuffle(items);
+        }
+    }
+
+    /// Put words in the order to try them: shuffled if a seed is set, and
+    /// most common first if the dictionary has word frequencies, unless
+    /// the search is deterministic.
+    pub(crate) fn order_words(&mut self, words: &mut [String]) {
+        self.shuffle(words);
+        if self.dict.has_frequencies() && !self.deterministic {
+            // Stable, so equally common words keep their order
+            words.sort_by_key(|word| std::cmp::Reverse(self.dict.frequency(word)));
+        }
+    }
+
+    /// The seed the search shuffles with: none if it is deterministic.
+    pub(crate) fn search_seed(&self) -> Option<u64> {
+        self.seed.filter(|_| !self.deterministic)
+    }
+
+    /// The cell order the search follows: reading order if it is
+    /// deterministic.
+    pub(crate) fn cell_order(&self) -> CellOrder {
+        if self.deterministic {
+            CellOrder::Reading
+        } else {
+            self.cell_order
+        }
+    }
+
+    /// Redraw the progress line, if it is enabled and due.
+    pub(crate) fn draw_progress(&mut self) {
+        let Some(progress) = self.progress.as_mut() else {
+            return;
+        };
+        if !progress.due() {
+            return;
+        }
+        // Every choice before the current one at each depth has been fully
+        // explored, and each depth splits its parent's share once per choice.
+        let mut explored = 0.0;
+        let mut share = 1.0;
+        for &(i, choices) in self.path.iter() {
+            share /= choices.max(1) as f64;
+            explored += i as f64 * share;
+        }
+        progress.draw(
+            self._attempt,
+            self.path.len(),
+            self.backtracks.iter().sum(),
+            explored,
+        );
+    }
+
+    /// Count the editable cells that currently hold a letter.
+    fn filled_cell_count(&self) -> usize {
+        self.square
+            .iter()
+            .flatten()
+            .zip(self.editable_mask.iter().flatten())
+            .filter(|(c, editable)| **editable && **c != '_')
+            .count()
+    }
+
+    /// Check if a letter is valid at a given position in the square.
+    /// A letter is valid if its crosswords are valid words or valid templates.
+    pub(crate) fn is_valid_letter(&mut self, row: usize, col: usize, c: char) -> bool {
+        // The words fitting the row and column, if kept, answer for both
+        if let Some(fits) = self.tallied_letter(row, col, c) {
+            if !fits {
+                return false;
+            }
+            let mut mirrored = vec![self.run_with(Slot::Row(row), col, c)];
+            if self.symmetric {
+                mirrored.push(self.run_with(Slot::Col(col), row, c));
+            }
+            if self.mirror_rows
+                && !mirrored
+                    .into_iter()
+                    .flatten()
+                    .all(|ww| self.is_valid_mirror_template(&ww))
+            {
+                return false;
+            }
+            return self.fits_diagonals(row, col, c);
+        }
+
+        // Check if the letter is valid in the row's word through it, the
+        // whole row unless it has blocks. A run too short to be a word
+        // takes any letter
+        if let Some(ww) = self.run_with(Slot::Row(row), col, c) {
+            if !self.is_valid_word_or_template(&ww) {
+                return false;
+            }
+            if self.mirror_rows && !self.is_valid_mirror_template(&ww) {
+                return false;
+            }
+        }
+
+        // Check if the letter is valid in the column's word through it
+        if let Some(www) = self.run_with(Slot::Col(col), row, c) {
+            if !self.is_valid_word_or_template(&www) {
+                return false;
+            }
+            // In a symmetric square the column is also a row
+            if self.symmetric && self.mirror_rows && !self.is_valid_mirror_template(&www) {
+                return false;
+            }
+        }
+
+        self.fits_diagonals(row, col, c)
+    }
+
+    /// The cells of each diagonal that must spell a word, top row first.
+    /// None unless the grid is square.
+    fn diagonal_cells(&self) -> Vec<Vec<(usize, usize)>> {
+        let size = self.square.len();
+        if self.diagonals == Diagonals::Off || self.square.iter().any(|row| row.len() != size) {
+            return vec![];
+        }
+        let main = (0..size).map(|i| (i, i)).collect();
+        if self.diagonals == Diagonals::Main {
+            return vec![main];
+        }
+        vec![main, (0..size).map(|i| (i, size - 1 - i)).collect()]
+    }
+
+    /// Whether `c` at `(row, col)`, and at `(col, row)` in a symmetric
+    /// square, leaves every diagonal through it the start of a word.
+    fn fits_diagonals(&mut self, row: usize, col: usize, c: char) -> bool {
+        if self.diagonals == Diagonals::Off {
+            return true;
+        }
+        let symmetric = self.symmetric;
+        let placed = |cell: (usize, usize)| cell == (row, col) || symmetric && cell == (col, row);
+        for cells in self.diagonal_cells() {
+            if !cells.iter().any(|&cell| placed(cell)) {
+                continue;
+            }
+            let letters: Vec<char> = cells
+                .iter()
+                .map(|&(r, k)| if placed((r, k)) { c } else { self.square[r][k] })
+                .collect();
+            if !self.is_valid_word_or_template(&letters) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether every diagonal of `grid` that must spell a word still can.
+    pub(crate) fn diagonals_fit(&mut self, grid: &[Vec<char>]) -> bool {
+        self.diagonal_cells().iter().all(|cells| {
+            let letters: Vec<char> = cells.iter().map(|&(r, k)| grid[r][k]).collect();
+            self.is_valid_word_or_template(&letters)
+        })
+    }
+
+    /// The grid with only the fixed (hardened) letters, and blanks
+    /// everywhere the solver may write.
+    pub(crate) fn fixed_grid(&self) -> Vec<Vec<char>> {
+        self.square
+            .iter()
+            .zip(self.editable_mask.iter())
+            .map(|(row, mask)| {
+                row.iter()
+                    .zip(mask.iter())
+                    .map(|(&c, &editable)| if editable { '_' } else { c })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Replace every cell with the letters of `fixed`, hardening its letters
+    /// and leaving its blanks for the solver.
+    pub(crate) fn reset_to(&mut self, fixed: &[Vec<char>]) {
+        for (row, letters) in fixed.iter().enumerate() {
+            for (col, &c) in letters.iter().enumerate() {
+                self.square[row][col] = c;
+                self.editable_mask[row][col] = c == '_';
+            }
+        }
+    }
+
+    /// The letters of the square, one `Vec` per row.
+    pub fn grid(&self) -> &[Vec<char>] {
+        &self.square
+    }
+
+    /// Choose what `fill` shows while it searches (the grid by default).
+    pub fn set_live_view(&mut self, live: LiveView) {
+        self.live = live;
+    }
+
+    /// Choose how words are read out of rows and columns (left to right and
+    /// top to bottom by default).
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    /// Choose whether `fill` places single letters (the default), whole
+    /// rows, whole words one at a time, or whole words as an exact cover or
+    /// SAT formula.
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.strategy = strategy;
+    }
+
+    /// Choose how many partial grids [`Strategy::Beam`] keeps at each cell
+    /// (100 by default). A wider beam is slower but less likely to lose
+    /// every grid that leads to a square.
+    pub fn set_beam_width(&mut self, width: usize) {
+        self.beam_width = width.max(1);
+    }
+
+    /// Choose how many grids [`Strategy::Genetic`] evolves at once (200 by
+    /// default). A bigger population keeps more variety, but each
+    /// generation takes longer.
+    pub fn set_population(&mut self, size: usize) {
+        self.population = size.max(2);
+    }
+
+    /// Choose the chance [`Strategy::Genetic`] changes each letter of a
+    /// child grid, from 0 to 1 (0.02 by default).
+    pub fn set_mutation_rate(&mut self, rate: f64) {
+        self.mutation_rate = rate.clamp(0.0, 1.0);
+    }
+
+    /// Choose how many generations [`Strategy::Genetic`] breeds before
+    /// giving up (1000 by default).
+    pub fn set_generations(&mut self, generations: usize) {
+        self.generations = generations;
+    }
+
+    /// Make `fill` split the search over the machine's cores: each way to
+    /// fill the first cell (or row) is searched on its own, side by side
+    /// with the others. Without a seed, the square found is the one a
+    /// search on one thread finds; with one, each way shuffles with the
+    /// seed plus its number, so the square is the same on every run, but
+    /// not the one found without threads. With restarts, the threads race
+    /// instead (see [`set_restarts`](MagicSquare::set_restarts)). Nothing
+    /// is shown while searching, and an attempt limit turns threads off.
+    /// Off by default.
+    pub fn set_parallel(&mut self, on: bool) {
+        self.parallel = on;
+    }
+
+    /// Split a parallel search (see
+    /// [`set_parallel`](MagicSquare::set_parallel)) over `threads` threads,
+    /// or over one per core for `None` (the default). The square found is
+    /// the same whatever the number.
+    pub fn set_threads(&mut self, threads: Option<usize>) {
+        self.threads = threads;
+    }
+
+    /// Choose which empty cell `fill` fills next (the first in reading
+    /// order by default).
+    pub fn set_cell_order(&mut self, order: CellOrder) {
+        self.cell_order = order;
+    }
+
+    /// Choose the order `fill` tries letters in at a cell (the alphabet by
+    /// default).
+    pub fn set_letter_order(&mut self, order: LetterOrder) {
+        self.letter_order = order;
+    }
+
+    /// Make `fill` keep the letters each cell could still take, and narrow
+    /// them after every placement, so a letter that leaves any cell with
+    /// nothing is rejected at once, and a cell left with one letter is
+    /// filled with it without trying any other. Each placement costs more,
+    /// but far fewer are made. On by default; it applies only to
+    /// `Strategy::Letters`, and not to a toroidal square.
+    pub fn set_propagate(&mut self, on: bool) {
+        self.propagate = on;
+    }
+
+    /// Make `fill` check, after each placement, that every blank cell in
+    /// the same row and column still has some letter that fits both of its
+    /// slots, and try another letter at once if not. This is a cheaper,
+    /// shallower check than `set_propagate`, which makes it redundant. Off
+    /// by default.
+    pub fn set_forward_check(&mut self, on: bool) {
+        self.forward_check = on;
+    }
+
+    /// Make `fill` try letters in a shuffled order, so different seeds give
+    /// different squares and the same seed always gives the same one, or
+    /// in alphabet order (the default) for `None`.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// The seed `fill` shuffles the letter order with, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Make `fill` search in one fixed order whatever else is set: cells
+    /// in reading order, letters in alphabet order and whole words
+    /// alphabetically, ignoring the seed, the cell and letter orders and
+    /// word frequencies, so the square it finds can be compared against a
+    /// saved one. Restarts search again in the same order with a larger
+    /// allowance. Off by default.
+    pub fn set_deterministic(&mut self, on: bool) {
+        self.deterministic = on;
+    }
+
+    /// Make `fill` give up with an error after trying `limit` letters, or
+    /// never (the default) for `None`.
+    pub fn set_attempt_limit(&mut self, limit: Option<usize>) {
+        self.attempt_limit = limit;
+    }
+
+    /// Make `fill` give up with an error after searching for `limit`, or
+    /// never (the default) for `None`. The clock is read every few hundred
+    /// attempts, so a search may run a little past it.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.time_limit = limit;
+    }
+
+    /// Make `fill` save its place to `path` every so often (see
+    /// `set_checkpoint_interval`) and when it gives up, so that
+    /// [`resume_from`](MagicSquare::resume_from) can carry on from there
+    /// after the process is stopped, or on another machine; or never (the
+    /// default) for `None`. It applies to `Strategy::Letters` without
+    /// restarts, and turns threads off.
+    pub fn set_checkpoint(&mut self, path: Option<PathBuf>) {
+        self.checkpoint = path;
+    }
+
+    /// Choose how often `fill` saves its place, when it does (every minute
+    /// by default).
+    pub fn set_checkpoint_interval(&mut self, every: Duration) {
+        self.checkpoint_every = every;
+    }
+
+    /// Make `fill` abandon a search that has tried `unit` letters (or
+    /// words) and start again in another random order, allowing the
+    /// searches `unit` times 1, 1, 2, 1, 1, 2, 4, ... attempts (the Luby
+    /// sequence), or search once (the default) for `None`. A search that
+    /// ends within its attempts proves there is no square. With
+    /// [`set_parallel`](MagicSquare::set_parallel), each thread restarts
+    /// in orders of its own, and the first to find a square, or show there
+    /// is none, stops the rest.
+    pub fn set_restarts(&mut self, unit: Option<usize>) {
+        self.restart_unit = unit;
+    }
+
+    /// Whether the last `fill` failed because it hit the attempt or time
+    /// limit, rather than because the square has no solution.
+    pub fn gave_up(&self) -> bool {
+        self.gave_up
+    }
+
+    /// The most complete grid `fill` has reached, with `_` in the cells it
+    /// had not filled. After a `fill` that gave up, this is the best it
+    /// managed.
+    pub fn best_fill(&self) -> &[Vec<char>] {
+        &self.deepest
+    }
+
+    /// The rows and columns [`best_fill`](MagicSquare::best_fill) leaves
+    /// with blank cells.
+    pub fn unfilled_slots(&self) -> Vec<Slot> {
+        Slot::all(&self.deepest)
+            .into_iter()
+            .filter(|slot| slot.cells(&self.deepest).contains(&'_'))
+            .collect()
+    }
+
+    /// Count how often `fill` checks each template, for `hot_templates`.
+    /// Off by default, since counting slows the search down.
+    pub fn set_template_stats(&mut self, on: bool) {
+        self.template_stats = on.then(HashMap::new);
+    }
+
+    /// The templates `fill` checked most often while template counting was
+    /// on, to show which crossing patterns dominate a search.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - How many templates to return.
+    ///
+    /// # Returns
+    ///
+    /// * Up to `n` of `(template, checks, candidates)`, most checked first,
+    ///   where `candidates` is the number of words matching the template.
+    pub fn hot_templates(&self, n: usize) -> Vec<(String, usize, usize)> {
+        let Some(stats) = &self.template_stats else {
+            return vec![];
+        };
+        let mut hottest: Vec<(&String, &usize)> = stats.iter().collect();
+        hottest.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        hottest
+            .into_iter()
+            .take(n)
+            .map(|(template, &checks)| {
+                (template.clone(), checks, self.dict.count_with_template(template))
+            })
+            .collect()
+    }
+
+    /// Make `fill` remember each partial square it shows leads nowhere,
+    /// keyed on the endings every column can still take at the start of a
+    /// row, and backtrack at once when another path reaches the same
+    /// state. Only searches that fill cells in reading order, or a row at
+    /// a time, use it. Off by default, since each row costs a look through
+    /// the words fitting every column.
+    pub fn set_nogoods(&mut self, on: bool) {
+        self.nogoods = on.then(Nogoods::default);
+    }
+
+    /// How often the last `fill` looked up a state in the nogood cache,
+    /// how often that saved a search, and how many states it recorded, or
+    /// `None` if the cache is off.
+    pub fn nogood_stats(&self) -> Option<NogoodStats> {
+        self.nogoods.as_ref().map(|nogoods| nogoods.stats)
+    }
+
+    /// Require every row to be a word both forwards and backwards
+    /// (semordnilaps such as "stop"/"pots", or palindromes).
+    pub fn set_mirror_rows(&mut self, mirror_rows: bool) {
+        self.mirror_rows = mirror_rows;
+    }
+
+    /// Require the square to be symmetric about its main diagonal, so that
+    /// each row spells the same word as the column with its number: the
+    /// classic word square. The solver writes every letter in both cells,
+    /// so it has half as many to choose. Only square grids read in the
+    /// standard direction can be symmetric, and only the letter-by-letter
+    /// and whole-row strategies keep them so.
+    pub fn set_symmetric(&mut self, symmetric: bool) {
+        self.symmetric = symmetric;
+    }
+
+    /// Why a symmetric square cannot be filled as set up, if it is
+    /// symmetric and it cannot: its shape, direction or strategy, or fixed
+    /// letters that disagree across the diagonal. Otherwise copy each
+    /// fixed letter to the cell mirroring it.
+    pub(crate) fn symmetry_error(&mut self) -> Option<String> {
+        if !self.symmetric {
+            return None;
+        }
+        let size = self.square.len();
+        if self.square.iter().any(|row| row.len() != size) {
+            return Some("A symmetric square needs as many rows as columns".to_string());
+        }
+        if self.direction != Direction::Standard {
+            return Some("A symmetric square is read in the standard direction".to_string());
+        }
+        if !matches!(self.strategy, Strategy::Letters | Strategy::Rows) {
+            return Some("A symmetric square is filled a letter or a row at a time".to_string());
+        }
+        for row in 0..size {
+            for col in row + 1..size {
+                match (self.square[row][col], self.square[col][row]) {
+                    ('_', '_') => {}
+                    (c, '_') => self.set_and_harden(col, row, c),
+                    ('_', c) => self.set_and_harden(row, col, c),
+                    (a, b) if a != b => {
+                        return Some(format!(
+                            "({}, {}) and ({}, {}) must be the same letter",
+                            row, col, col, row
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// Require the main diagonal of a square grid, or both its diagonals,
+    /// to spell words too, each read from the top row down. The search
+    /// checks a diagonal whenever it places a letter on it, but does not
+    /// propagate along it. Only the letter-by-letter and whole-row
+    /// strategies check diagonals.
+    pub fn set_diagonals(&mut self, diagonals: Diagonals) {
+        self.diagonals = diagonals;
+    }
+
+    /// Why a square whose diagonals must be words cannot be filled as set
+    /// up, if it cannot: it is not square, its strategy does not check,
+    /// or its fixed letters leave a diagonal no word.
+    fn diagonal_error(&mut self) -> Option<String> {
+        if self.diagonals == Diagonals::Off {
+            return None;
+        }
+        let size = self.square.len();
+        if self.square.iter().any(|row| row.len() != size) {
+            return Some("Only a grid with as many rows as columns has diagonals".to_string());
+        }
+        if !matches!(self.strategy, Strategy::Letters | Strategy::Rows) {
+            return Some("Diagonals are checked a letter or a row at a time".to_string());
+        }
+        let names = ["main diagonal", "anti-diagonal"];
+        for (cells, name) in self.diagonal_cells().iter().zip(names) {
+            let letters: Vec<char> = cells.iter().map(|&(r, k)| self.square[r][k]).collect();
+            if !self.is_valid_word_or_template(&letters) {
+                return Some(format!("No word fits the {}", name));
+            }
+        }
+        None
+    }
+
+    /// Set the fewest letters a word between blocked cells may have. A run
+    /// of letters in a row or column with blocks, between two blocks or a
+    /// block and the edge, that is shorter than this is not a word, so any
+    /// letters may fill it. A row or column with no blocks is always a
+    /// word. The default is 2, so only single letters go unchecked.
+    pub fn set_min_word_len(&mut self, len: usize) {
+        self.min_word_len = len;
+    }
+
+    /// Why a square with blocked cells cannot be filled as set up, if it
+    /// cannot: only the letter-by-letter, whole-row, word-by-word and beam
+    /// strategies split words at blocks.
+    fn block_error(&self) -> Option<String> {
+        let splits = matches!(
+            self.strategy,
+            Strategy::Letters | Strategy::Rows | Strategy::Words | Strategy::Beam
+        );
+        if self.has_blocks() && !splits {
+            return Some("Blocked cells need the letters, rows, words or beam strategy".to_string());
+        }
+        None
+    }
+
+    /// Read rows and columns around a torus, as in a "wrapword" puzzle: a
+    /// line is filled if it spells a word starting from any of its cells
+    /// and wrapping round from its end to its start, so "pleap" fills a
+    /// row because "apple" is a word. Only the letter-by-letter strategy,
+    /// without forward checking, reads lines this way, and it does not
+    /// propagate. It cannot be combined with blocked cells or mirrored
+    /// rows.
+    pub fn set_toroidal(&mut self, toroidal: bool) {
+        self.toroidal = toroidal;
+    }
+
+    /// Why a toroidal square cannot be filled as set up, if it cannot:
+    /// see `set_toroidal`.
+    fn toroidal_error(&self) -> Option<String> {
+        if !self.toroidal {
+            return None;
+        }
+        if self.strategy != Strategy::Letters || self.forward_check {
+            return Some(
+                "A toroidal square is filled a letter at a time, without forward checking"
+                    .to_string(),
+            );
+        }
+        if self.has_blocks() {
+            return Some("A toroidal square cannot have blocked cells".to_string());
+        }
+        if self.mirror_rows {
+            return Some("A toroidal square cannot have mirrored rows".to_string());
+        }
+        None
+    }
+
+    /// Require every row and column to spell a different word, as in a
+    /// double word square, instead of letting a word fill more than one of
+    /// them. A symmetric square spells each word twice, so cannot have
+    /// distinct words, and only the letter-by-letter and whole-row
+    /// strategies check them.
+    pub fn set_distinct(&mut self, distinct: bool) {
+        self.distinct = distinct;
+    }
+
+    /// Let [`optimize`](MagicSquare::optimize) break each of `rules` at the
+    /// cost of its weight, which must be positive, off the score of the
+    /// square each time it does. `fill` and the other searches ignore
+    /// them.
+    pub fn set_soft_rules(&mut self, rules: Vec<(SoftRule, f64)>) {
+        self.soft_rules = rules;
+    }
+
+    /// Choose whether a word may fill both a row and a column: freely,
+    /// never, or always in the row and column with the same number, as
+    /// with [`set_symmetric`](MagicSquare::set_symmetric). Forbidding it is
+    /// a looser [`set_distinct`](MagicSquare::set_distinct), checked the
+    /// same way and by the same strategies.
+    pub fn set_overlap(&mut self, overlap: Overlap) {
+        self.symmetric = overlap == Overlap::Symmetric;
+        self.unshared = overlap == Overlap::Forbidden;
+    }
+
+    /// Whether a word may fill both a row and a column. See
+    /// [`set_overlap`](MagicSquare::set_overlap).
+    pub fn overlap(&self) -> Overlap {
+        if self.symmetric {
+            Overlap::Symmetric
+        } else if self.unshared {
+            Overlap::Forbidden
+        } else {
+            Overlap::Allowed
+        }
+    }
+
+    /// Whether some rows or columns must spell different words.
+    pub(crate) fn words_differ(&self) -> bool {
+        self.distinct || self.unshared
+    }
+
+    /// Whether two rows or columns must spell different words.
+    fn must_differ(&self, first: Slot, second: Slot) -> bool {
+        let crossing = matches!(
+            (first, second),
+            (Slot::Row(_), Slot::Col(_)) | (Slot::Col(_), Slot::Row(_))
+        );
+        first != second && (self.distinct || self.unshared && crossing)
+    }
+
+    /// Why a square whose words must differ cannot be filled as set up, if
+    /// it cannot: it is symmetric, its strategy does not check, or its
+    /// fixed letters already spell a word twice.
+    fn distinct_error(&self) -> Option<String> {
+        if !self.words_differ() {
+            return None;
+        }
+        if self.symmetric {
+            return Some("A symmetric square spells every word twice".to_string());
+        }
+        if !matches!(self.strategy, Strategy::Letters | Strategy::Rows) {
+            return Some("Distinct words are checked a letter or a row at a time".to_string());
+        }
+        self.repeated_word(&self.square).map(|(first, second)| {
+            format!("{} and {} spell the same word", first, second)
+        })
+    }
+
+    /// The first two rows or columns of `grid` with no blanks that spell
+    /// the same word but must differ, if any.
+    pub(crate) fn repeated_word(&self, grid: &[Vec<char>]) -> Option<(Slot, Slot)> {
+        let mut seen: Vec<(Slot, Vec<char>)> = vec![];
+        for slot in Slot::all(grid) {
+            let word = slot.read(grid, self.direction);
+            if word.contains(&'_') {
+                continue;
+            }
+            let first = seen
+                .iter()
+                .find(|(first, other)| *other == word && self.must_differ(*first, slot));
+            if let Some(&(first, _)) = first {
+                return Some((first, slot));
+            }
+            seen.push((slot, word));
+        }
+        None
+    }
+
+    /// Whether `c` at `(row, col)` finishes its row or column with a word
+    /// another finished row or column it must differ from already spells,
+    /// or finishes both with the same word.
+    fn repeats_word(&self, row: usize, col: usize, c: char) -> bool {
+        let finished = |slot: Slot| {
+            let mut letters = slot.cells(&self.square);
+            match slot {
+                Slot::Row(r) if r == row => letters[col] = c,
+                Slot::Col(k) if k == col => letters[row] = c,
+                _ => {}
+            }
+            (!letters.contains(&'_')).then(|| slot.orient(self.direction, letters))
+        };
+        let placed: Vec<(Slot, Vec<char>)> = [Slot::Row(row), Slot::Col(col)]
+            .into_iter()
+            .filter_map(|slot| finished(slot).map(|word| (slot, word)))
+            .collect();
+        if placed.is_empty() {
+            return false;
+        }
+        Slot::all(&self.square).into_iter().any(|slot| {
+            finished(slot).is_some_and(|word| {
+                placed
+                    .iter()
+                    .any(|(other, placed)| *placed == word && self.must_differ(*other, slot))
+            })
+        })
+    }
+
+    /// The words a row or column must spell, in reading order: the whole
+    /// slot, or each run of it between blocks long enough to be a word.
+    pub(crate) fn words_in(&self, slot: Slot) -> Vec<String> {
+        let letters = slot.read(&self.square, self.direction);
+        runs(&letters, |&c| c == BLOCK)
+            .into_iter()
+            .filter(|run| self.rules().is_word(run, letters.len()))
+            .map(|run| letters[run].iter().collect())
+            .collect()
+    }
+
+    /// The word spelled by a row or column, in reading order.
+    pub fn word(&self, slot: Slot) -> String {
+        slot.read(&self.square, self.direction).into_iter().collect()
+    }
+
+    /// Get the row at a given index, blocks and all: the short rows of a
+    /// ragged grid end in the blocks that pad them.
+    pub fn get_row(&self, row: usize) -> Vec<char> {
+        self.square[row].clone()
+    }
+
+    /// Get the column at a given index, blocks and all.
+    pub fn get_col(&self, col: usize) -> Vec<char> {
+        self.square.iter().map(|r| r[col]).collect()
+    }
+
+    /// Count a check of a template (but not of a complete word) for
+    /// `hot_templates`, if counting is on.
+    fn count_template(&mut self, template: &str) {
+        if let Some(stats) = self.template_stats.as_mut() {
+            if template.contains('_') {
+                *stats.entry(template.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Check if a word or template is valid.
+    /// A word is valid if it is a valid dictionary word or has nonzero
+    /// template matches.
+    pub(crate) fn is_valid_word_or_template(&mut self, word: &[char]) -> bool {
+        // Each run between blocks long enough to be a word must be one
+        if word.contains(&BLOCK) {
+            let rules = self.rules();
+            let words: Vec<_> = runs(word, |&c| c == BLOCK)
+                .into_iter()
+                .filter(|run| rules.is_word(run, word.len()))
+                .collect();
+            return words
+                .into_iter()
+                .all(|run| self.is_valid_word_or_template(&word[run]));
+        }
+        let word_as_str = word.iter().collect::<String>();
+        self.count_template(&word_as_str);
+        self.note_query();
+        // A line read around a torus may start a word at any of its cells
+        if self.toroidal {
+            let valid = self.dict.has_rotated_match(&word_as_str);
+            trace!("{} has a rotated match: {}", word_as_str, valid);
+            return valid;
+        }
+        // Check if the word is a valid dictionary word
+        if self.dict.contains(word_as_str.as_str()) {
+            return true;
+        }
+
+        // A template that is letters followed only by blanks (the usual shape
+        // when filling in reading order) just needs a word with that prefix
+        let prefix = word_as_str.trim_end_matches('_');
+        if !prefix.contains('_') {
+            return self.dict.has_prefix(prefix, word.len());
+        }
+
+        let valid = self.dict.has_match(word_as_str.as_str());
+        trace!("{} has a match: {}", word_as_str, valid);
+        valid
+    }
+
+    /// Check if a word or template, read backwards, is valid: that is, if
+    /// some dictionary word reversed matches it.
+    fn is_valid_mirror_template(&mut self, word: &[char]) -> bool {
+        if word.contains(&BLOCK) {
+            let rules = self.rules();
+            let words: Vec<_> = runs(word, |&c| c == BLOCK)
+                .into_iter()
+                .filter(|run| rules.is_word(run, word.len()))
+                .collect();
+            return words
+                .into_iter()
+                .all(|run| self.is_valid_mirror_template(&word[run]));
+        }
+        // A reversed word matches the template exactly when the word matches
+        // the reversed template
+        let reversed = word.iter().rev().collect::<String>();
+        self.count_template(&reversed);
+        self.note_query();
+        let valid = self.dict.has_match(reversed.as_str());
+        trace!("{} has a mirror match: {}", reversed, valid);
+        valid
+    }
+
+    /// Print the square to stdout.
+    pub fn print(&self) {
+        for row in self.square.iter() {
+            for &c in row.iter() {
+                print!("{} ", c);
+            }
+            println!();
+        }
+    }
+
+    /// The text `print` writes, after a code that clears the screen.
+    fn grid_frame(&self) -> String {
+        let mut frame = String::from("\x1b[2J");
+        for row in self.square.iter() {
+            for &c in row.iter() {
+                frame.push(c);
+                frame.push(' ');
+            }
+            frame.push('\n');
+        }
+        frame
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        assert_fills, assert_magic, Instance, ALL_FIXED, FIXED_MIDDLE, FIXED_NON_WORD,
+    };
+
+    /// Every strategy `fill` can use.
+    const STRATEGIES: &[Strategy] = &[
+        Strategy::Letters,
+        Strategy::Rows,
+        Strategy::ExactCover,
+        Strategy::Words,
+        Strategy::Beam,
+        Strategy::Anneal,
+        Strategy::Genetic,
+        #[cfg(feature = "sat")]
+        Strategy::Sat,
+    ];
+
+    /// Fill `instance` with `strategy`, checking that its fixed letters
+    /// stay where they were whether or not it is filled.
+    fn fill(instance: &Instance, strategy: Strategy) -> (MagicSquare, Result<(), String>) {
+        let dict = instance.dictionary();
+        let mut square = instance.square(&dict);
+        square.set_strategy(strategy);
+        square.set_seed(Some(1));
+        let fixed = square.fixed_grid();
+        let result = square.fill();
+        for (row, letters) in fixed.iter().enumerate() {
+            for (col, &c) in letters.iter().enumerate() {
+                if c != '_' {
+                    assert_eq!(
+                        square.grid()[row][col],
+                        c,
+                        "{} with {:?} moved the fixed letter at ({}, {})",
+                        instance.name,
+                        strategy,
+                        row,
+                        col
+                    );
+                }
+            }
+        }
+        (square, result)
+    }
+
+    #[test]
+    fn fixed_non_word_is_unsolvable() {
+        for &strategy in STRATEGIES {
+            let (_, result) = fill(&FIXED_NON_WORD, strategy);
+            assert_eq!(result, Err("No word fits row 0".to_string()), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn all_fixed_is_already_filled() {
+        for &strategy in STRATEGIES {
+            let (square, result) = fill(&ALL_FIXED, strategy);
+            assert_eq!(result, Ok(()), "{:?}", strategy);
+            assert_eq!(square.grid(), &ALL_FIXED.solution_grid().unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn fixed_middle_keeps_its_letter() {
+        for &strategy in STRATEGIES {
+            let (square, result) = fill(&FIXED_MIDDLE, strategy);
+            assert_eq!(result, Ok(()), "{:?}", strategy);
+            assert_magic(&square);
+        }
+    }
+
+    #[test]
+    fn fills_around_blocks() {
+        let strategies = [Strategy::Letters, Strategy::Rows, Strategy::Words, Strategy::Beam];
+        for strategy in strategies {
+            assert_fills("___#/____/____/#___", 4, strategy, |_| {});
+            assert_fills("#____/_____/___#_", 3, strategy, |square| square.set_min_word_len(3));
+        }
+    }
+
+    #[test]
+    fn toroidal_lines_are_rotated_words() {
+        assert_fills("___", 3, Strategy::Letters, |square| square.set_toroidal(true));
+        assert_fills("____", 4, Strategy::Letters, |square| square.set_toroidal(true));
+        assert_fills("___", 3, Strategy::Letters, |square| {
+            square.set_toroidal(true);
+            square.set_diagonals(Diagonals::Both);
+        });
+    }
+
+    #[test]
+    fn toroidal_needs_the_letter_search() {
+        let dict = crate::test_util::words();
+        let mut square = MagicSquare::from_template("___", 3, &dict);
+        square.set_toroidal(true);
+        square.set_strategy(Strategy::Rows);
+        assert_eq!(
+            square.fill(),
+            Err("A toroidal square is filled a letter at a time, without forward checking".into())
+        );
+    }
+}
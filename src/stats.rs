@@ -0,0 +1,120 @@
+//! Summaries of a wordlist, for judging whether it can fill squares of a
+//! given size before starting a long search.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::dictionary::Dictionary;
+
+/// The lowest `expected_squares_log10` at which a square size is still
+/// worth searching. The estimate ignores how letters in real words go
+/// together, and squares it puts at 1 in 10 or so are often found.
+pub const PLAUSIBLE_LOG10: f64 = -2.0;
+
+/// Counts describing a dictionary.
+pub struct Stats {
+    /// How many words there are of each length, shortest first.
+    pub lengths: Vec<(usize, usize)>,
+    /// For each position (the first letter is position 0), how many words
+    /// have each letter there, most common first. Ties are in alphabetical
+    /// order.
+    pub letters_by_position: Vec<Vec<(char, usize)>>,
+    /// For each length with words, the base-10 logarithm of the expected
+    /// number of ways to fill a blank square of that size; see
+    /// `expected_squares_log10`.
+    pub expected_squares: Vec<(usize, f64)>,
+}
+
+impl Stats {
+    /// The largest square size a blank grid plausibly has a filling for
+    /// (see `PLAUSIBLE_LOG10`), if any.
+    pub fn largest_square(&self) -> Option<usize> {
+        self.expected_squares
+            .iter()
+            .filter(|(_, log10)| *log10 >= PLAUSIBLE_LOG10)
+            .map(|(size, _)| *size)
+            .max()
+    }
+}
+
+/// Count the words of each length and the letters at each position, and
+/// estimate how many squares of each size the words can fill.
+pub fn stats(dict: &Dictionary) -> Stats {
+    let mut lengths: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut by_position: Vec<HashMap<char, usize>> = vec![];
+    for word in dict.iter() {
+        *lengths.entry(word.chars().count()).or_default() += 1;
+        for (pos, c) in word.chars().enumerate() {
+            if by_position.len() <= pos {
+                by_position.push(HashMap::new());
+            }
+            *by_position[pos].entry(c).or_default() += 1;
+        }
+    }
+    let letters_by_position = by_position
+        .into_iter()
+        .map(|counts| {
+            let mut counts: Vec<(char, usize)> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            counts
+        })
+        .collect();
+    let expected_squares = lengths
+        .keys()
+        .filter_map(|&size| expected_squares_log10(dict, size).map(|log10| (size, log10)))
+        .collect();
+    Stats {
+        lengths: lengths.into_iter().collect(),
+        letters_by_position,
+        expected_squares,
+    }
+}
+
+/// Estimate how many ways there are to fill a blank `size` by `size`
+/// square, as a base-10 logarithm.
+///
+/// This is the number of ways to pick the rows, times the chance that each
+/// column then spells a word. A column's chance is the sum, over the words
+/// it could spell, of the chance that random rows put that word's letters
+/// there, using how often each letter appears at that column's position in
+/// words of this length. Columns are treated as independent, so this is a
+/// rough guide: a result well above 0 means squares that size should be
+/// easy to find, and one well below 0 means there are probably none.
+///
+/// # Returns
+///
+/// * `Some(f64)` with the logarithm.
+/// * `None` if there are no words of this length.
+pub fn expected_squares_log10(dict: &Dictionary, size: usize) -> Option<f64> {
+    let words = dict.words_of_length(size);
+    if words.is_empty() {
+        return None;
+    }
+    let total = words.len() as f64;
+    let frequencies: Vec<HashMap<char, f64>> = dict
+        .letter_frequencies(size)
+        .into_iter()
+        .map(|counts| {
+            counts
+                .into_iter()
+                .map(|(c, count)| (c, count as f64 / total))
+                .collect()
+        })
+        .collect();
+
+    let mut log10 = size as f64 * total.log10();
+    for column in frequencies.iter() {
+        let chance: f64 = words
+            .iter()
+            .map(|word| {
+                word.chars()
+                    .map(|c| column.get(&c).copied().unwrap_or(0.0))
+                    .product::<f64>()
+            })
+            .sum();
+        if chance == 0.0 {
+            return Some(f64::NEG_INFINITY);
+        }
+        log10 += chance.log10();
+    }
+    Some(log10)
+}
@@ -0,0 +1,200 @@
+//! The `--stdio` protocol, for editor plugins that drive the solver as a
+//! subprocess. Each line on stdin is one JSON request:
+//!
+//! ```text
+//! {"id": 1, "method": "hints", "params": {"grid": ["c___", "____"]}}
+//! ```
+//!
+//! and each gets one line on stdout in reply, echoing the request's `id`:
+//! `{"id":1,"result":...}` on success or `{"id":1,"error":"..."}` if the
+//! request could not be handled. The dictionary is loaded once, up front.
+
+use std::io::{BufRead, Write};
+
+use magic_square::check;
+use magic_square::dictionary::Dictionary;
+use magic_square::normalize::fold_diacritics;
+use magic_square::report;
+use magic_square::{LiveView, MagicSquare, Slot};
+
+use crate::cli::Args;
+use crate::json::Json;
+
+/// Build the square in a request's `grid` parameter: one string per row,
+/// with `_` for blank cells.
+fn request_square(params: &Json, args: &Args, dict: &Dictionary) -> Result<MagicSquare, String> {
+    let rows = params
+        .get("grid")
+        .and_then(Json::as_array)
+        .ok_or("params.grid must be an array of row strings")?;
+    let rows: Vec<&str> = rows
+        .iter()
+        .map(|row| {
+            row.as_str()
+                .ok_or("params.grid must be an array of row strings")
+        })
+        .collect::<Result<_, _>>()?;
+    let cols = rows.first().map_or(0, |row| row.chars().count());
+    if cols == 0 {
+        return Err("params.grid must have at least one non-empty row".to_string());
+    }
+    Ok(grid_square(&rows, args, dict))
+}
+
+/// Build a square from rows of letters, `_` blanks and `#` blocks, with
+/// shorter rows padded with blocks (see `MagicSquare::from_template`), set
+/// up as the command-line flags ask and with no live view.
+pub(crate) fn grid_square(rows: &[&str], args: &Args, dict: &Dictionary) -> MagicSquare {
+    let template = rows.join("/").to_lowercase();
+    let template = if args.fold_accents {
+        fold_diacritics(&template)
+    } else {
+        template
+    };
+    let mut square = MagicSquare::from_template(&template, rows.len(), dict);
+    square.set_direction(args.direction);
+    square.set_mirror_rows(args.mirror_rows);
+    square.set_overlap(args.overlap);
+    square.set_distinct(args.distinct);
+    square.set_diagonals(args.diagonals);
+    square.set_min_word_len(args.min_word_len);
+    square.set_toroidal(args.toroidal);
+    square.set_strategy(args.strategy);
+    square.set_beam_width(args.beam_width);
+    square.set_population(args.population);
+    square.set_mutation_rate(args.mutation_rate);
+    square.set_generations(args.generations);
+    square.set_cell_order(args.cell_order);
+    square.set_letter_order(args.letter_order);
+    square.set_propagate(args.propagate);
+    square.set_forward_check(args.forward_check);
+    square.set_nogoods(args.nogoods);
+    square.set_parallel(args.parallel);
+    square.set_threads(args.threads);
+    square.set_seed(args.seed());
+    square.set_deterministic(args.deterministic);
+    square.set_restarts(args.restarts);
+    square.set_attempt_limit(args.max_attempts);
+    square.set_time_limit(args.time_limit);
+    square.set_live_view(LiveView::Off);
+    square
+}
+
+/// Parse a slot name as printed by the solver: `row N` or `column N`.
+fn parse_slot(name: &str, square: &MagicSquare) -> Result<Slot, String> {
+    let grid = square.grid();
+    let (make, n, count): (fn(usize) -> Slot, _, _) = match name.split_once(' ') {
+        Some(("row", n)) => (Slot::Row, n, grid.len()),
+        Some(("column", n)) => (Slot::Col, n, grid[0].len()),
+        _ => return Err(format!("bad slot {} (expected row N or column N)", name)),
+    };
+    match n.parse::<usize>() {
+        Ok(n) if n < count => Ok(make(n)),
+        _ => Err(format!(
+            "no {} in a {}x{} grid",
+            name,
+            grid.len(),
+            grid[0].len()
+        )),
+    }
+}
+
+/// Handle one request's method.
+///
+/// # Returns
+///
+/// * `Ok(String)` holding the JSON result.
+/// * `Err(String)` if the method or its parameters are bad.
+fn handle(method: &str, params: &Json, args: &Args, dict: &Dictionary) -> Result<String, String> {
+    let mut square = request_square(params, args, dict)?;
+    match method {
+        "validate" => Ok(report::check_json(&check::check(&square))),
+        "complete-slot" => {
+            let name = params
+                .get("slot")
+                .and_then(Json::as_str)
+                .ok_or("params.slot must be a slot name such as \"row 0\"")?;
+            let slot = parse_slot(name, &square)?;
+            let mut words = check::slot_candidates(&square, slot);
+            if let Some(limit) = params.get("limit") {
+                words.truncate(limit.as_usize().ok_or("params.limit must be a count")?);
+            }
+            let words = words.into_iter().map(Json::String).collect();
+            Ok(Json::object([
+                ("slot", Json::String(slot.to_string())),
+                ("words", Json::Array(words)),
+            ])
+            .to_string())
+        }
+        "hints" => {
+            let cells = check::hints(&square)
+                .into_iter()
+                .map(|((row, col), letters)| {
+                    Json::object([
+                        (
+                            "cell",
+                            Json::Array(vec![Json::Number(row as f64), Json::Number(col as f64)]),
+                        ),
+                        ("letters", Json::String(letters.into_iter().collect())),
+                    ])
+                })
+                .collect();
+            Ok(Json::Array(cells).to_string())
+        }
+        "fill-remaining" => Ok(match square.fill() {
+            Ok(()) => report::solved_json(&square),
+            Err(_) => report::failure_json(&square),
+        }),
+        _ => Err(format!(
+            "unknown method {} (expected validate, complete-slot, hints or fill-remaining)",
+            method
+        )),
+    }
+}
+
+/// Answer one request line.
+fn respond(line: &str, args: &Args, dict: &Dictionary) -> String {
+    let request = match Json::parse(line) {
+        Ok(request) => request,
+        Err(e) => return reply(&Json::Null, Err(format!("bad request: {}", e))),
+    };
+    let id = request.get("id").cloned().unwrap_or(Json::Null);
+    let Some(method) = request.get("method").and_then(Json::as_str) else {
+        return reply(&id, Err("request has no method".to_string()));
+    };
+    let params = request
+        .get("params")
+        .cloned()
+        .unwrap_or(Json::Object(vec![]));
+    reply(&id, handle(method, &params, args, dict))
+}
+
+fn reply(id: &Json, result: Result<String, String>) -> String {
+    match result {
+        Ok(result) => format!("{{\"id\":{},\"result\":{}}}", id, result),
+        Err(e) => format!("{{\"id\":{},\"error\":{}}}", id, Json::String(e)),
+    }
+}
+
+/// `--stdio [dictionary]` answers requests from stdin, one per line, until
+/// stdin is closed.
+pub(crate) fn run(args: &Args) {
+    let (dict, _) = crate::load_dictionary(args, &args.positional);
+    let stdout = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = respond(&line, args, &dict);
+        let mut out = stdout.lock();
+        if writeln!(out, "{}", response)
+            .and_then(|_| out.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
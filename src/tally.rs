@@ -0,0 +1,139 @@
+//! Keeping track of the words that still fit each row and column.
+//!
+//! Checking a letter against a slot's template intersects the bitsets of
+//! every letter the template fixes, and counting the words that fit it
+//! intersects them all again. The letter-by-letter search instead keeps,
+//! for every row and column, the bitset of the words that fit its letters
+//! so far: placing a letter narrows the two slots through it by that
+//! letter's bitset, taking it back restores them, and checking a letter
+//! is a single intersection.
+
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// For every row and then every column, the IDs of the words that fit its
+/// letters, as a bitset over the words of its length.
+#[derive(Clone)]
+pub(crate) struct Tallies {
+    fits: Vec<Vec<u64>>,
+    rows: usize,
+}
+
+/// The bitsets a placement narrowed, by slot, as they were before it.
+pub(crate) type Narrowed = Vec<(usize, Vec<u64>)>;
+
+impl Tallies {
+    fn index(&self, slot: Slot) -> usize {
+        match slot {
+            Slot::Row(row) => row,
+            Slot::Col(col) => self.rows + col,
+        }
+    }
+
+    /// How many words fit a slot.
+    pub(crate) fn count(&self, slot: Slot) -> usize {
+        self.fits[self.index(slot)]
+            .iter()
+            .map(|chunk| chunk.count_ones() as usize)
+            .sum()
+    }
+}
+
+impl MagicSquare {
+    /// Start keeping track of the words fitting each slot as the grid
+    /// stands, if the letter-by-letter search can use them: not when
+    /// propagating, whose candidate letters already answer the same
+    /// questions, nor for a grid with blocks or a toroidal one, whose
+    /// lines are not one word read from their start, nor while counting
+    /// templates, which it would skip.
+    pub(crate) fn start_tallies(&mut self) {
+        self.tallies = None;
+        if self.domains.is_some()
+            || self.has_blocks()
+            || self.toroidal
+            || self.template_stats.is_some()
+        {
+            return;
+        }
+        let fits = Slot::all(&self.square)
+            .into_iter()
+            .map(|slot| {
+                let template: String = slot.read(&self.square, self.direction).into_iter().collect();
+                self.dict.match_bits(&template)
+            })
+            .collect();
+        let rows = self.square.len();
+        self.tallies = Some(Tallies { fits, rows });
+    }
+
+    /// The slots through a cell, with the cell's position in each as it
+    /// is read: its row and column, and in a symmetric square those of
+    /// the cell mirroring it too.
+    fn tally_slots(&self, row: usize, col: usize) -> Vec<(Slot, usize)> {
+        let mut cells = vec![(row, col)];
+        if self.symmetric && row != col {
+            cells.push((col, row));
+        }
+        let read = |slot: Slot, position: usize| {
+            let len = slot.cells(&self.square).len();
+            if slot.is_reversed(self.direction) {
+                (slot, len - 1 - position)
+            } else {
+                (slot, position)
+            }
+        };
+        cells
+            .into_iter()
+            .flat_map(|(row, col)| [read(Slot::Row(row), col), read(Slot::Col(col), row)])
+            .collect()
+    }
+
+    /// Whether some word fits the row and the column through a cell with
+    /// `c` in it. `None` if the words fitting each slot are not being kept.
+    pub(crate) fn tallied_letter(&self, row: usize, col: usize, c: char) -> Option<bool> {
+        let tallies = self.tallies.as_ref()?;
+        let fits = |(slot, position): (Slot, usize)| {
+            self.note_query();
+            let fits = &tallies.fits[tallies.index(slot)];
+            self.dict
+                .letter_bits(slot.cells(&self.square).len(), position, c)
+                .is_some_and(|bits| bits.iter().zip(fits).any(|(b, f)| b & f != 0))
+        };
+        // The mirroring cell's slots spell the same words as these two
+        Some(self.tally_slots(row, col).into_iter().take(2).all(fits))
+    }
+
+    /// Narrow the slots through a cell by the `c` just placed there,
+    /// saving what they were in `narrowed`, if the words fitting each slot
+    /// are being kept.
+    pub(crate) fn tally(&mut self, row: usize, col: usize, c: char, narrowed: &mut Narrowed) {
+        if self.tallies.is_none() {
+            return;
+        }
+        for (slot, position) in self.tally_slots(row, col) {
+            let len = slot.cells(&self.square).len();
+            let bits = self.dict.letter_bits(len, position, c);
+            let Some(tallies) = self.tallies.as_mut() else {
+                return;
+            };
+            let index = tallies.index(slot);
+            let fits = &mut tallies.fits[index];
+            narrowed.push((index, fits.clone()));
+            match bits {
+                Some(bits) => fits.iter_mut().zip(bits).for_each(|(f, b)| *f &= b),
+                None => fits.fill(0),
+            }
+        }
+    }
+
+    /// Put back the slots `tally` narrowed, latest first.
+    pub(crate) fn untally(&mut self, narrowed: &mut Narrowed) {
+        let Some(tallies) = self.tallies.as_mut() else {
+            narrowed.clear();
+            return;
+        };
+        while let Some((index, fits)) = narrowed.pop() {
+            tallies.fits[index] = fits;
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Tiny deterministic puzzles for testing code that embeds the solver,
+//! without shipping a wordlist. Only available with the `test-util`
+//! feature.
+//!
+//! Each [`Instance`] carries its own handful of words, and the grid the
+//! solver is known to produce from them (or `None` if it cannot be
+//! filled). The solver tries letters in alphabetical order, so the result
+//! is the same on every run and platform.
+//!
+//! ```
+//! use magic_square::test_util::{assert_magic, INSTANCES};
+//!
+//! for instance in INSTANCES {
+//!     let dict = instance.dictionary();
+//!     let mut square = instance.square(&dict);
+//!     match instance.solution {
+//!         Some(_) => {
+//!             square.fill().unwrap();
+//!             assert_magic(&square);
+//!             assert_eq!(square.grid(), &instance.solution_grid().unwrap()[..]);
+//!         }
+//!         None => assert!(square.fill().is_err()),
+//!     }
+//! }
+//! ```
+
+use crate::dictionary::Dictionary;
+use crate::slot::{runs, Slot, BLOCK};
+use crate::{Diagonals, MagicSquare};
+#[cfg(test)]
+use crate::{LiveView, Strategy};
+
+/// A small puzzle with its own dictionary and known outcome.
+pub struct Instance {
+    /// A short name, for test failure messages.
+    pub name: &'static str,
+    /// The number of rows.
+    pub rows: usize,
+    /// The grid template, in the command-line syntax (`_` for blanks, `/`
+    /// between rows).
+    pub template: &'static str,
+    /// Every word in the instance's dictionary.
+    pub words: &'static [&'static str],
+    /// The rows of the filled grid, or `None` if the puzzle cannot be
+    /// filled.
+    pub solution: Option<&'static [&'static str]>,
+}
+
+/// A 2x2 square whose rows and columns are the same two words.
+pub const TWO_BY_TWO: Instance = Instance {
+    name: "two-by-two",
+    rows: 2,
+    template: "__",
+    words: &["at", "to", "ox"],
+    solution: Some(&["at", "to"]),
+};
+
+/// The 3x3 square from the README, with "fun" fixed down the middle
+/// column and a few decoy words.
+pub const FUN_COLUMN: Instance = Instance {
+    name: "fun-column",
+    rows: 3,
+    template: "_f_/_u_/_n_",
+    words: &[
+        "ace", "aff", "cue", "end", "fed", "fun", "fan", "bun", "ink", "nut",
+    ],
+    solution: Some(&["aff", "cue", "end"]),
+};
+
+/// A 4x4 square from the README, with its first row fixed.
+pub const HELP: Instance = Instance {
+    name: "help",
+    rows: 4,
+    template: "help",
+    words: &[
+        "help", "oval", "menu", "ends", "home", "even", "land", "plus", "hope", "lens",
+    ],
+    solution: Some(&["help", "oval", "menu", "ends"]),
+};
+
+/// A 2x2 puzzle whose only rows make no column words.
+pub const UNSOLVABLE: Instance = Instance {
+    name: "unsolvable",
+    rows: 2,
+    template: "__",
+    words: &["ab", "cd"],
+    solution: None,
+};
+
+/// A 2x2 puzzle whose fixed first row is not a word, although the row
+/// below it and both columns can be filled.
+pub const FIXED_NON_WORD: Instance = Instance {
+    name: "fixed-non-word",
+    rows: 2,
+    template: "qz/__",
+    words: &["qa", "zb", "ab"],
+    solution: None,
+};
+
+/// A 2x2 square with every letter fixed, so there is nothing to fill.
+pub const ALL_FIXED: Instance = Instance {
+    name: "all-fixed",
+    rows: 2,
+    template: "at/to",
+    words: &["at", "to"],
+    solution: Some(&["at", "to"]),
+};
+
+/// A 3x3 square with one letter fixed in the middle, so the search fills
+/// cells on both sides of it and backtracks past it.
+pub const FIXED_MIDDLE: Instance = Instance {
+    name: "fixed-middle",
+    rows: 3,
+    template: "___/_c_/___",
+    words: &["ant", "bat", "ace", "cat", "tan", "ten"],
+    solution: Some(&["bat", "ace", "ten"]),
+};
+
+/// Every instance, smallest first.
+pub const INSTANCES: &[Instance] = &[
+    TWO_BY_TWO,
+    FIXED_NON_WORD,
+    ALL_FIXED,
+    FUN_COLUMN,
+    HELP,
+    UNSOLVABLE,
+    FIXED_MIDDLE,
+];
+
+impl Instance {
+    /// The instance's dictionary.
+    pub fn dictionary(&self) -> Dictionary {
+        Dictionary::from_words(self.words.iter().copied())
+    }
+
+    /// A square set up from the template, ready to `fill`. Live output is
+    /// turned off.
+    pub fn square(&self, dict: &Dictionary) -> MagicSquare {
+        let mut square = MagicSquare::from_template(self.template, self.rows, dict);
+        square.set_live_view(crate::LiveView::Off);
+        square
+    }
+
+    /// The known solution as a grid of letters, to compare with
+    /// `MagicSquare::grid`.
+    pub fn solution_grid(&self) -> Option<Vec<Vec<char>>> {
+        self.solution
+            .map(|rows| rows.iter().map(|row| row.chars().collect()).collect())
+    }
+}
+
+/// Panic unless every cell of `square` is filled and every row and column
+/// is a word of its dictionary, reading rows left to right and columns
+/// top to bottom.
+pub fn assert_magic(square: &MagicSquare) {
+    let grid = square.grid();
+    for (row, letters) in grid.iter().enumerate() {
+        let word: String = letters.iter().collect();
+        assert!(
+            square.dict.contains(&word),
+            "row {} ({}) is not a word",
+            row,
+            word
+        );
+    }
+    for col in 0..grid.first().map_or(0, |r| r.len()) {
+        let word: String = grid.iter().map(|r| r[col]).collect();
+        assert!(
+            square.dict.contains(&word),
+            "column {} ({}) is not a word",
+            col,
+            word
+        );
+    }
+}
+
+/// Panic unless every cell of `square` is filled and the grid keeps every
+/// rule the square was set up with: each row and column read in the
+/// square's direction is a word, or with blocks, each run between them long enough to be a word
+/// is one; mirrored rows are words backwards too; the diagonals asked for
+/// are words; and a symmetric square reads the same across its main
+/// diagonal. In a toroidal square, some rotation of each line must be a
+/// word.
+pub fn assert_square(square: &MagicSquare) {
+    let grid = square.grid();
+    for (row, letters) in grid.iter().enumerate() {
+        let line: String = letters.iter().collect();
+        assert!(!letters.contains(&'_'), "row {} ({}) has a blank", row, line);
+    }
+    let is_word = |word: &str| {
+        if square.toroidal {
+            square.dict.contains_rotated(word)
+        } else {
+            square.dict.contains(word)
+        }
+    };
+    for slot in Slot::all(grid) {
+        let cells = slot.cells(grid);
+        for run in runs(&cells, |&c| c == BLOCK) {
+            if !square.rules().is_word(&run, cells.len()) {
+                continue;
+            }
+            let letters = slot.orient(square.direction, cells[run].to_vec());
+            let word: String = letters.into_iter().collect();
+            assert!(is_word(&word), "{} ({}) is not a word", slot, word);
+            if square.mirror_rows && matches!(slot, Slot::Row(_)) {
+                assert!(
+                    square.dict.contains_reversed(&word),
+                    "{} ({}) is not a word backwards",
+                    slot,
+                    word
+                );
+            }
+        }
+    }
+    let size = grid.len();
+    let mut diagonals = vec![];
+    if square.diagonals != Diagonals::Off {
+        diagonals.push((0..size).map(|i| grid[i][i]).collect::<String>());
+    }
+    if square.diagonals == Diagonals::Both {
+        diagonals.push((0..size).map(|i| grid[i][size - 1 - i]).collect());
+    }
+    for word in diagonals {
+        assert!(is_word(&word), "diagonal {} is not a word", word);
+    }
+    if square.symmetric {
+        for (row, letters) in grid.iter().enumerate() {
+            for (col, &c) in letters.iter().enumerate() {
+                assert_eq!(c, grid[col][row], "({}, {}) differs from ({}, {})", row, col, col, row);
+            }
+        }
+    }
+}
+
+/// The wordlist in `data/words.txt`, for tests that need squares bigger
+/// than an instance's handful of words can fill.
+#[cfg(test)]
+pub(crate) fn words() -> Dictionary {
+    Dictionary::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/data/words.txt")).unwrap()
+}
+
+/// Fill a square of [`words`] from `template` with `strategy`, after
+/// `setup` has set its other rules, and check the result with
+/// [`assert_square`].
+#[cfg(test)]
+pub(crate) fn assert_fills(
+    template: &str,
+    rows: usize,
+    strategy: Strategy,
+    setup: impl FnOnce(&mut MagicSquare),
+) -> MagicSquare {
+    let dict = words();
+    let mut square = MagicSquare::from_template(template, rows, &dict);
+    square.set_live_view(LiveView::Off);
+    square.set_strategy(strategy);
+    setup(&mut square);
+    if let Err(e) = square.fill() {
+        panic!("{} with {:?}: {}", template, strategy, e);
+    }
+    assert_square(&square);
+    square
+}
@@ -0,0 +1,190 @@
+use crate::check::check;
+use crate::slot::Slot;
+use crate::MagicSquare;
+
+/// The slot that 180-degree rotational symmetry maps `slot` to: row `r` of
+/// `rows` pairs with row `rows - 1 - r`, and likewise for columns. The middle
+/// row or column of an odd-sized grid is its own partner.
+fn partner(slot: Slot, rows: usize, cols: usize) -> Slot {
+    match slot {
+        Slot::Row(row) => Slot::Row(rows - 1 - row),
+        Slot::Col(col) => Slot::Col(cols - 1 - col),
+    }
+}
+
+/// Every slot, in the order placements are tried: rows before columns, and
+/// the outermost pairs first (row 0, the last row, row 1, ...), since
+/// those are the conventional homes of theme entries.
+fn slots_by_preference(rows: usize, cols: usize) -> Vec<Slot> {
+    fn outside_in(n: usize) -> Vec<usize> {
+        let mut order = vec![];
+        for i in 0..n.div_ceil(2) {
+            order.push(i);
+            if n - 1 - i != i {
+                order.push(n - 1 - i);
+            }
+        }
+        order
+    }
+    outside_in(rows)
+        .into_iter()
+        .map(Slot::Row)
+        .chain(outside_in(cols).into_iter().map(Slot::Col))
+        .collect()
+}
+
+/// Write `word` (in reading order) into `slot` of `grid`.
+///
+/// # Returns
+///
+/// * `true` if the word fit, `false` if it is the wrong length or clashes
+///   with a letter already in the grid. `grid` is only changed on success.
+fn write_word(square: &MagicSquare, grid: &mut [Vec<char>], slot: Slot, word: &str) -> bool {
+    let letters = slot.orient(square.direction, word.chars().collect());
+    let cells = slot.cells(grid);
+    if letters.len() != cells.len() {
+        return false;
+    }
+    if cells
+        .iter()
+        .zip(letters.iter())
+        .any(|(&cell, &c)| cell != '_' && cell != c)
+    {
+        return false;
+    }
+    for (i, c) in letters.into_iter().enumerate() {
+        match slot {
+            Slot::Row(row) => grid[row][i] = c,
+            Slot::Col(col) => grid[i][col] = c,
+        }
+    }
+    true
+}
+
+/// The state of the search over theme placements.
+struct Placer<'a> {
+    themes: &'a [String],
+    slots: Vec<Slot>,
+    rows: usize,
+    cols: usize,
+    /// The theme word placed in each slot so far.
+    placed: Vec<(Slot, String)>,
+    /// How many placements reached the solver, for the error message.
+    tried: usize,
+}
+
+impl Placer<'_> {
+    /// How many placed slots still lack a theme word in their partner slot.
+    fn unpaired(&self) -> usize {
+        self.placed
+            .iter()
+            .filter(|(slot, _)| {
+                let partner = partner(*slot, self.rows, self.cols);
+                !self.placed.iter().any(|(s, _)| *s == partner)
+            })
+            .count()
+    }
+
+    /// Place the themes from `next` on, then fill the square around them.
+    /// On success the square is left filled; otherwise it is reset to `grid`.
+    fn place(&mut self, square: &mut MagicSquare, grid: &[Vec<char>], next: usize) -> bool {
+        // Every unpaired slot needs one of the remaining themes as a partner
+        if self.unpaired() > self.themes.len() - next {
+            return false;
+        }
+        if next == self.themes.len() {
+            return self.try_fill(square, grid);
+        }
+
+        let word = &self.themes[next];
+        for slot in self.slots.clone() {
+            if self.placed.iter().any(|(s, _)| *s == slot) {
+                continue;
+            }
+            let mut candidate = grid.to_vec();
+            if !write_word(square, &mut candidate, slot, word) {
+                continue;
+            }
+            self.placed.push((slot, word.clone()));
+            if self.place(square, &candidate, next + 1) {
+                return true;
+            }
+            self.placed.pop();
+        }
+        false
+    }
+
+    /// Fix the letters of a complete placement and try to fill the rest.
+    fn try_fill(&mut self, square: &mut MagicSquare, grid: &[Vec<char>]) -> bool {
+        let original = square.fixed_grid();
+        square.reset_to(grid);
+        if !check(square).is_plausible() {
+            square.reset_to(&original);
+            return false;
+        }
+        self.tried += 1;
+        debug!(
+            "trying theme placement {}",
+            self.placed
+                .iter()
+                .map(|(slot, word)| format!("{} = {}", slot, word))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        // A placement that covers the whole grid was just checked in full
+        let blank = grid.iter().flatten().any(|c| *c == '_');
+        if !blank || square.fill().is_ok() {
+            return true;
+        }
+        square.reset_to(&original);
+        false
+    }
+}
+
+impl MagicSquare {
+    /// Place theme words at symmetric slots, then fill the rest of the
+    /// square around them.
+    ///
+    /// Theme entries follow the crossword convention of 180-degree
+    /// rotational symmetry: if a theme word is placed in a row, another must
+    /// go in the mirrored row (counting from the bottom), and likewise for
+    /// columns; only the middle row or column of an odd-sized grid stands
+    /// alone. The outermost slots are tried first. If the square cannot be
+    /// filled around a placement, the next placement is searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `themes` - The theme words, each as long as a row or a column.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<(Slot, String)>)` with the slot chosen for each theme
+    ///   word, in order, once the square is filled.
+    /// * `Err(String)` if a theme word is not in the dictionary, or no
+    ///   symmetric placement could be filled.
+    pub fn fill_with_themes(&mut self, themes: &[String]) -> Result<Vec<(Slot, String)>, String> {
+        let themes: Vec<String> = themes.iter().map(|w| w.to_lowercase()).collect();
+        if let Some(word) = themes.iter().find(|w| !self.dict.contains(w)) {
+            return Err(format!("theme word {} is not in the dictionary", word));
+        }
+
+        let rows = self.square.len();
+        let cols = self.square.first().map_or(0, |r| r.len());
+        let mut placer = Placer {
+            themes: &themes,
+            slots: slots_by_preference(rows, cols),
+            rows,
+            cols,
+            placed: vec![],
+            tried: 0,
+        };
+        let grid = self.fixed_grid();
+        if placer.place(self, &grid, 0) {
+            return Ok(placer.placed);
+        }
+        Err(format!(
+            "could not fill the square around any symmetric placement of the theme words ({} tried)",
+            placer.tried
+        ))
+    }
+}
@@ -0,0 +1,97 @@
+use crate::codec::{Reader, Writer};
+
+/// A prefix tree over words of a single length, for "does any word start
+/// with these letters?" queries in time proportional to the prefix.
+#[derive(Clone)]
+pub(crate) struct Trie {
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Clone, Default)]
+struct TrieNode {
+    /// Child node indexes keyed by letter, in insertion order. Nodes have at
+    /// most a few dozen children, so a linear scan beats a map here.
+    children: Vec<(char, usize)>,
+}
+
+impl TrieNode {
+    fn child(&self, c: char) -> Option<usize> {
+        self.children
+            .iter()
+            .find(|(letter, _)| *letter == c)
+            .map(|(_, index)| *index)
+    }
+}
+
+impl Trie {
+    pub(crate) fn new() -> Trie {
+        Trie {
+            nodes: vec![TrieNode::default()],
+        }
+    }
+
+    /// Add a word. Words added in sorted order find their existing prefix
+    /// in the last child of each node, so that is checked first.
+    pub(crate) fn insert(&mut self, word: &str) {
+        let mut node = 0;
+        for c in word.chars() {
+            let last = self.nodes[node].children.last();
+            let child = match last {
+                Some(&(letter, child)) if letter == c => Some(child),
+                _ => self.nodes[node].child(c),
+            };
+            node = match child {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(TrieNode::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((c, child));
+                    child
+                }
+            };
+        }
+    }
+
+    /// Whether any word in the trie starts with `prefix`.
+    pub(crate) fn has_prefix(&self, prefix: &str) -> bool {
+        let mut node = 0;
+        for c in prefix.chars() {
+            match self.nodes[node].child(c) {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Append the trie to an index cache.
+    pub(crate) fn encode(&self, out: &mut Writer) {
+        out.usize(self.nodes.len());
+        for node in self.nodes.iter() {
+            out.usize(node.children.len());
+            for &(c, child) in node.children.iter() {
+                out.char(c);
+                out.usize(child);
+            }
+        }
+    }
+
+    /// Read a trie written by `encode`.
+    pub(crate) fn decode(input: &mut Reader) -> Result<Trie, String> {
+        let count = input.count()?;
+        let mut nodes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let children = (0..input.count()?)
+                .map(|_| Ok((input.char()?, input.usize()?)))
+                .collect::<Result<Vec<(char, usize)>, String>>()?;
+            if children.iter().any(|(_, child)| *child >= count) {
+                return Err("index cache has a bad trie".to_string());
+            }
+            nodes.push(TrieNode { children });
+        }
+        if nodes.is_empty() {
+            return Err("index cache has a bad trie".to_string());
+        }
+        Ok(Trie { nodes })
+    }
+}
@@ -0,0 +1,70 @@
+//! Checking that a puzzle has exactly one answer.
+
+use crate::MagicSquare;
+
+/// How many ways a puzzle can be completed, as far as a setter cares. See
+/// [`verify_unique`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Uniqueness {
+    /// No way at all.
+    Unsolvable,
+    /// Exactly one way: the grid.
+    Unique(Vec<Vec<char>>),
+    /// More than one: the first two grids found.
+    Ambiguous(Vec<Vec<char>>, Vec<Vec<char>>),
+}
+
+impl Uniqueness {
+    /// The cells two grids disagree on, as `(row, col)` in reading order:
+    /// the blanks a solver cannot decide between.
+    pub fn differences(first: &[Vec<char>], second: &[Vec<char>]) -> Vec<(usize, usize)> {
+        first
+            .iter()
+            .zip(second)
+            .enumerate()
+            .flat_map(|(row, (a, b))| {
+                a.iter()
+                    .zip(b)
+                    .enumerate()
+                    .filter(|(_, (x, y))| x != y)
+                    .map(move |(col, _)| (row, col))
+            })
+            .collect()
+    }
+}
+
+/// Check that a puzzle meant for people to solve has only one answer.
+///
+/// The search stops at the second square it finds, so this costs at most
+/// a little more than two fills, where [`count`](crate::count::count)
+/// would find every square and [`alternates`](crate::alternates::alternates)
+/// searches once per letter.
+///
+/// # Arguments
+///
+/// * `square` - A square with its fixed letters set. It is left filled
+///   with the second square if there is one, or the only one.
+///
+/// # Returns
+///
+/// * `Ok(Uniqueness)` saying whether there are no, one or several squares.
+/// * `Err(String)` if the search gave up at its attempt or time limit
+///   before it could tell.
+pub fn verify_unique(square: &mut MagicSquare) -> Result<Uniqueness, String> {
+    let mut solutions = square.solutions();
+    let first = solutions.next();
+    let second = first.as_ref().and_then(|_| solutions.next());
+    if let Some(error) = solutions.error() {
+        return Err(error.to_string());
+    }
+    drop(solutions);
+    Ok(match (first, second) {
+        (None, _) => Uniqueness::Unsolvable,
+        (Some(first), None) => {
+            // Every square has been found, which puts the letters back
+            square.square.clone_from(&first);
+            Uniqueness::Unique(first)
+        }
+        (Some(first), Some(second)) => Uniqueness::Ambiguous(first, second),
+    })
+}
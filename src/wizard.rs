@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::process::exit;
+
+use magic_square::check::check;
+use magic_square::dictionary::Dictionary;
+use magic_square::puzzle::Puzzle;
+use magic_square::report;
+use magic_square::{LiveView, MagicSquare};
+
+use crate::cli::{Args, Format};
+
+/// The share of each length's words kept at each difficulty, most common
+/// first. Hard keeps every word.
+const DIFFICULTIES: &[(&str, f64)] = &[("easy", 0.25), ("medium", 0.6), ("hard", 1.0)];
+
+/// Print a question and read the answer, trimmed. An empty answer gives
+/// `default`. Exits if stdin is closed.
+fn ask(question: &str, default: &str) -> String {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    let _ = std::io::stdout().flush();
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => {
+            println!();
+            exit(1);
+        }
+        Ok(_) => {}
+    }
+    match line.trim() {
+        "" => default.to_string(),
+        answer => answer.to_string(),
+    }
+}
+
+/// Ask for a positive number until one is given.
+fn ask_number(question: &str, default: usize) -> usize {
+    loop {
+        match ask(question, &default.to_string()).parse::<usize>() {
+            Ok(n) if n > 0 => return n,
+            _ => println!("Please enter a positive number."),
+        }
+    }
+}
+
+/// Keep only the most common `share` of the words of each length in
+/// `lengths`, plus any words in `keep`.
+fn keep_common(dict: &mut Dictionary, lengths: &[usize], share: f64, keep: &[String]) {
+    let mut kept: HashSet<String> = keep.iter().cloned().collect();
+    for &len in lengths {
+        let mut words: Vec<&String> = dict.words_of_length(len).iter().collect();
+        words.sort_by_key(|w| std::cmp::Reverse(dict.frequency(w)));
+        let count = ((words.len() as f64) * share).ceil() as usize;
+        kept.extend(words.into_iter().take(count).cloned());
+    }
+    dict.retain(|w| kept.contains(w));
+}
+
+/// `wizard [dictionary]` asks for the size, theme words, difficulty and
+/// output format of a square one question at a time, checking that the
+/// answers so far can still be filled, and then generates it.
+pub(crate) fn run(args: &Args) {
+    let (mut dict, _) = crate::load_dictionary(args, &args.positional[1..]);
+    println!("Answer each question, or press enter for the default in brackets.");
+
+    // Size: both lengths need words, and the blank grid must pass the check
+    let (rows, cols) = loop {
+        let rows = ask_number("How many rows?", 4);
+        let cols = ask_number("How many columns?", rows);
+        let square = MagicSquare::empty(rows, cols, &dict);
+        if check(&square).is_plausible() {
+            break (rows, cols);
+        }
+        println!(
+            "The dictionary has no words of length {}; try another size.",
+            if dict.words_of_length(cols).is_empty() {
+                cols
+            } else {
+                rows
+            }
+        );
+    };
+
+    // Theme words: each must be a word that fits a row or a column
+    let themes: Vec<String> = loop {
+        let answer = ask("Theme words, separated by spaces (none)", "");
+        let themes: Vec<String> = answer.split_whitespace().map(str::to_lowercase).collect();
+        let problem = themes.iter().find_map(|word| {
+            let len = word.chars().count();
+            if len != rows && len != cols {
+                Some(format!("{} is not {} or {} letters long", word, rows, cols))
+            } else if !dict.contains(word) {
+                Some(format!("{} is not in the dictionary", word))
+            } else {
+                None
+            }
+        });
+        match problem {
+            Some(problem) => println!("{}; try again.", problem),
+            None => break themes,
+        }
+    };
+
+    // Difficulty: how familiar the words are, which needs a frequency list
+    let mut freq = args.freq.clone();
+    if freq.is_none() {
+        println!("Difficulty picks more or less common words, using a word-frequency list.");
+        let path = ask("Frequency list (skip to use every word)", "");
+        freq = (!path.is_empty()).then_some(path);
+    }
+    if let Some(path) = freq.filter(|_| !dict.has_frequencies()) {
+        match dict.clone().with_frequencies(&path) {
+            Ok(with_frequencies) => dict = with_frequencies,
+            Err(e) => println!("{}; using every word.", e),
+        }
+    }
+    if dict.has_frequencies() {
+        let names: Vec<&str> = DIFFICULTIES.iter().map(|(name, _)| *name).collect();
+        let share = loop {
+            let answer = ask(&format!("Difficulty ({})", names.join(", ")), "medium");
+            match DIFFICULTIES.iter().find(|(name, _)| *name == answer) {
+                Some((_, share)) => break *share,
+                None => println!("Please choose one of {}.", names.join(", ")),
+            }
+        };
+        let mut trimmed = dict.clone();
+        keep_common(&mut trimmed, &[rows, cols], share, &themes);
+        if check(&MagicSquare::empty(rows, cols, &trimmed)).is_plausible() {
+            dict = trimmed;
+        } else {
+            println!("Too few common words at that difficulty; using every word.");
+        }
+    }
+
+    // Output
+    let format = loop {
+        match Format::parse(&ask("Output format (text, json, chat)", args.format.name())) {
+            Ok(format) => break format,
+            Err(e) => println!("{}", e),
+        }
+    };
+    let save = ask("Save the puzzle to a file (don't save)", "");
+
+    let mut square = MagicSquare::empty(rows, cols, &dict);
+    square.set_live_view(LiveView::Progress);
+    println!("Generating...");
+    let result = if themes.is_empty() {
+        square.fill().map(|()| vec![])
+    } else {
+        square.fill_with_themes(&themes)
+    };
+    let placed = match result {
+        Ok(placed) => placed,
+        Err(e) => {
+            if format == Format::Json {
+                println!("{}", report::failure_json(&square));
+            } else {
+                println!("Could not fill square: {}", e);
+            }
+            exit(1);
+        }
+    };
+
+    if !save.is_empty() {
+        if let Err(e) = std::fs::write(&save, Puzzle::from_square(&square).to_string()) {
+            println!("Could not save puzzle to {}: {}", save, e);
+            exit(1);
+        }
+    }
+    match format {
+        Format::Json => {
+            println!("{}", report::solved_json(&square));
+            return;
+        }
+        Format::Chat => {
+            print!("{}", report::chat(&square));
+            return;
+        }
+        Format::Text => {}
+    }
+    square.print();
+    for (slot, word) in placed.iter() {
+        println!("theme: {} = {}", slot, word);
+    }
+}
@@ -0,0 +1,212 @@
+//! Filling a grid a whole word at a time, crossword style.
+//!
+//! The grid's words are worked out from where its blocks are: each row and
+//! column with none, and each run of one between blocks long enough to be a
+//! word. The search fills the word with the fewest dictionary words fitting
+//! it next, and keeps a choice only if every word crossing it can still be
+//! completed, so a grid of many short words takes one step per word.
+
+use crate::slot::Entry;
+use crate::MagicSquare;
+
+/// An entry the search has reached, and the words it is trying there.
+struct EntryChoice {
+    /// Which entry, by its place among the grid's entries.
+    entry: usize,
+    /// Its cells, in grid order.
+    positions: Vec<(usize, usize)>,
+    /// What its cells held when the search reached it, blanks and all.
+    before: Vec<char>,
+    /// The words to try, in order.
+    words: Vec<String>,
+    /// The index in `words` of the next word to try.
+    next: usize,
+}
+
+impl MagicSquare {
+    /// Fill every word of the grid, so that the words crossing each one
+    /// stay words or templates some word matches, then the cells no word
+    /// runs through.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the grid was filled.
+    /// * `Err(String)` if no word fits some entry, leaving the grid as it
+    ///   was.
+    pub(crate) fn fill_words(&mut self) -> Result<(), String> {
+        let entries = self.entries();
+        self.fill_entries(&entries)
+    }
+
+    /// Fill the entries of `entries` that still have a blank, the one with
+    /// the fewest words fitting it first.
+    ///
+    /// The search keeps its own stack of entries, one per word placed,
+    /// rather than recursing, so a grid of many words cannot overflow the
+    /// thread's stack however deep it goes.
+    fn fill_entries(&mut self, entries: &[Entry]) -> Result<(), String> {
+        let Some(first) = self.next_entry(entries) else {
+            self.fill_unchecked();
+            return Ok(());
+        };
+        let mut stack = vec![self.entry_choice(entries, first)];
+        while let Some(choice) = stack.last_mut() {
+            let entry = &entries[choice.entry];
+            let Some(word) = choice.words.get(choice.next).cloned() else {
+                // If no word fits, backtrack
+                self.set_entry(&choice.positions, &choice.before);
+                let depth = self.note_backtrack();
+                trace!("backtracking from {} at depth {}", entry, depth);
+                stack.pop();
+                // The word in the entry before this one leads nowhere
+                if !stack.is_empty() {
+                    self.path.pop();
+                }
+                continue;
+            };
+            let i = choice.next;
+            choice.next += 1;
+
+            // Out of attempts: unwind without trying anything else, leaving
+            // the grid as it was
+            if self.should_stop() {
+                for choice in stack.iter().rev() {
+                    self.set_entry(&choice.positions, &choice.before);
+                }
+                for _ in 1..stack.len() {
+                    self.path.pop();
+                }
+                return Err(format!("Gave up at {}", entry));
+            }
+            self._attempt += 1;
+            let letters = entry.slot.orient(self.direction, word.chars().collect());
+            self.set_entry(&choice.positions, &letters);
+            // Only the cells that were blank can break a crossing word
+            let crossings_fit = choice
+                .positions
+                .iter()
+                .zip(&choice.before)
+                .filter(|(_, &c)| c == '_')
+                .all(|(&(row, col), _)| self.is_valid_letter(row, col, self.square[row][col]));
+            if !crossings_fit {
+                continue;
+            }
+            let choices = choice.words.len();
+            self.draw_grid();
+            self.path.push((i, choices));
+            self.draw_progress();
+            self.note_depth();
+            let Some(next) = self.next_entry(entries) else {
+                self.fill_unchecked();
+                return Ok(());
+            };
+            stack.push(self.entry_choice(entries, next));
+        }
+        // Every word in the first entry leads nowhere
+        Err(format!("Could not fill {}", entries[first]))
+    }
+
+    /// The entry of `entries` to fill next, by its place there: the one
+    /// with a blank that the fewest words fit, or `None` if all are full.
+    fn next_entry(&self, entries: &[Entry]) -> Option<usize> {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let template: String = entry.read(&self.square, self.direction).into_iter().collect();
+                (i, template)
+            })
+            .filter(|(_, template)| template.contains('_'))
+            .min_by_key(|(_, template)| {
+                self.note_query();
+                self.dict.count_with_template(template)
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Start on the entry of `entries` at `index`: note its cells as they
+    /// stand, and the words that fit it.
+    fn entry_choice(&mut self, entries: &[Entry], index: usize) -> EntryChoice {
+        let entry = &entries[index];
+        let positions = entry.positions();
+        let before = positions.iter().map(|&(r, c)| self.square[r][c]).collect();
+        EntryChoice {
+            entry: index,
+            positions,
+            before,
+            words: self.entry_words(entry),
+            next: 0,
+        }
+    }
+
+    /// Write letters, in grid order, into the cells at `positions`.
+    fn set_entry(&mut self, positions: &[(usize, usize)], letters: &[char]) {
+        for (&(row, col), &c) in positions.iter().zip(letters) {
+            self.square[row][col] = c;
+        }
+    }
+
+    /// The words that fit an entry as it stands, in the order to try them:
+    /// alphabetical order, or shuffled if a seed is set, and most common
+    /// first if the dictionary has word frequencies.
+    fn entry_words(&mut self, entry: &Entry) -> Vec<String> {
+        let template: String = entry.read(&self.square, self.direction).into_iter().collect();
+        let mut words: Vec<String> = self
+            .dict
+            .iter_with_template(&template)
+            .map(str::to_string)
+            .collect();
+        self.order_words(&mut words);
+        words
+    }
+
+    /// Fill the blank cells no word runs through, which may take any
+    /// letter, with the first letter the letter order would try there.
+    fn fill_unchecked(&mut self) {
+        let blanks: Vec<(usize, usize)> = self.empty_cells().collect();
+        for (row, col) in blanks {
+            if let Some(&c) = self.letter_order(row, col).first() {
+                self.square[row][col] = c;
+            }
+        }
+        self.note_depth();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{assert_fills, words};
+    use crate::{Direction, LiveView, MagicSquare, Strategy};
+
+    #[test]
+    fn fills_blank_and_blocked_grids() {
+        assert_fills("____", 4, Strategy::Words, |_| {});
+        assert_fills("#___#/_____/__#__/_____/#___#", 5, Strategy::Words, |square| {
+            square.set_min_word_len(3)
+        });
+    }
+
+    #[test]
+    fn keeps_mirrored_rows_and_directions() {
+        assert_fills("____", 4, Strategy::Words, |square| square.set_mirror_rows(true));
+        for direction in [Direction::BottomUp, Direction::Boustrophedon] {
+            assert_fills("___#/____/____/#___", 4, Strategy::Words, |square| {
+                square.set_direction(direction)
+            });
+        }
+    }
+
+    #[test]
+    fn gives_up_leaving_the_grid_as_it_was() {
+        let dict = words();
+        let mut square = MagicSquare::from_template("s____/_____/__#__", 5, &dict);
+        let before = square.grid().to_vec();
+        square.set_live_view(LiveView::Off);
+        square.set_strategy(Strategy::Words);
+        square.set_min_word_len(3);
+        square.set_attempt_limit(Some(5));
+        assert!(square.fill().is_err());
+        assert!(square.gave_up());
+        assert_eq!(square.grid(), before);
+    }
+}